@@ -0,0 +1,130 @@
+use crate::compiler::compile::compile_instruction;
+use crate::compiler::interface::Interface;
+use crate::evmm_error::evmm_error::EVMMError;
+use crate::parser::ast::{Builtin, SignatureArgument};
+use crate::parser::parse::Rule;
+use crate::utils::keccak::keccak256;
+
+///Compiles the hashing built-ins into a push of the value computed at compile time. Each built-in takes a quoted signature
+///or the name of a declared function, event or error. `__FUNC_SIG` pushes the 4 byte selector of a function,
+///`__EVENT_HASH` pushes the 32 byte event topic and `__ERROR` pushes the 4 byte error selector left aligned in 32 bytes, ready to be stored at the start of the revert data.
+pub fn compile_builtin(builtin: &Builtin, interface: &Interface) -> Result<String, EVMMError> {
+    let argument = match builtin {
+        Builtin::FuncSig(argument) | Builtin::EventHash(argument) | Builtin::Error(argument) => {
+            argument
+        }
+        //the dispatcher is compiled by `compile_dispatcher`
+        Builtin::Dispatcher { .. } => {
+            return Err(EVMMError::UnexpectedInstruction(String::from(
+                "__DISPATCHER",
+            )))
+        }
+    };
+
+    let signature = match argument {
+        SignatureArgument::Signature(signature) => signature.clone(),
+
+        SignatureArgument::Name(name) => match interface.signature_of(name) {
+            Some(signature) => signature,
+            None => return Err(EVMMError::UndeclaredSignature(name.clone())),
+        },
+    };
+
+    let hash = keccak256(signature.as_bytes());
+
+    match builtin {
+        Builtin::FuncSig(_) => Ok(compile_push(Rule::push4, &hash[0..4])),
+        Builtin::EventHash(_) => Ok(compile_push(Rule::push32, &hash)),
+        //__ERROR
+        _ => {
+            let mut left_aligned_selector = [0u8; 32];
+            left_aligned_selector[0..4].copy_from_slice(&hash[0..4]);
+
+            Ok(compile_push(Rule::push32, &left_aligned_selector))
+        }
+    }
+}
+
+fn compile_push(push_instruction: Rule, value: &[u8]) -> String {
+    let mut compiled_push = compile_instruction(push_instruction);
+
+    for byte in value {
+        compiled_push.push_str(&format!("{:02X}", byte));
+    }
+
+    compiled_push
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compile::compile_instructions;
+    use crate::evmm_error::evmm_error::EVMMError;
+    use crate::parser::ast::parse_program;
+
+    fn compile(file: &str) -> Result<String, EVMMError> {
+        compile_instructions(&parse_program(file)?, "".to_owned())
+            .map(|compiled_bytecode| compiled_bytecode.bytecode)
+    }
+
+    #[test]
+    fn test_compile_func_sig() {
+        let bytecode = compile(r#"__FUNC_SIG("transfer(address, uint)")"#).unwrap();
+        assert_eq!(bytecode, "63A9059CBB");
+    }
+
+    #[test]
+    fn test_compile_func_sig_of_declared_function() {
+        let bytecode = compile(
+            r#"
+    #define function transfer(address to, uint256 amount) returns (bool)
+    transfer:
+        __FUNC_SIG(transfer)
+    "#,
+        )
+        .unwrap();
+        assert_eq!(bytecode, "5B63A9059CBB");
+    }
+
+    #[test]
+    fn test_compile_func_sig_of_undeclared_function() {
+        assert!(matches!(
+            compile("__FUNC_SIG(transfer)"),
+            Err(EVMMError::UndeclaredSignature(_))
+        ));
+    }
+
+    #[test]
+    fn test_compile_event_hash() {
+        let bytecode = compile(r#"__EVENT_HASH("Transfer(address,address,uint256)")"#).unwrap();
+        assert_eq!(
+            bytecode,
+            "7FDDF252AD1BE2C89B69C2B068FC378DAA952BA7F163C4A11628F55A4DF523B3EF"
+        );
+    }
+
+    #[test]
+    fn test_compile_error() {
+        let bytecode = compile(r#"__ERROR("Unauthorized()")"#).unwrap();
+        assert_eq!(bytecode, format!("7F82B42900{}", "00".repeat(28)));
+    }
+
+    #[test]
+    fn test_compile_declared_event_and_error() {
+        let bytecode = compile(
+            r#"
+    #define event Transfer(address indexed from, address indexed to, uint256 value)
+    #define error Unauthorized()
+    __EVENT_HASH(Transfer)
+    __ERROR(Unauthorized)
+    "#,
+        )
+        .unwrap();
+        assert_eq!(
+            bytecode,
+            format!(
+                "7FDDF252AD1BE2C89B69C2B068FC378DAA952BA7F163C4A11628F55A4DF523B3EF7F82B42900{}",
+                "00".repeat(28)
+            )
+        );
+    }
+}
@@ -1,399 +1,296 @@
+use crate::compiler::builtins::compile_builtin;
+use crate::compiler::dispatcher::compile_dispatcher;
+use crate::compiler::labels::Labels;
+use crate::compiler::opcodes::{opcode_info, push_size};
 use crate::evmm_error::evmm_error::EVMMError;
+use crate::parser::ast::{Builtin, Instruction, InstructionKind, Program, PushValue, Span};
 use crate::parser::parse::Rule;
 use core::num::ParseIntError;
-use num256::uint256;
-use pest::iterators::{Pair, Pairs};
-use std::iter::Peekable;
-use std::str::FromStr;
 
-pub fn compile_instructions(
-    mut peekable_instructions: Peekable<Pairs<Rule>>,
-    mut contract_bytecode: String,
-) -> Result<String, EVMMError> {
-    let mut stack_size: usize = 0;
+//the largest runtime bytecode the constructor can deploy, since its size is pushed with PUSH2
+const MAX_DEPLOYABLE_RUNTIME_SIZE: usize = 0xFFFF;
 
-    loop {
-        let next_instruction = peekable_instructions.peek();
-
-        if next_instruction.is_some() {
-            let instruction = peekable_instructions.next().unwrap();
+///Maps a compiled instruction back to the span of the source that produced it
+pub struct SourceMapEntry {
+    pub bytecode_offset: usize,
+    pub source_start: usize,
+    pub source_end: usize,
+    //the index of the file in the program sources, which is 0 unless the instruction is from an included file
+    pub source_index: usize,
+}
 
-            let instruction_as_rule = instruction.as_rule();
+///Compiled bytecode along with the labels and source map needed to relate it back to the source
+pub struct CompiledBytecode {
+    pub bytecode: String,
+    //label names and their byte offsets, in bytecode order
+    pub labels: Vec<(String, usize)>,
+    //the label referenced by each push of a label offset and the offset of the push, in bytecode order
+    pub label_references: Vec<(String, usize)>,
+    //one entry per instruction, in bytecode order
+    pub source_map: Vec<SourceMapEntry>,
+}
 
-            match instruction_as_rule {
-                //Compile instructions that consume 0 stack values and do not push a value on the stack
-                Rule::stop => {
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
-                }
+pub fn compile_instructions(
+    program: &Program,
+    mut contract_bytecode: String,
+) -> Result<CompiledBytecode, EVMMError> {
+    //the number of values on the stack, which is unknown at a label since it can be jumped to from anywhere
+    let mut stack_size: Option<usize> = Some(0);
+    let mut labels = Labels::new();
 
-                //Compile instructions that consume 0 stack values and push a value on the stack
-                Rule::address
-                | Rule::origin
-                | Rule::caller
-                | Rule::callvalue
-                | Rule::calldatasize
-                | Rule::codesize
-                | Rule::returndatasize
-                | Rule::pc
-                | Rule::msize
-                | Rule::gas
-                | Rule::jumpdest
-                | Rule::balance
-                | Rule::gasprice
-                | Rule::blockhash
-                | Rule::coinbase
-                | Rule::timestamp
-                | Rule::codecopy
-                | Rule::blockNumber
-                | Rule::difficulty
-                | Rule::gaslimit
-                | Rule::chainid
-                | Rule::selfbalance
-                | Rule::extcodesize
-                | Rule::basefee
-                | Rule::extcodehash => {
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
-                    //consume 0 stack values and add a value onto the stack, resulting in a increase of the stack size by 1
-
-                    stack_size += 1;
-                }
+    //the bytecode offset and source span of each instruction as it is compiled
+    let mut source_spans: Vec<SourceMapEntry> = vec![];
 
-                //Compile instructions that consume 1 stack value and push 0 values on the stack
-                Rule::pop | Rule::jump | Rule::selfdestruct => {
-                    if stack_size < 1 {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            1,
-                            stack_size,
-                        ));
-                    }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
+    //collect the interface declarations up front so that the dispatcher can be placed anywhere in the contract
+    let interface = program.interface();
 
-                    //consume 1 stack values and add nothing onto the stack, resulting in a reduction of the stack size by 1
-                    stack_size -= 1;
-                }
+    for instruction in program.instructions.iter() {
+        //interface declarations do not compile to any bytecode
+        if let InstructionKind::Definition(_) = instruction.kind {
+            continue;
+        }
 
-                //Compile instructions that consume 1 stack values and push a value on the stack
-                Rule::iszero | Rule::calldataload | Rule::mload | Rule::sload => {
-                    if stack_size < 1 {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            1,
-                            stack_size,
-                        ));
-                    }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
+        source_spans.push(SourceMapEntry {
+            bytecode_offset: contract_bytecode.len() / 2,
+            source_start: instruction.span.start,
+            source_end: instruction.span.end,
+            source_index: instruction.span.source,
+        });
+
+        match &instruction.kind {
+            //Define a label at the current offset, compiling to a JUMPDEST
+            InstructionKind::Label(label) => {
+                labels
+                    .define_label(label, &mut contract_bytecode)
+                    .map_err(|error| in_source_file(program, &instruction.span, error))?;
+
+                stack_size = None;
+            }
 
-                    //consume 1 stack values and add 1 onto the stack, resulting in no change to the stack size
-                }
+            //Compile the function dispatcher, which leaves the selector on the stack
+            InstructionKind::Builtin(Builtin::Dispatcher { fallback, mode }) => {
+                compile_dispatcher(
+                    fallback,
+                    *mode,
+                    &interface.functions,
+                    &mut labels,
+                    &mut contract_bytecode,
+                )?;
+
+                stack_size = stack_size.map(|stack_size| stack_size + 1);
+            }
 
-                //Compile instructions that consume 2 stack values and push 0 values on the stack
-                Rule::mstore
-                | Rule::mstore8
-                | Rule::sstore
-                | Rule::jumpi
-                | Rule::log0
-                | Rule::evmReturn
-                | Rule::revert => {
-                    if stack_size < 2 {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            2,
-                            stack_size,
-                        ));
-                    }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
+            //Compile built-ins that hash a signature at compile time and push the result onto the stack
+            InstructionKind::Builtin(builtin) => {
+                contract_bytecode.push_str(&compile_builtin(builtin, &interface)?);
 
-                    stack_size -= 2;
-                }
+                stack_size = stack_size.map(|stack_size| stack_size + 1);
+            }
 
-                //Compile instructions that consume 2 stack values and push a value on the stack
-                Rule::add
-                | Rule::mul
-                | Rule::sub
-                | Rule::div
-                | Rule::sdiv
-                | Rule::evmMod
-                | Rule::smod
-                | Rule::lt
-                | Rule::gt
-                | Rule::slt
-                | Rule::sgt
-                | Rule::eq
-                | Rule::and
-                | Rule::or
-                | Rule::xor
-                | Rule::not
-                | Rule::byte
-                | Rule::shl
-                | Rule::shr
-                | Rule::sar
-                | Rule::keccak256
-                | Rule::exp
-                | Rule::signextend => {
-                    if stack_size < 2 {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            2,
-                            stack_size,
-                        ));
+            //Compile an opcode, checking that there are enough values on the stack for it to consume
+            InstructionKind::Opcode(opcode) => {
+                let opcode_info = match opcode_info(*opcode) {
+                    Some(opcode_info) => opcode_info,
+                    None => {
+                        return Err(EVMMError::UnexpectedInstruction(format!(
+                            "0x{:02X}",
+                            opcode
+                        )))
                     }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
-
-                    //consume 2 stack values and add 1 onto the stack, resulting in a reduction of the stack size by 1
-                    stack_size -= 1;
-                }
-
-                //Compile instructions that consume 3 stack values and push 0 values on the stack
-                Rule::returndatacopy | Rule::log1 => {
-                    if stack_size < 3 {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            3,
-                            stack_size,
+                };
+
+                if let Some(stack_size) = stack_size {
+                    if stack_size < opcode_info.stack_inputs {
+                        return Err(in_source_file(
+                            program,
+                            &instruction.span,
+                            EVMMError::NotEnoughValuesOnStack(
+                                opcode_info.mnemonic.to_owned(),
+                                opcode_info.stack_inputs,
+                                stack_size,
+                            ),
                         ));
                     }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
-
-                    //consume 3 stack values and add 0 onto the stack, resulting in a reduction of the stack size by 3
-                    stack_size -= 3;
                 }
+                contract_bytecode.push_str(&format!("{:02X}", opcode));
+
+                stack_size = match opcode {
+                    //JUMPDEST starts a new basic block, and STOP, JUMP, RETURN, REVERT, INVALID and SELFDESTRUCT end
+                    //the block, so the code after them can only be reached by a jump with an unknown stack
+                    0x5B | 0x00 | 0x56 | 0xF3 | 0xFD | 0xFE | 0xFF => None,
+                    _ => stack_size.map(|stack_size| {
+                        stack_size - opcode_info.stack_inputs + opcode_info.stack_outputs
+                    }),
+                };
+            }
 
-                //Compile instructions that consume 3 stack values and push 1 value on the stack
-                Rule::addmod | Rule::mulmod | Rule::calldatacopy | Rule::create => {
-                    if stack_size < 3 {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            3,
-                            stack_size,
-                        ));
+            //Compile a push instruction and its value, padded to the size of the push
+            InstructionKind::Push { size, value } => {
+                //PUSH1 is 0x60 and PUSH32 is 0x7F
+                contract_bytecode.push_str(&format!("{:02X}", 0x5F + size));
+
+                match value {
+                    //If the value is a label, push a placeholder that is replaced with the label offset once all labels are known
+                    PushValue::Label(label) => labels.reference_label(
+                        label,
+                        &format!("PUSH{}", size),
+                        *size,
+                        &mut contract_bytecode,
+                    ),
+
+                    //the parser checks that the value fits in the push
+                    PushValue::Literal(bytes) => {
+                        contract_bytecode.push_str(&"00".repeat(size - bytes.len()));
+                        for byte in bytes {
+                            contract_bytecode.push_str(&format!("{:02X}", byte));
+                        }
                     }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
-
-                    //consume 3 stack values and add 1 onto the stack, resulting in a reduction of the stack size by 2
-                    stack_size -= 2;
                 }
 
-                //Compile instructions that consume 4 stack values and push 0 values on the stack
-                Rule::log2 => {
-                    if stack_size < 4 {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            4,
-                            stack_size,
-                        ));
-                    }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
-
-                    //consume 4 stack values and add 0 onto the stack, resulting in a reduction of the stack size by 4
-                    stack_size -= 4;
-                }
-
-                //Compile instructions that consume 4 stack values and push a value on the stack
-                Rule::extcodecopy | Rule::create2 => {
-                    if stack_size < 4 {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            4,
-                            stack_size,
-                        ));
-                    }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
+                stack_size = stack_size.map(|stack_size| stack_size + 1);
+            }
 
-                    //consume 4 stack values and add 1 onto the stack, resulting in a reduction of the stack size by 3
-                    stack_size -= 3;
-                }
+            //includes are replaced with the instructions of the included file when the program is parsed
+            InstructionKind::Include(include_path) => {
+                return Err(EVMMError::UnexpectedInstruction(format!(
+                    "#include \"{}\"",
+                    include_path
+                )))
+            }
 
-                //Compile instructions that consume 5 stack values and push 0 values on the stack
-                Rule::log3 => {
-                    if stack_size < 5 {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            5,
-                            stack_size,
-                        ));
-                    }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
+            InstructionKind::Definition(_) => {}
+        }
+    }
 
-                    //consume 4 stack values and add 0 onto the stack, resulting in a reduction of the stack size by 4
-                    stack_size -= 5;
-                }
+    //an undefined label is reported in the file of the first instruction that references it
+    labels
+        .resolve_labels(&mut contract_bytecode)
+        .map_err(|error| match &error {
+            EVMMError::UndefinedLabel(label) => match referencing_instruction(program, label) {
+                Some(instruction) => in_source_file(program, &instruction.span, error),
+                None => error,
+            },
+            _ => error,
+        })?;
+
+    //every declared function needs a label with the same name that implements it
+    for function in interface.functions.iter() {
+        if !labels.is_defined(&function.name) {
+            return Err(EVMMError::MissingFunctionImplementation(
+                function.signature(),
+            ));
+        }
+    }
 
-                //Compile instructions that consume 6 stack values and push 0 values on the stack
-                Rule::log4 => {
-                    if stack_size < 6 {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            6,
-                            stack_size,
-                        ));
-                    }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
+    let source_map = build_source_map(&contract_bytecode, &source_spans);
 
-                    //consume 6 stack values and add 0 onto the stack, resulting in a reduction of the stack size by 4
-                    stack_size -= 6;
-                }
+    Ok(CompiledBytecode {
+        bytecode: contract_bytecode,
+        labels: labels.offsets(),
+        label_references: labels.references(),
+        source_map,
+    })
+}
 
-                //Compile instructions that consume 6 stack values and push 1 value on the stack
-                Rule::delegatecall | Rule::staticcall => {
-                    if stack_size < 6 {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            6,
-                            stack_size,
-                        ));
-                    }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
+///Names the file and line of an instruction in an error if the instruction is from an included file, ex.
+///`In included file "lib/math.evmm:3": ...`
+fn in_source_file(program: &Program, span: &Span, error: EVMMError) -> EVMMError {
+    match program.sources.get(span.source) {
+        Some(source) if span.source != 0 => {
+            let line_number = source.contents[..span.start].matches('\n').count() + 1;
+            EVMMError::InIncludedFile(format!("{}:{}", source.path, line_number), Box::new(error))
+        }
+        _ => error,
+    }
+}
 
-                    //consume 6 stack values and add 1 onto the stack, resulting in a reduction of the stack size by 4
-                    stack_size -= 5;
-                }
+//the first push of the offset of a label, or the dispatcher if the label is its fallback
+fn referencing_instruction<'a>(program: &'a Program, label: &str) -> Option<&'a Instruction> {
+    program
+        .instructions
+        .iter()
+        .find(|instruction| match &instruction.kind {
+            InstructionKind::Push {
+                value: PushValue::Label(reference),
+                ..
+            } => reference == label,
+            InstructionKind::Builtin(Builtin::Dispatcher { fallback, .. }) => fallback == label,
+            _ => false,
+        })
+}
 
-                //Compile instructions that consume 7 stack values and push 1 value on the stack
-                Rule::call | Rule::callcode => {
-                    if stack_size < 7 {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            7,
-                            stack_size,
-                        ));
-                    }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
+///Prefixes the runtime bytecode with a constructor that copies the runtime bytecode into memory and returns it.
+///The size of the runtime bytecode is pushed with PUSH2, so it can be at most 0xFFFF bytes.
+pub fn compile_deployment_bytecode(runtime_bytecode: &str) -> Result<String, EVMMError> {
+    let runtime_size = runtime_bytecode.len() / 2;
+    if runtime_size > MAX_DEPLOYABLE_RUNTIME_SIZE {
+        return Err(EVMMError::RuntimeTooLarge(runtime_size));
+    }
+
+    //the constructor is 13 bytes long, so the runtime bytecode starts at offset 0x0D
+    Ok([
+        compile_instruction(Rule::push2),
+        format!("{:04X}", runtime_size),
+        compile_instruction(Rule::dup1),
+        compile_instruction(Rule::push2),
+        String::from("000D"),
+        compile_instruction(Rule::push1),
+        String::from("00"),
+        compile_instruction(Rule::codecopy),
+        compile_instruction(Rule::push1),
+        String::from("00"),
+        compile_instruction(Rule::evmReturn),
+        runtime_bytecode.to_owned(),
+    ]
+    .join(""))
+}
 
-                    //consume 7 stack values and add 1 onto the stack, resulting in a reduction of the stack size by 6
-                    stack_size -= 6;
-                }
+///Gets the byte offset of every instruction in the bytecode, skipping over push values
+pub fn instruction_offsets(contract_bytecode: &str) -> Vec<usize> {
+    let bytecode = decode_hex(contract_bytecode).unwrap_or_default();
+    let mut offsets = vec![];
 
-                //Compile dup instructions that duplicate a stack value at a specific position, pushing the duplicate to the top of the stack
-                //These instructions consume 0 values and add a value to the stack
-                Rule::dup1
-                | Rule::dup2
-                | Rule::dup3
-                | Rule::dup4
-                | Rule::dup5
-                | Rule::dup6
-                | Rule::dup7
-                | Rule::dup8
-                | Rule::dup9
-                | Rule::dup10
-                | Rule::dup11
-                | Rule::dup12
-                | Rule::dup13
-                | Rule::dup14
-                | Rule::dup15
-                | Rule::dup16 => {
-                    let expected_stack_size =
-                        instruction.as_str().split_at(3).1.parse::<usize>().unwrap();
-
-                    if stack_size < expected_stack_size {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            expected_stack_size,
-                            stack_size,
-                        ));
-                    }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
+    let mut offset = 0;
+    while offset < bytecode.len() {
+        offsets.push(offset);
 
-                    stack_size += 1;
-                }
+        //PUSH1 through PUSH32 are followed by 1 to 32 bytes of push data
+        offset += 1 + push_size(bytecode[offset]);
+    }
 
-                //Compile swap instructions that swap the top position with a specified position on the stack
-                //No stack values are consumed or added
-                Rule::swap1
-                | Rule::swap2
-                | Rule::swap3
-                | Rule::swap4
-                | Rule::swap5
-                | Rule::swap6
-                | Rule::swap7
-                | Rule::swap8
-                | Rule::swap9
-                | Rule::swap10
-                | Rule::swap11
-                | Rule::swap12
-                | Rule::swap13
-                | Rule::swap14
-                | Rule::swap15
-                | Rule::swap16 => {
-                    let expected_stack_size =
-                        instruction.as_str().split_at(3).1.parse::<usize>().unwrap();
-
-                    if stack_size < expected_stack_size {
-                        return Err(EVMMError::NotEnoughValuesOnStack(
-                            instruction.as_str().to_owned(),
-                            expected_stack_size,
-                            stack_size,
-                        ));
-                    }
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
-                }
+    offsets
+}
 
-                //Validate size of the value following the instruction, compile instruction and add it to the contract bytecode
-                Rule::push1
-                | Rule::push2
-                | Rule::push3
-                | Rule::push4
-                | Rule::push5
-                | Rule::push6
-                | Rule::push7
-                | Rule::push8
-                | Rule::push9
-                | Rule::push10
-                | Rule::push11
-                | Rule::push12
-                | Rule::push13
-                | Rule::push14
-                | Rule::push15
-                | Rule::push16
-                | Rule::push17
-                | Rule::push18
-                | Rule::push19
-                | Rule::push20
-                | Rule::push21
-                | Rule::push22
-                | Rule::push23
-                | Rule::push24
-                | Rule::push25
-                | Rule::push26
-                | Rule::push27
-                | Rule::push28
-                | Rule::push29
-                | Rule::push30
-                | Rule::push31
-                | Rule::push32 => {
-                    //Split the instruction as a string so the result is ("push", expected_size), then parse the expected size as a usize
-
-                    let expected_size =
-                        instruction.as_str().split_at(4).1.parse::<usize>().unwrap();
-
-                    //validate the value to be pushed, apply padding and return the compiled value to be added to the contract bytecode
-                    let compiled_push_value = validate_proceeding_push_instruction(
-                        &instruction,
-                        peekable_instructions.peek(),
-                        expected_size,
-                    )?;
-
-                    //add the push instruction to the bytecode
-                    contract_bytecode.push_str(&compile_instruction(instruction_as_rule));
-
-                    //add the padded value to the  bytecode
-                    contract_bytecode.push_str(&compiled_push_value);
-
-                    //update the stack size
-                    stack_size += 1;
-                }
+///Assigns every compiled instruction the span of the source instruction that produced it.
+///Built-ins like the dispatcher compile to many instructions which all map to the span of the built-in.
+fn build_source_map(
+    contract_bytecode: &str,
+    source_spans: &[SourceMapEntry],
+) -> Vec<SourceMapEntry> {
+    let mut source_map = vec![];
+    let mut span_index = 0;
+
+    for offset in instruction_offsets(contract_bytecode) {
+        while span_index + 1 < source_spans.len()
+            && source_spans[span_index + 1].bytecode_offset <= offset
+        {
+            span_index += 1;
+        }
 
-                _ => {}
-            }
-        } else {
-            break;
+        if let Some(span) = source_spans.get(span_index) {
+            source_map.push(SourceMapEntry {
+                bytecode_offset: offset,
+                source_start: span.source_start,
+                source_end: span.source_end,
+                source_index: span.source_index,
+            });
         }
     }
 
-    return Ok(contract_bytecode);
+    source_map
 }
 
-fn compile_instruction(instruction: Rule) -> String {
+pub fn compile_instruction(instruction: Rule) -> String {
     match instruction {
         Rule::stop => String::from("00"),
         Rule::add => String::from("01"),
@@ -445,49 +342,49 @@ fn compile_instruction(instruction: Rule) -> String {
         Rule::difficulty => String::from("44"),
         Rule::gaslimit => String::from("45"),
         Rule::chainid => String::from("46"),
-        Rule::selfbalance => String::from("67"),
+        Rule::selfbalance => String::from("47"),
         Rule::basefee => String::from("48"),
-        Rule::pop => String::from("49"),
-        Rule::mload => String::from("50"),
-        Rule::mstore => String::from("51"),
-        Rule::mstore8 => String::from("52"),
-        Rule::sload => String::from("53"),
-        Rule::sstore => String::from("54"),
-        Rule::jump => String::from("55"),
-        Rule::jumpi => String::from("56"),
-        Rule::pc => String::from("57"),
-        Rule::msize => String::from("58"),
-        Rule::gas => String::from("59"),
-        Rule::jumpdest => String::from("5A"),
-        Rule::push1 => String::from("5B"),
-        Rule::push2 => String::from("60"),
-        Rule::push3 => String::from("61"),
-        Rule::push4 => String::from("62"),
-        Rule::push5 => String::from("63"),
-        Rule::push6 => String::from("64"),
-        Rule::push7 => String::from("65"),
-        Rule::push8 => String::from("66"),
-        Rule::push9 => String::from("67"),
-        Rule::push10 => String::from("68"),
-        Rule::push11 => String::from("69"),
-        Rule::push12 => String::from("6A"),
-        Rule::push13 => String::from("6B"),
-        Rule::push14 => String::from("6C"),
-        Rule::push15 => String::from("6D"),
-        Rule::push16 => String::from("6E"),
-        Rule::push17 => String::from("6F"),
-        Rule::push18 => String::from("70"),
-        Rule::push19 => String::from("71"),
-        Rule::push20 => String::from("72"),
-        Rule::push21 => String::from("73"),
-        Rule::push22 => String::from("74"),
-        Rule::push23 => String::from("75"),
-        Rule::push24 => String::from("76"),
-        Rule::push25 => String::from("77"),
-        Rule::push26 => String::from("78"),
-        Rule::push27 => String::from("79"),
-        Rule::push28 => String::from("7A"),
-        Rule::push29 => String::from("7B"),
+        Rule::pop => String::from("50"),
+        Rule::mload => String::from("51"),
+        Rule::mstore => String::from("52"),
+        Rule::mstore8 => String::from("53"),
+        Rule::sload => String::from("54"),
+        Rule::sstore => String::from("55"),
+        Rule::jump => String::from("56"),
+        Rule::jumpi => String::from("57"),
+        Rule::pc => String::from("58"),
+        Rule::msize => String::from("59"),
+        Rule::gas => String::from("5A"),
+        Rule::jumpdest => String::from("5B"),
+        Rule::push1 => String::from("60"),
+        Rule::push2 => String::from("61"),
+        Rule::push3 => String::from("62"),
+        Rule::push4 => String::from("63"),
+        Rule::push5 => String::from("64"),
+        Rule::push6 => String::from("65"),
+        Rule::push7 => String::from("66"),
+        Rule::push8 => String::from("67"),
+        Rule::push9 => String::from("68"),
+        Rule::push10 => String::from("69"),
+        Rule::push11 => String::from("6A"),
+        Rule::push12 => String::from("6B"),
+        Rule::push13 => String::from("6C"),
+        Rule::push14 => String::from("6D"),
+        Rule::push15 => String::from("6E"),
+        Rule::push16 => String::from("6F"),
+        Rule::push17 => String::from("70"),
+        Rule::push18 => String::from("71"),
+        Rule::push19 => String::from("72"),
+        Rule::push20 => String::from("73"),
+        Rule::push21 => String::from("74"),
+        Rule::push22 => String::from("75"),
+        Rule::push23 => String::from("76"),
+        Rule::push24 => String::from("77"),
+        Rule::push25 => String::from("78"),
+        Rule::push26 => String::from("79"),
+        Rule::push27 => String::from("7A"),
+        Rule::push28 => String::from("7B"),
+        Rule::push29 => String::from("7C"),
         Rule::push30 => String::from("7D"),
         Rule::push31 => String::from("7E"),
         Rule::push32 => String::from("7F"),
@@ -544,88 +441,7 @@ fn compile_instruction(instruction: Rule) -> String {
     }
 }
 
-///Validate the size of a value proceeding a push instruction, returns the push value to be compiled and the size of padding to apply to the value
-fn validate_proceeding_push_instruction(
-    push_instruction: &Pair<Rule>,
-    optional_next_instruction: Option<&Pair<Rule>>,
-    expected_size: usize,
-) -> Result<String, EVMMError> {
-    if optional_next_instruction.is_some() {
-        let next_instruction = optional_next_instruction.unwrap();
-
-        match next_instruction.as_rule() {
-            Rule::number | Rule::hex_number => {
-                let value_byte_size = get_byte_size(next_instruction)?;
-
-                if value_byte_size > expected_size {
-                    return Err(EVMMError::ValueTooBigForPushInstruction(
-                        push_instruction.as_str().to_owned(),
-                        next_instruction.as_str().to_owned(),
-                        value_byte_size,
-                    ));
-                } else if value_byte_size == expected_size {
-                    Ok(convert_to_hex_number_and_strip_prefix(next_instruction)?)
-                } else {
-                    let compiled_push_value =
-                        convert_to_hex_number_and_strip_prefix(next_instruction)?;
-
-                    //pad the value and return the compiled value to be added to the contract code
-                    Ok([
-                        "00".repeat(expected_size - value_byte_size),
-                        compiled_push_value,
-                    ]
-                    .join(""))
-                }
-            }
-
-            _ => {
-                return Err(EVMMError::UnexpectedInstruction(
-                    next_instruction.as_str().to_owned(),
-                ));
-            }
-        }
-    } else {
-        return Err(EVMMError::ExpectedInstruction());
-    }
-}
-
-fn convert_to_hex_number_and_strip_prefix(value: &Pair<Rule>) -> Result<String, EVMMError> {
-    match value.as_rule() {
-        Rule::number => {
-            let value_as_uint256 = uint256::Uint256::from_str(value.as_str()).unwrap();
-
-            Ok(format!("{:X}", value_as_uint256))
-        }
-
-        Rule::hex_number => Ok(value.as_str()[2..].to_string()),
-        _ => Err(EVMMError::UnexpectedInstruction(value.as_str().to_owned())),
-    }
-}
-
-///Gets the size of a number or hex number when represented as bytes
-fn get_byte_size(instruction: &Pair<Rule>) -> Result<usize, EVMMError> {
-    match instruction.as_rule() {
-        Rule::number => {
-            let number_value = uint256::Uint256::from_str(instruction.as_str()).unwrap();
-            let number_value_as_bytes = number_value.to_bytes_be();
-
-            //return the length of bytes
-            Ok(number_value_as_bytes.len())
-        }
-        Rule::hex_number => {
-            let hex_number_value_as_bytes =
-                decode_hex(&convert_to_hex_number_and_strip_prefix(instruction)?).unwrap();
-
-            //return the length of bytes
-            Ok(hex_number_value_as_bytes.len())
-        }
-        _ => Err(EVMMError::UnexpectedInstruction(
-            instruction.as_str().to_owned(),
-        )),
-    }
-}
-
-fn decode_hex(s: &str) -> Result<Vec<u8>, ParseIntError> {
+pub fn decode_hex(s: &str) -> Result<Vec<u8>, ParseIntError> {
     (0..s.len())
         .step_by(2)
         .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
@@ -939,7 +755,7 @@ mod tests {
     #[test]
     fn test_compile_selfbalance() {
         let bytecode = compile_instruction(Rule::selfbalance);
-        assert_eq!(bytecode, "67");
+        assert_eq!(bytecode, "47");
     }
 
     #[test]
@@ -951,247 +767,247 @@ mod tests {
     #[test]
     fn test_compile_pop() {
         let bytecode = compile_instruction(Rule::pop);
-        assert_eq!(bytecode, "49");
+        assert_eq!(bytecode, "50");
     }
 
     #[test]
     fn test_compile_mload() {
         let bytecode = compile_instruction(Rule::mload);
-        assert_eq!(bytecode, "50");
+        assert_eq!(bytecode, "51");
     }
 
     #[test]
     fn test_compile_mstore() {
         let bytecode = compile_instruction(Rule::mstore);
-        assert_eq!(bytecode, "51");
+        assert_eq!(bytecode, "52");
     }
 
     #[test]
     fn test_compile_mstore8() {
         let bytecode = compile_instruction(Rule::mstore8);
-        assert_eq!(bytecode, "52");
+        assert_eq!(bytecode, "53");
     }
 
     #[test]
     fn test_compile_sload() {
         let bytecode = compile_instruction(Rule::sload);
-        assert_eq!(bytecode, "53");
+        assert_eq!(bytecode, "54");
     }
 
     #[test]
     fn test_compile_sstore() {
         let bytecode = compile_instruction(Rule::sstore);
-        assert_eq!(bytecode, "54");
+        assert_eq!(bytecode, "55");
     }
 
     #[test]
     fn test_compile_jump() {
         let bytecode = compile_instruction(Rule::jump);
-        assert_eq!(bytecode, "55");
+        assert_eq!(bytecode, "56");
     }
 
     #[test]
     fn test_compile_jumpi() {
         let bytecode = compile_instruction(Rule::jumpi);
-        assert_eq!(bytecode, "56");
+        assert_eq!(bytecode, "57");
     }
 
     #[test]
     fn test_compile_pc() {
         let bytecode = compile_instruction(Rule::pc);
-        assert_eq!(bytecode, "57");
+        assert_eq!(bytecode, "58");
     }
 
     #[test]
     fn test_compile_msize() {
         let bytecode = compile_instruction(Rule::msize);
-        assert_eq!(bytecode, "58");
+        assert_eq!(bytecode, "59");
     }
 
     #[test]
     fn test_compile_gas() {
         let bytecode = compile_instruction(Rule::gas);
-        assert_eq!(bytecode, "59");
+        assert_eq!(bytecode, "5A");
     }
 
     #[test]
     fn test_compile_jumpdest() {
         let bytecode = compile_instruction(Rule::jumpdest);
-        assert_eq!(bytecode, "5A");
+        assert_eq!(bytecode, "5B");
     }
 
     #[test]
     fn test_compile_push1() {
         let bytecode = compile_instruction(Rule::push1);
-        assert_eq!(bytecode, "5B");
+        assert_eq!(bytecode, "60");
     }
 
     #[test]
     fn test_compile_push2() {
         let bytecode = compile_instruction(Rule::push2);
-        assert_eq!(bytecode, "60");
+        assert_eq!(bytecode, "61");
     }
 
     #[test]
     fn test_compile_push3() {
         let bytecode = compile_instruction(Rule::push3);
-        assert_eq!(bytecode, "61");
+        assert_eq!(bytecode, "62");
     }
 
     #[test]
     fn test_compile_push4() {
         let bytecode = compile_instruction(Rule::push4);
-        assert_eq!(bytecode, "62");
+        assert_eq!(bytecode, "63");
     }
 
     #[test]
     fn test_compile_push5() {
         let bytecode = compile_instruction(Rule::push5);
-        assert_eq!(bytecode, "63");
+        assert_eq!(bytecode, "64");
     }
 
     #[test]
     fn test_compile_push6() {
         let bytecode = compile_instruction(Rule::push6);
-        assert_eq!(bytecode, "64");
+        assert_eq!(bytecode, "65");
     }
 
     #[test]
     fn test_compile_push7() {
         let bytecode = compile_instruction(Rule::push7);
-        assert_eq!(bytecode, "65");
+        assert_eq!(bytecode, "66");
     }
 
     #[test]
     fn test_compile_push8() {
         let bytecode = compile_instruction(Rule::push8);
-        assert_eq!(bytecode, "66");
+        assert_eq!(bytecode, "67");
     }
 
     #[test]
     fn test_compile_push9() {
         let bytecode = compile_instruction(Rule::push9);
-        assert_eq!(bytecode, "67");
+        assert_eq!(bytecode, "68");
     }
 
     #[test]
     fn test_compile_push10() {
         let bytecode = compile_instruction(Rule::push10);
-        assert_eq!(bytecode, "68");
+        assert_eq!(bytecode, "69");
     }
 
     #[test]
     fn test_compile_push11() {
         let bytecode = compile_instruction(Rule::push11);
-        assert_eq!(bytecode, "69");
+        assert_eq!(bytecode, "6A");
     }
 
     #[test]
     fn test_compile_push12() {
         let bytecode = compile_instruction(Rule::push12);
-        assert_eq!(bytecode, "6A");
+        assert_eq!(bytecode, "6B");
     }
 
     #[test]
     fn test_compile_push13() {
         let bytecode = compile_instruction(Rule::push13);
-        assert_eq!(bytecode, "6B");
+        assert_eq!(bytecode, "6C");
     }
 
     #[test]
     fn test_compile_push14() {
         let bytecode = compile_instruction(Rule::push14);
-        assert_eq!(bytecode, "6C");
+        assert_eq!(bytecode, "6D");
     }
 
     #[test]
     fn test_compile_push15() {
         let bytecode = compile_instruction(Rule::push15);
-        assert_eq!(bytecode, "6D");
+        assert_eq!(bytecode, "6E");
     }
 
     #[test]
     fn test_compile_push16() {
         let bytecode = compile_instruction(Rule::push16);
-        assert_eq!(bytecode, "6E");
+        assert_eq!(bytecode, "6F");
     }
 
     #[test]
     fn test_compile_push17() {
         let bytecode = compile_instruction(Rule::push17);
-        assert_eq!(bytecode, "6F");
+        assert_eq!(bytecode, "70");
     }
 
     #[test]
     fn test_compile_push18() {
         let bytecode = compile_instruction(Rule::push18);
-        assert_eq!(bytecode, "70");
+        assert_eq!(bytecode, "71");
     }
 
     #[test]
     fn test_compile_push19() {
         let bytecode = compile_instruction(Rule::push19);
-        assert_eq!(bytecode, "71");
+        assert_eq!(bytecode, "72");
     }
 
     #[test]
     fn test_compile_push20() {
         let bytecode = compile_instruction(Rule::push20);
-        assert_eq!(bytecode, "72");
+        assert_eq!(bytecode, "73");
     }
 
     #[test]
     fn test_compile_push21() {
         let bytecode = compile_instruction(Rule::push21);
-        assert_eq!(bytecode, "73");
+        assert_eq!(bytecode, "74");
     }
 
     #[test]
     fn test_compile_push22() {
         let bytecode = compile_instruction(Rule::push22);
-        assert_eq!(bytecode, "74");
+        assert_eq!(bytecode, "75");
     }
 
     #[test]
     fn test_compile_push23() {
         let bytecode = compile_instruction(Rule::push23);
-        assert_eq!(bytecode, "75");
+        assert_eq!(bytecode, "76");
     }
 
     #[test]
     fn test_compile_push24() {
         let bytecode = compile_instruction(Rule::push24);
-        assert_eq!(bytecode, "76");
+        assert_eq!(bytecode, "77");
     }
 
     #[test]
     fn test_compile_push25() {
         let bytecode = compile_instruction(Rule::push25);
-        assert_eq!(bytecode, "77");
+        assert_eq!(bytecode, "78");
     }
 
     #[test]
     fn test_compile_push26() {
         let bytecode = compile_instruction(Rule::push26);
-        assert_eq!(bytecode, "78");
+        assert_eq!(bytecode, "79");
     }
 
     #[test]
     fn test_compile_push27() {
         let bytecode = compile_instruction(Rule::push27);
-        assert_eq!(bytecode, "79");
+        assert_eq!(bytecode, "7A");
     }
 
     #[test]
     fn test_compile_push28() {
         let bytecode = compile_instruction(Rule::push28);
-        assert_eq!(bytecode, "7A");
+        assert_eq!(bytecode, "7B");
     }
 
     #[test]
     fn test_compile_push29() {
         let bytecode = compile_instruction(Rule::push29);
-        assert_eq!(bytecode, "7B");
+        assert_eq!(bytecode, "7C");
     }
 
     #[test]
@@ -1487,4 +1303,153 @@ mod tests {
         let bytecode = compile_instruction(Rule::selfdestruct);
         assert_eq!(bytecode, "FF");
     }
+
+    #[test]
+    fn test_compile_deployment_bytecode() {
+        let bytecode = compile_deployment_bytecode("600160005260206000F3").unwrap();
+        assert_eq!(bytecode, "61000A8061000D6000396000F3600160005260206000F3");
+    }
+
+    #[test]
+    fn test_compile_deployment_bytecode_size_limit() {
+        //the largest runtime the constructor can push the size of
+        let bytecode = compile_deployment_bytecode(&"00".repeat(0xFFFF)).unwrap();
+        assert_eq!(&bytecode[..26], "61FFFF8061000D6000396000F3");
+        assert_eq!(bytecode.len(), (13 + 0xFFFF) * 2);
+
+        assert!(matches!(
+            compile_deployment_bytecode(&"00".repeat(0x10000)),
+            Err(EVMMError::RuntimeTooLarge(0x10000))
+        ));
+    }
+
+    #[test]
+    fn test_compile_not_enough_values_on_stack() {
+        let program = crate::parser::ast::parse_program("PUSH1 0x00 PUSH1 0x20 CODECOPY").unwrap();
+
+        assert!(matches!(
+            compile_instructions(&program, "".to_owned()),
+            Err(EVMMError::NotEnoughValuesOnStack(instruction, 3, 2)) if instruction == "CODECOPY"
+        ));
+    }
+
+    #[test]
+    fn test_compile_label_reached_by_a_jump() {
+        //the values ADD consumes are pushed after it in the source, before jumping back to its label
+        let program = crate::parser::ast::parse_program(
+            "PUSH2 main JUMP add_them: ADD PUSH1 0x00 MSTORE STOP main: PUSH1 0x01 PUSH1 0x02 PUSH2 add_them JUMP",
+        )
+        .unwrap();
+        let compiled_bytecode = compile_instructions(&program, "".to_owned()).unwrap();
+        assert_eq!(
+            compiled_bytecode.bytecode,
+            "61000A565B01600052005B6001600261000456"
+        );
+    }
+
+    //compiles a contract that includes `./lib/bad.evmm` with the given contents on its second line
+    fn compile_with_include(test_name: &str, main: &str, included: &str) -> EVMMError {
+        let directory = std::env::temp_dir().join(format!("evmm_compile_{}", test_name));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(directory.join("lib")).unwrap();
+        std::fs::write(
+            directory.join("lib/bad.evmm"),
+            format!("//included\n{}", included),
+        )
+        .unwrap();
+
+        let program = crate::parser::include::parse_program_with_includes(
+            main,
+            &directory.join("Main.evmm"),
+            &[],
+        )
+        .unwrap();
+        match compile_instructions(&program, "".to_owned()) {
+            Ok(_) => panic!("expected an error"),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn test_not_enough_values_on_stack_in_included_file() {
+        match compile_with_include("stack", "#include \"./lib/bad.evmm\"", "PUSH1 0x00\nADD") {
+            EVMMError::InIncludedFile(location, error) => {
+                assert!(location.ends_with("bad.evmm:3"));
+                assert!(
+                    matches!(*error, EVMMError::NotEnoughValuesOnStack(instruction, 2, 1) if instruction == "ADD")
+                );
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn test_duplicate_label_in_included_file() {
+        match compile_with_include(
+            "duplicate_label",
+            "start:\n#include \"./lib/bad.evmm\"",
+            "start:",
+        ) {
+            EVMMError::InIncludedFile(location, error) => {
+                assert!(location.ends_with("bad.evmm:2"));
+                assert!(matches!(*error, EVMMError::DuplicateLabel(label) if label == "start"));
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn test_undefined_label_in_included_file() {
+        match compile_with_include(
+            "undefined_label",
+            "#include \"./lib/bad.evmm\"",
+            "PUSH2 nowhere\nJUMP",
+        ) {
+            EVMMError::InIncludedFile(location, error) => {
+                assert!(location.ends_with("bad.evmm:2"));
+                assert!(matches!(*error, EVMMError::UndefinedLabel(label) if label == "nowhere"));
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+
+        //errors in the contract itself are not wrapped
+        assert!(matches!(
+            compile_with_include(
+                "root",
+                "#include \"./lib/bad.evmm\"\nPUSH2 nowhere",
+                "JUMPDEST"
+            ),
+            EVMMError::UndefinedLabel(_)
+        ));
+    }
+
+    #[test]
+    fn test_compile_contract_source_map() {
+        let file = "PUSH1 0x01\nstart:\n  PUSH1 0x02\n  ADD";
+        let program = crate::parser::ast::parse_program(file).unwrap();
+        let compiled_bytecode = compile_instructions(&program, "".to_owned()).unwrap();
+
+        assert_eq!(compiled_bytecode.bytecode, "60015B600201");
+        assert_eq!(compiled_bytecode.labels, vec![(String::from("start"), 2)]);
+
+        let source_map: Vec<(usize, &str)> = compiled_bytecode
+            .source_map
+            .iter()
+            .map(|entry| {
+                (
+                    entry.bytecode_offset,
+                    &file[entry.source_start..entry.source_end],
+                )
+            })
+            .collect();
+        assert_eq!(
+            source_map,
+            vec![
+                (0, "PUSH1 0x01"),
+                (2, "start:"),
+                (3, "PUSH1 0x02"),
+                (5, "ADD")
+            ]
+        );
+    }
 }
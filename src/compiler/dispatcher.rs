@@ -0,0 +1,218 @@
+use crate::compiler::compile::compile_instruction;
+use crate::compiler::interface::FunctionDefinition;
+use crate::compiler::labels::Labels;
+use crate::evmm_error::evmm_error::EVMMError;
+use crate::parser::ast::DispatchMode;
+use crate::parser::parse::Rule;
+
+//When the binary search dispatcher is down to this many functions, the remaining selectors are compared one by one
+const BINARY_SEARCH_LEAF_SIZE: usize = 4;
+
+///Compiles `__DISPATCHER(fallback)` or `__DISPATCHER(fallback, binary)` into a selector dispatcher.
+///Each declared function jumps to the label with the same name as the function, and calls with an unknown selector
+///or less than 4 bytes of calldata jump to the fallback label. The selector is left on the stack in both cases.
+pub fn compile_dispatcher(
+    fallback_label: &str,
+    mode: DispatchMode,
+    functions: &[FunctionDefinition],
+    labels: &mut Labels,
+    contract_bytecode: &mut String,
+) -> Result<(), EVMMError> {
+    //sort the functions by selector so that the dispatcher output is deterministic and can be binary searched
+    let mut selectors: Vec<([u8; 4], &FunctionDefinition)> = functions
+        .iter()
+        .map(|function| (function.selector(), function))
+        .collect();
+    selectors.sort_by_key(|(selector, _)| *selector);
+
+    for pair in selectors.windows(2) {
+        if pair[0].0 == pair[1].0 {
+            return Err(EVMMError::DuplicateFunctionSelector(
+                pair[0].1.signature(),
+                pair[1].1.signature(),
+            ));
+        }
+    }
+
+    //load the selector from the first 4 bytes of calldata
+    push_value(contract_bytecode, Rule::push1, "00");
+    contract_bytecode.push_str(&compile_instruction(Rule::calldataload));
+    push_value(contract_bytecode, Rule::push1, "E0");
+    contract_bytecode.push_str(&compile_instruction(Rule::shr));
+
+    //jump to the fallback if the calldata is too short to contain a selector
+    push_value(contract_bytecode, Rule::push1, "04");
+    contract_bytecode.push_str(&compile_instruction(Rule::calldatasize));
+    contract_bytecode.push_str(&compile_instruction(Rule::lt));
+    push_label(contract_bytecode, labels, fallback_label);
+    contract_bytecode.push_str(&compile_instruction(Rule::jumpi));
+
+    if mode == DispatchMode::Binary {
+        compile_binary_search(&selectors, fallback_label, labels, contract_bytecode)
+    } else {
+        compile_linear_search(&selectors, fallback_label, labels, contract_bytecode);
+        Ok(())
+    }
+}
+
+///Compares the selector against each function in order, jumping to the fallback if none match
+fn compile_linear_search(
+    selectors: &[([u8; 4], &FunctionDefinition)],
+    fallback_label: &str,
+    labels: &mut Labels,
+    contract_bytecode: &mut String,
+) {
+    for (selector, function) in selectors {
+        contract_bytecode.push_str(&compile_instruction(Rule::dup1));
+        push_value(contract_bytecode, Rule::push4, &selector_to_hex(selector));
+        contract_bytecode.push_str(&compile_instruction(Rule::eq));
+        push_label(contract_bytecode, labels, &function.name);
+        contract_bytecode.push_str(&compile_instruction(Rule::jumpi));
+    }
+
+    push_label(contract_bytecode, labels, fallback_label);
+    contract_bytecode.push_str(&compile_instruction(Rule::jump));
+}
+
+///Splits the sorted selectors in half at a pivot, jumping to the lower half if the selector is less than the pivot
+fn compile_binary_search(
+    selectors: &[([u8; 4], &FunctionDefinition)],
+    fallback_label: &str,
+    labels: &mut Labels,
+    contract_bytecode: &mut String,
+) -> Result<(), EVMMError> {
+    if selectors.len() <= BINARY_SEARCH_LEAF_SIZE {
+        compile_linear_search(selectors, fallback_label, labels, contract_bytecode);
+        return Ok(());
+    }
+
+    let (lower_half, upper_half) = selectors.split_at(selectors.len() / 2);
+    let pivot = upper_half[0].0;
+
+    //`#` can not appear in an identifier, so the generated label can not collide with a label in the contract
+    let lower_half_label = format!("__dispatcher#{}", selector_to_hex(&pivot));
+
+    contract_bytecode.push_str(&compile_instruction(Rule::dup1));
+    push_value(contract_bytecode, Rule::push4, &selector_to_hex(&pivot));
+    contract_bytecode.push_str(&compile_instruction(Rule::gt));
+    push_label(contract_bytecode, labels, &lower_half_label);
+    contract_bytecode.push_str(&compile_instruction(Rule::jumpi));
+
+    compile_binary_search(upper_half, fallback_label, labels, contract_bytecode)?;
+
+    labels.define_label(&lower_half_label, contract_bytecode)?;
+    compile_binary_search(lower_half, fallback_label, labels, contract_bytecode)
+}
+
+fn push_value(contract_bytecode: &mut String, push_instruction: Rule, value: &str) {
+    contract_bytecode.push_str(&compile_instruction(push_instruction));
+    contract_bytecode.push_str(value);
+}
+
+fn push_label(contract_bytecode: &mut String, labels: &mut Labels, label: &str) {
+    contract_bytecode.push_str(&compile_instruction(Rule::push2));
+    labels.reference_label(label, "PUSH2", 2, contract_bytecode);
+}
+
+fn selector_to_hex(selector: &[u8; 4]) -> String {
+    selector
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compile::compile_instructions;
+    use crate::evmm_error::evmm_error::EVMMError;
+    use crate::parser::ast::parse_program;
+
+    #[test]
+    fn test_compile_linear_dispatcher() {
+        let file = r#"
+    #define function transfer(address,uint256) returns (bool)
+    #define function balanceOf(address) returns (uint256)
+
+    __DISPATCHER(fallback)
+
+    transfer:
+        STOP
+    balanceOf:
+        STOP
+    fallback:
+        PUSH1 0x00
+        DUP1
+        REVERT
+    "#;
+
+        let bytecode = compile_instructions(&parse_program(file).unwrap(), "".to_owned())
+            .unwrap()
+            .bytecode;
+
+        assert_eq!(
+            bytecode,
+            [
+                //load the selector and jump to the fallback if the calldata is too short
+                "60003560E01C",
+                "6004361061002C57",
+                //balanceOf(address)
+                "806370A082311461002A57",
+                //transfer(address,uint256)
+                "8063A9059CBB1461002857",
+                //no match
+                "61002C56",
+                //transfer, balanceOf and fallback labels
+                "5B00",
+                "5B00",
+                "5B600080FD",
+            ]
+            .join("")
+        );
+    }
+
+    #[test]
+    fn test_compile_binary_search_dispatcher() {
+        let file = r#"
+    #define function a()
+    #define function b()
+    #define function c()
+    #define function d()
+    #define function e()
+
+    __DISPATCHER(fallback, binary)
+
+    a: STOP
+    b: STOP
+    c: STOP
+    d: STOP
+    e: STOP
+    fallback: STOP
+    "#;
+
+        let bytecode = compile_instructions(&parse_program(file).unwrap(), "".to_owned())
+            .unwrap()
+            .bytecode;
+
+        //sorted selectors: a() 0x0dbe671f, b() 0x4df7e3d0, d() 0x8a054ac2, c() 0xc3da42b8, e() 0xffae15ba
+        //the pivot is the lowest selector of the upper half
+        assert!(bytecode.contains("80638A054AC21161"));
+        for selector in ["0DBE671F", "4DF7E3D0", "8A054AC2", "C3DA42B8", "FFAE15BA"] {
+            assert!(bytecode.contains(&format!("8063{}14", selector)));
+        }
+    }
+
+    #[test]
+    fn test_dispatcher_missing_function_label() {
+        let file = r#"
+    #define function transfer(address,uint256)
+    __DISPATCHER(fallback)
+    fallback:
+        STOP
+    "#;
+
+        assert!(matches!(
+            compile_instructions(&parse_program(file).unwrap(), "".to_owned()),
+            Err(EVMMError::UndefinedLabel(_))
+        ));
+    }
+}
@@ -0,0 +1,128 @@
+use crate::compiler::compile::{decode_hex, instruction_offsets};
+use crate::compiler::opcodes::mnemonic;
+use crate::evmm_error::evmm_error::EVMMError;
+
+///The hard forks that changed the instruction set, in order. Compiled bytecode can only use opcodes available in the target version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum EvmVersion {
+    Frontier,
+    Homestead,
+    Byzantium,
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    Paris,
+    Shanghai,
+    #[default]
+    Cancun,
+}
+
+impl EvmVersion {
+    pub const NAMES: [&'static str; 11] = [
+        "frontier",
+        "homestead",
+        "byzantium",
+        "constantinople",
+        "petersburg",
+        "istanbul",
+        "berlin",
+        "london",
+        "paris",
+        "shanghai",
+        "cancun",
+    ];
+
+    const VERSIONS: [EvmVersion; 11] = [
+        EvmVersion::Frontier,
+        EvmVersion::Homestead,
+        EvmVersion::Byzantium,
+        EvmVersion::Constantinople,
+        EvmVersion::Petersburg,
+        EvmVersion::Istanbul,
+        EvmVersion::Berlin,
+        EvmVersion::London,
+        EvmVersion::Paris,
+        EvmVersion::Shanghai,
+        EvmVersion::Cancun,
+    ];
+
+    pub fn from_name(name: &str) -> Option<EvmVersion> {
+        EvmVersion::NAMES
+            .iter()
+            .position(|version_name| *version_name == name)
+            .map(|index| EvmVersion::VERSIONS[index])
+    }
+
+    pub fn name(&self) -> &'static str {
+        EvmVersion::NAMES[*self as usize]
+    }
+}
+
+///Gets the version that introduced an opcode
+pub fn introduced_in(opcode: u8) -> EvmVersion {
+    match opcode {
+        //DELEGATECALL
+        0xF4 => EvmVersion::Homestead,
+        //RETURNDATASIZE, RETURNDATACOPY, STATICCALL and REVERT
+        0x3D | 0x3E | 0xFA | 0xFD => EvmVersion::Byzantium,
+        //SHL, SHR, SAR, EXTCODEHASH and CREATE2
+        0x1B | 0x1C | 0x1D | 0x3F | 0xF5 => EvmVersion::Constantinople,
+        //CHAINID and SELFBALANCE
+        0x46 | 0x47 => EvmVersion::Istanbul,
+        //BASEFEE
+        0x48 => EvmVersion::London,
+        //PUSH0
+        0x5F => EvmVersion::Shanghai,
+        //BLOBHASH, BLOBBASEFEE, TLOAD, TSTORE and MCOPY
+        0x49 | 0x4A | 0x5C | 0x5D | 0x5E => EvmVersion::Cancun,
+        _ => EvmVersion::Frontier,
+    }
+}
+
+///Checks that every opcode in the bytecode is available in the target version, including the opcodes generated for built-ins
+pub fn check_evm_version(bytecode: &str, evm_version: EvmVersion) -> Result<(), EVMMError> {
+    let bytes = decode_hex(bytecode).unwrap_or_default();
+
+    for offset in instruction_offsets(bytecode) {
+        let opcode = bytes[offset];
+
+        if introduced_in(opcode) > evm_version {
+            return Err(EVMMError::UnsupportedOpcode(
+                mnemonic(opcode).unwrap_or_default().to_owned(),
+                evm_version.name().to_owned(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evm_version_names() {
+        for name in EvmVersion::NAMES {
+            assert_eq!(EvmVersion::from_name(name).unwrap().name(), name);
+        }
+        assert_eq!(EvmVersion::from_name("prague"), None);
+        assert!(EvmVersion::Byzantium < EvmVersion::London);
+    }
+
+    #[test]
+    fn test_check_evm_version() {
+        //PUSH1 0xE0 PUSH1 0x00 CALLDATALOAD SHR
+        assert!(check_evm_version("60E06000351C", EvmVersion::Constantinople).is_ok());
+        assert!(matches!(
+            check_evm_version("60E06000351C", EvmVersion::Byzantium),
+            Err(EVMMError::UnsupportedOpcode(mnemonic, evm_version))
+                if mnemonic == "SHR" && evm_version == "byzantium"
+        ));
+
+        //the push data is not checked as opcodes
+        assert!(check_evm_version("6048", EvmVersion::Frontier).is_ok());
+    }
+}
@@ -0,0 +1,368 @@
+use crate::parser::parse::Rule;
+use crate::utils::keccak::keccak256;
+use pest::iterators::Pair;
+use serde_json::{json, Value};
+
+///A parameter of a declared function, event or error
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    //the type as it appears in a signature, ex. `(uint256,address)[]`
+    pub canonical_type: String,
+    //the type as it appears in the abi, ex. `tuple[]`
+    pub abi_type: String,
+    //the components of a tuple type
+    pub components: Vec<Parameter>,
+    pub indexed: bool,
+}
+
+impl Parameter {
+    pub fn from_pair(parameter: Pair<Rule>) -> Parameter {
+        let mut name = String::new();
+        let mut indexed = false;
+        let mut parameter_type = None;
+
+        for inner_pair in parameter.into_inner() {
+            match inner_pair.as_rule() {
+                Rule::abi_type => parameter_type = Some(inner_pair),
+                Rule::indexed => indexed = true,
+                Rule::identifier => name = inner_pair.as_str().to_owned(),
+                _ => {}
+            }
+        }
+
+        //the grammar requires a type for every parameter
+        let mut parameter = Parameter::from_abi_type(parameter_type.unwrap());
+        parameter.name = name;
+        parameter.indexed = indexed;
+        parameter
+    }
+
+    ///Converts an abi type to its canonical form, expanding aliases like `uint` and removing whitespace in tuples
+    fn from_abi_type(abi_type: Pair<Rule>) -> Parameter {
+        let mut canonical_type = String::new();
+        let mut json_abi_type = String::new();
+        let mut components = vec![];
+
+        for inner_pair in abi_type.into_inner() {
+            match inner_pair.as_rule() {
+                Rule::tuple_type => {
+                    components = inner_pair
+                        .into_inner()
+                        .map(Parameter::from_abi_type)
+                        .collect();
+
+                    let component_types: Vec<&str> = components
+                        .iter()
+                        .map(|component| component.canonical_type.as_str())
+                        .collect();
+                    canonical_type.push_str(&format!("({})", component_types.join(",")));
+                    json_abi_type.push_str("tuple");
+                }
+                Rule::elementary_type => {
+                    let elementary_type = match inner_pair.as_str() {
+                        "uint" => "uint256",
+                        "int" => "int256",
+                        elementary_type => elementary_type,
+                    };
+                    canonical_type.push_str(elementary_type);
+                    json_abi_type.push_str(elementary_type);
+                }
+                _ => {
+                    canonical_type.push_str(inner_pair.as_str());
+                    json_abi_type.push_str(inner_pair.as_str());
+                }
+            }
+        }
+
+        Parameter {
+            name: String::new(),
+            canonical_type,
+            abi_type: json_abi_type,
+            components,
+            indexed: false,
+        }
+    }
+
+    fn to_abi_json(&self, include_indexed: bool) -> Value {
+        let mut parameter = json!({
+            "name": self.name,
+            "type": self.abi_type,
+        });
+
+        if !self.components.is_empty() {
+            parameter["components"] = self
+                .components
+                .iter()
+                .map(|component| component.to_abi_json(false))
+                .collect();
+        }
+
+        if include_indexed {
+            parameter["indexed"] = json!(self.indexed);
+        }
+
+        parameter
+    }
+}
+
+///A function declared in the contract interface with `#define function`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub inputs: Vec<Parameter>,
+    pub outputs: Vec<Parameter>,
+    pub state_mutability: String,
+}
+
+impl FunctionDefinition {
+    pub fn new(
+        name: String,
+        inputs: Vec<Parameter>,
+        outputs: Vec<Parameter>,
+        state_mutability: String,
+    ) -> FunctionDefinition {
+        FunctionDefinition {
+            name,
+            inputs,
+            outputs,
+            state_mutability,
+        }
+    }
+
+    pub fn from_pair(function_definition: Pair<Rule>) -> FunctionDefinition {
+        let mut name = String::new();
+        let mut inputs = vec![];
+        let mut outputs = vec![];
+        let mut state_mutability = String::from("nonpayable");
+
+        for inner_pair in function_definition.into_inner() {
+            match inner_pair.as_rule() {
+                Rule::identifier => name = inner_pair.as_str().to_owned(),
+                Rule::inputs => inputs = parameters(inner_pair),
+                Rule::outputs => outputs = parameters(inner_pair),
+                Rule::state_mutability => state_mutability = inner_pair.as_str().to_owned(),
+                _ => {}
+            }
+        }
+
+        FunctionDefinition::new(name, inputs, outputs, state_mutability)
+    }
+
+    ///The canonical signature used to derive the selector, ex. `transfer(address,uint256)`
+    pub fn signature(&self) -> String {
+        signature(&self.name, &self.inputs)
+    }
+
+    ///The first four bytes of the keccak256 hash of the signature
+    pub fn selector(&self) -> [u8; 4] {
+        let mut selector = [0u8; 4];
+        selector.copy_from_slice(&keccak256(self.signature().as_bytes())[0..4]);
+        selector
+    }
+
+    fn to_abi_json(&self) -> Value {
+        json!({
+            "type": "function",
+            "name": self.name,
+            "inputs": self.inputs.iter().map(|input| input.to_abi_json(false)).collect::<Vec<Value>>(),
+            "outputs": self.outputs.iter().map(|output| output.to_abi_json(false)).collect::<Vec<Value>>(),
+            "stateMutability": self.state_mutability,
+        })
+    }
+}
+
+///An event declared with `#define event` or a custom error declared with `#define error`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventOrErrorDefinition {
+    pub name: String,
+    pub inputs: Vec<Parameter>,
+}
+
+impl EventOrErrorDefinition {
+    pub fn from_pair(definition: Pair<Rule>) -> EventOrErrorDefinition {
+        let mut name = String::new();
+        let mut inputs = vec![];
+
+        for inner_pair in definition.into_inner() {
+            match inner_pair.as_rule() {
+                Rule::identifier => name = inner_pair.as_str().to_owned(),
+                Rule::inputs => inputs = parameters(inner_pair),
+                _ => {}
+            }
+        }
+
+        EventOrErrorDefinition { name, inputs }
+    }
+
+    pub fn signature(&self) -> String {
+        signature(&self.name, &self.inputs)
+    }
+}
+
+///The functions, events and errors declared in a contract
+#[derive(Default)]
+pub struct Interface {
+    pub functions: Vec<FunctionDefinition>,
+    pub events: Vec<EventOrErrorDefinition>,
+    pub errors: Vec<EventOrErrorDefinition>,
+}
+
+impl Interface {
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty() && self.events.is_empty() && self.errors.is_empty()
+    }
+
+    ///Gets the signature of a declared function, event or error by name
+    pub fn signature_of(&self, name: &str) -> Option<String> {
+        self.functions
+            .iter()
+            .find(|function| function.name == name)
+            .map(|function| function.signature())
+            .or_else(|| {
+                self.events
+                    .iter()
+                    .chain(self.errors.iter())
+                    .find(|definition| definition.name == name)
+                    .map(|definition| definition.signature())
+            })
+    }
+
+    ///Builds a Solidity compatible abi, with entries in the order they were declared
+    pub fn to_abi_json(&self) -> Value {
+        let mut abi: Vec<Value> = self
+            .functions
+            .iter()
+            .map(|function| function.to_abi_json())
+            .collect();
+
+        for event in self.events.iter() {
+            abi.push(json!({
+                "type": "event",
+                "name": event.name,
+                "inputs": event.inputs.iter().map(|input| input.to_abi_json(true)).collect::<Vec<Value>>(),
+                "anonymous": false,
+            }));
+        }
+
+        for error in self.errors.iter() {
+            abi.push(json!({
+                "type": "error",
+                "name": error.name,
+                "inputs": error.inputs.iter().map(|input| input.to_abi_json(false)).collect::<Vec<Value>>(),
+            }));
+        }
+
+        Value::Array(abi)
+    }
+}
+
+fn signature(name: &str, inputs: &[Parameter]) -> String {
+    let input_types: Vec<&str> = inputs
+        .iter()
+        .map(|input| input.canonical_type.as_str())
+        .collect();
+
+    format!("{}({})", name, input_types.join(","))
+}
+
+fn parameters(parameters: Pair<Rule>) -> Vec<Parameter> {
+    let mut parsed_parameters = vec![];
+
+    for parameter_list in parameters.into_inner() {
+        for parameter in parameter_list.into_inner() {
+            parsed_parameters.push(Parameter::from_pair(parameter));
+        }
+    }
+
+    parsed_parameters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ast::parse_program;
+
+    #[test]
+    fn test_function_signature_and_selector() {
+        let interface =
+            parse_program("#define function transfer(address to, uint amount) returns (bool)")
+                .unwrap()
+                .interface();
+
+        assert_eq!(
+            interface.functions[0].signature(),
+            "transfer(address,uint256)"
+        );
+        assert_eq!(interface.functions[0].selector(), [0xa9, 0x05, 0x9c, 0xbb]);
+    }
+
+    #[test]
+    fn test_function_signature_with_tuples_and_arrays() {
+        let interface =
+            parse_program("#define function submit((uint256, address)[] orders, bytes32[2] proof)")
+                .unwrap()
+                .interface();
+
+        assert_eq!(
+            interface.functions[0].signature(),
+            "submit((uint256,address)[],bytes32[2])"
+        );
+    }
+
+    #[test]
+    fn test_abi_json() {
+        let interface = parse_program(
+            r#"
+    #define function balanceOf(address owner) view returns (uint256)
+    #define function submit((uint256, address)[] orders) payable
+    #define event Transfer(address indexed from, address indexed to, uint256 value)
+    #define error Unauthorized(address caller)
+    "#,
+        )
+        .unwrap()
+        .interface();
+
+        assert_eq!(
+            interface.to_abi_json(),
+            json!([
+                {
+                    "type": "function",
+                    "name": "balanceOf",
+                    "inputs": [{ "name": "owner", "type": "address" }],
+                    "outputs": [{ "name": "", "type": "uint256" }],
+                    "stateMutability": "view"
+                },
+                {
+                    "type": "function",
+                    "name": "submit",
+                    "inputs": [{
+                        "name": "orders",
+                        "type": "tuple[]",
+                        "components": [
+                            { "name": "", "type": "uint256" },
+                            { "name": "", "type": "address" }
+                        ]
+                    }],
+                    "outputs": [],
+                    "stateMutability": "payable"
+                },
+                {
+                    "type": "event",
+                    "name": "Transfer",
+                    "inputs": [
+                        { "name": "from", "type": "address", "indexed": true },
+                        { "name": "to", "type": "address", "indexed": true },
+                        { "name": "value", "type": "uint256", "indexed": false }
+                    ],
+                    "anonymous": false
+                },
+                {
+                    "type": "error",
+                    "name": "Unauthorized",
+                    "inputs": [{ "name": "caller", "type": "address" }]
+                }
+            ])
+        );
+    }
+}
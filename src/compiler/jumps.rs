@@ -0,0 +1,194 @@
+use crate::compiler::compile::{decode_hex, instruction_offsets};
+use crate::compiler::opcodes::{opcode_info, push_size};
+use crate::compiler::stack::BlockStack;
+use primitive_types::U256;
+
+const JUMP: u8 = 0x56;
+const JUMPI: u8 = 0x57;
+const JUMPDEST: u8 = 0x5B;
+
+///Why a jump destination known at compile time is not valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidDestination {
+    //past the end of the bytecode
+    OutOfBounds,
+    //inside the data of a push instruction, even if the byte there is 0x5B
+    PushData(usize),
+    //an instruction other than JUMPDEST
+    NotJumpdest(u8),
+}
+
+///The jumps in the bytecode whose destination is known at compile time but is not a JUMPDEST, and the jumps whose
+///destination depends on values only known when the contract runs
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct JumpTargets {
+    //the offset of the jump, its destination and why the destination is not valid
+    pub invalid: Vec<(usize, U256, InvalidDestination)>,
+    //the offset of each jump that could not be resolved
+    pub dynamic: Vec<usize>,
+}
+
+///Finds the destination of every JUMP and JUMPI by following the constants pushed in each basic block through
+///DUP, SWAP and POP. The values on the stack when a block starts are not known, so a destination pushed in one block
+///and jumped to in another, such as a return address, is dynamic.
+pub fn check_jump_targets(bytecode: &str) -> JumpTargets {
+    let bytes = decode_hex(bytecode).unwrap_or_default();
+    let is_instruction = instruction_starts(bytecode, bytes.len());
+
+    let mut jump_targets = JumpTargets::default();
+    for (offset, destination) in resolve_jumps(bytecode, &bytes) {
+        match destination {
+            Some(destination) => {
+                if let Some(reason) = invalid_destination(&bytes, &is_instruction, destination) {
+                    jump_targets.invalid.push((offset, destination, reason));
+                }
+            }
+            None => jump_targets.dynamic.push(offset),
+        }
+    }
+
+    jump_targets
+}
+
+///Gets the offset of every JUMP and JUMPI whose destination is known at compile time and is a JUMPDEST, along with
+///the destination
+pub fn static_jumps(bytecode: &str) -> Vec<(usize, usize)> {
+    let bytes = decode_hex(bytecode).unwrap_or_default();
+    let is_instruction = instruction_starts(bytecode, bytes.len());
+
+    resolve_jumps(bytecode, &bytes)
+        .into_iter()
+        .filter_map(|(offset, destination)| {
+            let destination = destination?;
+            match invalid_destination(&bytes, &is_instruction, destination) {
+                Some(_) => None,
+                None => Some((offset, destination.as_usize())),
+            }
+        })
+        .collect()
+}
+
+//the byte at each offset is an instruction rather than push data
+fn instruction_starts(bytecode: &str, length: usize) -> Vec<bool> {
+    let mut is_instruction = vec![false; length];
+    for offset in instruction_offsets(bytecode) {
+        is_instruction[offset] = true;
+    }
+    is_instruction
+}
+
+//the offset of every jump and its destination, or None if the destination is not known at compile time
+fn resolve_jumps(bytecode: &str, bytes: &[u8]) -> Vec<(usize, Option<U256>)> {
+    let mut jumps = vec![];
+    //the values on the stack that are constants known at compile time
+    let mut stack: BlockStack<Option<U256>> = BlockStack::new();
+
+    for offset in instruction_offsets(bytecode) {
+        let opcode = bytes[offset];
+
+        //a block starts at every JUMPDEST, which can be reached with any stack
+        if opcode == JUMPDEST {
+            stack.clear();
+            continue;
+        }
+
+        //PUSH0 through PUSH32, which can run past the end of the bytecode, in which case the data is zero padded
+        let pushed_value = matches!(opcode, 0x5F..=0x7F).then(|| {
+            let size = push_size(opcode);
+            let mut data = [0; 32];
+            for (index, byte) in bytes[offset + 1..].iter().take(size).enumerate() {
+                data[32 - size + index] = *byte;
+            }
+            U256::from_big_endian(&data)
+        });
+
+        let inputs = stack.execute(opcode, |_| pushed_value);
+        if opcode == JUMP || opcode == JUMPI {
+            jumps.push((offset, inputs[0]));
+        }
+
+        //JUMP, STOP, RETURN, REVERT, INVALID, SELFDESTRUCT and undefined opcodes end the block
+        if matches!(opcode, JUMP | 0x00 | 0xF3 | 0xFD | 0xFE | 0xFF)
+            || opcode_info(opcode).is_none()
+        {
+            stack.clear();
+        }
+    }
+
+    jumps
+}
+
+fn invalid_destination(
+    bytes: &[u8],
+    is_instruction: &[bool],
+    destination: U256,
+) -> Option<InvalidDestination> {
+    if destination >= U256::from(bytes.len()) {
+        return Some(InvalidDestination::OutOfBounds);
+    }
+
+    let destination = destination.as_usize();
+    if !is_instruction[destination] {
+        //the push instruction is the closest instruction before the destination
+        let push_offset = (0..destination)
+            .rev()
+            .find(|offset| is_instruction[*offset])
+            .unwrap_or_default();
+        Some(InvalidDestination::PushData(push_offset))
+    } else if bytes[destination] != JUMPDEST {
+        Some(InvalidDestination::NotJumpdest(bytes[destination]))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_jumps_to_jumpdests() {
+        //PUSH1 0x04 JUMP STOP JUMPDEST PUSH1 0x01 PUSH1 0x04 JUMPI
+        let jump_targets = check_jump_targets("600456005B6001600457");
+        assert_eq!(jump_targets, JumpTargets::default());
+    }
+
+    #[test]
+    fn test_invalid_static_jumps() {
+        //PUSH1 0x03 JUMP STOP, which jumps to STOP
+        assert_eq!(
+            check_jump_targets("60035600").invalid,
+            vec![(2, U256::from(3), InvalidDestination::NotJumpdest(0x00))]
+        );
+
+        //PUSH1 0x04 JUMP PUSH1 0x5B, which jumps into the push data even though it is 0x5B
+        assert_eq!(
+            check_jump_targets("600456605B").invalid,
+            vec![(2, U256::from(4), InvalidDestination::PushData(3))]
+        );
+
+        //PUSH2 0x0100 DUP1 SWAP1 POP JUMP, followed through DUP, SWAP and POP to a destination past the end
+        assert_eq!(
+            check_jump_targets("61010080905056").invalid,
+            vec![(6, U256::from(0x100), InvalidDestination::OutOfBounds)]
+        );
+    }
+
+    #[test]
+    fn test_dynamic_jumps() {
+        //CALLDATASIZE JUMP, then a JUMPDEST that jumps to a value from before the block started
+        let jump_targets = check_jump_targets("36565B56");
+        assert!(jump_targets.invalid.is_empty());
+        assert_eq!(jump_targets.dynamic, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_static_jumps() {
+        //PUSH1 0x04 JUMP STOP JUMPDEST CALLDATASIZE JUMP PUSH1 0x03 JUMP PUSH1 0x04 JUMP STOP, leaving out the dynamic
+        //jump and the jump to STOP
+        assert_eq!(
+            static_jumps("600456005B365660035660045600"),
+            vec![(2, 4), (12, 4)]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use crate::compiler::compile::compile_instruction;
+use crate::evmm_error::evmm_error::EVMMError;
+use crate::parser::parse::Rule;
+use std::collections::HashMap;
+
+///A push instruction whose value is the offset of a label, filled in once every label in the contract is known
+struct LabelReference {
+    label: String,
+    push_instruction: String,
+    bytecode_index: usize,
+    size: usize,
+}
+
+#[derive(Default)]
+pub struct Labels {
+    offsets: HashMap<String, usize>,
+    references: Vec<LabelReference>,
+}
+
+impl Labels {
+    pub fn new() -> Labels {
+        Labels::default()
+    }
+
+    ///Defines a label at the current end of the bytecode and adds a JUMPDEST so that the label is a valid jump destination
+    pub fn define_label(
+        &mut self,
+        label: &str,
+        contract_bytecode: &mut String,
+    ) -> Result<(), EVMMError> {
+        if self.offsets.contains_key(label) {
+            return Err(EVMMError::DuplicateLabel(label.to_owned()));
+        }
+
+        //each byte is two hex characters
+        self.offsets
+            .insert(label.to_owned(), contract_bytecode.len() / 2);
+        contract_bytecode.push_str(&compile_instruction(Rule::jumpdest));
+
+        Ok(())
+    }
+
+    ///Gets every defined label and its offset, sorted by offset
+    pub fn offsets(&self) -> Vec<(String, usize)> {
+        let mut offsets: Vec<(String, usize)> = self
+            .offsets
+            .iter()
+            .map(|(label, offset)| (label.clone(), *offset))
+            .collect();
+        offsets.sort_by_key(|(_, offset)| *offset);
+        offsets
+    }
+
+    ///Gets the offset of every push instruction that references a label, sorted by offset
+    pub fn references(&self) -> Vec<(String, usize)> {
+        let mut references: Vec<(String, usize)> = self
+            .references
+            .iter()
+            //the push value placeholder starts one byte after the push opcode
+            .map(|reference| (reference.label.clone(), reference.bytecode_index / 2 - 1))
+            .collect();
+        references.sort_by_key(|(_, offset)| *offset);
+        references
+    }
+
+    pub fn is_defined(&self, label: &str) -> bool {
+        self.offsets.contains_key(label)
+    }
+
+    ///Adds a zeroed placeholder for the push value to the bytecode, which is replaced with the label offset in `resolve_labels`
+    pub fn reference_label(
+        &mut self,
+        label: &str,
+        push_instruction: &str,
+        size: usize,
+        contract_bytecode: &mut String,
+    ) {
+        self.references.push(LabelReference {
+            label: label.to_owned(),
+            push_instruction: push_instruction.to_owned(),
+            bytecode_index: contract_bytecode.len(),
+            size,
+        });
+
+        contract_bytecode.push_str(&"00".repeat(size));
+    }
+
+    ///Replaces every label placeholder in the bytecode with the offset of the label it references
+    pub fn resolve_labels(&self, contract_bytecode: &mut String) -> Result<(), EVMMError> {
+        for reference in self.references.iter() {
+            let offset = match self.offsets.get(&reference.label) {
+                Some(offset) => *offset,
+                None => return Err(EVMMError::UndefinedLabel(reference.label.clone())),
+            };
+
+            let padded_offset = format!("{:0width$X}", offset, width = reference.size * 2);
+
+            if padded_offset.len() > reference.size * 2 {
+                return Err(EVMMError::ValueTooBigForPushInstruction(
+                    reference.push_instruction.clone(),
+                    reference.label.clone(),
+                    padded_offset.len() / 2,
+                ));
+            }
+
+            contract_bytecode.replace_range(
+                reference.bytecode_index..reference.bytecode_index + padded_offset.len(),
+                &padded_offset,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_forward_label_reference() {
+        let mut labels = Labels::new();
+        let mut contract_bytecode = String::from("61");
+
+        labels.reference_label("end", "PUSH2", 2, &mut contract_bytecode);
+        contract_bytecode.push_str("56");
+        labels.define_label("end", &mut contract_bytecode).unwrap();
+        labels.resolve_labels(&mut contract_bytecode).unwrap();
+
+        assert_eq!(contract_bytecode, "610004565B");
+        assert_eq!(labels.references(), vec![(String::from("end"), 0)]);
+    }
+
+    #[test]
+    fn test_undefined_label() {
+        let mut labels = Labels::new();
+        let mut contract_bytecode = String::from("61");
+
+        labels.reference_label("missing", "PUSH2", 2, &mut contract_bytecode);
+
+        assert!(matches!(
+            labels.resolve_labels(&mut contract_bytecode),
+            Err(EVMMError::UndefinedLabel(_))
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_label() {
+        let mut labels = Labels::new();
+        let mut contract_bytecode = String::new();
+
+        labels
+            .define_label("start", &mut contract_bytecode)
+            .unwrap();
+
+        assert!(matches!(
+            labels.define_label("start", &mut contract_bytecode),
+            Err(EVMMError::DuplicateLabel(_))
+        ));
+    }
+}
@@ -1 +1,9 @@
+pub mod builtins;
 pub mod compile;
+pub mod dispatcher;
+pub mod evm_version;
+pub mod interface;
+pub mod jumps;
+pub mod labels;
+pub mod opcodes;
+pub mod stack;
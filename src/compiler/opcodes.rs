@@ -0,0 +1,341 @@
+///The metadata of an opcode needed to disassemble and annotate bytecode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    pub mnemonic: &'static str,
+    //the number of values the opcode takes from the stack
+    pub stack_inputs: usize,
+    //the number of values the opcode leaves on the stack
+    pub stack_outputs: usize,
+    //the minimum gas charged for the opcode
+    pub static_gas: u64,
+    //true if the opcode costs more than the static gas depending on its inputs, memory expansion or account access
+    pub dynamic_gas: bool,
+}
+
+///Gets the metadata of an opcode, or None if the byte is not a known opcode
+pub fn opcode_info(opcode: u8) -> Option<OpcodeInfo> {
+    let (mnemonic, stack_inputs, stack_outputs, static_gas, dynamic_gas) = match opcode {
+        0x00 => ("STOP", 0, 0, 0, false),
+        0x01 => ("ADD", 2, 1, 3, false),
+        0x02 => ("MUL", 2, 1, 5, false),
+        0x03 => ("SUB", 2, 1, 3, false),
+        0x04 => ("DIV", 2, 1, 5, false),
+        0x05 => ("SDIV", 2, 1, 5, false),
+        0x06 => ("MOD", 2, 1, 5, false),
+        0x07 => ("SMOD", 2, 1, 5, false),
+        0x08 => ("ADDMOD", 3, 1, 8, false),
+        0x09 => ("MULMOD", 3, 1, 8, false),
+        0x0A => ("EXP", 2, 1, 10, true),
+        0x0B => ("SIGNEXTEND", 2, 1, 5, false),
+        0x10 => ("LT", 2, 1, 3, false),
+        0x11 => ("GT", 2, 1, 3, false),
+        0x12 => ("SLT", 2, 1, 3, false),
+        0x13 => ("SGT", 2, 1, 3, false),
+        0x14 => ("EQ", 2, 1, 3, false),
+        0x15 => ("ISZERO", 1, 1, 3, false),
+        0x16 => ("AND", 2, 1, 3, false),
+        0x17 => ("OR", 2, 1, 3, false),
+        0x18 => ("XOR", 2, 1, 3, false),
+        0x19 => ("NOT", 1, 1, 3, false),
+        0x1A => ("BYTE", 2, 1, 3, false),
+        0x1B => ("SHL", 2, 1, 3, false),
+        0x1C => ("SHR", 2, 1, 3, false),
+        0x1D => ("SAR", 2, 1, 3, false),
+        0x20 => ("KECCAK256", 2, 1, 30, true),
+        0x30 => ("ADDRESS", 0, 1, 2, false),
+        0x31 => ("BALANCE", 1, 1, 100, true),
+        0x32 => ("ORIGIN", 0, 1, 2, false),
+        0x33 => ("CALLER", 0, 1, 2, false),
+        0x34 => ("CALLVALUE", 0, 1, 2, false),
+        0x35 => ("CALLDATALOAD", 1, 1, 3, false),
+        0x36 => ("CALLDATASIZE", 0, 1, 2, false),
+        0x37 => ("CALLDATACOPY", 3, 0, 3, true),
+        0x38 => ("CODESIZE", 0, 1, 2, false),
+        0x39 => ("CODECOPY", 3, 0, 3, true),
+        0x3A => ("GASPRICE", 0, 1, 2, false),
+        0x3B => ("EXTCODESIZE", 1, 1, 100, true),
+        0x3C => ("EXTCODECOPY", 4, 0, 100, true),
+        0x3D => ("RETURNDATASIZE", 0, 1, 2, false),
+        0x3E => ("RETURNDATACOPY", 3, 0, 3, true),
+        0x3F => ("EXTCODEHASH", 1, 1, 100, true),
+        0x40 => ("BLOCKHASH", 1, 1, 20, false),
+        0x41 => ("COINBASE", 0, 1, 2, false),
+        0x42 => ("TIMESTAMP", 0, 1, 2, false),
+        0x43 => ("NUMBER", 0, 1, 2, false),
+        0x44 => ("DIFFICULTY", 0, 1, 2, false),
+        0x45 => ("GASLIMIT", 0, 1, 2, false),
+        0x46 => ("CHAINID", 0, 1, 2, false),
+        0x47 => ("SELFBALANCE", 0, 1, 5, false),
+        0x48 => ("BASEFEE", 0, 1, 2, false),
+        0x49 => ("BLOBHASH", 1, 1, 3, false),
+        0x4A => ("BLOBBASEFEE", 0, 1, 2, false),
+        0x50 => ("POP", 1, 0, 2, false),
+        0x51 => ("MLOAD", 1, 1, 3, true),
+        0x52 => ("MSTORE", 2, 0, 3, true),
+        0x53 => ("MSTORE8", 2, 0, 3, true),
+        0x54 => ("SLOAD", 1, 1, 100, true),
+        0x55 => ("SSTORE", 2, 0, 100, true),
+        0x56 => ("JUMP", 1, 0, 8, false),
+        0x57 => ("JUMPI", 2, 0, 10, false),
+        0x58 => ("PC", 0, 1, 2, false),
+        0x59 => ("MSIZE", 0, 1, 2, false),
+        0x5A => ("GAS", 0, 1, 2, false),
+        0x5B => ("JUMPDEST", 0, 0, 1, false),
+        0x5C => ("TLOAD", 1, 1, 100, false),
+        0x5D => ("TSTORE", 2, 0, 100, false),
+        0x5E => ("MCOPY", 3, 0, 3, true),
+        0x5F => ("PUSH0", 0, 1, 2, false),
+        0x60 => ("PUSH1", 0, 1, 3, false),
+        0x61 => ("PUSH2", 0, 1, 3, false),
+        0x62 => ("PUSH3", 0, 1, 3, false),
+        0x63 => ("PUSH4", 0, 1, 3, false),
+        0x64 => ("PUSH5", 0, 1, 3, false),
+        0x65 => ("PUSH6", 0, 1, 3, false),
+        0x66 => ("PUSH7", 0, 1, 3, false),
+        0x67 => ("PUSH8", 0, 1, 3, false),
+        0x68 => ("PUSH9", 0, 1, 3, false),
+        0x69 => ("PUSH10", 0, 1, 3, false),
+        0x6A => ("PUSH11", 0, 1, 3, false),
+        0x6B => ("PUSH12", 0, 1, 3, false),
+        0x6C => ("PUSH13", 0, 1, 3, false),
+        0x6D => ("PUSH14", 0, 1, 3, false),
+        0x6E => ("PUSH15", 0, 1, 3, false),
+        0x6F => ("PUSH16", 0, 1, 3, false),
+        0x70 => ("PUSH17", 0, 1, 3, false),
+        0x71 => ("PUSH18", 0, 1, 3, false),
+        0x72 => ("PUSH19", 0, 1, 3, false),
+        0x73 => ("PUSH20", 0, 1, 3, false),
+        0x74 => ("PUSH21", 0, 1, 3, false),
+        0x75 => ("PUSH22", 0, 1, 3, false),
+        0x76 => ("PUSH23", 0, 1, 3, false),
+        0x77 => ("PUSH24", 0, 1, 3, false),
+        0x78 => ("PUSH25", 0, 1, 3, false),
+        0x79 => ("PUSH26", 0, 1, 3, false),
+        0x7A => ("PUSH27", 0, 1, 3, false),
+        0x7B => ("PUSH28", 0, 1, 3, false),
+        0x7C => ("PUSH29", 0, 1, 3, false),
+        0x7D => ("PUSH30", 0, 1, 3, false),
+        0x7E => ("PUSH31", 0, 1, 3, false),
+        0x7F => ("PUSH32", 0, 1, 3, false),
+        0x80 => ("DUP1", 1, 2, 3, false),
+        0x81 => ("DUP2", 2, 3, 3, false),
+        0x82 => ("DUP3", 3, 4, 3, false),
+        0x83 => ("DUP4", 4, 5, 3, false),
+        0x84 => ("DUP5", 5, 6, 3, false),
+        0x85 => ("DUP6", 6, 7, 3, false),
+        0x86 => ("DUP7", 7, 8, 3, false),
+        0x87 => ("DUP8", 8, 9, 3, false),
+        0x88 => ("DUP9", 9, 10, 3, false),
+        0x89 => ("DUP10", 10, 11, 3, false),
+        0x8A => ("DUP11", 11, 12, 3, false),
+        0x8B => ("DUP12", 12, 13, 3, false),
+        0x8C => ("DUP13", 13, 14, 3, false),
+        0x8D => ("DUP14", 14, 15, 3, false),
+        0x8E => ("DUP15", 15, 16, 3, false),
+        0x8F => ("DUP16", 16, 17, 3, false),
+        0x90 => ("SWAP1", 2, 2, 3, false),
+        0x91 => ("SWAP2", 3, 3, 3, false),
+        0x92 => ("SWAP3", 4, 4, 3, false),
+        0x93 => ("SWAP4", 5, 5, 3, false),
+        0x94 => ("SWAP5", 6, 6, 3, false),
+        0x95 => ("SWAP6", 7, 7, 3, false),
+        0x96 => ("SWAP7", 8, 8, 3, false),
+        0x97 => ("SWAP8", 9, 9, 3, false),
+        0x98 => ("SWAP9", 10, 10, 3, false),
+        0x99 => ("SWAP10", 11, 11, 3, false),
+        0x9A => ("SWAP11", 12, 12, 3, false),
+        0x9B => ("SWAP12", 13, 13, 3, false),
+        0x9C => ("SWAP13", 14, 14, 3, false),
+        0x9D => ("SWAP14", 15, 15, 3, false),
+        0x9E => ("SWAP15", 16, 16, 3, false),
+        0x9F => ("SWAP16", 17, 17, 3, false),
+        0xA0 => ("LOG0", 2, 0, 375, true),
+        0xA1 => ("LOG1", 3, 0, 750, true),
+        0xA2 => ("LOG2", 4, 0, 1125, true),
+        0xA3 => ("LOG3", 5, 0, 1500, true),
+        0xA4 => ("LOG4", 6, 0, 1875, true),
+        0xF0 => ("CREATE", 3, 1, 32000, true),
+        0xF1 => ("CALL", 7, 1, 100, true),
+        0xF2 => ("CALLCODE", 7, 1, 100, true),
+        0xF3 => ("RETURN", 2, 0, 0, true),
+        0xF4 => ("DELEGATECALL", 6, 1, 100, true),
+        0xF5 => ("CREATE2", 4, 1, 32000, true),
+        0xFA => ("STATICCALL", 6, 1, 100, true),
+        0xFD => ("REVERT", 2, 0, 0, true),
+        0xFE => ("INVALID", 0, 0, 0, false),
+        0xFF => ("SELFDESTRUCT", 1, 0, 5000, true),
+        _ => return None,
+    };
+
+    Some(OpcodeInfo {
+        mnemonic,
+        stack_inputs,
+        stack_outputs,
+        static_gas,
+        dynamic_gas,
+    })
+}
+
+///Describes what an opcode does, or None if the byte is not a known opcode
+pub fn opcode_description(opcode: u8) -> Option<&'static str> {
+    let description = match opcode {
+        0x00 => "Halts execution",
+        0x01 => "Adds the top two values, wrapping on overflow",
+        0x02 => "Multiplies the top two values, wrapping on overflow",
+        0x03 => "Subtracts the second value from the top value, wrapping on underflow",
+        0x04 => "Divides the top value by the second value, or 0 if the second value is 0",
+        0x05 => "Divides the top value by the second value as signed integers, or 0 if the second value is 0",
+        0x06 => "The remainder of the top value divided by the second value, or 0 if the second value is 0",
+        0x07 => "The signed remainder of the top value divided by the second value, or 0 if the second value is 0",
+        0x08 => "Adds the top two values modulo the third value, without wrapping the sum",
+        0x09 => "Multiplies the top two values modulo the third value, without wrapping the product",
+        0x0A => "Raises the top value to the power of the second value, wrapping on overflow",
+        0x0B => "Extends the sign of the second value from the byte at the index of the top value",
+        0x10 => "1 if the top value is less than the second value, otherwise 0",
+        0x11 => "1 if the top value is greater than the second value, otherwise 0",
+        0x12 => "1 if the top value is less than the second value as signed integers, otherwise 0",
+        0x13 => "1 if the top value is greater than the second value as signed integers, otherwise 0",
+        0x14 => "1 if the top two values are equal, otherwise 0",
+        0x15 => "1 if the top value is 0, otherwise 0",
+        0x16 => "Bitwise AND of the top two values",
+        0x17 => "Bitwise OR of the top two values",
+        0x18 => "Bitwise XOR of the top two values",
+        0x19 => "Bitwise NOT of the top value",
+        0x1A => "The byte of the second value at the index of the top value, counting from the most significant byte",
+        0x1B => "Shifts the second value left by the number of bits of the top value",
+        0x1C => "Shifts the second value right by the number of bits of the top value",
+        0x1D => "Shifts the second value right by the number of bits of the top value, keeping its sign",
+        0x20 => "The Keccak-256 hash of the memory starting at the top value, with the length of the second value",
+        0x30 => "The address of the running contract",
+        0x31 => "The balance of the account at the top value",
+        0x32 => "The address that signed the transaction",
+        0x33 => "The address of the caller",
+        0x34 => "The value sent with the call, in wei",
+        0x35 => "The 32 bytes of calldata starting at the offset of the top value, padded with zeros",
+        0x36 => "The size of the calldata in bytes",
+        0x37 => "Copies calldata to memory, taking the memory offset, the calldata offset and the size",
+        0x38 => "The size of the code of the running contract in bytes",
+        0x39 => "Copies the code of the running contract to memory, taking the memory offset, the code offset and the size",
+        0x3A => "The gas price of the transaction",
+        0x3B => "The size of the code of the account at the top value",
+        0x3C => "Copies the code of an account to memory, taking the address, the memory offset, the code offset and the size",
+        0x3D => "The size of the data returned by the last call",
+        0x3E => "Copies the data returned by the last call to memory, taking the memory offset, the data offset and the size",
+        0x3F => "The hash of the code of the account at the top value",
+        0x40 => "The hash of the block with the number of the top value, for one of the 256 most recent blocks",
+        0x41 => "The address of the block producer",
+        0x42 => "The timestamp of the block",
+        0x43 => "The number of the block",
+        0x44 => "The difficulty of the block, or the randomness of the beacon chain (PREVRANDAO) since the merge",
+        0x45 => "The gas limit of the block",
+        0x46 => "The chain id",
+        0x47 => "The balance of the running contract",
+        0x48 => "The base fee of the block",
+        0x49 => "The versioned hash of the blob at the index of the top value",
+        0x4A => "The blob base fee of the block",
+        0x50 => "Removes the top value",
+        0x51 => "The 32 bytes of memory starting at the offset of the top value",
+        0x52 => "Stores the second value in the 32 bytes of memory starting at the offset of the top value",
+        0x53 => "Stores the lowest byte of the second value in memory at the offset of the top value",
+        0x54 => "The value in the storage slot of the top value",
+        0x55 => "Stores the second value in the storage slot of the top value",
+        0x56 => "Jumps to the offset of the top value, which must be a JUMPDEST",
+        0x57 => "Jumps to the offset of the top value if the second value is not 0",
+        0x58 => "The offset of this instruction in the bytecode",
+        0x59 => "The size of the memory in use, in bytes",
+        0x5A => "The gas left after this instruction",
+        0x5B => "Marks a valid jump destination",
+        0x5C => "The value in the transient storage slot of the top value",
+        0x5D => "Stores the second value in the transient storage slot of the top value",
+        0x5E => "Copies memory, taking the destination offset, the source offset and the size",
+        0x5F => "Pushes 0",
+        0x60..=0x7F => "Pushes the value that follows the instruction",
+        0x80..=0x8F => "Duplicates a value on the stack, where DUP1 duplicates the top value",
+        0x90..=0x9F => "Swaps the top value with a value below it, where SWAP1 swaps the top two values",
+        0xA0..=0xA4 => "Emits a log of the memory starting at the top value with the length of the second value, followed by its topics",
+        0xF0 => "Creates a contract, taking the value to send, the memory offset and the size of the deployment bytecode",
+        0xF1 => "Calls an account, taking the gas, address, value, calldata offset and size, and the offset and size to copy the returned data to. Pushes 1 if the call succeeded, otherwise 0",
+        0xF2 => "Runs the code of an account in the context of the running contract, taking the same values as CALL",
+        0xF3 => "Halts execution, returning the memory starting at the top value with the length of the second value",
+        0xF4 => "Runs the code of an account in the context of the running contract with the same caller and value, taking the same values as CALL without the value",
+        0xF5 => "Creates a contract at an address derived from a salt, taking the value to send, the memory offset and size of the deployment bytecode and the salt",
+        0xFA => "Calls an account without allowing it to change state, taking the same values as CALL without the value",
+        0xFD => "Halts execution and reverts its changes, returning the memory starting at the top value with the length of the second value",
+        0xFE => "Halts execution with an error, using up all the gas",
+        0xFF => "Sends the balance of the running contract to the address of the top value, and deletes the contract if it was created in the same transaction",
+        _ => return None,
+    };
+
+    Some(description)
+}
+
+///Gets the mnemonic of an opcode, or None if the byte is not a known opcode
+pub fn mnemonic(opcode: u8) -> Option<&'static str> {
+    opcode_info(opcode).map(|opcode_info| opcode_info.mnemonic)
+}
+
+///Gets the number of bytes of push data that follow an opcode
+pub fn push_size(opcode: u8) -> usize {
+    if (0x60..=0x7F).contains(&opcode) {
+        (opcode - 0x5F) as usize
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile::compile_instruction;
+    use crate::parser::parse::Rule;
+
+    #[test]
+    fn test_mnemonic_matches_compiled_instruction() {
+        for (rule, expected_mnemonic) in [
+            (Rule::stop, "STOP"),
+            (Rule::selfbalance, "SELFBALANCE"),
+            (Rule::jumpdest, "JUMPDEST"),
+            (Rule::push1, "PUSH1"),
+            (Rule::push32, "PUSH32"),
+            (Rule::dup16, "DUP16"),
+            (Rule::swap1, "SWAP1"),
+            (Rule::log4, "LOG4"),
+            (Rule::selfdestruct, "SELFDESTRUCT"),
+        ] {
+            let opcode = u8::from_str_radix(&compile_instruction(rule), 16).unwrap();
+            assert_eq!(mnemonic(opcode), Some(expected_mnemonic));
+        }
+    }
+
+    #[test]
+    fn test_opcode_info() {
+        let log2 = opcode_info(0xA2).unwrap();
+        assert_eq!(log2.mnemonic, "LOG2");
+        assert_eq!((log2.stack_inputs, log2.stack_outputs), (4, 0));
+        assert_eq!(log2.static_gas, 1125);
+        assert!(log2.dynamic_gas);
+
+        let swap3 = opcode_info(0x92).unwrap();
+        assert_eq!((swap3.stack_inputs, swap3.stack_outputs), (4, 4));
+        assert!(!swap3.dynamic_gas);
+
+        assert_eq!(opcode_info(0x0C), None);
+    }
+
+    #[test]
+    fn test_every_opcode_has_a_description() {
+        for opcode in 0..=u8::MAX {
+            assert_eq!(
+                opcode_info(opcode).is_some(),
+                opcode_description(opcode).is_some()
+            );
+        }
+    }
+
+    #[test]
+    fn test_push_size() {
+        assert_eq!(push_size(0x60), 1);
+        assert_eq!(push_size(0x7F), 32);
+        assert_eq!(push_size(0x5B), 0);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::compiler::opcodes::opcode_info;
+
+///The values on the stack in a basic block, as whatever the analysis tracks about each value. The values that were
+///already on the stack when the block was entered are not known, so they are the default value, ex. `None` for a
+///constant that is not known at compile time.
+#[derive(Debug, Default)]
+pub struct BlockStack<T> {
+    //the top of the stack is the last value
+    values: Vec<T>,
+}
+
+impl<T: Clone + Default> BlockStack<T> {
+    pub fn new() -> BlockStack<T> {
+        BlockStack { values: vec![] }
+    }
+
+    ///Forgets every value, ex. at a JUMPDEST, which can be reached with any stack
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    ///Applies the stack effect of an instruction and returns the values it takes from the stack, from the top of the
+    ///stack down. DUP copies a value and SWAP exchanges two values without taking them. Other opcodes take their inputs
+    ///and leave `output` of the inputs for each of their outputs. Unknown opcodes do not change the stack.
+    pub fn execute(&mut self, opcode: u8, output: impl FnOnce(&[T]) -> T) -> Vec<T> {
+        match opcode {
+            //DUP1 through DUP16
+            0x80..=0x8F => {
+                let depth = (opcode - 0x7F) as usize;
+                let value = self
+                    .values
+                    .len()
+                    .checked_sub(depth)
+                    .map(|index| self.values[index].clone())
+                    .unwrap_or_default();
+                self.values.push(value);
+                vec![]
+            }
+
+            //SWAP1 through SWAP16, filling in the values below the known ones
+            0x90..=0x9F => {
+                let depth = (opcode - 0x8F) as usize;
+                if self.values.len() <= depth {
+                    let missing = depth + 1 - self.values.len();
+                    self.values.splice(0..0, vec![T::default(); missing]);
+                }
+                let top = self.values.len() - 1;
+                self.values.swap(top, top - depth);
+                vec![]
+            }
+
+            _ => {
+                let (stack_inputs, stack_outputs) = match opcode_info(opcode) {
+                    Some(opcode_info) => (opcode_info.stack_inputs, opcode_info.stack_outputs),
+                    None => (0, 0),
+                };
+
+                let inputs: Vec<T> = (0..stack_inputs)
+                    .map(|_| self.values.pop().unwrap_or_default())
+                    .collect();
+                if stack_outputs > 0 {
+                    let value = output(&inputs);
+                    for _ in 0..stack_outputs {
+                        self.values.push(value.clone());
+                    }
+                }
+                inputs
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execute() {
+        let mut stack: BlockStack<Option<u8>> = BlockStack::new();
+
+        //PUSH1 0x01 PUSH1 0x02
+        stack.execute(0x60, |_| Some(1));
+        stack.execute(0x60, |_| Some(2));
+        //SWAP2 fills in the value that was on the stack when the block was entered
+        assert_eq!(stack.execute(0x91, |_| None), vec![]);
+        assert_eq!(stack.values, vec![Some(2), Some(1), None]);
+        //DUP4 copies a value from below the known ones
+        stack.execute(0x83, |_| None);
+        assert_eq!(stack.values, vec![Some(2), Some(1), None, None]);
+        //ADD takes the two values at the top and leaves their sum
+        assert_eq!(
+            stack.execute(0x01, |inputs| Some(inputs.iter().flatten().sum())),
+            vec![None, None]
+        );
+        assert_eq!(stack.values, vec![Some(2), Some(1), Some(0)]);
+        //JUMPI takes the destination and the condition, and leaves nothing
+        assert_eq!(stack.execute(0x57, |_| Some(9)), vec![Some(0), Some(1)]);
+        assert_eq!(stack.values, vec![Some(2)]);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::compiler::opcodes::mnemonic;
+use crate::core::contract::{compile_file, CompiledContract};
+use crate::core::evmm::{contract_name, get_contract_paths};
+use crate::core::manifest::Manifest;
+use crate::core::test_runner::{deploy_contract, DEFAULT_CALLER};
+use crate::evm::abi::{format_hex, parse_hex};
+use crate::evm::interpreter::{Evm, ExitStatus, HaltReason, Step, Tracer, Transaction};
+use crate::evm::state::Address;
+use crate::evm::symbolic::{explore, Exploration, Finding, Issue, SymbolicContext, MAX_PATHS};
+use crate::evmm_error::evmm_error::EVMMError;
+
+///Explores every path of a call to each contract, or only to the contract at `contract_path` if it is given, and
+///prints the paths that reach INVALID, jump to an offset that is not a JUMPDEST, underflow the stack or reach
+///SELFDESTRUCT, with a transaction that reaches each of them
+pub fn analyze_contracts(manifest: &Manifest, contract_path: &str) -> Result<(), EVMMError> {
+    let mut issues_found = 0;
+
+    for contract_path in get_contract_paths(manifest, contract_path, "")? {
+        let name = contract_name(&contract_path.path);
+        let compiled_contract =
+            compile_file(&contract_path.path, &manifest.compile_options(&name))?;
+
+        println!("Analyzing {}", contract_path.path.display());
+        let (evm, address, exploration) =
+            analyze_contract(&compiled_contract).map_err(|error| {
+                EVMMError::AnalysisFailed(contract_path.path.display().to_string(), error)
+            })?;
+
+        for finding in exploration.findings.iter() {
+            let reproduced = reproduce(&evm, &address, finding);
+            print_finding(&compiled_contract, finding, reproduced);
+            issues_found += 1;
+        }
+
+        let mut summary = format!(
+            "{} paths explored, {} issues found",
+            exploration.paths,
+            exploration.findings.len()
+        );
+        if !exploration.complete {
+            summary.push_str(&format!(
+                ", stopped after {} paths so some paths were not explored",
+                MAX_PATHS
+            ));
+        }
+        println!("{}", summary);
+
+        if !exploration.unexplored.is_empty() {
+            println!("Paths that were not explored to the end:");
+            for (offset, reason) in exploration.unexplored.iter() {
+                println!(
+                    "    {}: {}",
+                    instruction_location(&compiled_contract, *offset),
+                    reason
+                );
+            }
+        }
+        println!();
+    }
+
+    if issues_found > 0 {
+        Err(EVMMError::IssuesFound(issues_found))
+    } else {
+        Ok(())
+    }
+}
+
+///Deploys the contract from the default caller, then explores every path of a call to it
+pub fn analyze_contract(
+    compiled_contract: &CompiledContract,
+) -> Result<(Evm, Address, Exploration), String> {
+    let (evm, address) = deploy_contract(compiled_contract)?;
+    let code = evm.state.code(&address).to_vec();
+
+    let exploration = explore(
+        &code,
+        &SymbolicContext {
+            state: &evm.state,
+            block: &evm.block,
+            address,
+            caller: DEFAULT_CALLER,
+        },
+    );
+
+    Ok((evm, address, exploration))
+}
+
+//the last instruction the called contract ran
+struct LastStepTracer {
+    last_pc: Option<usize>,
+}
+
+impl Tracer for LastStepTracer {
+    fn step(&mut self, step: &Step) {
+        if step.depth == 0 {
+            self.last_pc = Some(step.pc);
+        }
+    }
+}
+
+///Runs the transaction of a finding on the interpreter, from the state the contract was explored in, returning true
+///if the call stops at the instruction of the finding for the reason it was reported
+pub fn reproduce(evm: &Evm, address: &Address, finding: &Finding) -> bool {
+    let witness = &finding.witness;
+    let mut evm = Evm::new(evm.state.clone(), evm.block.clone());
+    let balance = evm.state.balance(&witness.caller);
+    evm.state.account_mut(&witness.caller).balance = balance.saturating_add(witness.value);
+
+    let mut tracer = LastStepTracer { last_pc: None };
+    let result = evm.transact_with_tracer(
+        &Transaction::new(
+            witness.caller,
+            Some(*address),
+            witness.value,
+            witness.calldata.clone(),
+        ),
+        &mut tracer,
+    );
+
+    let expected_status = match finding.issue {
+        Issue::InvalidOpcode(opcode) => {
+            matches!(result.status, ExitStatus::Halt(HaltReason::InvalidOpcode(halt_opcode)) if halt_opcode == opcode)
+        }
+        Issue::InvalidJump(_) => {
+            matches!(result.status, ExitStatus::Halt(HaltReason::InvalidJump(_)))
+        }
+        Issue::StackUnderflow => result.status == ExitStatus::Halt(HaltReason::StackUnderflow),
+        Issue::Selfdestruct => result.is_success(),
+    };
+
+    expected_status && tracer.last_pc == Some(finding.pc)
+}
+
+fn print_finding(compiled_contract: &CompiledContract, finding: &Finding, reproduced: bool) {
+    let witness = &finding.witness;
+
+    println!(
+        "{} {}",
+        instruction_location(compiled_contract, finding.pc),
+        finding.issue
+    );
+
+    //names the function the calldata calls, if the contract declares it
+    let function = compiled_contract
+        .interface
+        .functions
+        .iter()
+        .find(|function| witness.calldata.get(..4) == Some(&function.selector()[..]));
+    if let Some(function) = function {
+        println!("    function: {}", function.signature());
+    }
+    println!("    calldata: {}", format_hex(&witness.calldata));
+    println!("    caller: {}", format_hex(witness.caller.as_bytes()));
+    println!("    value: {}", witness.value);
+    if !reproduced {
+        println!("    the transaction did not reproduce the issue on the interpreter");
+    }
+}
+
+//the source line and mnemonic of an instruction in the runtime bytecode, ex. `src/Counter.evmm:12 JUMP (pc 0x1f)`
+fn instruction_location(compiled_contract: &CompiledContract, offset: usize) -> String {
+    //the compiler only produces valid hex
+    let runtime =
+        parse_hex(&format!("0x{}", compiled_contract.runtime.bytecode)).unwrap_or_default();
+    let instruction = match runtime.get(offset) {
+        Some(opcode) => mnemonic(*opcode)
+            .map(String::from)
+            .unwrap_or_else(|| format!("0x{:02x}", opcode)),
+        //running past the end of the code is a STOP
+        None => String::from("STOP"),
+    };
+
+    match compiled_contract.source_line(offset) {
+        Some((source, line_number)) => format!(
+            "{}:{} {} (pc {:#x})",
+            source.path, line_number, instruction, offset
+        ),
+        None => format!("{} (pc {:#x})", instruction, offset),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::contract::{compile_str, CompileOptions};
+
+    #[test]
+    fn test_finds_and_reproduces_invalid_jump() {
+        //jumps to the value of the first argument when it is 3
+        let source = r#"#define function jump(uint256)
+
+__DISPATCHER(fallback)
+
+jump:
+    PUSH1 0x04
+    CALLDATALOAD
+    DUP1
+    PUSH1 0x03
+    EQ
+    PUSH2 target
+    JUMPI
+    STOP
+
+target:
+    JUMP
+
+fallback:
+    PUSH1 0x00
+    DUP1
+    REVERT
+"#;
+        let compiled_contract = compile_str("Jump", source, &CompileOptions::default()).unwrap();
+        let (evm, address, exploration) = analyze_contract(&compiled_contract).unwrap();
+
+        assert!(exploration.complete);
+        assert_eq!(exploration.findings.len(), 1);
+        let finding = &exploration.findings[0];
+        assert_eq!(finding.issue, Issue::InvalidJump(3.into()));
+        assert_eq!(
+            finding.witness.calldata[..4],
+            compiled_contract.interface.functions[0].selector()
+        );
+        assert!(reproduce(&evm, &address, finding));
+        assert_eq!(
+            instruction_location(&compiled_contract, finding.pc),
+            format!("Jump.evmm:16 JUMP (pc {:#x})", finding.pc)
+        );
+    }
+
+    #[test]
+    fn test_no_findings_in_safe_contract() {
+        let source = r#"__DISPATCHER(fallback)
+
+fallback:
+    CALLVALUE
+    PUSH1 0x00
+    SSTORE
+    STOP
+"#;
+        let compiled_contract = compile_str("Safe", source, &CompileOptions::default()).unwrap();
+        let (_, _, exploration) = analyze_contract(&compiled_contract).unwrap();
+
+        assert!(exploration.complete);
+        assert!(exploration.findings.is_empty());
+    }
+}
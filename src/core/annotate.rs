@@ -0,0 +1,307 @@
+use crate::compiler::compile::CompiledBytecode;
+use crate::compiler::opcodes::{opcode_info, push_size, OpcodeInfo};
+use std::collections::HashMap;
+use std::fmt;
+
+//symbolic values longer than this are replaced by the mnemonic and offset of the instruction that produced them
+const MAX_SYMBOLIC_VALUE_LENGTH: usize = 48;
+
+///The values on the stack at a point in a basic block, as expressions of the instructions that produced them.
+///Values that were already on the stack when the block was entered are named `$0`, `$1`, ... from the top of the stack down.
+#[derive(Default)]
+pub(crate) struct SymbolicStack {
+    //the top of the stack is the last value
+    values: Vec<String>,
+    entry_values: usize,
+}
+
+impl SymbolicStack {
+    pub(crate) fn reset(&mut self) {
+        *self = SymbolicStack::default();
+    }
+
+    ///Makes sure at least `depth` values are known by naming the values that were on the stack when the block was entered
+    fn ensure_depth(&mut self, depth: usize) {
+        while self.values.len() < depth {
+            self.values.insert(0, format!("${}", self.entry_values));
+            self.entry_values += 1;
+        }
+    }
+
+    pub(crate) fn push(&mut self, value: String) {
+        self.values.push(value);
+    }
+
+    fn pop(&mut self) -> String {
+        self.ensure_depth(1);
+        self.values.pop().unwrap()
+    }
+
+    fn dup(&mut self, depth: usize) {
+        self.ensure_depth(depth);
+        let value = self.values[self.values.len() - depth].clone();
+        self.values.push(value);
+    }
+
+    fn swap(&mut self, depth: usize) {
+        self.ensure_depth(depth + 1);
+        let top = self.values.len() - 1;
+        self.values.swap(top, top - depth);
+    }
+}
+
+impl fmt::Display for SymbolicStack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let values: Vec<&str> = self
+            .values
+            .iter()
+            .rev()
+            .map(|value| value.as_str())
+            .collect();
+        write!(f, "[{}]", values.join(", "))
+    }
+}
+
+///Disassembles the bytecode into a listing of the offset, bytes, mnemonic with its push value, static gas and symbolic stack after each instruction.
+///Labels are written on their own line before their JUMPDEST and label offsets that are pushed are shown by name.
+///`runtime_offset` is the offset of the compiled contract within `bytecode`, ex. the size of the constructor for deployment bytecode.
+pub fn annotated_listing(
+    bytecode: &[u8],
+    compiled_bytecode: &CompiledBytecode,
+    runtime_offset: usize,
+) -> String {
+    let labels: HashMap<usize, &str> = compiled_bytecode
+        .labels
+        .iter()
+        .map(|(label, offset)| (offset + runtime_offset, label.as_str()))
+        .collect();
+    let label_references: HashMap<usize, &str> = compiled_bytecode
+        .label_references
+        .iter()
+        .map(|(label, offset)| (offset + runtime_offset, label.as_str()))
+        .collect();
+
+    //each row is either a label or the offset, bytes, instruction, gas and stack columns of an instruction
+    let mut rows: Vec<Result<[String; 5], String>> = vec![Ok([
+        String::from("offset"),
+        String::from("bytes"),
+        String::from("instruction"),
+        String::from("gas"),
+        String::from("stack"),
+    ])];
+
+    let mut stack = SymbolicStack::default();
+
+    let mut offset = 0;
+    while offset < bytecode.len() {
+        let opcode = bytecode[offset];
+        //push data can be cut short at the end of the bytecode
+        let end = (offset + 1 + push_size(opcode)).min(bytecode.len());
+        let push_value = format!("0x{}", to_hex(&bytecode[offset + 1..end]));
+
+        if let Some(label) = labels.get(&offset) {
+            rows.push(Err(format!("{}:", label)));
+        }
+
+        let (instruction, gas) = match opcode_info(opcode) {
+            Some(opcode_info) => {
+                let mut instruction = opcode_info.mnemonic.to_owned();
+                //the value pushed onto the stack, using the label name for label offsets
+                let mut pushed_value = push_value.clone();
+
+                if end > offset + 1 {
+                    instruction.push_str(&format!(" {}", push_value));
+
+                    if let Some(label) = label_references.get(&offset) {
+                        instruction.push_str(&format!(" ({})", label));
+                        pushed_value = label.to_string();
+                    }
+                }
+
+                execute(
+                    opcode,
+                    &opcode_info,
+                    pushed_value,
+                    &format!("{:04x}", offset),
+                    &mut stack,
+                );
+
+                let gas = if opcode_info.dynamic_gas {
+                    format!("{}+", opcode_info.static_gas)
+                } else {
+                    opcode_info.static_gas.to_string()
+                };
+
+                (instruction, gas)
+            }
+            None => {
+                //executing an unknown opcode reverts like INVALID
+                stack.reset();
+                (format!("UNKNOWN 0x{:02x}", opcode), String::from("0"))
+            }
+        };
+
+        let formatted_bytes: Vec<String> = bytecode[offset..end]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        rows.push(Ok([
+            format!("{:04x}", offset),
+            formatted_bytes.join(" "),
+            instruction,
+            gas,
+            stack.to_string(),
+        ]));
+
+        offset = end;
+    }
+
+    let column_width = |column: usize| {
+        rows.iter()
+            .filter_map(|row| row.as_ref().ok())
+            .map(|row| row[column].len())
+            .max()
+            .unwrap_or(0)
+    };
+    let offset_width = column_width(0);
+    let bytes_width = column_width(1);
+    let instruction_width = column_width(2);
+    let gas_width = column_width(3);
+
+    let mut listing = String::new();
+    for row in rows {
+        match row {
+            Ok([offset, bytes, instruction, gas, stack]) => listing.push_str(&format!(
+                "{:<offset_width$}  {:<bytes_width$}  {:<instruction_width$}  {:>gas_width$}  {}\n",
+                offset, bytes, instruction, gas, stack
+            )),
+            Err(label) => listing.push_str(&format!("{}\n", label)),
+        }
+    }
+
+    listing
+}
+
+///Applies the stack effect of an instruction to the symbolic stack. Values that are too long are named by the mnemonic
+///and `location` of the instruction, ex. `add@001f` for the instruction at offset 0x1F.
+pub(crate) fn execute(
+    opcode: u8,
+    opcode_info: &OpcodeInfo,
+    pushed_value: String,
+    location: &str,
+    stack: &mut SymbolicStack,
+) {
+    match opcode {
+        //PUSH1 - PUSH32
+        0x60..=0x7F => stack.push(pushed_value),
+
+        //DUP1 - DUP16
+        0x80..=0x8F => stack.dup((opcode - 0x7F) as usize),
+
+        //SWAP1 - SWAP16
+        0x90..=0x9F => stack.swap((opcode - 0x8F) as usize),
+
+        //JUMPDEST starts a new basic block that can be reached from anywhere, so the stack is unknown
+        0x5B => stack.reset(),
+
+        //STOP, JUMP, RETURN, REVERT, INVALID and SELFDESTRUCT end the block, so the following code starts with an unknown stack
+        0x00 | 0x56 | 0xF3 | 0xFD | 0xFE | 0xFF => stack.reset(),
+
+        _ => {
+            let inputs: Vec<String> = (0..opcode_info.stack_inputs).map(|_| stack.pop()).collect();
+
+            //only instructions that leave a value on the stack need an expression
+            if opcode_info.stack_outputs == 1 {
+                let name = opcode_info.mnemonic.to_lowercase();
+
+                let value = if inputs.is_empty() {
+                    name
+                } else {
+                    format!("{}({})", name, inputs.join(", "))
+                };
+
+                if value.len() > MAX_SYMBOLIC_VALUE_LENGTH {
+                    stack.push(format!(
+                        "{}@{}",
+                        opcode_info.mnemonic.to_lowercase(),
+                        location
+                    ));
+                } else {
+                    stack.push(value);
+                }
+            }
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile::{compile_instructions, decode_hex};
+    use crate::parser::ast::parse_program;
+
+    fn annotate(source: &str) -> String {
+        let compiled_bytecode =
+            compile_instructions(&parse_program(source).unwrap(), "".to_owned()).unwrap();
+
+        annotated_listing(
+            &decode_hex(&compiled_bytecode.bytecode).unwrap(),
+            &compiled_bytecode,
+            0,
+        )
+    }
+
+    #[test]
+    fn test_annotated_listing() {
+        let listing = annotate(
+            r#"
+    PUSH1 0x20
+    PUSH1 0x00
+    CALLDATALOAD
+    ADD
+    PUSH1 0x00
+    MSTORE
+    "#,
+        );
+
+        assert_eq!(
+            listing,
+            "offset  bytes  instruction   gas  stack\n\
+             0000    60 20  PUSH1 0x20      3  [0x20]\n\
+             0002    60 00  PUSH1 0x00      3  [0x00, 0x20]\n\
+             0004    35     CALLDATALOAD    3  [calldataload(0x00), 0x20]\n\
+             0005    01     ADD             3  [add(calldataload(0x00), 0x20)]\n\
+             0006    60 00  PUSH1 0x00      3  [0x00, add(calldataload(0x00), 0x20)]\n\
+             0008    52     MSTORE         3+  []\n"
+        );
+    }
+
+    #[test]
+    fn test_annotated_listing_labels() {
+        let listing = annotate(
+            r#"
+    PUSH1 0x01
+    PUSH1 0x02
+    PUSH2 end
+    JUMP
+    end:
+        DUP1
+        SWAP2
+        STOP
+    "#,
+        );
+        let lines: Vec<&str> = listing.lines().collect();
+
+        assert!(lines[3].contains("PUSH2 0x0008 (end)"));
+        assert!(lines[3].ends_with("[end, 0x02, 0x01]"));
+        assert!(lines[4].ends_with("[]"));
+        assert_eq!(lines[5], "end:");
+        assert!(lines[7].ends_with("[$0, $0]"));
+        assert!(lines[8].ends_with("[$1, $0, $0]"));
+    }
+}
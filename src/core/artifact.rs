@@ -0,0 +1,177 @@
+use crate::compiler::compile::{CompiledBytecode, SourceMapEntry};
+use crate::compiler::interface::Interface;
+use crate::core::contract::CompileOptions;
+use crate::parser::ast::SourceFile;
+use crate::utils::keccak::keccak256;
+use serde_json::{json, Map, Value};
+
+pub const COMPILER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+///Builds the json build artifact for a contract. The layout follows the Hardhat artifact format, which Foundry can also load
+///with `vm.getCode`, with the EVMM specific source map, label table, compiler version and source hash added alongside.
+///The sources are the contract followed by the files it includes, in the order used by the file field of the source map.
+pub fn build_artifact(
+    contract_name: &str,
+    source_name: &str,
+    sources: &[SourceFile],
+    deployment_bytecode: &str,
+    compiled_bytecode: &CompiledBytecode,
+    interface: &Interface,
+    options: &CompileOptions,
+) -> Value {
+    let mut method_identifiers = Map::new();
+    for function in interface.functions.iter() {
+        method_identifiers.insert(function.signature(), json!(to_hex(&function.selector())));
+    }
+
+    let mut labels = Map::new();
+    for (label, offset) in compiled_bytecode.labels.iter() {
+        labels.insert(label.clone(), json!(offset));
+    }
+
+    let source = sources
+        .first()
+        .map(|source| source.contents.as_str())
+        .unwrap_or_default();
+    let source_paths: Vec<&str> = sources.iter().map(|source| source.path.as_str()).collect();
+
+    json!({
+        "_format": "hh-sol-artifact-1",
+        "contractName": contract_name,
+        "sourceName": source_name,
+        "abi": interface.to_abi_json(),
+        "bytecode": format!("0x{}", deployment_bytecode.to_lowercase()),
+        "deployedBytecode": format!("0x{}", compiled_bytecode.bytecode.to_lowercase()),
+        "linkReferences": {},
+        "deployedLinkReferences": {},
+        "methodIdentifiers": method_identifiers,
+        "deployedSourceMap": solc_source_map(&compiled_bytecode.source_map),
+        "sources": source_paths,
+        "labels": labels,
+        "compiler": {
+            "name": "evmm",
+            "version": COMPILER_VERSION,
+            "settings": {
+                "evmVersion": options.evm_version.name(),
+                "optimizer": {
+                    "enabled": options.optimizer.enabled,
+                    "runs": options.optimizer.runs,
+                },
+            },
+        },
+        "sourceHash": format!("0x{}", to_hex(&keccak256(source.as_bytes()))),
+    })
+}
+
+///Encodes the source map in the compressed `start:length:file:jump` format used by solc, where fields that are
+///unchanged from the previous instruction are left empty
+pub fn solc_source_map(source_map: &[SourceMapEntry]) -> String {
+    let mut previous_fields: Vec<String> = vec![];
+    let mut encoded_entries = vec![];
+
+    for entry in source_map {
+        let fields = vec![
+            entry.source_start.to_string(),
+            (entry.source_end - entry.source_start).to_string(),
+            entry.source_index.to_string(),
+            String::from("-"),
+        ];
+
+        let compressed_fields: Vec<&str> = fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                if previous_fields.get(index) == Some(field) {
+                    ""
+                } else {
+                    field.as_str()
+                }
+            })
+            .collect();
+
+        encoded_entries.push(compressed_fields.join(":").trim_end_matches(':').to_owned());
+        previous_fields = fields;
+    }
+
+    encoded_entries.join(";")
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile::{compile_deployment_bytecode, compile_instructions};
+    use crate::parser::ast::parse_program;
+
+    #[test]
+    fn test_build_artifact() {
+        let source = r#"#define function stop()
+__DISPATCHER(stop)
+stop:
+    STOP
+"#;
+        let program = parse_program(source).unwrap();
+        let interface = program.interface();
+        let compiled_bytecode = compile_instructions(&program, "".to_owned()).unwrap();
+        let deployment_bytecode = compile_deployment_bytecode(&compiled_bytecode.bytecode).unwrap();
+
+        let artifact = build_artifact(
+            "Stop",
+            "evmm_contracts/Stop.evmm",
+            &program.sources,
+            &deployment_bytecode,
+            &compiled_bytecode,
+            &interface,
+            &CompileOptions::default(),
+        );
+
+        assert_eq!(artifact["contractName"], "Stop");
+        assert_eq!(artifact["abi"][0]["name"], "stop");
+        assert_eq!(artifact["methodIdentifiers"]["stop()"], "07da68f5");
+        assert_eq!(artifact["labels"]["stop"], 29);
+        assert_eq!(artifact["sources"], json!([""]));
+        assert_eq!(
+            artifact["deployedBytecode"],
+            format!("0x{}", compiled_bytecode.bytecode.to_lowercase())
+        );
+        assert!(artifact["bytecode"]
+            .as_str()
+            .unwrap()
+            .ends_with(&compiled_bytecode.bytecode.to_lowercase()));
+    }
+
+    #[test]
+    fn test_solc_source_map_compression() {
+        let source_map = vec![
+            SourceMapEntry {
+                bytecode_offset: 0,
+                source_start: 0,
+                source_end: 10,
+                source_index: 0,
+            },
+            SourceMapEntry {
+                bytecode_offset: 2,
+                source_start: 11,
+                source_end: 21,
+                source_index: 0,
+            },
+            SourceMapEntry {
+                bytecode_offset: 4,
+                source_start: 11,
+                source_end: 21,
+                source_index: 0,
+            },
+            SourceMapEntry {
+                bytecode_offset: 5,
+                source_start: 22,
+                source_end: 25,
+                source_index: 0,
+            },
+        ];
+
+        assert_eq!(solc_source_map(&source_map), "0:10:0:-;11;;22:3");
+    }
+}
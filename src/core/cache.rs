@@ -0,0 +1,188 @@
+use crate::core::artifact::COMPILER_VERSION;
+use crate::core::contract::CompileOptions;
+use crate::core::format::OutputFormat;
+use crate::parser::ast::SourceFile;
+use crate::utils::keccak::keccak256;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+pub const CACHE_FILE_NAME: &str = ".evmm_cache.json";
+
+///Records the sources, options and output files of each compiled contract, so that contracts are only recompiled when
+///the contract, a file it includes or the options it is compiled with have changed. The cache is kept in the output
+///directory as `.evmm_cache.json`.
+pub struct BuildCache {
+    path: PathBuf,
+    //cache entries by the canonical path of the contract
+    contracts: Map<String, Value>,
+}
+
+impl BuildCache {
+    ///Loads the cache from the output directory. A missing or unreadable cache, or a cache written by another version
+    ///of the compiler, is treated as empty.
+    pub fn load(output_directory: &Path) -> BuildCache {
+        let path = output_directory.join(CACHE_FILE_NAME);
+
+        let contracts = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+            .filter(|cache| cache["compilerVersion"] == COMPILER_VERSION)
+            .and_then(|cache| cache["contracts"].as_object().cloned())
+            .unwrap_or_default();
+
+        BuildCache { path, contracts }
+    }
+
+    ///Checks whether the contract was compiled with the same options, its sources have not changed since and its
+    ///output files still exist
+    pub fn is_up_to_date(
+        &self,
+        contract_path: &Path,
+        options_key: &str,
+        output_directory: &Path,
+    ) -> bool {
+        let entry = match self.contracts.get(&cache_path(contract_path)) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        if entry["options"] != options_key {
+            return false;
+        }
+
+        let sources_unchanged = entry["sources"].as_object().is_some_and(|sources| {
+            sources.iter().all(|(source_path, source_hash)| {
+                fs::read(source_path).is_ok_and(|contents| *source_hash == hash(&contents))
+            })
+        });
+
+        let outputs_exist = entry["outputs"].as_array().is_some_and(|outputs| {
+            outputs.iter().all(|output| {
+                output
+                    .as_str()
+                    .is_some_and(|output| output_directory.join(output).is_file())
+            })
+        });
+
+        sources_unchanged && outputs_exist
+    }
+
+    ///Records a compiled contract, where the sources are the contract followed by every file it includes and the output
+    ///files are relative to the output directory
+    pub fn insert(
+        &mut self,
+        contract_path: &Path,
+        options_key: &str,
+        sources: &[SourceFile],
+        output_files: &[PathBuf],
+    ) {
+        let mut source_hashes = Map::new();
+        for source in sources {
+            source_hashes.insert(
+                cache_path(Path::new(&source.path)),
+                json!(hash(source.contents.as_bytes())),
+            );
+        }
+
+        let output_files: Vec<String> = output_files
+            .iter()
+            .map(|output_file| output_file.display().to_string())
+            .collect();
+
+        self.contracts.insert(
+            cache_path(contract_path),
+            json!({
+                "options": options_key,
+                "sources": source_hashes,
+                "outputs": output_files,
+            }),
+        );
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let cache = json!({
+            "compilerVersion": COMPILER_VERSION,
+            "contracts": self.contracts,
+        });
+
+        fs::write(&self.path, serde_json::to_string_pretty(&cache).unwrap())
+    }
+}
+
+///Identifies everything about how a contract is compiled that changes its output
+pub fn options_key(options: &CompileOptions, output_formats: &[OutputFormat]) -> String {
+    hash(format!("{} {:?} {:?}", COMPILER_VERSION, options, output_formats).as_bytes())
+}
+
+//paths are canonicalized so that the cache works no matter which directory the compiler is run from
+fn cache_path(path: &Path) -> String {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+fn hash(contents: &[u8]) -> String {
+    keccak256(contents)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_build_cache() {
+        let directory = env::temp_dir().join("evmm_build_cache");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(directory.join("out")).unwrap();
+
+        let contract_path = directory.join("Token.evmm");
+        let include_path = directory.join("ownable.evmm");
+        fs::write(&contract_path, "#include \"./ownable.evmm\"").unwrap();
+        fs::write(&include_path, "CALLER").unwrap();
+        fs::write(directory.join("out/Token.evmasm"), "33").unwrap();
+
+        let options = CompileOptions::default();
+        let key = options_key(&options, &[OutputFormat::Hex]);
+        let sources = vec![
+            SourceFile::new(
+                contract_path.display().to_string(),
+                String::from("#include \"./ownable.evmm\""),
+            ),
+            SourceFile::new(include_path.display().to_string(), String::from("CALLER")),
+        ];
+
+        let mut cache = BuildCache::load(&directory.join("out"));
+        assert!(!cache.is_up_to_date(&contract_path, &key, &directory.join("out")));
+
+        cache.insert(
+            &contract_path,
+            &key,
+            &sources,
+            &[PathBuf::from("Token.evmasm")],
+        );
+        cache.save().unwrap();
+
+        let cache = BuildCache::load(&directory.join("out"));
+        assert!(cache.is_up_to_date(&contract_path, &key, &directory.join("out")));
+
+        //the options changed
+        let deployment_key = options_key(&CompileOptions::new(true, OutputFormat::Hex), &[]);
+        assert!(!cache.is_up_to_date(&contract_path, &deployment_key, &directory.join("out")));
+
+        //an included file changed
+        fs::write(&include_path, "ORIGIN").unwrap();
+        assert!(!cache.is_up_to_date(&contract_path, &key, &directory.join("out")));
+        fs::write(&include_path, "CALLER").unwrap();
+
+        //an output file was deleted
+        fs::remove_file(directory.join("out/Token.evmasm")).unwrap();
+        assert!(!cache.is_up_to_date(&contract_path, &key, &directory.join("out")));
+    }
+}
@@ -0,0 +1,454 @@
+use crate::compiler::compile::{
+    compile_deployment_bytecode, compile_instructions, decode_hex, CompiledBytecode,
+};
+use crate::compiler::evm_version::{check_evm_version, EvmVersion};
+use crate::compiler::interface::Interface;
+use crate::compiler::jumps::{check_jump_targets, InvalidDestination};
+use crate::compiler::opcodes::mnemonic;
+use crate::core::artifact::build_artifact;
+use crate::core::format::{format_bytecode, OutputFormat};
+use crate::evm::gas::MAX_CODE_SIZE;
+use crate::evmm_error::evmm_error::EVMMError;
+use crate::parser::ast::SourceFile;
+use crate::parser::include::parse_program_with_includes;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+///Options that control how a contract is compiled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileOptions {
+    //use the deployment bytecode instead of the runtime bytecode for the formatted output
+    pub deployment_bytecode: bool,
+    pub output_format: OutputFormat,
+    //the compiled bytecode can only use opcodes available in this version
+    pub evm_version: EvmVersion,
+    pub optimizer: OptimizerSettings,
+    //directories searched for included files that are not found relative to the file including them
+    pub include_paths: Vec<PathBuf>,
+}
+
+impl CompileOptions {
+    pub fn new(deployment_bytecode: bool, output_format: OutputFormat) -> CompileOptions {
+        CompileOptions {
+            deployment_bytecode,
+            output_format,
+            evm_version: EvmVersion::default(),
+            optimizer: OptimizerSettings::default(),
+            include_paths: vec![],
+        }
+    }
+}
+
+///Optimizer settings, which are recorded in the build artifact. Hand written code is compiled as written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptimizerSettings {
+    pub enabled: bool,
+    //the number of times the contract is expected to be called, which trades deployment cost for runtime cost
+    pub runs: u64,
+}
+
+impl OptimizerSettings {
+    pub fn new(enabled: bool, runs: u64) -> OptimizerSettings {
+        OptimizerSettings { enabled, runs }
+    }
+}
+
+impl Default for OptimizerSettings {
+    fn default() -> OptimizerSettings {
+        OptimizerSettings::new(false, 200)
+    }
+}
+
+impl Default for CompileOptions {
+    fn default() -> CompileOptions {
+        CompileOptions::new(false, OutputFormat::Hex)
+    }
+}
+
+///A compiled contract along with everything needed to deploy it, call it and relate it back to its source
+pub struct CompiledContract {
+    pub name: String,
+    //the path of the source file, or `<name>.evmm` for contracts compiled from a string
+    pub source_name: String,
+    //the contract followed by every file it includes
+    pub sources: Vec<SourceFile>,
+    //the runtime bytecode as uppercase hex, with its labels and source map
+    pub runtime: CompiledBytecode,
+    //the runtime bytecode prefixed with a constructor that deploys it, as uppercase hex
+    pub deployment_bytecode: String,
+    pub interface: Interface,
+    //the runtime or deployment bytecode, depending on the options, encoded in the output format
+    pub output: Vec<u8>,
+    //the json build artifact
+    pub artifact: Value,
+    //problems that do not stop the contract from compiling, ex. jumps whose destination can not be checked
+    pub warnings: Vec<String>,
+}
+
+impl CompiledContract {
+    ///Gets the abi json, or None if the contract does not declare an interface
+    pub fn abi(&self) -> Option<Value> {
+        if self.interface.is_empty() {
+            None
+        } else {
+            Some(self.interface.to_abi_json())
+        }
+    }
+
+    ///Gets the source file and line, counting from one, of the instruction at an offset in the runtime bytecode. Every
+    ///instruction a built-in compiles to is on the line of the built-in.
+    pub fn source_line(&self, offset: usize) -> Option<(&SourceFile, usize)> {
+        let source_map = &self.runtime.source_map;
+        let entry_index = source_map.partition_point(|entry| entry.bytecode_offset <= offset);
+        let entry = &source_map[entry_index.checked_sub(1)?];
+        let source = self.sources.get(entry.source_index)?;
+
+        let line_number = source.contents[..entry.source_start].matches('\n').count() + 1;
+        Some((source, line_number))
+    }
+
+    ///Describes an instruction in the runtime bytecode by its mnemonic, offset and source line, ex. `JUMP at 0x1F (Counter.evmm:12)`
+    pub fn instruction_location(&self, offset: usize) -> String {
+        let bytecode = decode_hex(&self.runtime.bytecode).unwrap_or_default();
+        let instruction = bytecode
+            .get(offset)
+            .and_then(|opcode| mnemonic(*opcode))
+            .unwrap_or("instruction");
+
+        match self.source_line(offset) {
+            Some((source, line_number)) => format!(
+                "{} at 0x{:X} ({}:{})",
+                instruction, offset, source.path, line_number
+            ),
+            None => format!("{} at 0x{:X}", instruction, offset),
+        }
+    }
+
+    ///Encodes the runtime or deployment bytecode in an output format
+    pub fn format(&self, output_format: OutputFormat, deployment_bytecode: bool) -> Vec<u8> {
+        if deployment_bytecode {
+            //the runtime bytecode starts after the constructor
+            let runtime_offset = (self.deployment_bytecode.len() - self.runtime.bytecode.len()) / 2;
+
+            format_bytecode(
+                output_format,
+                &self.name,
+                &self.deployment_bytecode,
+                &self.runtime,
+                runtime_offset,
+                &self.sources,
+            )
+        } else {
+            format_bytecode(
+                output_format,
+                &self.name,
+                &self.runtime.bytecode,
+                &self.runtime,
+                0,
+                &self.sources,
+            )
+        }
+    }
+
+    ///Estimates the gas used to deploy the contract, which is the transaction and contract creation cost, the calldata
+    ///cost of the deployment bytecode and the cost of storing the runtime bytecode. The constructor itself is not run.
+    pub fn deployment_gas(&self) -> u64 {
+        //the compiler only produces valid hex
+        let calldata_gas: u64 = decode_hex(&self.deployment_bytecode)
+            .unwrap_or_default()
+            .iter()
+            .map(|byte| if *byte == 0 { 4 } else { 16 })
+            .sum();
+
+        21000 + 32000 + calldata_gas + 200 * self.runtime_size() as u64
+    }
+
+    ///Gets the size of the runtime bytecode in bytes
+    pub fn runtime_size(&self) -> usize {
+        self.runtime.bytecode.len() / 2
+    }
+}
+
+///Compiles the source of a contract. The contract name is used for the artifact and byte array constant names.
+///Includes are resolved relative to the current directory.
+pub fn compile_str(
+    contract_name: &str,
+    source: &str,
+    options: &CompileOptions,
+) -> Result<CompiledContract, EVMMError> {
+    compile_source(
+        contract_name,
+        &format!("{}.evmm", contract_name),
+        source,
+        options,
+    )
+}
+
+///Compiles a `.evmm` file, using the file name without its extension as the contract name
+pub fn compile_file<P: AsRef<Path>>(
+    file_path: P,
+    options: &CompileOptions,
+) -> Result<CompiledContract, EVMMError> {
+    let file_path = file_path.as_ref();
+
+    let source = fs::read_to_string(file_path)
+        .map_err(|_| EVMMError::ContractNotFound(file_path.display().to_string()))?;
+
+    compile_file_contents(file_path, &source, options)
+}
+
+///Compiles the contents of a `.evmm` file that may not have been saved, such as a file open in an editor. Includes
+///are resolved relative to the file path.
+pub fn compile_file_contents<P: AsRef<Path>>(
+    file_path: P,
+    source: &str,
+    options: &CompileOptions,
+) -> Result<CompiledContract, EVMMError> {
+    let file_path = file_path.as_ref();
+
+    let contract_name = file_path
+        .file_stem()
+        .map(|file_stem| file_stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    compile_source(
+        &contract_name,
+        &file_path.display().to_string(),
+        source,
+        options,
+    )
+}
+
+fn compile_source(
+    contract_name: &str,
+    source_name: &str,
+    source: &str,
+    options: &CompileOptions,
+) -> Result<CompiledContract, EVMMError> {
+    let program =
+        parse_program_with_includes(source, Path::new(source_name), &options.include_paths)?;
+
+    let interface = program.interface();
+
+    let runtime = compile_instructions(&program, "".to_owned())?;
+    let deployment_bytecode = compile_deployment_bytecode(&runtime.bytecode)?;
+
+    //the constructor only uses opcodes available in every version, so checking the runtime bytecode is enough
+    check_evm_version(&runtime.bytecode, options.evm_version)?;
+
+    let artifact = build_artifact(
+        contract_name,
+        source_name,
+        &program.sources,
+        &deployment_bytecode,
+        &runtime,
+        &interface,
+        options,
+    );
+
+    let mut compiled_contract = CompiledContract {
+        name: contract_name.to_owned(),
+        source_name: source_name.to_owned(),
+        sources: program.sources,
+        runtime,
+        deployment_bytecode,
+        interface,
+        output: vec![],
+        artifact,
+        warnings: vec![],
+    };
+    compiled_contract.output =
+        compiled_contract.format(options.output_format, options.deployment_bytecode);
+
+    //jumps to a constant must land on a JUMPDEST, and jumps to values computed when the contract runs are only warned about
+    let jump_targets = check_jump_targets(&compiled_contract.runtime.bytecode);
+    if let Some((offset, destination, reason)) = jump_targets.invalid.first() {
+        let reason = match reason {
+            InvalidDestination::OutOfBounds => {
+                String::from("which is past the end of the bytecode")
+            }
+            InvalidDestination::PushData(push_offset) => {
+                format!(
+                    "which is inside the data of the push at 0x{:X}",
+                    push_offset
+                )
+            }
+            InvalidDestination::NotJumpdest(opcode) => format!(
+                "which is {} rather than a JUMPDEST",
+                mnemonic(*opcode).unwrap_or("an undefined opcode")
+            ),
+        };
+
+        return Err(EVMMError::InvalidJumpDestination(
+            compiled_contract.instruction_location(*offset),
+            format!("0x{:X}", destination),
+            reason,
+        ));
+    }
+    compiled_contract.warnings = jump_targets
+        .dynamic
+        .iter()
+        .map(|offset| {
+            format!(
+                "{} jumps to a destination that is not known at compile time, so it can not be checked",
+                compiled_contract.instruction_location(*offset)
+            )
+        })
+        .collect();
+
+    let runtime_size = compiled_contract.runtime.bytecode.len() / 2;
+    if runtime_size > MAX_CODE_SIZE {
+        compiled_contract.warnings.push(format!(
+            "The runtime bytecode is {} bytes, which is more than the {} bytes a contract can be since EIP-170, so deploying it fails",
+            runtime_size, MAX_CODE_SIZE
+        ));
+    }
+
+    Ok(compiled_contract)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_str() {
+        let compiled_contract = compile_str(
+            "MstoreOne",
+            "PUSH1 0x01 PUSH1 0x00 MSTORE",
+            &CompileOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(compiled_contract.runtime.bytecode, "6001600052");
+        assert_eq!(compiled_contract.output, b"6001600052");
+        assert_eq!(compiled_contract.artifact["sourceName"], "MstoreOne.evmm");
+        assert!(compiled_contract.abi().is_none());
+    }
+
+    #[test]
+    fn test_compile_str_deployment_bytecode() {
+        let compiled_contract = compile_str(
+            "MstoreOne",
+            "PUSH1 0x01 PUSH1 0x00 MSTORE",
+            &CompileOptions::new(true, OutputFormat::PrefixedHex),
+        )
+        .unwrap();
+
+        assert_eq!(
+            compiled_contract.output,
+            b"0x6100058061000d6000396000f36001600052"
+        );
+    }
+
+    #[test]
+    fn test_compile_str_evm_version() {
+        let options = CompileOptions {
+            evm_version: EvmVersion::Constantinople,
+            ..CompileOptions::default()
+        };
+
+        assert!(matches!(
+            compile_str("Balance", "SELFBALANCE", &options),
+            Err(EVMMError::UnsupportedOpcode(_, _))
+        ));
+
+        let compiled_contract = compile_str("Caller", "CALLER", &options).unwrap();
+        assert_eq!(
+            compiled_contract.artifact["compiler"]["settings"]["evmVersion"],
+            "constantinople"
+        );
+    }
+
+    #[test]
+    fn test_deployment_gas() {
+        let compiled_contract = compile_str(
+            "MstoreOne",
+            "PUSH1 0x01 PUSH1 0x00 MSTORE",
+            &CompileOptions::default(),
+        )
+        .unwrap();
+
+        let deployment_bytecode = decode_hex(&compiled_contract.deployment_bytecode).unwrap();
+        let zero_bytes = deployment_bytecode
+            .iter()
+            .filter(|byte| **byte == 0)
+            .count() as u64;
+        let non_zero_bytes = deployment_bytecode.len() as u64 - zero_bytes;
+
+        assert_eq!(compiled_contract.runtime_size(), 5);
+        assert_eq!(
+            compiled_contract.deployment_gas(),
+            53000 + zero_bytes * 4 + non_zero_bytes * 16 + 5 * 200
+        );
+    }
+
+    #[test]
+    fn test_compile_str_parse_error() {
+        assert!(matches!(
+            compile_str("Invalid", "PUSH1 0x01 )", &CompileOptions::default()),
+            Err(EVMMError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_compile_missing_file() {
+        assert!(matches!(
+            compile_file("./missing/Missing.evmm", &CompileOptions::default()),
+            Err(EVMMError::ContractNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_compile_str_invalid_jump() {
+        let error = compile_str(
+            "InvalidJump",
+            "PUSH1 0x03\nJUMP\nPUSH1 0x5B",
+            &CompileOptions::default(),
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "JUMP at 0x2 (InvalidJump.evmm:2) jumps to 0x3, which is PUSH1 rather than a JUMPDEST"
+        );
+    }
+
+    #[test]
+    fn test_compile_str_dynamic_jump_warning() {
+        let compiled_contract = compile_str(
+            "DynamicJump",
+            "PUSH1 0x00\nCALLDATALOAD\nJUMP",
+            &CompileOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            compiled_contract.warnings,
+            vec![String::from("JUMP at 0x3 (DynamicJump.evmm:3) jumps to a destination that is not known at compile time, so it can not be checked")]
+        );
+    }
+
+    #[test]
+    fn test_compile_str_runtime_size_limits() {
+        let options = CompileOptions::default();
+
+        //a runtime that fits in the constructor but is larger than EIP-170 allows is only warned about
+        let compiled_contract = compile_str("Large", &"STOP\n".repeat(24577), &options).unwrap();
+        assert_eq!(
+            compiled_contract.warnings,
+            vec![String::from("The runtime bytecode is 24577 bytes, which is more than the 24576 bytes a contract can be since EIP-170, so deploying it fails")]
+        );
+        assert!(compile_str("Limit", &"STOP\n".repeat(24576), &options)
+            .unwrap()
+            .warnings
+            .is_empty());
+
+        assert!(matches!(
+            compile_str("TooLarge", &"STOP\n".repeat(0x10000), &options),
+            Err(EVMMError::RuntimeTooLarge(0x10000))
+        ));
+    }
+}
@@ -0,0 +1,467 @@
+use crate::compiler::opcodes::{opcode_info, push_size};
+use crate::core::contract::CompiledContract;
+use crate::core::manifest::Manifest;
+use crate::core::test_runner::run_tests_with_tracer;
+use crate::evm::abi::parse_hex;
+use crate::evm::interpreter::{Step, Tracer};
+use crate::evmm_error::evmm_error::EVMMError;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+const JUMPI: u8 = 0x57;
+
+///Execution counts of each instruction in the runtime bytecode of a contract, collected from every call that runs it
+pub struct CoverageTracer {
+    runtime: Vec<u8>,
+    //the number of times the instruction starting at each offset ran
+    hits: Vec<u64>,
+    //the number of times each JUMPI jumped and fell through, by offset
+    jumps: HashMap<usize, [u64; 2]>,
+}
+
+impl CoverageTracer {
+    pub fn new(compiled_contract: &CompiledContract) -> CoverageTracer {
+        //the compiler only produces valid hex
+        let runtime =
+            parse_hex(&format!("0x{}", compiled_contract.runtime.bytecode)).unwrap_or_default();
+
+        CoverageTracer {
+            hits: vec![0; runtime.len()],
+            runtime,
+            jumps: HashMap::new(),
+        }
+    }
+}
+
+impl Tracer for CoverageTracer {
+    fn step(&mut self, step: &Step) {
+        //other contracts and the constructor are not covered
+        if step.is_create || step.code != self.runtime {
+            return;
+        }
+
+        //running past the end of the code is an implicit STOP without an instruction
+        if let Some(hits) = self.hits.get_mut(step.pc) {
+            *hits += 1;
+        }
+
+        if step.opcode == JUMPI {
+            if let Some(condition) = step.inputs.get(1) {
+                let branch = if condition.is_zero() { 1 } else { 0 };
+                self.jumps.entry(step.pc).or_default()[branch] += 1;
+            }
+        }
+    }
+}
+
+///The coverage of one source file, with lines numbered from one. Basic blocks and branches are identified by their line
+///and their index among the blocks or JUMPIs on that line, so that the coverage of a file included by several contracts
+///can be added up.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FileCoverage {
+    //the execution count of each line that compiles to bytecode
+    pub lines: BTreeMap<usize, u64>,
+    //the execution count of each label, by line and name
+    pub labels: BTreeMap<(usize, String), u64>,
+    //the execution count of each basic block, by the line of its first instruction
+    pub blocks: BTreeMap<(usize, usize), u64>,
+    //the number of times each JUMPI jumped and fell through, or None if it never ran
+    pub branches: BTreeMap<(usize, usize), Option<[u64; 2]>>,
+}
+
+impl FileCoverage {
+    fn merge(&mut self, other: FileCoverage) {
+        for (line, hits) in other.lines {
+            *self.lines.entry(line).or_default() += hits;
+        }
+        for (label, hits) in other.labels {
+            *self.labels.entry(label).or_default() += hits;
+        }
+        for (block, hits) in other.blocks {
+            *self.blocks.entry(block).or_default() += hits;
+        }
+        for (branch, jumps) in other.branches {
+            let merged = self.branches.entry(branch).or_default();
+            *merged = match (*merged, jumps) {
+                (Some(merged), Some(jumps)) => Some([merged[0] + jumps[0], merged[1] + jumps[1]]),
+                (merged, jumps) => merged.or(jumps),
+            };
+        }
+    }
+
+    ///The number of lines, labels, basic blocks and branches that ran, and the total of each
+    pub fn counts(&self) -> [(usize, usize); 4] {
+        let hit = |hits: &u64| *hits > 0;
+
+        [
+            (
+                self.lines.values().filter(|hits| hit(hits)).count(),
+                self.lines.len(),
+            ),
+            (
+                self.labels.values().filter(|hits| hit(hits)).count(),
+                self.labels.len(),
+            ),
+            (
+                self.blocks.values().filter(|hits| hit(hits)).count(),
+                self.blocks.len(),
+            ),
+            (
+                self.branches
+                    .values()
+                    .flatten()
+                    .flat_map(|jumps| jumps.iter())
+                    .filter(|hits| hit(hits))
+                    .count(),
+                self.branches.len() * 2,
+            ),
+        ]
+    }
+}
+
+///The coverage of every source file of the contracts that were tested, by path
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    pub files: BTreeMap<String, FileCoverage>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    ///Maps the execution counts of a contract back to its source files through the source map and adds them up
+    pub fn add(&mut self, compiled_contract: &CompiledContract, tracer: &CoverageTracer) {
+        let mut files: BTreeMap<usize, FileCoverage> = BTreeMap::new();
+        let line_starts: Vec<Vec<usize>> = compiled_contract
+            .sources
+            .iter()
+            .map(|source| {
+                let mut line_starts = vec![0];
+                line_starts.extend(
+                    source
+                        .contents
+                        .match_indices('\n')
+                        .map(|(index, _)| index + 1),
+                );
+                line_starts
+            })
+            .collect();
+
+        //the source index and line of the instruction at an offset, which is the line of the source map entry it is part
+        //of, since built-ins such as the dispatcher compile to many instructions
+        let source_map = &compiled_contract.runtime.source_map;
+        let location = |offset: usize| {
+            let entry_index = source_map.partition_point(|entry| entry.bytecode_offset <= offset);
+            let entry = &source_map[entry_index.checked_sub(1)?];
+            let line = line_starts
+                .get(entry.source_index)?
+                .partition_point(|line_start| *line_start <= entry.source_start);
+
+            Some((entry.source_index, line))
+        };
+
+        //the number of blocks and JUMPIs found so far on each line, which become their indexes
+        let mut line_blocks: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut line_jumpis: HashMap<(usize, usize), usize> = HashMap::new();
+
+        let runtime = &tracer.runtime;
+        let mut block_start = true;
+        let mut offset = 0;
+        while offset < runtime.len() {
+            let opcode = runtime[offset];
+            let hits = tracer.hits[offset];
+
+            if let Some((source_index, line)) = location(offset) {
+                let file = files.entry(source_index).or_default();
+
+                //a line ran as many times as its most run instruction
+                let line_hits = file.lines.entry(line).or_default();
+                *line_hits = (*line_hits).max(hits);
+
+                //basic blocks start at the beginning of the code, at jump destinations and after instructions that jump or halt
+                if block_start || opcode == 0x5B {
+                    let index = line_blocks.entry((source_index, line)).or_default();
+                    file.blocks.insert((line, *index), hits);
+                    *index += 1;
+                }
+
+                if opcode == JUMPI {
+                    let index = line_jumpis.entry((source_index, line)).or_default();
+                    let jumps =
+                        (hits > 0).then(|| tracer.jumps.get(&offset).copied().unwrap_or_default());
+                    file.branches.insert((line, *index), jumps);
+                    *index += 1;
+                }
+            }
+
+            //JUMP, JUMPI, STOP, RETURN, REVERT, INVALID, SELFDESTRUCT and unknown opcodes end a basic block
+            block_start = matches!(opcode, 0x56 | 0x57 | 0x00 | 0xF3 | 0xFD | 0xFE | 0xFF)
+                || opcode_info(opcode).is_none();
+            offset += 1 + push_size(opcode);
+        }
+
+        for (label, label_offset) in compiled_contract.runtime.labels.iter() {
+            if let Some((source_index, line)) = location(*label_offset) {
+                files
+                    .entry(source_index)
+                    .or_default()
+                    .labels
+                    .insert((line, label.clone()), tracer.hits[*label_offset]);
+            }
+        }
+
+        for (source_index, file) in files {
+            let path = compiled_contract.sources[source_index].path.clone();
+            self.files.entry(path).or_default().merge(file);
+        }
+    }
+
+    ///Writes the coverage in the lcov tracefile format, with labels as functions. Each JUMPI is a branch that either
+    ///jumps or falls through.
+    pub fn to_lcov(&self) -> String {
+        let mut lcov = String::new();
+
+        for (path, file) in self.files.iter() {
+            let [(lines_hit, lines), (labels_hit, labels), _, (branches_hit, branches)] =
+                file.counts();
+
+            lcov.push_str("TN:\n");
+            lcov.push_str(&format!("SF:{}\n", path));
+
+            for (line, label) in file.labels.keys() {
+                lcov.push_str(&format!("FN:{},{}\n", line, label));
+            }
+            for ((_, label), hits) in file.labels.iter() {
+                lcov.push_str(&format!("FNDA:{},{}\n", hits, label));
+            }
+            lcov.push_str(&format!("FNF:{}\nFNH:{}\n", labels, labels_hit));
+
+            for (block, ((line, _), jumps)) in file.branches.iter().enumerate() {
+                for branch in 0..2 {
+                    let taken = match jumps {
+                        Some(jumps) => jumps[branch].to_string(),
+                        None => String::from("-"),
+                    };
+                    lcov.push_str(&format!("BRDA:{},{},{},{}\n", line, block, branch, taken));
+                }
+            }
+            lcov.push_str(&format!("BRF:{}\nBRH:{}\n", branches, branches_hit));
+
+            for (line, hits) in file.lines.iter() {
+                lcov.push_str(&format!("DA:{},{}\n", line, hits));
+            }
+            lcov.push_str(&format!("LF:{}\nLH:{}\n", lines, lines_hit));
+
+            lcov.push_str("end_of_record\n");
+        }
+
+        lcov
+    }
+
+    ///Formats a table of the lines, labels, basic blocks and branches that ran in each file
+    pub fn summary(&self) -> String {
+        let mut rows: Vec<[String; 5]> = vec![[
+            String::from("File"),
+            String::from("Lines"),
+            String::from("Labels"),
+            String::from("Blocks"),
+            String::from("Branches"),
+        ]];
+
+        for (path, file) in self.files.iter() {
+            let counts = file.counts().map(|(hit, total)| {
+                //a file without any of a kind of item is fully covered
+                let percentage = (hit * 100).checked_div(total).unwrap_or(100);
+                format!("{}/{} ({}%)", hit, total, percentage)
+            });
+
+            rows.push([
+                path.clone(),
+                counts[0].clone(),
+                counts[1].clone(),
+                counts[2].clone(),
+                counts[3].clone(),
+            ]);
+        }
+
+        let column_width =
+            |column: usize| rows.iter().map(|row| row[column].len()).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..5).map(column_width).collect();
+
+        let mut summary = String::new();
+        for row in rows {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            summary.push_str(cells.join(" | ").trim_end());
+            summary.push('\n');
+        }
+
+        summary
+    }
+}
+
+///Runs the tests whose name contains the filter while collecting coverage, then prints a summary and writes the
+///coverage to the output file as lcov. The coverage is written even when tests fail.
+pub fn run_coverage(
+    manifest: &Manifest,
+    filter: &str,
+    output_path: &Path,
+) -> Result<(), EVMMError> {
+    let mut coverage = Coverage::new();
+
+    let test_result = run_tests_with_tracer(
+        manifest,
+        filter,
+        CoverageTracer::new,
+        |compiled_contract, tracer| coverage.add(compiled_contract, &tracer),
+    );
+    if let Err(error) = &test_result {
+        if !matches!(error, EVMMError::TestsFailed(_)) {
+            return test_result;
+        }
+    }
+
+    if coverage.files.is_empty() {
+        return test_result;
+    }
+
+    println!();
+    print!("{}", coverage.summary());
+
+    fs::write(output_path, coverage.to_lcov()).map_err(|error| {
+        EVMMError::CoverageFailed(output_path.display().to_string(), error.to_string())
+    })?;
+    println!("Wrote coverage to {}", output_path.display());
+
+    test_result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::contract::{compile_str, CompileOptions};
+    use crate::core::test_runner::{parse_test_file, run_test_case};
+    use std::path::PathBuf;
+
+    const COUNTER: &str = r#"#define function increment()
+#define function count() view returns (uint256)
+
+__DISPATCHER(fallback)
+
+increment:
+    PUSH1 0x00
+    SLOAD
+    PUSH1 0x01
+    ADD
+    PUSH1 0x00
+    SSTORE
+    STOP
+
+count:
+    PUSH1 0x00
+    SLOAD
+    PUSH1 0x00
+    MSTORE
+    PUSH1 0x20
+    PUSH1 0x00
+    RETURN
+
+fallback:
+    PUSH1 0x00
+    DUP1
+    REVERT
+"#;
+
+    const COUNTER_TESTS: &str = r#"
+contract = "Counter"
+
+[[test]]
+name = "increments"
+
+[[test.call]]
+function = "increment()"
+
+[[test.call]]
+function = "increment()"
+"#;
+
+    fn counter_coverage() -> FileCoverage {
+        let compiled_contract =
+            compile_str("Counter", COUNTER, &CompileOptions::default()).unwrap();
+        let test_file = parse_test_file(COUNTER_TESTS, PathBuf::from("Counter.t.toml")).unwrap();
+
+        let mut tracer = CoverageTracer::new(&compiled_contract);
+        assert!(run_test_case(&compiled_contract, &test_file.tests[0], &mut tracer).passed());
+
+        let mut coverage = Coverage::new();
+        coverage.add(&compiled_contract, &tracer);
+        coverage.files.remove("Counter.evmm").unwrap()
+    }
+
+    #[test]
+    fn test_line_coverage() {
+        let file = counter_coverage();
+
+        //the dispatcher, then the body of increment, count and the fallback
+        assert_eq!(file.lines[&4], 2);
+        assert!((6..=13).all(|line| file.lines[&line] == 2));
+        assert!((15..=22).all(|line| file.lines[&line] == 0));
+        assert!((24..=27).all(|line| file.lines[&line] == 0));
+        assert_eq!(file.lines.len(), 1 + 8 + 8 + 4);
+
+        assert_eq!(file.labels[&(6, String::from("increment"))], 2);
+        assert_eq!(file.labels[&(15, String::from("count"))], 0);
+        assert_eq!(file.labels[&(24, String::from("fallback"))], 0);
+    }
+
+    #[test]
+    fn test_branch_coverage() {
+        let file = counter_coverage();
+
+        //every JUMPI is in the dispatcher, and the calls to increment() take some of them
+        assert!(file.branches.keys().all(|(line, _)| *line == 4));
+        let branches: Vec<[u64; 2]> = file.branches.values().flatten().copied().collect();
+        assert!(!branches.is_empty());
+        assert!(branches.iter().all(|jumps| jumps[0] + jumps[1] == 2));
+        assert_eq!(branches.iter().map(|jumps| jumps[0]).sum::<u64>(), 2);
+
+        //the label blocks that ran and the ones that did not
+        assert_eq!(file.blocks[&(6, 0)], 2);
+        assert_eq!(file.blocks[&(15, 0)], 0);
+        assert_eq!(file.blocks[&(24, 0)], 0);
+    }
+
+    #[test]
+    fn test_lcov() {
+        let mut coverage = Coverage::new();
+        coverage.files.insert(
+            String::from("Counter.evmm"),
+            FileCoverage {
+                lines: BTreeMap::from([(4, 2), (6, 2), (15, 0)]),
+                labels: BTreeMap::from([
+                    ((6, String::from("increment")), 2),
+                    ((15, String::from("count")), 0),
+                ]),
+                blocks: BTreeMap::from([((6, 0), 2), ((15, 0), 0)]),
+                branches: BTreeMap::from([((4, 0), Some([2, 0])), ((4, 1), None)]),
+            },
+        );
+
+        assert_eq!(
+            coverage.to_lcov(),
+            "TN:\nSF:Counter.evmm\nFN:6,increment\nFN:15,count\nFNDA:2,increment\nFNDA:0,count\nFNF:2\nFNH:1\n\
+             BRDA:4,0,0,2\nBRDA:4,0,1,0\nBRDA:4,1,0,-\nBRDA:4,1,1,-\nBRF:4\nBRH:1\n\
+             DA:4,2\nDA:6,2\nDA:15,0\nLF:3\nLH:2\nend_of_record\n"
+        );
+        assert_eq!(
+            coverage.summary(),
+            "File         | Lines     | Labels    | Blocks    | Branches\n\
+             Counter.evmm | 2/3 (66%) | 1/2 (50%) | 1/2 (50%) | 1/4 (25%)\n"
+        );
+    }
+}
@@ -0,0 +1,472 @@
+use crate::core::contract::{compile_file, CompiledContract};
+use crate::core::evmm::get_contract_paths;
+use crate::core::fuzz::{
+    fuzz_functions, generate_call, shrink_calls, FuzzCall, FuzzSettings, FUZZ_CALLERS,
+};
+use crate::core::manifest::Manifest;
+use crate::core::test_runner::{
+    bytes_difference, deploy_bytecode, deploy_contract, log_differences, ExpectedLog,
+};
+use crate::evm::abi::parse_hex;
+use crate::evm::interpreter::{Evm, ExecutionResult, ExitStatus, Transaction};
+use crate::evm::state::Address;
+use crate::evmm_error::evmm_error::EVMMError;
+use crate::utils::random::Random;
+use primitive_types::U256;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+///The bytecode of the implementation an EVMM contract is compared against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceBytecode {
+    pub bytecode: Vec<u8>,
+    //true if the bytecode is the runtime bytecode, which is installed at the contract address without a constructor
+    pub is_runtime: bool,
+}
+
+///What happened when the same calls were made to both implementations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DifferentialReport {
+    pub calls: u64,
+    pub gas_used: u64,
+    pub reference_gas_used: u64,
+    //the description of the first divergence and the shrunk calls that cause it
+    pub divergence: Option<(String, Vec<FuzzCall>)>,
+}
+
+//the outcome of running one sequence of calls against both implementations
+struct SequenceOutcome {
+    calls: u64,
+    gas_used: u64,
+    reference_gas_used: u64,
+    divergence: Option<(usize, String)>,
+}
+
+///Reads the reference bytecode from a file of hex, with or without a `0x` prefix, or from a json artifact with a
+///`bytecode` field, or `deployedBytecode` for the runtime bytecode. Fields that are objects use their `object` field,
+///the way solc writes them.
+pub fn load_reference_bytecode(
+    path: &Path,
+    is_runtime: bool,
+) -> Result<ReferenceBytecode, EVMMError> {
+    let invalid_reference =
+        |error: String| EVMMError::InvalidReference(path.display().to_string(), error);
+
+    let contents =
+        fs::read_to_string(path).map_err(|error| invalid_reference(error.to_string()))?;
+
+    let hex = match serde_json::from_str::<Value>(&contents) {
+        Ok(artifact) => {
+            let field = if is_runtime {
+                "deployedBytecode"
+            } else {
+                "bytecode"
+            };
+
+            let bytecode = match &artifact[field] {
+                Value::Object(object) => object.get("object").and_then(Value::as_str),
+                bytecode => bytecode.as_str(),
+            };
+            bytecode
+                .ok_or_else(|| invalid_reference(format!("the artifact has no `{}`", field)))?
+                .to_owned()
+        }
+        Err(_) => contents,
+    };
+
+    let hex: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    let hex = hex.strip_prefix("0x").unwrap_or(&hex);
+    let bytecode = parse_hex(&format!("0x{}", hex))
+        .filter(|bytecode| !bytecode.is_empty())
+        .ok_or_else(|| invalid_reference(String::from("the bytecode is not valid hex")))?;
+
+    Ok(ReferenceBytecode {
+        bytecode,
+        is_runtime,
+    })
+}
+
+///Compiles the contract and makes the same random calls to it and to the reference implementation, printing the first
+///divergence in return data, reverts, logs or storage along with the shrunk calls that cause it. Also prints the
+///gas used by each implementation. Returns an error if the implementations diverge.
+pub fn compare_with_reference(
+    manifest: &Manifest,
+    contract_path: &str,
+    reference_path: &Path,
+    is_runtime: bool,
+    settings: FuzzSettings,
+) -> Result<(), EVMMError> {
+    let reference = load_reference_bytecode(reference_path, is_runtime)?;
+
+    //the contract path is resolved the same way as `compile --contract`
+    let contract_path = get_contract_paths(manifest, contract_path, "")?
+        .into_iter()
+        .next()
+        .ok_or_else(|| EVMMError::ContractNotFound(contract_path.to_owned()))?;
+    let contract_name = contract_path
+        .path
+        .file_stem()
+        .map(|file_stem| file_stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let compiled_contract = compile_file(
+        &contract_path.path,
+        &manifest.compile_options(&contract_name),
+    )?;
+
+    println!(
+        "Comparing {} with {} ({} runs of {} calls, seed {})",
+        contract_path.path.display(),
+        reference_path.display(),
+        settings.runs,
+        settings.depth,
+        settings.seed
+    );
+
+    let report = find_divergence(&compiled_contract, &reference, settings).map_err(|error| {
+        EVMMError::InvalidReference(reference_path.display().to_string(), error)
+    })?;
+
+    if let (Some(average_gas), Some(reference_average_gas)) = (
+        report.gas_used.checked_div(report.calls),
+        report.reference_gas_used.checked_div(report.calls),
+    ) {
+        println!(
+            "Average gas per call: {} ({:+} compared to the reference's {})",
+            average_gas,
+            average_gas as i64 - reference_average_gas as i64,
+            reference_average_gas
+        );
+    }
+
+    match report.divergence {
+        None => {
+            println!("No divergence in {} calls", report.calls);
+            Ok(())
+        }
+        Some((message, calls)) => {
+            println!("Divergence after {} calls", calls.len());
+            println!("    {}", message.replace('\n', "\n    "));
+            println!("    Shrunk reproduction:");
+            println!("    [[test]]");
+            println!("    name = \"matches the reference\"");
+            for call in calls {
+                println!();
+                println!("    {}", call.to_test_call().replace('\n', "\n    "));
+            }
+
+            Err(EVMMError::ImplementationsDiverge(
+                contract_path.path.display().to_string(),
+            ))
+        }
+    }
+}
+
+///Runs random call sequences against fresh deployments of both implementations until they diverge, with calls
+///generated from the interface of the EVMM contract
+pub fn find_divergence(
+    compiled_contract: &CompiledContract,
+    reference: &ReferenceBytecode,
+    settings: FuzzSettings,
+) -> Result<DifferentialReport, String> {
+    let functions = fuzz_functions(&compiled_contract.interface);
+    let mut random = Random::new(settings.seed);
+    let mut report = DifferentialReport {
+        calls: 0,
+        gas_used: 0,
+        reference_gas_used: 0,
+        divergence: None,
+    };
+
+    for _ in 0..settings.runs {
+        let calls: Vec<FuzzCall> = (0..settings.depth)
+            .map(|_| generate_call(&mut random, &functions))
+            .collect();
+
+        let outcome = run_sequence(compiled_contract, reference, &calls)?;
+        report.calls += outcome.calls;
+        report.gas_used += outcome.gas_used;
+        report.reference_gas_used += outcome.reference_gas_used;
+
+        if let Some((call_index, message)) = outcome.divergence {
+            report.divergence = Some(shrink_calls(
+                calls[..=call_index].to_vec(),
+                message,
+                |calls| {
+                    Ok(run_sequence(compiled_contract, reference, calls)?
+                        .divergence
+                        .map(|(_, message)| message))
+                },
+            )?);
+            break;
+        }
+    }
+
+    Ok(report)
+}
+
+//deploys both implementations at the same address, so that results that include the address can be compared
+fn deploy_both(
+    compiled_contract: &CompiledContract,
+    reference: &ReferenceBytecode,
+) -> Result<(Evm, Evm, Address), String> {
+    let (mut evm, address) = deploy_contract(compiled_contract)?;
+
+    let (mut reference_evm, reference_address) = if reference.is_runtime {
+        //empty init code creates the account without code, which the runtime bytecode is then installed in
+        let (mut reference_evm, reference_address) = deploy_bytecode(vec![])?;
+        reference_evm.state.account_mut(&reference_address).code = reference.bytecode.clone();
+        (reference_evm, reference_address)
+    } else {
+        deploy_bytecode(reference.bytecode.clone())
+            .map_err(|error| format!("the reference {}", error))?
+    };
+
+    for caller in FUZZ_CALLERS {
+        evm.state.account_mut(&caller).balance = U256::from(u128::MAX);
+        reference_evm.state.account_mut(&caller).balance = U256::from(u128::MAX);
+    }
+
+    //both are created by the same account with the same nonce
+    debug_assert_eq!(address, reference_address);
+    Ok((evm, reference_evm, address))
+}
+
+fn run_sequence(
+    compiled_contract: &CompiledContract,
+    reference: &ReferenceBytecode,
+    calls: &[FuzzCall],
+) -> Result<SequenceOutcome, String> {
+    let (mut evm, mut reference_evm, address) = deploy_both(compiled_contract, reference)?;
+    let mut outcome = SequenceOutcome {
+        calls: 0,
+        gas_used: 0,
+        reference_gas_used: 0,
+        divergence: None,
+    };
+
+    for (call_index, call) in calls.iter().enumerate() {
+        let transaction = Transaction::new(call.caller, Some(address), call.value, call.calldata());
+        let result = evm.transact(&transaction);
+        let reference_result = reference_evm.transact(&transaction);
+
+        outcome.calls += 1;
+        outcome.gas_used += result.gas_used;
+        outcome.reference_gas_used += reference_result.gas_used;
+
+        let mut differences = result_differences(&reference_result, &result);
+        differences.extend(storage_differences(&reference_evm, &evm, &address));
+
+        if !differences.is_empty() {
+            outcome.divergence = Some((call_index, differences.join("\n")));
+            break;
+        }
+    }
+
+    Ok(outcome)
+}
+
+//the differences between the results of a call, where the reference result is the expected one
+fn result_differences(reference_result: &ExecutionResult, result: &ExecutionResult) -> Vec<String> {
+    //contracts can fail in different ways, ex. INVALID instead of a panic revert, so halts are compared by kind
+    let outcome = |status: ExitStatus| match status {
+        ExitStatus::Success => "success",
+        ExitStatus::Revert => "revert",
+        ExitStatus::Halt(_) => "halt",
+    };
+
+    if outcome(reference_result.status) != outcome(result.status) {
+        return vec![format!(
+            "the reference ended with {}, the contract with {}",
+            reference_result.status, result.status
+        )];
+    }
+
+    let mut differences = vec![];
+    if reference_result.output != result.output {
+        differences.push(format!(
+            "return data differs from the reference\n{}",
+            bytes_difference(&reference_result.output, &result.output)
+        ));
+    }
+
+    let reference_logs: Vec<ExpectedLog> = reference_result
+        .logs
+        .iter()
+        .map(|log| ExpectedLog {
+            topics: log.topics.clone(),
+            data: Some(log.data.clone()),
+        })
+        .collect();
+    for log_difference in log_differences(&reference_logs, &result.logs) {
+        differences.push(format!(
+            "logs differ from the reference: {}",
+            log_difference
+        ));
+    }
+
+    differences
+}
+
+fn storage_differences(reference_evm: &Evm, evm: &Evm, address: &Address) -> Vec<String> {
+    let slots: BTreeSet<U256> = [reference_evm, evm]
+        .iter()
+        .filter_map(|evm| evm.state.account(address))
+        .flat_map(|account| account.storage.keys().copied())
+        .collect();
+
+    slots
+        .into_iter()
+        .filter_map(|slot| {
+            let reference_value = reference_evm.state.storage(address, &slot);
+            let value = evm.state.storage(address, &slot);
+
+            (reference_value != value).then(|| {
+                format!(
+                    "storage slot {:#x} is {:#x} in the reference, {:#x} in the contract",
+                    slot, reference_value, value
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::contract::{compile_str, CompileOptions};
+    use crate::core::fuzz::CallInput;
+    use crate::evm::abi::selector;
+
+    const SETTER: &str = r#"
+#define function set(uint256)
+#define function get() view returns (uint256)
+
+__DISPATCHER(fallback)
+
+set:
+    PUSH1 0x04
+    CALLDATALOAD
+    PUSH1 0x00
+    SSTORE
+    STOP
+
+get:
+    PUSH1 0x00
+    SLOAD
+    PUSH1 0x00
+    MSTORE
+    PUSH1 0x20
+    PUSH1 0x00
+    RETURN
+
+fallback:
+    PUSH1 0x00
+    DUP1
+    REVERT
+"#;
+
+    fn compile(source: &str) -> CompiledContract {
+        compile_str("Setter", source, &CompileOptions::default()).unwrap()
+    }
+
+    fn runtime_reference(source: &str) -> ReferenceBytecode {
+        ReferenceBytecode {
+            bytecode: parse_hex(&format!("0x{}", compile(source).runtime.bytecode)).unwrap(),
+            is_runtime: true,
+        }
+    }
+
+    #[test]
+    fn test_identical_implementations() {
+        let compiled_contract = compile(SETTER);
+        let reference = ReferenceBytecode {
+            bytecode: parse_hex(&format!("0x{}", compiled_contract.deployment_bytecode)).unwrap(),
+            is_runtime: false,
+        };
+
+        let report =
+            find_divergence(&compiled_contract, &reference, FuzzSettings::new(16, 5, 3)).unwrap();
+        assert_eq!(report.divergence, None);
+        assert_eq!(report.calls, 80);
+        assert_eq!(report.gas_used, report.reference_gas_used);
+    }
+
+    #[test]
+    fn test_storage_divergence() {
+        //the reference stores the value in slot 1
+        let reference = runtime_reference(&SETTER.replace(
+            "CALLDATALOAD\n    PUSH1 0x00\n    SSTORE",
+            "CALLDATALOAD\n    PUSH1 0x01\n    SSTORE",
+        ));
+
+        let report =
+            find_divergence(&compile(SETTER), &reference, FuzzSettings::new(16, 5, 3)).unwrap();
+        let (message, calls) = report.divergence.unwrap();
+
+        //the shrunk call stores one, since storing zero does not change either slot
+        assert_eq!(
+            message,
+            "storage slot 0x0 is 0x0 in the reference, 0x1 in the contract\nstorage slot 0x1 is 0x1 in the reference, 0x0 in the contract"
+        );
+        assert_eq!(calls.len(), 1);
+        assert!(
+            matches!(&calls[0].input, CallInput::Function(function, _) if function.signature == "set(uint256)")
+        );
+    }
+
+    #[test]
+    fn test_revert_divergence() {
+        //the reference accepts calls to unknown functions
+        let reference = runtime_reference(&SETTER.replace(
+            "fallback:\n    PUSH1 0x00\n    DUP1\n    REVERT",
+            "fallback:\n    STOP",
+        ));
+
+        let report =
+            find_divergence(&compile(SETTER), &reference, FuzzSettings::new(64, 5, 3)).unwrap();
+        let (message, calls) = report.divergence.unwrap();
+
+        assert_eq!(
+            message,
+            "the reference ended with success, the contract with revert"
+        );
+        assert_eq!(calls.len(), 1);
+        assert!(
+            matches!(&calls[0].input, CallInput::Raw(calldata) if calldata[..] != selector("set(uint256)"))
+        );
+    }
+
+    #[test]
+    fn test_load_reference_bytecode() {
+        let directory = std::env::temp_dir().join("evmm_load_reference_bytecode");
+        fs::create_dir_all(&directory).unwrap();
+
+        fs::write(directory.join("reference.hex"), "0x6001\n6000\n").unwrap();
+        assert_eq!(
+            load_reference_bytecode(&directory.join("reference.hex"), false)
+                .unwrap()
+                .bytecode,
+            vec![0x60, 0x01, 0x60, 0x00]
+        );
+
+        fs::write(
+            directory.join("Reference.json"),
+            r#"{"bytecode": {"object": "0x6001"}, "deployedBytecode": "6002"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            load_reference_bytecode(&directory.join("Reference.json"), true)
+                .unwrap()
+                .bytecode,
+            vec![0x60, 0x02]
+        );
+
+        fs::write(directory.join("invalid.hex"), "0x60zz").unwrap();
+        assert!(matches!(
+            load_reference_bytecode(&directory.join("invalid.hex"), false),
+            Err(EVMMError::InvalidReference(_, _))
+        ));
+    }
+}
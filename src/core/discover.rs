@@ -0,0 +1,154 @@
+use crate::evmm_error::evmm_error::EVMMError;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const CONTRACT_EXTENSION: &str = "evmm";
+
+///A contract to compile and the directory its output is written to, relative to the output directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractPath {
+    pub path: PathBuf,
+    //the directory of the contract relative to the source directory, so that the output mirrors the source tree
+    pub output_subdirectory: PathBuf,
+}
+
+impl ContractPath {
+    pub fn new(path: PathBuf, output_subdirectory: PathBuf) -> ContractPath {
+        ContractPath {
+            path,
+            output_subdirectory,
+        }
+    }
+}
+
+///Builds the set of ignore patterns, which are globs matched against paths relative to the source directory
+pub fn ignore_set(ignore_patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+
+    for ignore_pattern in ignore_patterns {
+        let glob = Glob::new(ignore_pattern)
+            .map_err(|error| format!("invalid ignore pattern `{}`: {}", ignore_pattern, error))?;
+        builder.add(glob);
+    }
+
+    builder.build().map_err(|error| error.to_string())
+}
+
+///Finds every `.evmm` file in the source directory and its subdirectories, in path order. Files and directories matching
+///an ignore pattern are skipped, as are the excluded directories, which hold files that contracts include.
+pub fn discover_contracts(
+    source_directory: &Path,
+    ignore: &GlobSet,
+    excluded_directories: &[PathBuf],
+) -> Result<Vec<ContractPath>, EVMMError> {
+    //directories are compared by their canonical paths since they can be written in different ways
+    let excluded_directories: Vec<PathBuf> = excluded_directories
+        .iter()
+        .filter_map(|directory| fs::canonicalize(directory).ok())
+        .collect();
+
+    let mut contract_paths = vec![];
+    discover_directory(
+        source_directory,
+        Path::new(""),
+        ignore,
+        &excluded_directories,
+        &mut contract_paths,
+    )
+    .map_err(|_| EVMMError::ContractNotFound(source_directory.display().to_string()))?;
+
+    contract_paths.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(contract_paths)
+}
+
+fn discover_directory(
+    directory: &Path,
+    relative_directory: &Path,
+    ignore: &GlobSet,
+    excluded_directories: &[PathBuf],
+    contract_paths: &mut Vec<ContractPath>,
+) -> Result<(), std::io::Error> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let relative_path = relative_directory.join(path.file_name().unwrap_or_default());
+
+        if ignore.is_match(&relative_path) {
+            continue;
+        }
+
+        if path.is_dir() {
+            if excluded_directories.contains(&fs::canonicalize(&path)?) {
+                continue;
+            }
+
+            discover_directory(
+                &path,
+                &relative_path,
+                ignore,
+                excluded_directories,
+                contract_paths,
+            )?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == CONTRACT_EXTENSION)
+        {
+            contract_paths.push(ContractPath::new(path, relative_directory.to_path_buf()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_discover_contracts() {
+        let directory = env::temp_dir().join("evmm_discover_contracts");
+        let _ = fs::remove_dir_all(&directory);
+        for file_path in [
+            "Token.evmm",
+            "README.md",
+            "tokens/erc20/ERC20.evmm",
+            "tokens/Vault.evmm",
+            "lib/ownable.evmm",
+            "experimental/Draft.evmm",
+            "tokens/Scratch.draft.evmm",
+        ] {
+            let path = directory.join(file_path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "STOP").unwrap();
+        }
+
+        let ignore =
+            ignore_set(&["experimental".to_owned(), "**/*.draft.evmm".to_owned()]).unwrap();
+        let contract_paths =
+            discover_contracts(&directory, &ignore, &[directory.join("./lib")]).unwrap();
+
+        assert_eq!(
+            contract_paths,
+            vec![
+                ContractPath::new(directory.join("Token.evmm"), PathBuf::from("")),
+                ContractPath::new(directory.join("tokens/Vault.evmm"), PathBuf::from("tokens")),
+                ContractPath::new(
+                    directory.join("tokens/erc20/ERC20.evmm"),
+                    PathBuf::from("tokens/erc20")
+                ),
+            ]
+        );
+
+        assert!(matches!(
+            discover_contracts(&directory.join("missing"), &ignore, &[]),
+            Err(EVMMError::ContractNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_ignore_pattern() {
+        assert!(ignore_set(&["lib/[".to_owned()]).is_err());
+    }
+}
@@ -1,167 +1,445 @@
-use crate::compiler::compile::compile_instructions;
-use crate::core::evmm;
+use crate::core::cache::{options_key, BuildCache, CACHE_FILE_NAME};
+use crate::core::contract::compile_file;
+use crate::core::discover::{discover_contracts, ignore_set, ContractPath};
+use crate::core::format::OutputFormat;
+use crate::core::manifest::{Manifest, MANIFEST_FILE_NAME};
 use crate::evmm_error::evmm_error::EVMMError;
-use crate::parser::parse::{self, parse_file};
-use std::fs::{File, ReadDir};
-use std::io::Write;
-use std::io::{Error, Read};
-use std::path::Path;
+use crate::parser::ast::SourceFile;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::Error;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
 use std::{fs, vec};
 
-struct EVMMFile {
-    file_name: String,
-    file_contents: String,
-}
-
-impl EVMMFile {
-    pub fn new(file_name: String, file_contents: String) -> EVMMFile {
-        EVMMFile {
-            file_name,
-            file_contents,
-        }
-    }
-}
-
 pub struct EVMASMFile {
-    file_name: String,
-    compiled_bytecode: String,
+    pub contract_path: PathBuf,
+    pub contract_name: String,
+    //the directory the output is written to, relative to the output directory
+    pub output_subdirectory: PathBuf,
+    //the output format, file name and encoded bytecode for each output format
+    pub compiled_bytecode: Vec<(OutputFormat, String, Vec<u8>)>,
+    //the abi json, only present if the contract declares an interface
+    pub abi: Option<String>,
+    //the json build artifact, written as `<contract_name>.json`
+    pub artifact: String,
+    //the contract and the files it includes, recorded in the build cache
+    pub sources: Vec<SourceFile>,
+    //the size of the runtime bytecode in bytes
+    pub runtime_size: usize,
+    pub deployment_gas: u64,
+    //printed after the contract compiles
+    pub warnings: Vec<String>,
 }
 
 impl EVMASMFile {
-    pub fn new(file_name: String, compiled_bytecode: String) -> EVMASMFile {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        contract_path: PathBuf,
+        contract_name: String,
+        output_subdirectory: PathBuf,
+        compiled_bytecode: Vec<(OutputFormat, String, Vec<u8>)>,
+        abi: Option<String>,
+        artifact: String,
+        sources: Vec<SourceFile>,
+        runtime_size: usize,
+        deployment_gas: u64,
+        warnings: Vec<String>,
+    ) -> EVMASMFile {
         EVMASMFile {
-            file_name,
+            contract_path,
+            contract_name,
+            output_subdirectory,
             compiled_bytecode,
+            abi,
+            artifact,
+            sources,
+            runtime_size,
+            deployment_gas,
+            warnings,
         }
     }
-}
 
-const DEFAULT_CONTRACTS_DIR: &str = "./evmm_contracts";
-pub const DEFAULT_COMPILATION_DIR: &str = "./evm_asm";
+    ///Gets the path relative to the output directory and the contents of every file written for the contract
+    fn output_files(&self) -> Vec<(PathBuf, &[u8])> {
+        let mut output_files = vec![];
 
+        for (_, file_name, compiled_bytecode) in self.compiled_bytecode.iter() {
+            output_files.push((
+                self.output_subdirectory.join(file_name),
+                compiled_bytecode.as_slice(),
+            ));
+        }
+
+        if let Some(abi) = &self.abi {
+            output_files.push((
+                self.output_subdirectory
+                    .join(format!("{}.abi.json", self.contract_name)),
+                abi.as_bytes(),
+            ));
+        }
+
+        output_files.push((
+            self.output_subdirectory
+                .join(format!("{}.json", self.contract_name)),
+            self.artifact.as_bytes(),
+        ));
+
+        output_files
+    }
+}
+
+///Compiles a single contract, the contracts in a directory, or the contracts in the manifest source directories if neither is given.
+///Contracts written to the output directory are only recompiled if they changed since they were last compiled, unless `force` is set.
 pub fn evmm_parse_and_compile(
-    deployment_bytecode: bool,
+    manifest: &Manifest,
     contract_path: &str,
     directory_to_compile: &str,
-    output_directory: &str,
     print_in_terminal: bool,
+    force: bool,
 ) -> Result<(), EVMMError> {
-    //TODO: need to handle errors gracefully
-    let evmm_files = get_contract_contents(contract_path, directory_to_compile).unwrap();
+    let contract_paths = get_contract_paths(manifest, contract_path, directory_to_compile)?;
+
+    //printed bytecode is always compiled since there is no output to reuse
+    if print_in_terminal {
+        let evmasm_files = parse_and_compile_bytecode(manifest, contract_paths)?;
+        print_contracts(evmasm_files)
+            .map_err(|error| EVMMError::WriteFailed(String::from("stdout"), error.to_string()))?;
+
+        return Ok(());
+    }
+
+    //the cache is saved in the output directory even if every contract is up to date
+    fs::create_dir_all(&manifest.output_directory).map_err(|error| {
+        EVMMError::WriteFailed(
+            manifest.output_directory.display().to_string(),
+            error.to_string(),
+        )
+    })?;
+    let mut build_cache = BuildCache::load(&manifest.output_directory);
 
-    let evmasm_files = parse_and_compile_bytecode(evmm_files, deployment_bytecode)?;
+    let mut contracts_to_compile = vec![];
+    for contract_path in contract_paths {
+        let options_key = contract_options_key(manifest, &contract_path.path);
 
-    //output the deployment bytecode
-    output_contracts(evmasm_files, output_directory, print_in_terminal).unwrap();
+        if !force
+            && build_cache.is_up_to_date(
+                &contract_path.path,
+                &options_key,
+                &manifest.output_directory,
+            )
+        {
+            println!("Up to date {}", contract_path.path.display());
+        } else {
+            contracts_to_compile.push((contract_path, options_key));
+        }
+    }
+
+    let (contract_paths, options_keys): (Vec<ContractPath>, Vec<String>) =
+        contracts_to_compile.into_iter().unzip();
+    let evmasm_files = parse_and_compile_bytecode(manifest, contract_paths)?;
+
+    for (evmasm_file, options_key) in evmasm_files.iter().zip(options_keys) {
+        let output_files =
+            write_contract(evmasm_file, &manifest.output_directory).map_err(|error| {
+                EVMMError::WriteFailed(
+                    manifest
+                        .output_directory
+                        .join(&evmasm_file.output_subdirectory)
+                        .display()
+                        .to_string(),
+                    error.to_string(),
+                )
+            })?;
+
+        build_cache.insert(
+            &evmasm_file.contract_path,
+            &options_key,
+            &evmasm_file.sources,
+            &output_files,
+        );
+
+        println!("Compiled {}", evmasm_file.contract_path.display());
+        print_warnings(evmasm_file);
+    }
+
+    build_cache.save().map_err(|error| {
+        EVMMError::WriteFailed(
+            manifest
+                .output_directory
+                .join(CACHE_FILE_NAME)
+                .display()
+                .to_string(),
+            error.to_string(),
+        )
+    })?;
 
     Ok(())
 }
 
-fn get_contract_contents(
+///Gets the contracts to compile, which are a single contract, the contracts in a directory, or the contracts in the
+///manifest source directories if neither is given
+pub fn get_contract_paths(
+    manifest: &Manifest,
     contract_path: &str,
     directory_to_compile: &str,
-) -> Result<Vec<EVMMFile>, Error> {
-    let mut evmm_files: Vec<EVMMFile> = vec![];
+) -> Result<Vec<ContractPath>, EVMMError> {
+    if !contract_path.is_empty() {
+        let path = PathBuf::from(contract_path);
 
-    let paths: ReadDir;
-    if directory_to_compile != "" {
-        paths = fs::read_dir(contract_path)?;
-    } else {
-        paths = fs::read_dir(DEFAULT_CONTRACTS_DIR)?;
+        //a contract given by file name alone is looked for in the source directories
+        if !path.exists() && path.parent() == Some(Path::new("")) {
+            for source_directory in manifest.source_directories.iter() {
+                let source_path = source_directory.join(&path);
+                if source_path.exists() {
+                    return Ok(vec![ContractPath::new(source_path, PathBuf::new())]);
+                }
+            }
+        }
+
+        return Ok(vec![ContractPath::new(path, PathBuf::new())]);
     }
 
-    for path in paths {
-        let file_path = path.unwrap().path();
-        //Sheild your eyes, please disregard this line
-        let file_name = file_path.file_name().unwrap().to_str().unwrap().to_string();
+    let directories = if !directory_to_compile.is_empty() {
+        vec![PathBuf::from(directory_to_compile)]
+    } else {
+        manifest.source_directories.clone()
+    };
 
-        let file_contents = fs::read_to_string(file_path).unwrap();
+    //the manifest checks the ignore patterns when it is parsed
+    let ignore = ignore_set(&manifest.ignore).map_err(|error| {
+        EVMMError::InvalidManifest(
+            manifest.root.join(MANIFEST_FILE_NAME).display().to_string(),
+            error,
+        )
+    })?;
 
-        evmm_files.push(EVMMFile::new(file_name, file_contents.to_string()))
+    //files in the include paths are included by contracts rather than compiled on their own
+    let mut contract_paths = vec![];
+    for directory in directories {
+        contract_paths.extend(discover_contracts(
+            &directory,
+            &ignore,
+            &manifest.include_paths,
+        )?);
     }
 
-    Ok(evmm_files)
+    Ok(contract_paths)
 }
 
+///Compiles the contracts across all cores. The compiled contracts are in the same order as the contract paths, and if
+///several contracts fail to compile, the error is from the first of them, so the output does not depend on which
+///contract finishes compiling first.
 fn parse_and_compile_bytecode(
-    evmm_files: Vec<EVMMFile>,
-    deployment_bytecode: bool,
+    manifest: &Manifest,
+    contract_paths: Vec<ContractPath>,
 ) -> Result<Vec<EVMASMFile>, EVMMError> {
-    let mut compiled_evmasm_files: Vec<EVMASMFile> = vec![];
+    let compiled_contracts: Vec<Result<EVMASMFile, EVMMError>> = contract_paths
+        .into_par_iter()
+        .map(|contract_path| compile_contract(manifest, contract_path))
+        .collect();
 
-    for mut evmm_file in evmm_files {
-        let parsed_file = parse_file(&evmm_file.file_contents);
+    compiled_contracts.into_iter().collect()
+}
 
-        let compiled_bytecode =
-            compile_instructions(parsed_file.into_inner().peekable(), "".to_owned())?;
+///Compiles a contract in each of its output formats, with the options the manifest sets for it
+pub fn compile_contract(
+    manifest: &Manifest,
+    ContractPath {
+        path: contract_path,
+        output_subdirectory,
+    }: ContractPath,
+) -> Result<EVMASMFile, EVMMError> {
+    let contract_name = contract_name(&contract_path);
+    let options = manifest.compile_options(&contract_name);
 
-        //If the contract should compile to deployment bytecode
-        if deployment_bytecode {
-            //add deployment bytecode
+    let compiled_contract = compile_file(&contract_path, &options)?;
 
-            //add _deploy to the filename to indicate that it is deployment bytecode
-            //otherwise, just add the evmasm extension
-            let mut evmasm_file_name = evmm_file
-                .file_name
-                .strip_suffix(".evmm")
-                .unwrap()
-                .to_owned();
+    //If the contract declares an interface, emit the abi next to the bytecode
+    let abi = compiled_contract
+        .abi()
+        .map(|abi| serde_json::to_string_pretty(&abi).unwrap());
 
-            evmasm_file_name.push_str("_deploy.evmasm");
+    let artifact = serde_json::to_string_pretty(&compiled_contract.artifact).unwrap();
 
-            compiled_evmasm_files.push(EVMASMFile::new(evmasm_file_name, compiled_bytecode));
+    let mut compiled_bytecode = vec![];
+    for output_format in manifest.output_formats(&contract_name) {
+        //add _deploy to the filename to indicate that it is deployment bytecode
+        let evmasm_file_name = if options.deployment_bytecode {
+            format!(
+                "{}_deploy.{}",
+                compiled_contract.name,
+                output_format.file_extension()
+            )
         } else {
-            //otherwise, just add the evmasm extension
-            let mut evmasm_file_name = evmm_file
-                .file_name
-                .strip_suffix(".evmm")
-                .unwrap()
-                .to_owned();
+            format!(
+                "{}.{}",
+                compiled_contract.name,
+                output_format.file_extension()
+            )
+        };
 
-            evmasm_file_name.push_str(".evmasm");
+        //the contract is already formatted in the first output format
+        let output = if *output_format == options.output_format {
+            compiled_contract.output.clone()
+        } else {
+            compiled_contract.format(*output_format, options.deployment_bytecode)
+        };
 
-            compiled_evmasm_files.push(EVMASMFile::new(evmasm_file_name, compiled_bytecode));
-        }
+        compiled_bytecode.push((*output_format, evmasm_file_name, output));
     }
 
-    Ok(compiled_evmasm_files)
+    let runtime_size = compiled_contract.runtime_size();
+    let deployment_gas = compiled_contract.deployment_gas();
+
+    Ok(EVMASMFile::new(
+        contract_path,
+        compiled_contract.name,
+        output_subdirectory,
+        compiled_bytecode,
+        abi,
+        artifact,
+        compiled_contract.sources,
+        runtime_size,
+        deployment_gas,
+        compiled_contract.warnings,
+    ))
 }
 
-fn output_contracts(
-    evmasm_files: Vec<EVMASMFile>,
-    output_directory: &str,
-    print_in_terminal: bool,
-) -> Result<(), Error> {
+///Identifies the options and output formats a contract is compiled with in the build cache
+pub fn contract_options_key(manifest: &Manifest, contract_path: &Path) -> String {
+    let contract_name = contract_name(contract_path);
+
+    options_key(
+        &manifest.compile_options(&contract_name),
+        manifest.output_formats(&contract_name),
+    )
+}
+
+//the contract name is the file name without its extension
+pub(crate) fn contract_name(contract_path: &Path) -> String {
+    contract_path
+        .file_stem()
+        .map(|file_stem| file_stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+///Writes the output files of a contract, returning their paths relative to the output directory
+pub fn write_contract(
+    evmasm_file: &EVMASMFile,
+    output_directory: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    //contracts in subdirectories of the source directory are written to the same subdirectories of the output directory
+    let contract_output_directory = output_directory.join(&evmasm_file.output_subdirectory);
+    if fs::metadata(&contract_output_directory).is_err() {
+        std::fs::create_dir_all(&contract_output_directory)?;
+    }
+
+    let mut output_paths = vec![];
+    for (output_path, contents) in evmasm_file.output_files() {
+        let mut output_file = File::create(output_directory.join(&output_path))?;
+        output_file.write_all(contents)?;
+
+        output_paths.push(output_path);
+    }
+
+    Ok(output_paths)
+}
+
+///Prints the warnings of a compiled contract to stderr
+pub fn print_warnings(evmasm_file: &EVMASMFile) {
+    for warning in evmasm_file.warnings.iter() {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+fn print_contracts(evmasm_files: Vec<EVMASMFile>) -> Result<(), Error> {
     let evmasm_files_length = evmasm_files.len();
+    let mut stdout = stdout();
 
     for evmasm_file in evmasm_files {
-        //If an output directory is specified, write to a file
-        if !print_in_terminal {
-            if fs::metadata(output_directory).is_ok() {
-                let mut new_evmasm_file =
-                    File::create(format!("{}/{}", output_directory, evmasm_file.file_name))
-                        .unwrap();
-                new_evmasm_file.write_all(evmasm_file.compiled_bytecode.as_bytes())?;
-            } else {
-                std::fs::create_dir(output_directory).unwrap();
-
-                let mut new_evmasm_file =
-                    File::create(format!("{}/{}", output_directory, evmasm_file.file_name))
-                        .unwrap();
-                new_evmasm_file.write_all(evmasm_file.compiled_bytecode.as_bytes())?;
-            }
-        } else {
-            //otherwise, just log the output in the terminal
-            println!("{}", evmasm_file.compiled_bytecode);
-
-            //Add a space between contract byecode if there are more than one file being logged into the terminal.
-            //Usually, you should just log one file. It is set up this way so that the Foundry x EVMM Deployer
-            // can function as it needs the deployment bytecode and nothing else to work properly.
-            if evmasm_files_length > 1 {
-                println!("")
+        //warnings go to stderr so that the printed bytecode can be piped
+        print_warnings(&evmasm_file);
+
+        for (output_format, _, compiled_bytecode) in evmasm_file.compiled_bytecode.iter() {
+            stdout.write_all(compiled_bytecode)?;
+
+            //raw binary is written as is so that it can be piped into a file
+            if *output_format != OutputFormat::Binary && !compiled_bytecode.ends_with(b"\n") {
+                println!();
             }
         }
+
+        //Add a space between contract byecode if there are more than one file being logged into the terminal.
+        //Usually, you should just log one file. It is set up this way so that the Foundry x EVMM Deployer
+        // can function as it needs the deployment bytecode and nothing else to work properly.
+        if evmasm_files_length > 1 {
+            println!()
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_parse_and_compile_bytecode_order() {
+        let directory = env::temp_dir().join("evmm_parse_and_compile_bytecode_order");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        let mut contract_paths = vec![];
+        for index in 0..32 {
+            let path = directory.join(format!("Contract{:02}.evmm", index));
+            fs::write(&path, format!("PUSH1 {:#04x} PUSH1 0x00 MSTORE", index)).unwrap();
+            contract_paths.push(ContractPath::new(path, PathBuf::new()));
+        }
+
+        let manifest = Manifest::default();
+        let evmasm_files = parse_and_compile_bytecode(&manifest, contract_paths.clone()).unwrap();
+
+        //the compiled contracts are in the same order as the contract paths
+        for (index, evmasm_file) in evmasm_files.iter().enumerate() {
+            assert_eq!(evmasm_file.contract_name, format!("Contract{:02}", index));
+            assert_eq!(
+                evmasm_file.compiled_bytecode[0].2,
+                format!("60{:02X}600052", index).into_bytes()
+            );
+        }
+
+        //the error is from the first contract that fails to compile
+        fs::write(&contract_paths[5].path, "PUSH1 first_undefined JUMP").unwrap();
+        fs::write(&contract_paths[20].path, "PUSH1 second_undefined JUMP").unwrap();
+        for _ in 0..8 {
+            assert!(matches!(
+                parse_and_compile_bytecode(&manifest, contract_paths.clone()),
+                Err(EVMMError::UndefinedLabel(label)) if label == "first_undefined"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_output_directory_that_cannot_be_created() {
+        let directory = env::temp_dir().join("evmm_output_directory_that_cannot_be_created");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        let contract_path = directory.join("A.evmm");
+        fs::write(&contract_path, "STOP").unwrap();
+
+        //the output directory is inside a file, so it cannot be created
+        let output_file = directory.join("out");
+        fs::write(&output_file, "").unwrap();
+        let mut manifest = Manifest::new(directory.clone());
+        manifest.output_directory = output_file.join("build");
+
+        assert!(matches!(
+            evmm_parse_and_compile(&manifest, &contract_path.display().to_string(), "", false, false),
+            Err(EVMMError::WriteFailed(path, _)) if path == manifest.output_directory.display().to_string()
+        ));
+    }
+}
@@ -0,0 +1,421 @@
+use crate::core::discover::discover_contracts;
+use crate::core::evmm::get_contract_paths;
+use crate::core::manifest::Manifest;
+use crate::evmm_error::evmm_error::EVMMError;
+use crate::parser::ast::{InstructionKind, Program};
+use crate::parser::parse::{parse_named_file, Rule};
+use globset::GlobSet;
+use std::fs;
+use std::path::PathBuf;
+
+///The indentation of the instructions after a label
+const INDENT: &str = "    ";
+
+///Formats each contract and the files in the include paths, or only the contract at `contract_path` if it is given.
+///With `check`, the files are left as they are and the ones that are not formatted are listed instead.
+pub fn format_contracts(
+    manifest: &Manifest,
+    contract_path: &str,
+    check: bool,
+) -> Result<(), EVMMError> {
+    let mut paths: Vec<PathBuf> = get_contract_paths(manifest, contract_path, "")?
+        .into_iter()
+        .map(|contract_path| contract_path.path)
+        .collect();
+
+    //included files are not compiled on their own, but are still formatted
+    if contract_path.is_empty() {
+        for include_path in manifest.include_paths.iter().filter(|path| path.is_dir()) {
+            for contract_path in discover_contracts(include_path, &GlobSet::empty(), &[])? {
+                paths.push(contract_path.path);
+            }
+        }
+    }
+
+    //an include path can be inside a source directory, so the same file can be found twice
+    let mut formatted_paths = vec![];
+    let mut unformatted_files = 0;
+    for path in paths {
+        let canonical_path = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if formatted_paths.contains(&canonical_path) {
+            continue;
+        }
+        formatted_paths.push(canonical_path);
+
+        let source = fs::read_to_string(&path)
+            .map_err(|_| EVMMError::ContractNotFound(path.display().to_string()))?;
+        let formatted_source = format_source(&source, &path.display().to_string())?;
+        if formatted_source == source {
+            continue;
+        }
+
+        if check {
+            println!("Not formatted {}", path.display());
+            unformatted_files += 1;
+        } else {
+            fs::write(&path, formatted_source).map_err(|error| {
+                EVMMError::FormatFailed(path.display().to_string(), error.to_string())
+            })?;
+            println!("Formatted {}", path.display());
+        }
+    }
+
+    if unformatted_files > 0 {
+        Err(EVMMError::NotFormatted(unformatted_files))
+    } else {
+        Ok(())
+    }
+}
+
+///Formats a source file, keeping its comments and the lines its instructions are on. Mnemonics are written in
+///uppercase, hex push values are written as `0x` followed by uppercase digits padded to whole bytes, instructions
+///after a label are indented, whitespace is collapsed and the comments at the end of consecutive lines are aligned.
+pub fn format_source(source: &str, file_path: &str) -> Result<String, EVMMError> {
+    let file = parse_named_file(source, file_path)?;
+    let program = Program::from_pair(file.clone(), 0)?;
+
+    //the parts of the source to replace, in source order
+    let mut replacements = vec![];
+    for pair in file.into_inner() {
+        let span = pair.as_span();
+        let replacement = match pair.as_rule() {
+            Rule::hex_number => hex_literal(pair.as_str()),
+            rule if is_opcode(rule) => pair.as_str().to_uppercase(),
+            _ => continue,
+        };
+        if replacement != pair.as_str() {
+            replacements.push((span.start(), span.end(), replacement));
+        }
+    }
+
+    let mut normalized_source = source.to_owned();
+    for (start, end, replacement) in replacements.into_iter().rev() {
+        normalized_source.replace_range(start..end, &replacement);
+    }
+
+    let formatted_source = layout(&split_lines(&normalized_source));
+
+    //formatting only changes how the source is written, so the instructions have to stay the same
+    let formatted_program = parse_named_file(&formatted_source, file_path)
+        .and_then(|file| Program::from_pair(file, 0))
+        .map_err(|error| EVMMError::FormatFailed(file_path.to_owned(), error.to_string()))?;
+    if instruction_kinds(&formatted_program) != instruction_kinds(&program) {
+        return Err(EVMMError::FormatFailed(
+            file_path.to_owned(),
+            String::from("formatting would change the instructions"),
+        ));
+    }
+
+    Ok(formatted_source)
+}
+
+fn instruction_kinds(program: &Program) -> Vec<&InstructionKind> {
+    program
+        .instructions
+        .iter()
+        .map(|instruction| &instruction.kind)
+        .collect()
+}
+
+//every rule at the top level of a file is an opcode other than the values, labels, directives and built-ins
+fn is_opcode(rule: Rule) -> bool {
+    !matches!(
+        rule,
+        Rule::EOI
+            | Rule::include
+            | Rule::function_definition
+            | Rule::event_definition
+            | Rule::error_definition
+            | Rule::dispatcher
+            | Rule::func_sig
+            | Rule::event_hash
+            | Rule::error_selector
+            | Rule::label
+            | Rule::hex_number
+            | Rule::number
+            | Rule::label_reference
+    )
+}
+
+//ex. `0X1f` is written as `0x1F` and `0x100` as `0x0100`
+fn hex_literal(hex_number: &str) -> String {
+    let digits = hex_number[2..].to_uppercase();
+    if digits.len() % 2 == 1 {
+        format!("0x0{}", digits)
+    } else {
+        format!("0x{}", digits)
+    }
+}
+
+//a line of source split into its code and the comment at the end of it
+struct SourceLine {
+    code: String,
+    comment: Option<String>,
+    //lines that are part of a block comment spanning several lines are kept as they are
+    verbatim: bool,
+}
+
+fn split_lines(source: &str) -> Vec<SourceLine> {
+    let mut lines = vec![];
+    let mut in_block_comment = false;
+
+    for line in source.lines() {
+        let line = line.trim_end();
+
+        if in_block_comment {
+            in_block_comment = !line.contains("*/");
+            lines.push(SourceLine {
+                code: line.to_owned(),
+                comment: None,
+                verbatim: true,
+            });
+            continue;
+        }
+
+        let (code, comment, opens_block_comment) = split_comment(line);
+        in_block_comment = opens_block_comment;
+        lines.push(SourceLine {
+            code: collapse_whitespace(code),
+            comment: comment.map(String::from),
+            verbatim: false,
+        });
+    }
+
+    lines
+}
+
+//splits a line at the comment that ends it, returning true if the comment is a block comment that does not end on
+//the line. Block comments followed by code are left in the code.
+fn split_comment(line: &str) -> (&str, Option<&str>, bool) {
+    let mut in_string = false;
+    let mut index = 0;
+
+    while index < line.len() {
+        let rest = &line[index..];
+
+        if rest.starts_with('"') {
+            in_string = !in_string;
+        } else if !in_string && rest.starts_with("//") {
+            return (&line[..index], Some(rest), false);
+        } else if !in_string && rest.starts_with("/*") {
+            match rest[2..].find("*/") {
+                Some(end) if !rest[end + 4..].trim().is_empty() => {
+                    index += end + 4;
+                    continue;
+                }
+                Some(_) => return (&line[..index], Some(rest), false),
+                None => return (&line[..index], Some(rest), true),
+            }
+        }
+
+        index += rest.chars().next().map(char::len_utf8).unwrap_or(1);
+    }
+
+    (line, None, false)
+}
+
+//replaces each run of whitespace outside of strings with a single space
+fn collapse_whitespace(code: &str) -> String {
+    let mut collapsed = String::new();
+    let mut in_string = false;
+
+    for character in code.trim().chars() {
+        if character == '"' {
+            in_string = !in_string;
+        }
+        if !in_string && character.is_whitespace() {
+            if !collapsed.ends_with(' ') {
+                collapsed.push(' ');
+            }
+        } else {
+            collapsed.push(character);
+        }
+    }
+
+    collapsed
+}
+
+//ex. `transfer:`
+fn is_label(code: &str) -> bool {
+    match code
+        .split(' ')
+        .next()
+        .and_then(|word| word.strip_suffix(':'))
+    {
+        Some(name) => {
+            name.starts_with(|character: char| character.is_ascii_alphabetic() || character == '_')
+                && name
+                    .chars()
+                    .all(|character| character.is_ascii_alphanumeric() || character == '_')
+        }
+        None => false,
+    }
+}
+
+fn layout(lines: &[SourceLine]) -> String {
+    //labels and directives start at the first column, and the instructions after the first label are indented
+    let mut indents = vec![""; lines.len()];
+    let mut after_label = false;
+    for (index, line) in lines.iter().enumerate() {
+        if line.verbatim || line.code.is_empty() {
+            continue;
+        }
+        if is_label(&line.code) {
+            after_label = true;
+        } else if !line.code.starts_with('#') && after_label {
+            indents[index] = INDENT;
+        }
+    }
+
+    //a comment on a line of its own is indented like the code it comes before
+    let mut next_indent = "";
+    for (index, line) in lines.iter().enumerate().rev() {
+        if line.verbatim {
+            continue;
+        }
+        if line.code.is_empty() {
+            if line.comment.is_some() {
+                indents[index] = next_indent;
+            }
+        } else {
+            next_indent = indents[index];
+        }
+    }
+
+    //the comments at the end of consecutive instructions start in the same column, so a run of lines ends at a blank
+    //line, a comment on a line of its own, a label or a block comment
+    let mut comment_columns = vec![0; lines.len()];
+    let mut run_start = 0;
+    for index in 0..=lines.len() {
+        let ends_run = match lines.get(index) {
+            Some(line) => line.verbatim || line.code.is_empty() || is_label(&line.code),
+            None => true,
+        };
+        if !ends_run {
+            continue;
+        }
+
+        let run = run_start..index;
+        let column = run
+            .clone()
+            .filter(|line| lines[*line].comment.is_some())
+            .map(|line| indents[line].len() + lines[line].code.len() + 1)
+            .max()
+            .unwrap_or_default();
+        for line in run {
+            comment_columns[line] = column;
+        }
+        run_start = index + 1;
+    }
+
+    let mut formatted_lines: Vec<String> = vec![];
+    for (index, line) in lines.iter().enumerate() {
+        let formatted_line = if line.verbatim {
+            line.code.clone()
+        } else {
+            let mut formatted_line = format!("{}{}", indents[index], line.code);
+            if let Some(comment) = &line.comment {
+                if !line.code.is_empty() {
+                    //labels are not part of a run, so their comment is a single space after them
+                    let column = comment_columns[index].max(formatted_line.len() + 1);
+                    formatted_line = format!("{:width$}", formatted_line, width = column);
+                }
+                formatted_line.push_str(comment);
+            }
+            formatted_line
+        };
+
+        //blank lines are collapsed, and there are none at the start of the file
+        if formatted_line.is_empty()
+            && formatted_lines
+                .last()
+                .is_none_or(|previous_line| previous_line.is_empty())
+        {
+            continue;
+        }
+        formatted_lines.push(formatted_line);
+    }
+
+    while formatted_lines.last().is_some_and(|line| line.is_empty()) {
+        formatted_lines.pop();
+    }
+
+    if formatted_lines.is_empty() {
+        String::new()
+    } else {
+        formatted_lines.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_source() {
+        let source = r#"
+
+#define function   increment()
+__DISPATCHER(fallback)
+//adds one to the count
+increment: //the selector jumps here
+push1 0X0   //slot
+  SLOAD //loads the count
+    PUSH1   0x1 //one
+add
+
+
+PUSH1 0x00
+   sstore /* stores the count */
+  stop
+fallback:
+/* a block comment
+   over several lines */
+PUSH2 0x100
+        DUP1 REVERT   "#;
+
+        let formatted_source = format_source(source, "Counter.evmm").unwrap();
+        assert_eq!(
+            formatted_source,
+            r#"#define function increment()
+__DISPATCHER(fallback)
+//adds one to the count
+increment: //the selector jumps here
+    PUSH1 0x00 //slot
+    SLOAD      //loads the count
+    PUSH1 0x01 //one
+    ADD
+
+    PUSH1 0x00
+    SSTORE /* stores the count */
+    STOP
+fallback:
+    /* a block comment
+   over several lines */
+    PUSH2 0x0100
+    DUP1 REVERT
+"#
+        );
+
+        //formatting a formatted file does not change it
+        assert_eq!(
+            format_source(&formatted_source, "Counter.evmm").unwrap(),
+            formatted_source
+        );
+    }
+
+    #[test]
+    fn test_format_keeps_strings_and_references() {
+        let source = "#include  \"./lib/a  b.evmm\"\nstart:\nPUSH2 start //jumps   back\nJUMP\n";
+
+        assert_eq!(
+            format_source(source, "A.evmm").unwrap(),
+            "#include \"./lib/a  b.evmm\"\nstart:\n    PUSH2 start //jumps   back\n    JUMP\n"
+        );
+    }
+
+    #[test]
+    fn test_format_invalid_source() {
+        assert!(format_source("PUSH1 PUSH1", "A.evmm").is_err());
+    }
+}
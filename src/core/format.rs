@@ -0,0 +1,321 @@
+use crate::compiler::compile::{decode_hex, CompiledBytecode, SourceMapEntry};
+use crate::compiler::opcodes::{mnemonic, push_size};
+use crate::core::annotate::annotated_listing;
+use crate::parser::ast::SourceFile;
+use std::collections::HashMap;
+
+///The encodings that compiled bytecode can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    //uppercase hex without a prefix, ex. `6001`
+    Hex,
+    //lowercase hex with a `0x` prefix, ex. `0x6001`
+    PrefixedHex,
+    //the raw bytes
+    Binary,
+    //a Rust byte array constant
+    Rust,
+    //a Solidity hex literal constant
+    Solidity,
+    //one instruction per line with the offset, bytes, mnemonic and source line
+    Listing,
+    //one instruction per line with the offset, bytes, mnemonic, label names, static gas and symbolic stack
+    Annotated,
+}
+
+impl OutputFormat {
+    pub const NAMES: [&'static str; 7] = [
+        "hex",
+        "0x",
+        "binary",
+        "rust",
+        "solidity",
+        "listing",
+        "annotated",
+    ];
+
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name {
+            "hex" => Some(OutputFormat::Hex),
+            "0x" => Some(OutputFormat::PrefixedHex),
+            "binary" => Some(OutputFormat::Binary),
+            "rust" => Some(OutputFormat::Rust),
+            "solidity" => Some(OutputFormat::Solidity),
+            "listing" => Some(OutputFormat::Listing),
+            "annotated" => Some(OutputFormat::Annotated),
+            _ => None,
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Hex | OutputFormat::PrefixedHex => "evmasm",
+            OutputFormat::Binary => "bin",
+            OutputFormat::Rust => "rs",
+            OutputFormat::Solidity => "sol",
+            OutputFormat::Listing => "lst",
+            OutputFormat::Annotated => "asm",
+        }
+    }
+}
+
+///Encodes bytecode in the given format. The compiled contract and sources are only used by the listings, where
+///`runtime_offset` is the offset of the compiled contract within `bytecode`, ex. the size of the constructor for deployment bytecode.
+pub fn format_bytecode(
+    output_format: OutputFormat,
+    contract_name: &str,
+    bytecode: &str,
+    compiled_bytecode: &CompiledBytecode,
+    runtime_offset: usize,
+    sources: &[SourceFile],
+) -> Vec<u8> {
+    let bytes = decode_hex(bytecode).unwrap_or_default();
+
+    match output_format {
+        OutputFormat::Hex => bytecode.as_bytes().to_vec(),
+
+        OutputFormat::PrefixedHex => format!("0x{}", bytecode.to_lowercase()).into_bytes(),
+
+        OutputFormat::Binary => bytes,
+
+        OutputFormat::Rust => {
+            let mut rust_constant = format!(
+                "pub const {}: [u8; {}] = [\n",
+                constant_name(contract_name),
+                bytes.len()
+            );
+
+            for chunk in bytes.chunks(16) {
+                let formatted_chunk: Vec<String> = chunk
+                    .iter()
+                    .map(|byte| format!("0x{:02x},", byte))
+                    .collect();
+                rust_constant.push_str(&format!("    {}\n", formatted_chunk.join(" ")));
+            }
+
+            rust_constant.push_str("];\n");
+            rust_constant.into_bytes()
+        }
+
+        OutputFormat::Solidity => format!(
+            "bytes constant {} = hex\"{}\";\n",
+            constant_name(contract_name),
+            bytecode.to_lowercase()
+        )
+        .into_bytes(),
+
+        OutputFormat::Listing => format_listing(
+            &bytes,
+            &compiled_bytecode.source_map,
+            runtime_offset,
+            sources,
+        )
+        .into_bytes(),
+
+        OutputFormat::Annotated => {
+            annotated_listing(&bytes, compiled_bytecode, runtime_offset).into_bytes()
+        }
+    }
+}
+
+///Disassembles the bytecode into aligned columns of offset, bytes, mnemonic and the source line that produced each instruction.
+///Lines from included files are prefixed with the path of the file.
+fn format_listing(
+    bytes: &[u8],
+    source_map: &[SourceMapEntry],
+    runtime_offset: usize,
+    sources: &[SourceFile],
+) -> String {
+    let source_entries: HashMap<usize, &SourceMapEntry> = source_map
+        .iter()
+        .map(|entry| (entry.bytecode_offset + runtime_offset, entry))
+        .collect();
+
+    //offset, bytes, instruction and source columns for each instruction
+    let mut rows: Vec<[String; 4]> = vec![];
+
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let opcode = bytes[offset];
+        //push data can be cut short at the end of the bytecode
+        let end = (offset + 1 + push_size(opcode)).min(bytes.len());
+        let instruction_bytes = &bytes[offset..end];
+
+        let mut instruction = match mnemonic(opcode) {
+            Some(mnemonic) => mnemonic.to_owned(),
+            None => format!("UNKNOWN 0x{:02x}", opcode),
+        };
+        if instruction_bytes.len() > 1 {
+            instruction.push_str(&format!(" 0x{}", to_hex(&instruction_bytes[1..])));
+        }
+
+        let source_line = match source_entries.get(&offset).and_then(|entry| {
+            sources
+                .get(entry.source_index)
+                .map(|source| (entry, source))
+        }) {
+            Some((entry, source)) => {
+                let contents = &source.contents;
+                let line_number = contents[..entry.source_start].matches('\n').count() + 1;
+                let line = contents.lines().nth(line_number - 1).unwrap_or("").trim();
+
+                if entry.source_index == 0 {
+                    format!("{}: {}", line_number, line)
+                } else {
+                    format!("{}:{}: {}", source.path, line_number, line)
+                }
+            }
+            None => String::new(),
+        };
+
+        let formatted_bytes: Vec<String> = instruction_bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        rows.push([
+            format!("{:04x}", offset),
+            formatted_bytes.join(" "),
+            instruction,
+            source_line,
+        ]);
+
+        offset = end;
+    }
+
+    let bytes_width = rows.iter().map(|row| row[1].len()).max().unwrap_or(0);
+    let instruction_width = rows.iter().map(|row| row[2].len()).max().unwrap_or(0);
+
+    let mut listing = String::new();
+    for [offset, bytes, instruction, source_line] in rows {
+        let row = format!(
+            "{}  {:<bytes_width$}  {:<instruction_width$}  {}",
+            offset, bytes, instruction, source_line
+        );
+        listing.push_str(row.trim_end());
+        listing.push('\n');
+    }
+
+    listing
+}
+
+///Converts a contract name to a SCREAMING_SNAKE_CASE constant name, ex. `MstoreDead` to `MSTORE_DEAD`
+fn constant_name(contract_name: &str) -> String {
+    let mut name = String::new();
+    let mut previous_char: Option<char> = None;
+
+    for c in contract_name.chars() {
+        if !c.is_ascii_alphanumeric() {
+            name.push('_');
+        } else {
+            if c.is_ascii_uppercase()
+                && previous_char.is_some_and(|previous_char| {
+                    previous_char.is_ascii_lowercase() || previous_char.is_ascii_digit()
+                })
+            {
+                name.push('_');
+            }
+            name.push(c.to_ascii_uppercase());
+        }
+        previous_char = Some(c);
+    }
+
+    //constants can not start with a digit
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+
+    name
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile::{compile_deployment_bytecode, compile_instructions};
+    use crate::parser::ast::parse_program;
+
+    const SOURCE: &str = "PUSH1 0x01\nPUSH1 0x00\nMSTORE\n";
+
+    fn format(output_format: OutputFormat) -> String {
+        let program = parse_program(SOURCE).unwrap();
+        let compiled_bytecode = compile_instructions(&program, "".to_owned()).unwrap();
+
+        String::from_utf8(format_bytecode(
+            output_format,
+            "MstoreOne",
+            &compiled_bytecode.bytecode,
+            &compiled_bytecode,
+            0,
+            &program.sources,
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_format_hex() {
+        assert_eq!(format(OutputFormat::Hex), "6001600052");
+        assert_eq!(format(OutputFormat::PrefixedHex), "0x6001600052");
+    }
+
+    #[test]
+    fn test_format_binary() {
+        assert_eq!(
+            format(OutputFormat::Binary).into_bytes(),
+            vec![0x60, 0x01, 0x60, 0x00, 0x52]
+        );
+    }
+
+    #[test]
+    fn test_format_byte_array_literals() {
+        assert_eq!(
+            format(OutputFormat::Rust),
+            "pub const MSTORE_ONE: [u8; 5] = [\n    0x60, 0x01, 0x60, 0x00, 0x52,\n];\n"
+        );
+        assert_eq!(
+            format(OutputFormat::Solidity),
+            "bytes constant MSTORE_ONE = hex\"6001600052\";\n"
+        );
+    }
+
+    #[test]
+    fn test_format_listing() {
+        assert_eq!(
+            format(OutputFormat::Listing),
+            "0000  60 01  PUSH1 0x01  1: PUSH1 0x01\n\
+             0002  60 00  PUSH1 0x00  2: PUSH1 0x00\n\
+             0004  52     MSTORE      3: MSTORE\n"
+        );
+    }
+
+    #[test]
+    fn test_format_deployment_listing() {
+        let program = parse_program(SOURCE).unwrap();
+        let compiled_bytecode = compile_instructions(&program, "".to_owned()).unwrap();
+        let deployment_bytecode = compile_deployment_bytecode(&compiled_bytecode.bytecode).unwrap();
+
+        let listing = String::from_utf8(format_bytecode(
+            OutputFormat::Listing,
+            "MstoreOne",
+            &deployment_bytecode,
+            &compiled_bytecode,
+            13,
+            &program.sources,
+        ))
+        .unwrap();
+        let lines: Vec<&str> = listing.lines().collect();
+
+        assert_eq!(lines[0], "0000  61 00 05  PUSH2 0x0005");
+        assert_eq!(lines[7], "000d  60 01     PUSH1 0x01    1: PUSH1 0x01");
+    }
+
+    #[test]
+    fn test_constant_name() {
+        assert_eq!(constant_name("Token"), "TOKEN");
+        assert_eq!(constant_name("mstore_dead"), "MSTORE_DEAD");
+        assert_eq!(constant_name("ERC20Token"), "ERC20_TOKEN");
+    }
+}
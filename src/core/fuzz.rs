@@ -0,0 +1,861 @@
+use crate::compiler::interface::Interface;
+use crate::core::contract::CompiledContract;
+use crate::core::manifest::{string, Manifest};
+use crate::core::test_runner::{
+    compile_test_contract, deploy_contract, load_test_files, DEFAULT_CALLER,
+};
+use crate::evm::abi::{encode, format_hex, format_value, parse_u256, selector, AbiType, AbiValue};
+use crate::evm::interpreter::{ExecutionResult, ExitStatus, Transaction};
+use crate::evm::state::{address_to_u256, Address};
+use crate::evmm_error::evmm_error::EVMMError;
+use crate::utils::random::Random;
+use primitive_types::U256;
+use toml::value::Table;
+
+///The accounts that calls are sent from, which are funded before the calls are made
+pub const FUZZ_CALLERS: [Address; 3] = [
+    DEFAULT_CALLER,
+    Address::repeat_byte(0xA1),
+    Address::repeat_byte(0xB0),
+];
+
+//bounds on the work done to shrink a failing sequence, since each attempt runs the whole sequence
+const MAX_SHRINK_ATTEMPTS: usize = 2000;
+
+///Settings for a fuzzing campaign
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuzzSettings {
+    //the number of call sequences run against a fresh deployment
+    pub runs: u64,
+    //the number of calls in each sequence
+    pub depth: usize,
+    pub seed: u64,
+}
+
+impl FuzzSettings {
+    pub fn new(runs: u64, depth: usize, seed: u64) -> FuzzSettings {
+        FuzzSettings { runs, depth, seed }
+    }
+}
+
+///A property of the contract that must hold after every call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invariant {
+    pub name: String,
+    pub kind: InvariantKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantKind {
+    //a storage slot of the contract never gets smaller
+    NeverDecreases(U256),
+    //a storage slot of the contract never gets larger
+    NeverIncreases(U256),
+    //calls to the function with valid arguments, and without value unless it is payable, always succeed
+    NeverReverts(String),
+}
+
+///A function that calls are generated for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzFunction {
+    pub signature: String,
+    pub parameter_types: Vec<AbiType>,
+    pub payable: bool,
+}
+
+///A generated call to the contract
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzCall {
+    pub caller: Address,
+    pub value: U256,
+    pub input: CallInput,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallInput {
+    //a call to a declared function with arguments that are valid for its parameter types
+    Function(FuzzFunction, Vec<AbiValue>),
+    //calldata that does not have to match any function
+    Raw(Vec<u8>),
+}
+
+impl FuzzCall {
+    pub fn calldata(&self) -> Vec<u8> {
+        match &self.input {
+            CallInput::Function(function, arguments) => {
+                let mut calldata = selector(&function.signature).to_vec();
+                calldata.extend(encode(&function.parameter_types, arguments));
+                calldata
+            }
+            CallInput::Raw(calldata) => calldata.clone(),
+        }
+    }
+
+    ///Writes the call as a `[[test.call]]` table for a `.t.toml` test file
+    pub fn to_test_call(&self) -> String {
+        let mut lines = vec![String::from("[[test.call]]")];
+
+        match &self.input {
+            CallInput::Function(function, arguments) => {
+                lines.push(format!("function = \"{}\"", function.signature));
+                if !arguments.is_empty() {
+                    let arguments: Vec<String> = function
+                        .parameter_types
+                        .iter()
+                        .zip(arguments.iter())
+                        .map(|(parameter_type, argument)| {
+                            toml::Value::String(format_value(parameter_type, argument)).to_string()
+                        })
+                        .collect();
+                    lines.push(format!("args = [{}]", arguments.join(", ")));
+                }
+            }
+            CallInput::Raw(calldata) => {
+                lines.push(format!("calldata = \"{}\"", format_hex(calldata)))
+            }
+        }
+
+        if self.caller != DEFAULT_CALLER {
+            lines.push(format!(
+                "caller = \"{}\"",
+                format_hex(self.caller.as_bytes())
+            ));
+        }
+        if !self.value.is_zero() {
+            lines.push(format!("value = \"{:#x}\"", self.value));
+        }
+
+        lines.join("\n")
+    }
+}
+
+//a broken invariant, found after the call at the index
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Violation {
+    invariant_index: usize,
+    call_index: usize,
+    message: String,
+}
+
+///Parses an `[[invariant]]` table of a test file, which has a name and one of `never_decreases`, `never_increases` or
+///`never_reverts`
+pub fn parse_invariant(table: &Table) -> Result<Invariant, String> {
+    let name = string(
+        "name",
+        table.get("name").ok_or("an invariant is missing `name`")?,
+    )?
+    .to_owned();
+    let in_invariant = |error: String| format!("{} in invariant `{}`", error, name);
+
+    let mut kind = None;
+    for (key, value) in table.iter() {
+        let invariant_kind = match key.as_str() {
+            "name" => continue,
+            "never_decreases" | "never_increases" => {
+                let slot = string(key, value).map_err(in_invariant)?;
+                let slot = parse_u256(slot)
+                    .ok_or_else(|| in_invariant(format!("invalid storage slot `{}`", slot)))?;
+
+                if key == "never_decreases" {
+                    InvariantKind::NeverDecreases(slot)
+                } else {
+                    InvariantKind::NeverIncreases(slot)
+                }
+            }
+            "never_reverts" => {
+                let signature: String = string(key, value)
+                    .map_err(in_invariant)?
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect();
+                InvariantKind::NeverReverts(signature)
+            }
+            _ => return Err(in_invariant(format!("unknown key `{}`", key))),
+        };
+
+        if kind.replace(invariant_kind).is_some() {
+            return Err(in_invariant(String::from("more than one property")));
+        }
+    }
+
+    Ok(Invariant {
+        name: name.clone(),
+        kind: kind.ok_or_else(|| in_invariant(String::from("missing a property")))?,
+    })
+}
+
+///Fuzzes the contract of every test file that declares invariants, printing whether each invariant held and the
+///shrunk call sequence that breaks it. Returns an error if any invariant was broken.
+pub fn fuzz_contracts(
+    manifest: &Manifest,
+    filter: &str,
+    settings: FuzzSettings,
+) -> Result<(), EVMMError> {
+    let mut held = 0;
+    let mut broken = 0;
+
+    for test_file in load_test_files(&manifest.test_directory)? {
+        let invariants: Vec<Invariant> = test_file
+            .invariants
+            .iter()
+            .filter(|invariant| invariant.name.contains(filter))
+            .cloned()
+            .collect();
+        if invariants.is_empty() {
+            continue;
+        }
+
+        let compiled_contract = compile_test_contract(manifest, &test_file)?;
+        println!(
+            "Fuzzing {} with {} invariants ({} runs of {} calls, seed {})",
+            test_file.contract,
+            invariants.len(),
+            settings.runs,
+            settings.depth,
+            settings.seed
+        );
+
+        let violations =
+            fuzz_contract(&compiled_contract, &invariants, settings).map_err(|error| {
+                EVMMError::InvalidTestFile(test_file.path.display().to_string(), error)
+            })?;
+
+        for (invariant, violation) in invariants.iter().zip(violations.iter()) {
+            match violation {
+                None => {
+                    held += 1;
+                    println!("[PASS] {}", invariant.name);
+                }
+                Some((message, calls)) => {
+                    broken += 1;
+                    println!("[FAIL] {}", invariant.name);
+                    println!("    {}, after {} calls", message, calls.len());
+                    println!("    Shrunk reproduction:");
+                    println!("    [[test]]");
+                    println!("    name = {}", toml::Value::String(invariant.name.clone()));
+                    for call in calls {
+                        println!();
+                        println!("    {}", call.to_test_call().replace('\n', "\n    "));
+                    }
+                }
+            }
+        }
+    }
+
+    if held + broken == 0 {
+        println!(
+            "No invariants found in {}",
+            manifest.test_directory.display()
+        );
+        return Ok(());
+    }
+
+    println!("Fuzz result: {} held; {} broken", held, broken);
+
+    if broken > 0 {
+        Err(EVMMError::InvariantsBroken(broken))
+    } else {
+        Ok(())
+    }
+}
+
+///Runs random call sequences against fresh deployments of the contract, returning for each invariant the message and
+///the shrunk calls that break it, or None if it held
+#[allow(clippy::type_complexity)]
+pub fn fuzz_contract(
+    compiled_contract: &CompiledContract,
+    invariants: &[Invariant],
+    settings: FuzzSettings,
+) -> Result<Vec<Option<(String, Vec<FuzzCall>)>>, String> {
+    let functions = fuzz_functions(&compiled_contract.interface);
+    let mut random = Random::new(settings.seed);
+    let mut violations: Vec<Option<(String, Vec<FuzzCall>)>> = vec![None; invariants.len()];
+
+    for _ in 0..settings.runs {
+        //invariants that were broken are not checked again
+        let active_invariants: Vec<usize> = (0..invariants.len())
+            .filter(|index| violations[*index].is_none())
+            .collect();
+        if active_invariants.is_empty() {
+            break;
+        }
+
+        let calls: Vec<FuzzCall> = (0..settings.depth)
+            .map(|_| generate_call(&mut random, &functions))
+            .collect();
+
+        if let Some(violation) =
+            run_sequence(compiled_contract, &calls, invariants, &active_invariants)?
+        {
+            let invariant_index = violation.invariant_index;
+            let (message, calls) = shrink_calls(
+                calls[..=violation.call_index].to_vec(),
+                violation.message,
+                |calls| {
+                    Ok(
+                        run_sequence(compiled_contract, calls, invariants, &[invariant_index])?
+                            .map(|violation| violation.message),
+                    )
+                },
+            )?;
+            violations[invariant_index] = Some((message, calls));
+        }
+    }
+
+    Ok(violations)
+}
+
+///Gets the functions declared in the interface, which calls are generated for
+pub fn fuzz_functions(interface: &Interface) -> Vec<FuzzFunction> {
+    interface
+        .functions
+        .iter()
+        .filter_map(|function| {
+            let parameter_types = function
+                .inputs
+                .iter()
+                .map(|input| AbiType::parse(&input.canonical_type))
+                .collect::<Option<Vec<AbiType>>>()?;
+
+            Some(FuzzFunction {
+                signature: function.signature(),
+                parameter_types,
+                payable: function.state_mutability == "payable",
+            })
+        })
+        .collect()
+}
+
+///Generates a call to one of the functions with valid arguments, or random calldata one time in eight or when there
+///are no functions
+pub fn generate_call(random: &mut Random, functions: &[FuzzFunction]) -> FuzzCall {
+    let caller = *random.choose(&FUZZ_CALLERS);
+
+    if functions.is_empty() || random.one_in(8) {
+        //random calldata often starts with a real selector so that it reaches the function
+        let mut calldata = match functions.is_empty() || random.one_in(2) {
+            true => vec![],
+            false => selector(&random.choose(functions).signature).to_vec(),
+        };
+        let length = random.below(68) as usize;
+        calldata.extend(random.bytes(length));
+
+        let value = generate_call_value(random, 4);
+        return FuzzCall {
+            caller,
+            value,
+            input: CallInput::Raw(calldata),
+        };
+    }
+
+    let function = random.choose(functions).clone();
+    let arguments = function
+        .parameter_types
+        .iter()
+        .map(|parameter_type| generate_value(random, parameter_type))
+        .collect();
+
+    //value is sent to functions that are not payable less often, to check that they reject it
+    let value = generate_call_value(random, if function.payable { 2 } else { 8 });
+    FuzzCall {
+        caller,
+        value,
+        input: CallInput::Function(function, arguments),
+    }
+}
+
+//sends value one time in `one_in`, which is at most a thousand ether so that the callers can always afford it
+fn generate_call_value(random: &mut Random, one_in: u64) -> U256 {
+    if !random.one_in(one_in) {
+        return U256::zero();
+    }
+
+    match random.below(3) {
+        0 => U256::one(),
+        1 => U256::from(random.below(1_000_000)),
+        _ => U256::from(random.below(1000)) * U256::exp10(18),
+    }
+}
+
+///Generates a valid value of the type, favoring edge cases like zero and the largest values
+pub fn generate_value(random: &mut Random, value_type: &AbiType) -> AbiValue {
+    match value_type {
+        AbiType::Uint(bits) => {
+            let max = max_value(*bits);
+            let word = match random.below(4) {
+                0 => U256::from(random.below(4)),
+                1 => *random.choose(&[max, max - 1, max >> 1, (max >> 1) + 1]),
+                _ => random.u256() & max,
+            };
+            AbiValue::Word(word)
+        }
+        AbiType::Int(bits) => {
+            //values are generated as unsigned and then sign extended
+            let max = max_value(*bits);
+            let unsigned = match random.below(4) {
+                0 => U256::from(random.below(4)),
+                1 => *random.choose(&[max, max >> 1, (max >> 1) + 1]),
+                _ => random.u256() & max,
+            };
+            let is_negative = unsigned.bit(bits - 1);
+            AbiValue::Word(if is_negative {
+                unsigned | !max
+            } else {
+                unsigned
+            })
+        }
+        AbiType::Address => {
+            //known accounts are more likely to reach interesting code than random addresses
+            let address = match random.one_in(2) {
+                true => *random.choose(&FUZZ_CALLERS),
+                false => Address::from_slice(&random.bytes(20)),
+            };
+            AbiValue::Word(address_to_u256(&address))
+        }
+        AbiType::Bool => AbiValue::Word(U256::from(random.below(2))),
+        AbiType::FixedBytes(size) => {
+            let mut bytes = random.bytes(*size);
+            bytes.resize(32, 0);
+            AbiValue::Word(U256::from_big_endian(&bytes))
+        }
+        AbiType::Bytes => {
+            let length = random.below(65) as usize;
+            AbiValue::Bytes(random.bytes(length))
+        }
+        AbiType::String => {
+            //letters and spaces, so the string can be written in a test file without escaping
+            const CHARACTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyz ";
+            let length = random.below(33) as usize;
+            AbiValue::Bytes((0..length).map(|_| *random.choose(CHARACTERS)).collect())
+        }
+        AbiType::Array(element_type) => {
+            let length = random.below(5) as usize;
+            AbiValue::List(
+                (0..length)
+                    .map(|_| generate_value(random, element_type))
+                    .collect(),
+            )
+        }
+        AbiType::FixedArray(element_type, length) => AbiValue::List(
+            (0..*length)
+                .map(|_| generate_value(random, element_type))
+                .collect(),
+        ),
+        AbiType::Tuple(element_types) => AbiValue::List(
+            element_types
+                .iter()
+                .map(|element_type| generate_value(random, element_type))
+                .collect(),
+        ),
+    }
+}
+
+fn max_value(bits: usize) -> U256 {
+    if bits >= 256 {
+        U256::MAX
+    } else {
+        (U256::one() << bits) - 1
+    }
+}
+
+//runs the calls against a fresh deployment, returning the first broken invariant among the active invariants
+fn run_sequence(
+    compiled_contract: &CompiledContract,
+    calls: &[FuzzCall],
+    invariants: &[Invariant],
+    active_invariants: &[usize],
+) -> Result<Option<Violation>, String> {
+    let (mut evm, address) = deploy_contract(compiled_contract)?;
+    for caller in FUZZ_CALLERS {
+        evm.state.account_mut(&caller).balance = U256::from(u128::MAX);
+    }
+
+    for (call_index, call) in calls.iter().enumerate() {
+        let storage_before: Vec<U256> = active_invariants
+            .iter()
+            .map(|index| match &invariants[*index].kind {
+                InvariantKind::NeverDecreases(slot) | InvariantKind::NeverIncreases(slot) => {
+                    evm.state.storage(&address, slot)
+                }
+                InvariantKind::NeverReverts(_) => U256::zero(),
+            })
+            .collect();
+
+        let result = evm.transact(&Transaction::new(
+            call.caller,
+            Some(address),
+            call.value,
+            call.calldata(),
+        ));
+
+        for (invariant_index, before) in active_invariants.iter().zip(storage_before) {
+            let message = match &invariants[*invariant_index].kind {
+                InvariantKind::NeverDecreases(slot) => {
+                    let after = evm.state.storage(&address, slot);
+                    (after < before).then(|| {
+                        format!(
+                            "storage slot {:#x} decreased from {:#x} to {:#x}",
+                            slot, before, after
+                        )
+                    })
+                }
+                InvariantKind::NeverIncreases(slot) => {
+                    let after = evm.state.storage(&address, slot);
+                    (after > before).then(|| {
+                        format!(
+                            "storage slot {:#x} increased from {:#x} to {:#x}",
+                            slot, before, after
+                        )
+                    })
+                }
+                InvariantKind::NeverReverts(signature) => {
+                    never_reverts_violation(signature, call, &result)
+                }
+            };
+
+            if let Some(message) = message {
+                return Ok(Some(Violation {
+                    invariant_index: *invariant_index,
+                    call_index,
+                    message,
+                }));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn never_reverts_violation(
+    signature: &str,
+    call: &FuzzCall,
+    result: &ExecutionResult,
+) -> Option<String> {
+    let function = match &call.input {
+        CallInput::Function(function, _) if function.signature == signature => function,
+        _ => return None,
+    };
+
+    //sending value to a function that is not payable is not a valid call
+    if !function.payable && !call.value.is_zero() {
+        return None;
+    }
+
+    match result.status {
+        ExitStatus::Success => None,
+        ExitStatus::Revert => Some(format!("{} reverted", signature)),
+        ExitStatus::Halt(halt_reason) => Some(format!("{} failed with {}", signature, halt_reason)),
+    }
+}
+
+///Finds a shorter sequence of simpler calls that still fails, by removing calls and then simplifying the value and
+///arguments of each remaining call. The check returns the failure message if the calls still fail.
+pub fn shrink_calls<F>(
+    mut calls: Vec<FuzzCall>,
+    mut message: String,
+    mut check: F,
+) -> Result<(String, Vec<FuzzCall>), String>
+where
+    F: FnMut(&[FuzzCall]) -> Result<Option<String>, String>,
+{
+    let mut attempts = 0;
+    let mut still_fails = |calls: &[FuzzCall], message: &mut String| -> Result<bool, String> {
+        attempts += 1;
+        if attempts > MAX_SHRINK_ATTEMPTS {
+            return Ok(false);
+        }
+
+        match check(calls)? {
+            Some(failure_message) => {
+                *message = failure_message;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    };
+
+    //calls are removed from the end first, since later calls depend on the state earlier calls leave behind
+    let mut index = calls.len();
+    while index > 0 {
+        index -= 1;
+        if calls.len() == 1 {
+            break;
+        }
+
+        let mut candidate = calls.clone();
+        candidate.remove(index);
+        if still_fails(&candidate, &mut message)? {
+            calls = candidate;
+        }
+        index = index.min(calls.len());
+    }
+
+    for index in 0..calls.len() {
+        loop {
+            let mut simplified = false;
+
+            for simpler_call in simpler_calls(&calls[index]) {
+                let mut candidate = calls.clone();
+                candidate[index] = simpler_call;
+
+                if still_fails(&candidate, &mut message)? {
+                    calls = candidate;
+                    simplified = true;
+                    break;
+                }
+            }
+
+            if !simplified {
+                break;
+            }
+        }
+    }
+
+    Ok((message, calls))
+}
+
+//calls that differ from the call in one simpler part
+fn simpler_calls(call: &FuzzCall) -> Vec<FuzzCall> {
+    let mut simpler_calls = vec![];
+
+    if !call.value.is_zero() {
+        simpler_calls.push(FuzzCall {
+            value: U256::zero(),
+            ..call.clone()
+        });
+        simpler_calls.push(FuzzCall {
+            value: call.value >> 1,
+            ..call.clone()
+        });
+    }
+
+    if call.caller != DEFAULT_CALLER {
+        simpler_calls.push(FuzzCall {
+            caller: DEFAULT_CALLER,
+            ..call.clone()
+        });
+    }
+
+    match &call.input {
+        CallInput::Function(function, arguments) => {
+            let argument_types = AbiType::Tuple(function.parameter_types.clone());
+            for simpler_arguments in
+                simpler_values(&argument_types, &AbiValue::List(arguments.clone()))
+            {
+                if let AbiValue::List(simpler_arguments) = simpler_arguments {
+                    simpler_calls.push(FuzzCall {
+                        input: CallInput::Function(function.clone(), simpler_arguments),
+                        ..call.clone()
+                    });
+                }
+            }
+        }
+        CallInput::Raw(calldata) => {
+            //the selector is kept while the rest is shortened
+            for length in [
+                calldata.len().min(4),
+                calldata.len() / 2,
+                calldata.len().saturating_sub(1),
+            ] {
+                if length < calldata.len() {
+                    simpler_calls.push(FuzzCall {
+                        input: CallInput::Raw(calldata[..length].to_vec()),
+                        ..call.clone()
+                    });
+                }
+            }
+        }
+    }
+
+    simpler_calls
+}
+
+//values of the type that differ from the value in one simpler part, and that are still valid for the type
+fn simpler_values(value_type: &AbiType, value: &AbiValue) -> Vec<AbiValue> {
+    match (value_type, value) {
+        (AbiType::Int(_), AbiValue::Word(word)) if word.bit(255) => {
+            //negative values are halved towards -1, keeping the sign
+            let halved = !((!*word) >> 1);
+            let mut candidates = vec![AbiValue::Word(U256::zero())];
+            if halved != *word {
+                candidates.push(AbiValue::Word(halved));
+            }
+            candidates
+        }
+        (_, AbiValue::Word(word)) if !word.is_zero() => {
+            let mut candidates = vec![AbiValue::Word(U256::zero())];
+            if *word > U256::one() {
+                candidates.push(AbiValue::Word(*word >> 1));
+            }
+            candidates
+        }
+        (_, AbiValue::Bytes(bytes)) if !bytes.is_empty() => vec![
+            AbiValue::Bytes(vec![]),
+            AbiValue::Bytes(bytes[..bytes.len() / 2].to_vec()),
+        ],
+        (_, AbiValue::List(elements)) => {
+            let element_types: Vec<AbiType> = match value_type {
+                AbiType::Tuple(element_types) => element_types.clone(),
+                AbiType::Array(element_type) | AbiType::FixedArray(element_type, _) => {
+                    vec![element_type.as_ref().clone(); elements.len()]
+                }
+                _ => return vec![],
+            };
+
+            let mut candidates = vec![];
+
+            //elements are only removed from dynamic arrays
+            if matches!(value_type, AbiType::Array(_)) {
+                for index in 0..elements.len() {
+                    let mut simpler_elements = elements.clone();
+                    simpler_elements.remove(index);
+                    candidates.push(AbiValue::List(simpler_elements));
+                }
+            }
+
+            for (index, (element_type, element)) in
+                element_types.iter().zip(elements.iter()).enumerate()
+            {
+                for simpler_element in simpler_values(element_type, element) {
+                    let mut simpler_elements = elements.clone();
+                    simpler_elements[index] = simpler_element;
+                    candidates.push(AbiValue::List(simpler_elements));
+                }
+            }
+
+            candidates
+        }
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::contract::{compile_str, CompileOptions};
+
+    //a counter that can be decremented by anyone, and that reverts on increments past 3
+    const COUNTER: &str = r#"
+#define function increment()
+#define function decrement()
+
+__DISPATCHER(fallback)
+
+increment:
+    PUSH1 0x00
+    SLOAD
+    PUSH1 0x01
+    ADD
+    DUP1
+    PUSH1 0x04
+    GT
+    PUSH2 increment_ok
+    JUMPI
+    PUSH1 0x00
+    DUP1
+    REVERT
+increment_ok:
+    PUSH1 0x00
+    SSTORE
+    STOP
+
+decrement:
+    PUSH1 0x00
+    SLOAD
+    PUSH1 0x01
+    SWAP1
+    SUB
+    PUSH1 0x00
+    SSTORE
+    STOP
+
+fallback:
+    STOP
+"#;
+
+    fn invariant(name: &str, kind: InvariantKind) -> Invariant {
+        Invariant {
+            name: name.to_owned(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_fuzz_contract_shrinks_violations() {
+        let compiled_contract =
+            compile_str("Counter", COUNTER, &CompileOptions::default()).unwrap();
+        let invariants = [
+            invariant(
+                "never decreases",
+                InvariantKind::NeverDecreases(U256::zero()),
+            ),
+            invariant(
+                "increment never reverts",
+                InvariantKind::NeverReverts(String::from("increment()")),
+            ),
+            invariant(
+                "slot 1 never increases",
+                InvariantKind::NeverIncreases(U256::one()),
+            ),
+        ];
+
+        let violations = fuzz_contract(
+            &compiled_contract,
+            &invariants,
+            FuzzSettings::new(64, 10, 7),
+        )
+        .unwrap();
+
+        //decrementing zero wraps around, so the shortest sequence that decreases the slot increments once first
+        let (message, calls) = violations[0].as_ref().unwrap();
+        assert_eq!(message, "storage slot 0x0 decreased from 0x1 to 0x0");
+        assert_eq!(calls.len(), 2);
+        assert_eq!(
+            calls[1].to_test_call(),
+            "[[test.call]]\nfunction = \"decrement()\""
+        );
+
+        //decrementing twice wraps the count around to one below the largest value, which is shorter than four increments
+        let (message, calls) = violations[1].as_ref().unwrap();
+        assert_eq!(message, "increment() reverted");
+        let signatures: Vec<String> = calls
+            .iter()
+            .map(|call| match &call.input {
+                CallInput::Function(function, _) => function.signature.clone(),
+                CallInput::Raw(calldata) => format_hex(calldata),
+            })
+            .collect();
+        assert_eq!(signatures, ["decrement()", "decrement()", "increment()"]);
+
+        assert!(violations[2].is_none());
+    }
+
+    #[test]
+    fn test_parse_invariant() {
+        let table: Table = toml::from_str("name = \"a\"\nnever_decreases = \"0x01\"").unwrap();
+        assert_eq!(
+            parse_invariant(&table),
+            Ok(invariant("a", InvariantKind::NeverDecreases(U256::one())))
+        );
+
+        let table: Table =
+            toml::from_str("name = \"a\"\nnever_decreases = \"0x01\"\nnever_reverts = \"f()\"")
+                .unwrap();
+        assert_eq!(
+            parse_invariant(&table),
+            Err(String::from("more than one property in invariant `a`"))
+        );
+    }
+
+    #[test]
+    fn test_generate_value_is_valid() {
+        let mut random = Random::new(1);
+        let value_type = AbiType::parse("(int8,uint16,bytes3,string,address[])").unwrap();
+
+        for _ in 0..100 {
+            let value = generate_value(&mut random, &value_type);
+            let text = format_value(&value_type, &value);
+            assert_eq!(crate::evm::abi::parse_value(&value_type, &text), Ok(value));
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use crate::core::manifest::{DEFAULT_COMPILATION_DIR, MANIFEST_FILE_NAME};
+use crate::evmm_error::evmm_error::EVMMError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MANIFEST: &str = r#"# Project settings for evmm, see https://github.com/0xKitsune/EVM_Mneumonic#project-manifest
+src = ["evmm_contracts"]
+out = "evm_asm"
+include_paths = ["evmm_contracts/lib"]
+tests = "evmm_tests"
+formats = ["hex"]
+"#;
+
+const COUNTER_CONTRACT: &str = r#"#include "./lib/non_payable.evmm"
+
+#define function increment()
+#define function count() view returns (uint256)
+
+__DISPATCHER(fallback)
+
+//adds one to the count in storage slot 0
+increment:
+    PUSH1 0x00
+    SLOAD
+    PUSH1 0x01
+    ADD
+    PUSH1 0x00
+    SSTORE
+    STOP
+
+//returns the count
+count:
+    PUSH1 0x00
+    SLOAD
+    PUSH1 0x00
+    MSTORE
+    PUSH1 0x20
+    PUSH1 0x00
+    RETURN
+
+fallback:
+    PUSH1 0x00
+    DUP1
+    REVERT
+"#;
+
+const NON_PAYABLE: &str = r#"//reverts if the call sends any value
+CALLVALUE
+ISZERO
+PUSH2 non_payable_ok
+JUMPI
+PUSH1 0x00
+DUP1
+REVERT
+non_payable_ok:
+"#;
+
+const COUNTER_TEST: &str = r#"# Tests for evmm_contracts/Counter.evmm, run with `evmm test`
+contract = "Counter"
+
+[[test]]
+name = "count starts at zero"
+
+[[test.call]]
+function = "count()"
+returns = "0x0000000000000000000000000000000000000000000000000000000000000000"
+
+[[test]]
+name = "increment adds one"
+
+[[test.call]]
+function = "increment()"
+
+[[test.call]]
+function = "count()"
+returns = "0x0000000000000000000000000000000000000000000000000000000000000001"
+
+[test.storage]
+"0x00" = "0x01"
+
+[[test]]
+name = "sending value reverts"
+
+[[test.call]]
+function = "increment()"
+value = 1
+reverts = true
+
+[[test]]
+name = "unknown function reverts"
+
+[[test.call]]
+calldata = "0xdeadbeef"
+reverts = true
+
+# properties checked against random calls by `evmm fuzz`
+[[invariant]]
+name = "count never decreases"
+never_decreases = "0x00"
+
+[[invariant]]
+name = "increment never reverts"
+never_reverts = "increment()"
+"#;
+
+const GITIGNORE: &str = r#"evm_asm/
+lcov.info
+gas.folded
+"#;
+
+const FOUNDRY_CONFIG: &str = r#"[profile.default]
+src = "foundry/src"
+test = "foundry/test"
+out = "foundry/out"
+libs = ["lib"]
+# the deployer runs `evmm compile` to get the deployment bytecode
+ffi = true
+"#;
+
+const FOUNDRY_DEPLOYER: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import "forge-std/Test.sol";
+
+///Compiles an EVMM contract with `evmm compile` and deploys it
+contract EVMMDeployer is Test {
+    function deployContract(string memory fileName) public returns (address deployedAddress) {
+        string[] memory cmds = new string[](8);
+        cmds[0] = "evmm";
+        cmds[1] = "compile";
+        cmds[2] = "-c";
+        cmds[3] = fileName;
+        cmds[4] = "-d";
+        cmds[5] = "-p";
+        cmds[6] = "-f";
+        cmds[7] = "0x";
+
+        bytes memory bytecode = vm.ffi(cmds);
+
+        assembly {
+            deployedAddress := create(0, add(bytecode, 0x20), mload(bytecode))
+        }
+
+        require(deployedAddress != address(0), "EVMMDeployer could not deploy the contract");
+    }
+}
+"#;
+
+const FOUNDRY_COUNTER_TEST: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import "forge-std/Test.sol";
+import "../src/EVMMDeployer.sol";
+
+interface Counter {
+    function increment() external;
+
+    function count() external view returns (uint256);
+}
+
+contract CounterTest is Test {
+    Counter counter;
+
+    function setUp() public {
+        counter = Counter(new EVMMDeployer().deployContract("Counter.evmm"));
+    }
+
+    function testIncrement() public {
+        counter.increment();
+        assertEq(counter.count(), 1);
+    }
+}
+"#;
+
+///Creates a new project in the directory with a manifest, an example contract and tests that `evmm test` can run.
+///With `foundry`, a Foundry config, a deployer contract that compiles EVMM contracts and an example Foundry test are
+///also created, with forge-std installed by `forge install foundry-rs/forge-std`.
+///Files that already exist are left as they are. Returns the paths of the files that were created.
+pub fn init_project(directory: &Path, foundry: bool) -> Result<Vec<PathBuf>, EVMMError> {
+    if directory.join(MANIFEST_FILE_NAME).exists() {
+        return Err(EVMMError::ProjectAlreadyExists(
+            directory.display().to_string(),
+        ));
+    }
+
+    let mut files = vec![
+        (MANIFEST_FILE_NAME, MANIFEST),
+        ("evmm_contracts/Counter.evmm", COUNTER_CONTRACT),
+        ("evmm_contracts/lib/non_payable.evmm", NON_PAYABLE),
+        ("evmm_tests/Counter.t.toml", COUNTER_TEST),
+        (".gitignore", GITIGNORE),
+    ];
+
+    if foundry {
+        files.extend([
+            ("foundry.toml", FOUNDRY_CONFIG),
+            ("foundry/src/EVMMDeployer.sol", FOUNDRY_DEPLOYER),
+            ("foundry/test/Counter.t.sol", FOUNDRY_COUNTER_TEST),
+        ]);
+    }
+
+    let mut created_files = vec![];
+    for (file_path, contents) in files {
+        let path = directory.join(file_path);
+        if path.exists() {
+            continue;
+        }
+
+        create_file(&path, contents).map_err(|error| {
+            EVMMError::InitFailed(path.display().to_string(), error.to_string())
+        })?;
+        created_files.push(path);
+    }
+
+    let output_directory = directory.join(DEFAULT_COMPILATION_DIR);
+    fs::create_dir_all(&output_directory).map_err(|error| {
+        EVMMError::InitFailed(output_directory.display().to_string(), error.to_string())
+    })?;
+
+    Ok(created_files)
+}
+
+fn create_file(path: &Path, contents: &str) -> Result<(), std::io::Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::contract::compile_file;
+    use crate::core::manifest::Manifest;
+    use std::env;
+
+    #[test]
+    fn test_init_project() {
+        let directory = env::temp_dir().join("evmm_init_project");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(directory.join("foundry/test")).unwrap();
+        fs::write(directory.join("foundry/test/Counter.t.sol"), "existing").unwrap();
+
+        let created_files = init_project(&directory, true).unwrap();
+
+        //existing files are not overwritten
+        assert_eq!(created_files.len(), 7);
+        assert_eq!(
+            fs::read_to_string(directory.join("foundry/test/Counter.t.sol")).unwrap(),
+            "existing"
+        );
+        assert!(directory.join(DEFAULT_COMPILATION_DIR).is_dir());
+
+        //the example contract compiles with the manifest settings
+        let manifest = Manifest::find(&directory).unwrap().unwrap();
+        let compiled_contract = compile_file(
+            manifest.source_directories[0].join("Counter.evmm"),
+            &manifest.compile_options("Counter"),
+        )
+        .unwrap();
+        assert_eq!(compiled_contract.interface.functions.len(), 2);
+
+        assert!(matches!(
+            init_project(&directory, false),
+            Err(EVMMError::ProjectAlreadyExists(_))
+        ));
+    }
+}
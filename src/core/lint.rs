@@ -0,0 +1,682 @@
+use crate::compiler::compile::{decode_hex, instruction_offsets};
+use crate::compiler::jumps::static_jumps;
+use crate::compiler::opcodes::{mnemonic, opcode_info};
+use crate::compiler::stack::BlockStack;
+use crate::core::contract::{compile_file, CompiledContract};
+use crate::core::evmm::{contract_name, get_contract_paths};
+use crate::core::manifest::Manifest;
+use crate::evmm_error::evmm_error::EVMMError;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+///Findings on a line with this comment, or on the line after it if the comment is on a line of its own, are not
+///reported, ex. `DELEGATECALL //lint-ignore delegatecall`. Without rule names, every rule is ignored on the line.
+pub const IGNORE_COMMENT: &str = "lint-ignore";
+
+const CALL: u8 = 0xF1;
+const CALLCODE: u8 = 0xF2;
+const DELEGATECALL: u8 = 0xF4;
+const STATICCALL: u8 = 0xFA;
+const JUMP: u8 = 0x56;
+const JUMPI: u8 = 0x57;
+const JUMPDEST: u8 = 0x5B;
+const POP: u8 = 0x50;
+const SSTORE: u8 = 0x55;
+const ORIGIN: u8 = 0x32;
+const CALLVALUE: u8 = 0x34;
+const RETURNDATASIZE: u8 = 0x3D;
+const RETURNDATACOPY: u8 = 0x3E;
+const TIMESTAMP: u8 = 0x42;
+//PREVRANDAO since the merge
+const DIFFICULTY: u8 = 0x44;
+const SELFDESTRUCT: u8 = 0xFF;
+
+///How a rule is reported. Errors make `evmm lint` fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintLevel {
+    Off,
+    Warn,
+    Error,
+}
+
+impl LintLevel {
+    pub const NAMES: [&'static str; 3] = ["off", "warn", "error"];
+
+    const LEVELS: [LintLevel; 3] = [LintLevel::Off, LintLevel::Warn, LintLevel::Error];
+
+    pub fn from_name(name: &str) -> Option<LintLevel> {
+        LintLevel::NAMES
+            .iter()
+            .position(|level_name| *level_name == name)
+            .map(|index| LintLevel::LEVELS[index])
+    }
+
+    pub fn name(&self) -> &'static str {
+        LintLevel::NAMES[*self as usize]
+    }
+}
+
+///A rule and the level it is reported at, unless the `[lint]` table of the manifest sets another level
+pub struct LintRule {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub default_level: LintLevel,
+}
+
+pub const RULES: [LintRule; 9] = [
+    LintRule {
+        name: "unchecked-call",
+        description: "the success value of a call is popped or never used",
+        default_level: LintLevel::Error,
+    },
+    LintRule {
+        name: "missing-callvalue-check",
+        description: "a function that is not payable can be reached without checking CALLVALUE",
+        default_level: LintLevel::Warn,
+    },
+    LintRule {
+        name: "delegatecall",
+        description: "DELEGATECALL runs other code with the storage and balance of the contract",
+        default_level: LintLevel::Warn,
+    },
+    LintRule {
+        name: "callcode",
+        description: "CALLCODE is deprecated and runs other code with the storage of the contract",
+        default_level: LintLevel::Warn,
+    },
+    LintRule {
+        name: "selfdestruct",
+        description: "SELFDESTRUCT sends away the balance of the contract",
+        default_level: LintLevel::Warn,
+    },
+    LintRule {
+        name: "weak-randomness",
+        description: "TIMESTAMP or DIFFICULTY (PREVRANDAO since the merge) is used as randomness, which block proposers can influence",
+        default_level: LintLevel::Warn,
+    },
+    LintRule {
+        name: "tx-origin",
+        description: "ORIGIN is compared, which any contract the account calls can pass as",
+        default_level: LintLevel::Error,
+    },
+    LintRule {
+        name: "unchecked-returndatacopy",
+        description: "RETURNDATACOPY without checking RETURNDATASIZE, which halts if the return data is too short",
+        default_level: LintLevel::Warn,
+    },
+    LintRule {
+        name: "reentrancy",
+        description: "SSTORE after a call, which the called contract can reenter before",
+        default_level: LintLevel::Warn,
+    },
+];
+
+///Gets a rule by name
+pub fn lint_rule(name: &str) -> Option<&'static LintRule> {
+    RULES.iter().find(|rule| rule.name == name)
+}
+
+///A rule broken by an instruction in the runtime bytecode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFinding {
+    pub rule: &'static str,
+    pub offset: usize,
+    pub message: String,
+}
+
+///Lints each contract, or only the contract at `contract_path` if it is given, printing the findings of every rule
+///that is not turned off. Returns an error if any finding is at the error level.
+pub fn lint_contracts(manifest: &Manifest, contract_path: &str) -> Result<(), EVMMError> {
+    let mut errors = 0;
+    let mut warnings = 0;
+
+    for contract_path in get_contract_paths(manifest, contract_path, "")? {
+        let name = contract_name(&contract_path.path);
+        let compiled_contract =
+            compile_file(&contract_path.path, &manifest.compile_options(&name))?;
+
+        //built-ins compile to many instructions, so a rule is only reported once for each line
+        let mut reported_lines = BTreeSet::new();
+
+        for finding in lint_bytecode(&compiled_contract) {
+            let level = manifest.lint_level(finding.rule);
+            if level == LintLevel::Off || is_ignored(&compiled_contract, &finding) {
+                continue;
+            }
+
+            let line = compiled_contract
+                .source_line(finding.offset)
+                .map(|(source, line_number)| (source.path.clone(), line_number));
+            if line.is_some() && !reported_lines.insert((finding.rule, line)) {
+                continue;
+            }
+
+            if level == LintLevel::Error {
+                errors += 1;
+            } else {
+                warnings += 1;
+            }
+            println!(
+                "{}[{}]: {}: {}",
+                if level == LintLevel::Error {
+                    "error"
+                } else {
+                    "warning"
+                },
+                finding.rule,
+                compiled_contract.instruction_location(finding.offset),
+                finding.message
+            );
+        }
+    }
+
+    println!("{} errors, {} warnings", errors, warnings);
+
+    if errors > 0 {
+        Err(EVMMError::LintFailed(errors))
+    } else {
+        Ok(())
+    }
+}
+
+///Checks the runtime bytecode of a contract against every rule, returning the findings in bytecode order
+pub fn lint_bytecode(compiled_contract: &CompiledContract) -> Vec<LintFinding> {
+    let bytecode = &compiled_contract.runtime.bytecode;
+    let bytes = decode_hex(bytecode).unwrap_or_default();
+    let offsets = instruction_offsets(bytecode);
+
+    let mut findings = vec![];
+    block_findings(&bytes, &offsets, &mut findings);
+    opcode_findings(&bytes, &offsets, &mut findings);
+    callvalue_findings(compiled_contract, &bytes, &offsets, &mut findings);
+    reentrancy_findings(bytecode, &bytes, &offsets, &mut findings);
+
+    findings.sort_by_key(|finding| finding.offset);
+    findings
+}
+
+///Checks whether the line of a finding, or the line before it, has a comment that ignores the rule
+pub fn is_ignored(compiled_contract: &CompiledContract, finding: &LintFinding) -> bool {
+    let (source, line_number) = match compiled_contract.source_line(finding.offset) {
+        Some(source_line) => source_line,
+        None => return false,
+    };
+    let lines: Vec<&str> = source.contents.lines().collect();
+
+    //the comment on the line of the finding
+    let trailing_comment = lines
+        .get(line_number - 1)
+        .and_then(|line| line.split_once("//"))
+        .map(|(_, comment)| comment);
+    //a comment on a line of its own before the finding
+    let previous_comment = line_number
+        .checked_sub(2)
+        .and_then(|index| lines.get(index))
+        .and_then(|line| line.trim_start().strip_prefix("//"));
+
+    [trailing_comment, previous_comment]
+        .into_iter()
+        .flatten()
+        .any(|comment| ignores_rule(comment, finding.rule))
+}
+
+//checks whether a comment is `lint-ignore` followed by no rules or a list of rules that includes the rule
+fn ignores_rule(comment: &str, rule: &str) -> bool {
+    let rules = match comment.trim().strip_prefix(IGNORE_COMMENT) {
+        Some(rules) => rules,
+        None => return false,
+    };
+
+    let mut rules = rules
+        .split(|character: char| character == ',' || character.is_whitespace())
+        .filter(|ignored_rule| !ignored_rule.is_empty())
+        .peekable();
+    rules.peek().is_none() || rules.any(|ignored_rule| ignored_rule == rule)
+}
+
+//describes an instruction in a message, ex. `CALL at 0x1F`
+fn describe(bytes: &[u8], offset: usize) -> String {
+    format!(
+        "{} at 0x{:X}",
+        mnemonic(bytes[offset]).unwrap_or("instruction"),
+        offset
+    )
+}
+
+fn is_call(opcode: u8) -> bool {
+    matches!(opcode, CALL | CALLCODE | DELEGATECALL | STATICCALL)
+}
+
+//STOP, RETURN, REVERT, INVALID and SELFDESTRUCT
+fn halts(opcode: u8) -> bool {
+    matches!(opcode, 0x00 | 0xF3 | 0xFD | 0xFE | 0xFF) || opcode_info(opcode).is_none()
+}
+
+///Follows the values on the stack through each basic block, tracking the instructions each value was computed from,
+///to find call success values that are dropped, ORIGIN being compared, TIMESTAMP and DIFFICULTY being reduced with MOD
+///and RETURNDATACOPY without RETURNDATASIZE since the last call
+fn block_findings(bytes: &[u8], offsets: &[usize], findings: &mut Vec<LintFinding>) {
+    //the offsets of the instructions each value was computed from, top last. Values that were on the stack when the
+    //block was entered were not computed from any instruction in the block.
+    let mut stack: BlockStack<Vec<usize>> = BlockStack::new();
+    //calls whose success value has not been used
+    let mut unchecked_calls: BTreeSet<usize> = BTreeSet::new();
+    let mut reported: BTreeSet<usize> = BTreeSet::new();
+    let mut returndatasize_since_call = false;
+
+    //the success value of the calls still waiting to be used when execution halts is never used
+    let report_unchecked_calls =
+        |unchecked_calls: &mut BTreeSet<usize>, findings: &mut Vec<LintFinding>| {
+            for call_offset in std::mem::take(unchecked_calls) {
+                findings.push(LintFinding {
+                    rule: "unchecked-call",
+                    offset: call_offset,
+                    message: String::from(
+                        "the success value is never checked, so a failed call is ignored",
+                    ),
+                });
+            }
+        };
+
+    for offset in offsets.iter().copied() {
+        let opcode = bytes[offset];
+
+        match opcode {
+            //a JUMPDEST can be reached with any stack
+            JUMPDEST => {
+                stack.clear();
+                unchecked_calls.clear();
+            }
+
+            _ => {
+                //each output is computed from the instruction and everything its inputs were computed from. DUP and
+                //SWAP move values without using them.
+                let inputs = stack.execute(opcode, |inputs| {
+                    let sources: BTreeSet<usize> = inputs.iter().flatten().copied().collect();
+                    let mut output: Vec<usize> = sources.into_iter().collect();
+                    output.push(offset);
+                    output
+                });
+
+                //a popped success value is dropped unless a copy of it was used
+                if opcode == POP {
+                    for source in inputs.into_iter().flatten() {
+                        if unchecked_calls.remove(&source) {
+                            findings.push(LintFinding {
+                                rule: "unchecked-call",
+                                offset: source,
+                                message: String::from(
+                                    "the success value is popped without being checked, so a failed call is ignored",
+                                ),
+                            });
+                        }
+                    }
+                    continue;
+                }
+
+                let sources: BTreeSet<usize> = inputs.iter().flatten().copied().collect();
+                for source in sources.iter() {
+                    unchecked_calls.remove(source);
+
+                    let source_opcode = bytes[*source];
+                    let reason = match (source_opcode, opcode) {
+                        //EQ, LT, GT, SLT, SGT, ISZERO, XOR, SUB or the condition of a JUMPI
+                        (ORIGIN, 0x10..=0x15 | 0x18 | 0x03 | JUMPI) => Some((
+                            "tx-origin",
+                            format!(
+                                "compared by {}, so any contract the account calls can pass the check",
+                                describe(bytes, offset)
+                            ),
+                        )),
+                        //MOD, SMOD, ADDMOD and MULMOD
+                        (TIMESTAMP | DIFFICULTY, 0x06..=0x09) => Some((
+                            "weak-randomness",
+                            format!(
+                                "reduced by {} as if it were random, but block proposers can influence it",
+                                describe(bytes, offset)
+                            ),
+                        )),
+                        _ => None,
+                    };
+
+                    if let Some((rule, message)) = reason {
+                        if reported.insert(*source) {
+                            findings.push(LintFinding {
+                                rule,
+                                offset: *source,
+                                message,
+                            });
+                        }
+                    }
+                }
+
+                match opcode {
+                    _ if is_call(opcode) => {
+                        unchecked_calls.insert(offset);
+                        returndatasize_since_call = false;
+                    }
+                    RETURNDATASIZE => returndatasize_since_call = true,
+                    RETURNDATACOPY if !returndatasize_since_call => findings.push(LintFinding {
+                        rule: "unchecked-returndatacopy",
+                        offset,
+                        message: String::from("RETURNDATACOPY without checking RETURNDATASIZE since the last call, which halts and uses all the gas if the return data is shorter than the copy"),
+                    }),
+                    //the success values left on the stack may be used by the block jumped to
+                    JUMP => {
+                        stack.clear();
+                        unchecked_calls.clear();
+                    }
+                    _ if halts(opcode) => {
+                        stack.clear();
+                        report_unchecked_calls(&mut unchecked_calls, findings);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    //running past the end of the code halts like STOP
+    report_unchecked_calls(&mut unchecked_calls, findings);
+}
+
+//DELEGATECALL, CALLCODE and SELFDESTRUCT are reported wherever they are used
+fn opcode_findings(bytes: &[u8], offsets: &[usize], findings: &mut Vec<LintFinding>) {
+    for offset in offsets.iter().copied() {
+        let (rule, message) = match bytes[offset] {
+            DELEGATECALL => (
+                "delegatecall",
+                "DELEGATECALL runs the code of another contract with the storage and balance of this contract, so the called code must be trusted",
+            ),
+            CALLCODE => (
+                "callcode",
+                "CALLCODE is deprecated and runs the code of another contract with the storage of this contract, use DELEGATECALL",
+            ),
+            SELFDESTRUCT => (
+                "selfdestruct",
+                "SELFDESTRUCT sends the balance of the contract to an address, and only deletes the contract in the transaction that created it",
+            ),
+            DIFFICULTY => (
+                "weak-randomness",
+                "DIFFICULTY is PREVRANDAO since the merge, which is only random within what the block proposer can choose",
+            ),
+            _ => continue,
+        };
+
+        findings.push(LintFinding {
+            rule,
+            offset,
+            message: String::from(message),
+        });
+    }
+}
+
+///Reports each declared function that is not payable when neither the code before the first label, which runs
+///before the dispatcher jumps to the function, nor the code of the function label checks CALLVALUE
+fn callvalue_findings(
+    compiled_contract: &CompiledContract,
+    bytes: &[u8],
+    offsets: &[usize],
+    findings: &mut Vec<LintFinding>,
+) {
+    let labels = &compiled_contract.runtime.labels;
+    let label_offsets: HashMap<&str, usize> = labels
+        .iter()
+        .map(|(label, offset)| (label.as_str(), *offset))
+        .collect();
+
+    //checks for CALLVALUE between an offset and the next label
+    let checks_callvalue = |start: usize| {
+        let end = labels
+            .iter()
+            .map(|(_, offset)| *offset)
+            .find(|offset| *offset > start)
+            .unwrap_or(bytes.len());
+        offsets
+            .iter()
+            .any(|offset| *offset >= start && *offset < end && bytes[*offset] == CALLVALUE)
+    };
+    if checks_callvalue(0) {
+        return;
+    }
+
+    for function in compiled_contract.interface.functions.iter() {
+        if function.state_mutability == "payable" {
+            continue;
+        }
+
+        //the compiler checks that every declared function has a label
+        if let Some(offset) = label_offsets.get(function.name.as_str()) {
+            if !checks_callvalue(*offset) {
+                findings.push(LintFinding {
+                    rule: "missing-callvalue-check",
+                    offset: *offset,
+                    message: format!(
+                        "{} is {}, but CALLVALUE is not checked before the dispatcher or in the function, so calls with value are accepted",
+                        function.signature(),
+                        function.state_mutability
+                    ),
+                });
+            }
+        }
+    }
+}
+
+///Reports every SSTORE that can run after a CALL, CALLCODE or DELEGATECALL, following jumps whose destination is known
+///at compile time. Dynamic jumps are not followed.
+fn reentrancy_findings(
+    bytecode: &str,
+    bytes: &[u8],
+    offsets: &[usize],
+    findings: &mut Vec<LintFinding>,
+) {
+    let index_of: HashMap<usize, usize> = offsets
+        .iter()
+        .enumerate()
+        .map(|(index, offset)| (*offset, index))
+        .collect();
+    let mut jump_destinations: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (offset, destination) in static_jumps(bytecode) {
+        jump_destinations
+            .entry(offset)
+            .or_default()
+            .push(index_of[&destination]);
+    }
+
+    //the instructions that can run next
+    let successors = |index: usize| -> Vec<usize> {
+        let offset = offsets[index];
+        let opcode = bytes[offset];
+        let mut successors = jump_destinations.get(&offset).cloned().unwrap_or_default();
+        if opcode != JUMP && !halts(opcode) && index + 1 < offsets.len() {
+            successors.push(index + 1);
+        }
+        successors
+    };
+
+    let mut reported = BTreeSet::new();
+    for (call_index, call_offset) in offsets.iter().enumerate() {
+        if !matches!(bytes[*call_offset], CALL | CALLCODE | DELEGATECALL) {
+            continue;
+        }
+
+        let mut visited = vec![false; offsets.len()];
+        let mut queue: VecDeque<usize> = successors(call_index).into();
+        while let Some(index) = queue.pop_front() {
+            if std::mem::replace(&mut visited[index], true) {
+                continue;
+            }
+
+            let offset = offsets[index];
+            if bytes[offset] == SSTORE && reported.insert(offset) {
+                findings.push(LintFinding {
+                    rule: "reentrancy",
+                    offset,
+                    message: format!(
+                        "SSTORE can run after {}, so the called contract can reenter before the storage is updated",
+                        describe(bytes, *call_offset)
+                    ),
+                });
+            }
+            queue.extend(successors(index));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::contract::{compile_str, CompileOptions};
+
+    const VAULT: &str = r#"#define function withdraw()
+#define function deposit() payable
+
+__DISPATCHER(fallback)
+
+withdraw:
+    ORIGIN
+    PUSH1 0x00
+    SLOAD
+    EQ
+    ISZERO
+    PUSH2 fallback
+    JUMPI
+    PUSH1 0x00
+    DUP1
+    DUP1
+    DUP1
+    SELFBALANCE
+    CALLER
+    GAS
+    CALL
+    POP
+    PUSH1 0x00
+    PUSH1 0x01
+    SSTORE
+    STOP
+
+deposit:
+    STOP
+
+fallback:
+    PUSH1 0x00
+    DUP1
+    REVERT
+"#;
+
+    fn rules(findings: &[LintFinding]) -> Vec<&'static str> {
+        findings.iter().map(|finding| finding.rule).collect()
+    }
+
+    #[test]
+    fn test_lint_bytecode() {
+        let compiled_contract = compile_str("Vault", VAULT, &CompileOptions::default()).unwrap();
+        let findings = lint_bytecode(&compiled_contract);
+
+        assert_eq!(
+            rules(&findings),
+            vec![
+                "missing-callvalue-check",
+                "tx-origin",
+                "unchecked-call",
+                "reentrancy"
+            ]
+        );
+        assert_eq!(
+            compiled_contract.source_line(findings[2].offset).unwrap().1,
+            21
+        );
+        assert!(findings[0].message.starts_with("withdraw() is nonpayable"));
+    }
+
+    #[test]
+    fn test_checked_call_and_callvalue() {
+        let source = r#"CALLVALUE
+PUSH2 fallback
+JUMPI
+PUSH1 0x00
+DUP1
+DUP1
+DUP1
+DUP1
+CALLER
+GAS
+CALL
+ISZERO
+PUSH2 fallback
+JUMPI
+RETURNDATASIZE
+PUSH1 0x00
+PUSH1 0x00
+RETURNDATACOPY
+STOP
+
+fallback:
+    PUSH1 0x00
+    DUP1
+    REVERT
+"#;
+        let compiled_contract = compile_str("Checked", source, &CompileOptions::default()).unwrap();
+        assert_eq!(lint_bytecode(&compiled_contract), vec![]);
+    }
+
+    #[test]
+    fn test_difficulty() {
+        //DIFFICULTY is reported wherever it is used, and once more when it is reduced with MOD
+        let compiled_contract = compile_str(
+            "Random",
+            "PUSH1 0x06\nDIFFICULTY\nMOD\nPUSH1 0x00\nMSTORE",
+            &CompileOptions::default(),
+        )
+        .unwrap();
+        let findings = lint_bytecode(&compiled_contract);
+
+        assert_eq!(rules(&findings), vec!["weak-randomness", "weak-randomness"]);
+        assert!(findings.iter().all(|finding| finding.offset == 2));
+        assert!(findings
+            .iter()
+            .any(|finding| finding.message.starts_with("DIFFICULTY is PREVRANDAO")));
+    }
+
+    #[test]
+    fn test_ignore_comments() {
+        let source = r#"PUSH1 0x00
+DUP1
+DUP1
+DUP1
+DUP1
+CALLER
+GAS
+//lint-ignore unchecked-call, reentrancy
+DELEGATECALL //lint-ignore delegatecall
+POP
+PUSH1 0x00
+DUP1
+TIMESTAMP
+MOD
+PUSH1 0x00
+RETURNDATACOPY //lint-ignore
+"#;
+        let compiled_contract = compile_str("Ignored", source, &CompileOptions::default()).unwrap();
+        let findings = lint_bytecode(&compiled_contract);
+        assert_eq!(
+            rules(&findings),
+            vec![
+                "unchecked-call",
+                "delegatecall",
+                "weak-randomness",
+                "unchecked-returndatacopy"
+            ]
+        );
+
+        let reported: Vec<&str> = findings
+            .iter()
+            .filter(|finding| !is_ignored(&compiled_contract, finding))
+            .map(|finding| finding.rule)
+            .collect();
+        assert_eq!(reported, vec!["weak-randomness"]);
+
+        assert!(ignores_rule(" lint-ignore", "tx-origin"));
+        assert!(!ignores_rule("lint-ignore delegatecall", "tx-origin"));
+        assert!(!ignores_rule("ignore this", "tx-origin"));
+    }
+}
@@ -0,0 +1,495 @@
+use crate::compiler::evm_version::EvmVersion;
+use crate::core::contract::{CompileOptions, OptimizerSettings};
+use crate::core::discover::ignore_set;
+use crate::core::format::OutputFormat;
+use crate::core::lint::{lint_rule, LintLevel, RULES};
+use crate::evmm_error::evmm_error::EVMMError;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::value::Table;
+use toml::Value;
+
+pub const MANIFEST_FILE_NAME: &str = "evmm.toml";
+pub const DEFAULT_CONTRACTS_DIR: &str = "evmm_contracts";
+pub const DEFAULT_COMPILATION_DIR: &str = "evm_asm";
+pub const DEFAULT_TESTS_DIR: &str = "evmm_tests";
+
+///Settings for a single contract that take precedence over the project settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContractSettings {
+    pub output_formats: Option<Vec<OutputFormat>>,
+    pub deployment_bytecode: Option<bool>,
+    pub evm_version: Option<EvmVersion>,
+    pub optimizer: Option<OptimizerSettings>,
+}
+
+///The project settings from `evmm.toml`, or the defaults if the project does not have a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    //the directory containing the manifest, which the paths in the manifest are relative to
+    pub root: PathBuf,
+    //the directories containing the contracts to compile
+    pub source_directories: Vec<PathBuf>,
+    //glob patterns of files and directories in the source directories that are not compiled, ex. `drafts/**`
+    pub ignore: Vec<String>,
+    pub output_directory: PathBuf,
+    //directories searched for included files
+    pub include_paths: Vec<PathBuf>,
+    //the directory searched for `.t.toml` test files
+    pub test_directory: PathBuf,
+    pub evm_version: EvmVersion,
+    pub optimizer: OptimizerSettings,
+    //every contract is written in each of these formats
+    pub output_formats: Vec<OutputFormat>,
+    pub deployment_bytecode: bool,
+    //settings for individual contracts, by contract name
+    pub contracts: BTreeMap<String, ContractSettings>,
+    //the level of each lint rule that is not reported at its default level, by rule name
+    pub lint: BTreeMap<String, LintLevel>,
+}
+
+impl Manifest {
+    ///Creates the default settings for a project in the root directory
+    pub fn new(root: PathBuf) -> Manifest {
+        Manifest {
+            source_directories: vec![root.join(DEFAULT_CONTRACTS_DIR)],
+            ignore: vec![],
+            output_directory: root.join(DEFAULT_COMPILATION_DIR),
+            include_paths: vec![],
+            test_directory: root.join(DEFAULT_TESTS_DIR),
+            evm_version: EvmVersion::default(),
+            optimizer: OptimizerSettings::default(),
+            output_formats: vec![OutputFormat::Hex],
+            deployment_bytecode: false,
+            contracts: BTreeMap::new(),
+            lint: BTreeMap::new(),
+            root,
+        }
+    }
+
+    ///Looks for `evmm.toml` in the directory and each of its parents, returning None if no manifest is found
+    pub fn find(directory: &Path) -> Result<Option<Manifest>, EVMMError> {
+        let canonical_directory = match fs::canonicalize(directory) {
+            Ok(canonical_directory) => canonical_directory,
+            Err(_) => return Ok(None),
+        };
+
+        //the root is kept relative to the directory so that paths are displayed the way they were given
+        let mut root = directory.to_path_buf();
+        for ancestor in canonical_directory.ancestors() {
+            let manifest_path = ancestor.join(MANIFEST_FILE_NAME);
+
+            if manifest_path.is_file() {
+                let contents = fs::read_to_string(&manifest_path).map_err(|error| {
+                    EVMMError::InvalidManifest(
+                        manifest_path.display().to_string(),
+                        error.to_string(),
+                    )
+                })?;
+
+                return Manifest::from_toml(&contents, root)
+                    .map(Some)
+                    .map_err(|error| {
+                        EVMMError::InvalidManifest(manifest_path.display().to_string(), error)
+                    });
+            }
+
+            root = root.join("..");
+        }
+
+        Ok(None)
+    }
+
+    ///Parses the contents of a manifest, resolving paths relative to the root directory
+    pub fn from_toml(contents: &str, root: PathBuf) -> Result<Manifest, String> {
+        let table: Table = toml::from_str(contents).map_err(|error| error.to_string())?;
+        let mut manifest = Manifest::new(root);
+
+        for (key, value) in table.iter() {
+            match key.as_str() {
+                "src" => {
+                    manifest.source_directories = string_array(key, value)?
+                        .iter()
+                        .map(|path| manifest.root.join(path))
+                        .collect()
+                }
+                "ignore" => {
+                    manifest.ignore = string_array(key, value)?
+                        .iter()
+                        .map(|ignore_pattern| ignore_pattern.to_string())
+                        .collect();
+                    ignore_set(&manifest.ignore)?;
+                }
+                "out" => manifest.output_directory = manifest.root.join(string(key, value)?),
+                "include_paths" => {
+                    manifest.include_paths = string_array(key, value)?
+                        .iter()
+                        .map(|path| manifest.root.join(path))
+                        .collect()
+                }
+                "tests" => manifest.test_directory = manifest.root.join(string(key, value)?),
+                "evm_version" => manifest.evm_version = evm_version(key, value)?,
+                "formats" => manifest.output_formats = output_formats(key, value)?,
+                "deployment_bytecode" => manifest.deployment_bytecode = boolean(key, value)?,
+                "optimizer" => manifest.optimizer = optimizer(key, value)?,
+                "contracts" => {
+                    for (contract_name, contract_value) in table_value(key, value)? {
+                        manifest.contracts.insert(
+                            contract_name.clone(),
+                            contract_settings(contract_name, contract_value)?,
+                        );
+                    }
+                }
+                "lint" => manifest.lint = lint_levels(key, value)?,
+                _ => return Err(format!("unknown key `{}`", key)),
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    ///Applies the command line flags, which take precedence over both the project and contract settings
+    pub fn merge_cli_flags(
+        &mut self,
+        deployment_bytecode: bool,
+        output_formats: Vec<OutputFormat>,
+        evm_version: Option<EvmVersion>,
+        output_directory: Option<PathBuf>,
+    ) {
+        if deployment_bytecode {
+            self.deployment_bytecode = true;
+            for contract_settings in self.contracts.values_mut() {
+                contract_settings.deployment_bytecode = None;
+            }
+        }
+
+        if !output_formats.is_empty() {
+            self.output_formats = output_formats;
+            for contract_settings in self.contracts.values_mut() {
+                contract_settings.output_formats = None;
+            }
+        }
+
+        if let Some(evm_version) = evm_version {
+            self.evm_version = evm_version;
+            for contract_settings in self.contracts.values_mut() {
+                contract_settings.evm_version = None;
+            }
+        }
+
+        if let Some(output_directory) = output_directory {
+            self.output_directory = output_directory;
+        }
+    }
+
+    ///Gets the formats a contract is written in
+    pub fn output_formats(&self, contract_name: &str) -> &[OutputFormat] {
+        self.contracts
+            .get(contract_name)
+            .and_then(|contract_settings| contract_settings.output_formats.as_deref())
+            .unwrap_or(&self.output_formats)
+    }
+
+    ///Gets the options to compile a contract with, using the first of its output formats
+    pub fn compile_options(&self, contract_name: &str) -> CompileOptions {
+        let contract_settings = self
+            .contracts
+            .get(contract_name)
+            .cloned()
+            .unwrap_or_default();
+
+        CompileOptions {
+            deployment_bytecode: contract_settings
+                .deployment_bytecode
+                .unwrap_or(self.deployment_bytecode),
+            output_format: self.output_formats(contract_name)[0],
+            evm_version: contract_settings.evm_version.unwrap_or(self.evm_version),
+            optimizer: contract_settings.optimizer.unwrap_or(self.optimizer),
+            include_paths: self.include_paths.clone(),
+        }
+    }
+
+    ///Gets the level a lint rule is reported at
+    pub fn lint_level(&self, rule_name: &str) -> LintLevel {
+        self.lint
+            .get(rule_name)
+            .copied()
+            .or_else(|| lint_rule(rule_name).map(|rule| rule.default_level))
+            .unwrap_or(LintLevel::Off)
+    }
+}
+
+impl Default for Manifest {
+    fn default() -> Manifest {
+        Manifest::new(PathBuf::from("."))
+    }
+}
+
+fn contract_settings(contract_name: &str, value: &Value) -> Result<ContractSettings, String> {
+    let mut contract_settings = ContractSettings::default();
+
+    for (key, value) in table_value(contract_name, value)? {
+        match key.as_str() {
+            "formats" => contract_settings.output_formats = Some(output_formats(key, value)?),
+            "deployment_bytecode" => {
+                contract_settings.deployment_bytecode = Some(boolean(key, value)?)
+            }
+            "evm_version" => contract_settings.evm_version = Some(evm_version(key, value)?),
+            "optimizer" => contract_settings.optimizer = Some(optimizer(key, value)?),
+            _ => {
+                return Err(format!(
+                    "unknown key `{}` in contract `{}`",
+                    key, contract_name
+                ))
+            }
+        }
+    }
+
+    Ok(contract_settings)
+}
+
+fn lint_levels(key: &str, value: &Value) -> Result<BTreeMap<String, LintLevel>, String> {
+    let mut lint_levels = BTreeMap::new();
+
+    for (rule_name, value) in table_value(key, value)? {
+        if lint_rule(rule_name).is_none() {
+            let rule_names: Vec<&str> = RULES.iter().map(|rule| rule.name).collect();
+            return Err(format!(
+                "unknown lint rule `{}`, expected one of {}",
+                rule_name,
+                rule_names.join(", ")
+            ));
+        }
+
+        let level_name = string(rule_name, value)?;
+        let level = LintLevel::from_name(level_name).ok_or_else(|| {
+            format!(
+                "unknown lint level `{}`, expected one of {}",
+                level_name,
+                LintLevel::NAMES.join(", ")
+            )
+        })?;
+        lint_levels.insert(rule_name.clone(), level);
+    }
+
+    Ok(lint_levels)
+}
+
+fn optimizer(key: &str, value: &Value) -> Result<OptimizerSettings, String> {
+    let mut optimizer = OptimizerSettings::default();
+
+    for (optimizer_key, value) in table_value(key, value)? {
+        match optimizer_key.as_str() {
+            "enabled" => optimizer.enabled = boolean(optimizer_key, value)?,
+            "runs" => {
+                optimizer.runs = value
+                    .as_integer()
+                    .and_then(|runs| u64::try_from(runs).ok())
+                    .ok_or_else(|| format!("`{}` must be a positive integer", optimizer_key))?
+            }
+            _ => return Err(format!("unknown key `{}` in `{}`", optimizer_key, key)),
+        }
+    }
+
+    Ok(optimizer)
+}
+
+fn output_formats(key: &str, value: &Value) -> Result<Vec<OutputFormat>, String> {
+    let names = string_array(key, value)?;
+    if names.is_empty() {
+        return Err(format!("`{}` must contain at least one format", key));
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            OutputFormat::from_name(name).ok_or_else(|| {
+                format!(
+                    "unknown format `{}`, expected one of {}",
+                    name,
+                    OutputFormat::NAMES.join(", ")
+                )
+            })
+        })
+        .collect()
+}
+
+fn evm_version(key: &str, value: &Value) -> Result<EvmVersion, String> {
+    let name = string(key, value)?;
+
+    EvmVersion::from_name(name).ok_or_else(|| {
+        format!(
+            "unknown EVM version `{}`, expected one of {}",
+            name,
+            EvmVersion::NAMES.join(", ")
+        )
+    })
+}
+
+pub(crate) fn table_value<'a>(key: &str, value: &'a Value) -> Result<&'a Table, String> {
+    value
+        .as_table()
+        .ok_or_else(|| format!("`{}` must be a table", key))
+}
+
+pub(crate) fn string<'a>(key: &str, value: &'a Value) -> Result<&'a str, String> {
+    value
+        .as_str()
+        .ok_or_else(|| format!("`{}` must be a string", key))
+}
+
+pub(crate) fn boolean(key: &str, value: &Value) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| format!("`{}` must be true or false", key))
+}
+
+pub(crate) fn string_array<'a>(key: &str, value: &'a Value) -> Result<Vec<&'a str>, String> {
+    value
+        .as_array()
+        .and_then(|values| values.iter().map(|value| value.as_str()).collect())
+        .ok_or_else(|| format!("`{}` must be an array of strings", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    const MANIFEST: &str = r#"
+src = ["contracts", "vendor"]
+ignore = ["drafts/**"]
+out = "build"
+include_paths = ["lib"]
+tests = "test"
+evm_version = "london"
+formats = ["hex", "annotated"]
+
+[optimizer]
+enabled = true
+runs = 1000
+
+[lint]
+delegatecall = "off"
+reentrancy = "error"
+
+[contracts.Token]
+formats = ["binary"]
+deployment_bytecode = true
+evm_version = "shanghai"
+"#;
+
+    #[test]
+    fn test_manifest_from_toml() {
+        let manifest = Manifest::from_toml(MANIFEST, PathBuf::from("./project")).unwrap();
+
+        assert_eq!(
+            manifest.source_directories,
+            vec![
+                PathBuf::from("./project/contracts"),
+                PathBuf::from("./project/vendor")
+            ]
+        );
+        assert_eq!(manifest.ignore, vec![String::from("drafts/**")]);
+        assert_eq!(manifest.output_directory, PathBuf::from("./project/build"));
+        assert_eq!(manifest.include_paths, vec![PathBuf::from("./project/lib")]);
+        assert_eq!(manifest.test_directory, PathBuf::from("./project/test"));
+        assert_eq!(manifest.optimizer, OptimizerSettings::new(true, 1000));
+
+        let token_options = manifest.compile_options("Token");
+        assert_eq!(manifest.output_formats("Token"), &[OutputFormat::Binary]);
+        assert!(token_options.deployment_bytecode);
+        assert_eq!(token_options.evm_version, EvmVersion::Shanghai);
+        assert_eq!(token_options.optimizer, OptimizerSettings::new(true, 1000));
+
+        let other_options = manifest.compile_options("Other");
+        assert_eq!(
+            manifest.output_formats("Other"),
+            &[OutputFormat::Hex, OutputFormat::Annotated]
+        );
+        assert_eq!(other_options.output_format, OutputFormat::Hex);
+        assert!(!other_options.deployment_bytecode);
+        assert_eq!(other_options.evm_version, EvmVersion::London);
+
+        assert_eq!(manifest.lint_level("delegatecall"), LintLevel::Off);
+        assert_eq!(manifest.lint_level("reentrancy"), LintLevel::Error);
+        assert_eq!(manifest.lint_level("selfdestruct"), LintLevel::Warn);
+    }
+
+    #[test]
+    fn test_manifest_errors() {
+        for (contents, error) in [
+            ("sources = []", "unknown key `sources`"),
+            (
+                "formats = [\"hexadecimal\"]",
+                "unknown format `hexadecimal`",
+            ),
+            ("evm_version = \"prague\"", "unknown EVM version `prague`"),
+            ("src = \"contracts\"", "`src` must be an array of strings"),
+            ("ignore = [\"lib/[\"]", "invalid ignore pattern `lib/[`"),
+            (
+                "[contracts.Token]\nout = \"build\"",
+                "unknown key `out` in contract `Token`",
+            ),
+            ("[lint]\nreentry = \"off\"", "unknown lint rule `reentry`"),
+            ("[lint]\nreentrancy = \"deny\"", "unknown lint level `deny`"),
+            (
+                "[optimizer]\nruns = -1",
+                "`runs` must be a positive integer",
+            ),
+        ] {
+            let manifest_error = Manifest::from_toml(contents, PathBuf::from(".")).unwrap_err();
+            assert!(
+                manifest_error.starts_with(error),
+                "{:?} does not start with {:?}",
+                manifest_error,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_cli_flags() {
+        let mut manifest = Manifest::from_toml(MANIFEST, PathBuf::from(".")).unwrap();
+        manifest.merge_cli_flags(
+            false,
+            vec![OutputFormat::Listing],
+            Some(EvmVersion::Paris),
+            Some(PathBuf::from("./out")),
+        );
+
+        let token_options = manifest.compile_options("Token");
+        assert_eq!(manifest.output_formats("Token"), &[OutputFormat::Listing]);
+        assert_eq!(token_options.evm_version, EvmVersion::Paris);
+        //flags that are not set leave the manifest settings as they are
+        assert!(token_options.deployment_bytecode);
+        assert_eq!(manifest.output_directory, PathBuf::from("./out"));
+    }
+
+    #[test]
+    fn test_find_manifest_in_parent_directory() {
+        let directory = env::temp_dir().join("evmm_manifest_find");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(directory.join("contracts/nested")).unwrap();
+        fs::write(directory.join(MANIFEST_FILE_NAME), "out = \"build\"").unwrap();
+
+        let manifest = Manifest::find(&directory.join("contracts/nested"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            fs::canonicalize(&manifest.root).unwrap(),
+            fs::canonicalize(&directory).unwrap()
+        );
+        assert_eq!(
+            manifest.output_directory,
+            directory.join("contracts/nested/../../build")
+        );
+
+        fs::write(directory.join(MANIFEST_FILE_NAME), "out = 1").unwrap();
+        assert!(matches!(
+            Manifest::find(&directory),
+            Err(EVMMError::InvalidManifest(_, _))
+        ));
+    }
+}
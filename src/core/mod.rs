@@ -1 +1,18 @@
+pub mod analyze;
+pub mod annotate;
+pub mod artifact;
+pub mod cache;
+pub mod contract;
+pub mod coverage;
+pub mod differential;
+pub mod discover;
 pub mod evmm;
+pub mod fmt;
+pub mod format;
+pub mod fuzz;
+pub mod init;
+pub mod lint;
+pub mod manifest;
+pub mod profile;
+pub mod test_runner;
+pub mod watch;
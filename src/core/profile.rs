@@ -0,0 +1,417 @@
+use crate::compiler::opcodes::push_size;
+use crate::core::contract::CompiledContract;
+use crate::core::manifest::Manifest;
+use crate::core::test_runner::run_tests_with_tracer;
+use crate::evm::abi::parse_hex;
+use crate::evm::interpreter::{Step, Tracer};
+use crate::evmm_error::evmm_error::EVMMError;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+//the frame of instructions before the first label, ex. the dispatcher
+const ENTRY_FRAME: &str = "<entry>";
+
+///The gas used by each instruction in the runtime bytecode of a contract and the number of times it ran, collected from
+///every call that runs it
+pub struct GasTracer {
+    runtime: Vec<u8>,
+    //the gas used and number of runs of the instruction starting at each offset
+    gas: Vec<(u64, u64)>,
+}
+
+impl GasTracer {
+    pub fn new(compiled_contract: &CompiledContract) -> GasTracer {
+        //the compiler only produces valid hex
+        let runtime =
+            parse_hex(&format!("0x{}", compiled_contract.runtime.bytecode)).unwrap_or_default();
+
+        GasTracer {
+            gas: vec![(0, 0); runtime.len()],
+            runtime,
+        }
+    }
+}
+
+impl Tracer for GasTracer {
+    fn step(&mut self, step: &Step) {
+        //other contracts and the constructor are not profiled
+        if step.is_create || step.code != self.runtime {
+            return;
+        }
+
+        //the gas of calls to other contracts is not included in the cost of the call instruction
+        if let Some((gas, runs)) = self.gas.get_mut(step.pc) {
+            *gas += step.gas_cost;
+            *runs += 1;
+        }
+    }
+}
+
+///The gas used by a line of source
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LineGas {
+    pub gas: u64,
+    //the number of times the line's most run instruction ran
+    pub runs: u64,
+    pub text: String,
+}
+
+///Instruction gas added up by source line, label and built-in, along with the folded stacks of
+///contract, label, built-in and line that flame graph tools read
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GasProfile {
+    //by file path and line number, counting from one
+    pub lines: BTreeMap<(String, usize), LineGas>,
+    //by contract and label name
+    pub labels: BTreeMap<(String, String), u64>,
+    //by the source of the built-in, ex. `__DISPATCHER(fallback)`
+    pub builtins: BTreeMap<String, u64>,
+    //by stack, ex. `Counter;increment;Counter.evmm:12`
+    pub stacks: BTreeMap<String, u64>,
+}
+
+impl GasProfile {
+    pub fn new() -> GasProfile {
+        GasProfile::default()
+    }
+
+    ///Maps the gas used by a contract back to its labels, built-ins and source lines through the source map and adds it up
+    pub fn add(&mut self, compiled_contract: &CompiledContract, tracer: &GasTracer) {
+        let source_map = &compiled_contract.runtime.source_map;
+        let labels = &compiled_contract.runtime.labels;
+
+        //the runs of each line, which is the number of times its most run instruction ran
+        let mut line_runs: BTreeMap<(String, usize), u64> = BTreeMap::new();
+
+        let runtime = &tracer.runtime;
+        let mut offset = 0;
+        while offset < runtime.len() {
+            let (gas, runs) = tracer.gas[offset];
+            let instruction_offset = offset;
+            offset += 1 + push_size(runtime[instruction_offset]);
+
+            if runs == 0 {
+                continue;
+            }
+
+            //the label the instruction is in is the last label before it, since labels are in bytecode order
+            let label_index = labels.partition_point(|(_, offset)| *offset <= instruction_offset);
+            let label = match label_index.checked_sub(1) {
+                Some(label_index) => labels[label_index].0.as_str(),
+                None => ENTRY_FRAME,
+            };
+            *self
+                .labels
+                .entry((compiled_contract.name.clone(), label.to_owned()))
+                .or_default() += gas;
+            let mut stack = vec![compiled_contract.name.clone(), label.to_owned()];
+
+            //the source map entry the instruction is part of, since built-ins compile to many instructions
+            let entry_index =
+                source_map.partition_point(|entry| entry.bytecode_offset <= instruction_offset);
+            let source = entry_index.checked_sub(1).and_then(|entry_index| {
+                let entry = &source_map[entry_index];
+                compiled_contract
+                    .sources
+                    .get(entry.source_index)
+                    .map(|source| (entry, source))
+            });
+
+            if let Some((entry, source)) = source {
+                let contents = &source.contents;
+                let source_text = contents
+                    .get(entry.source_start..entry.source_end)
+                    .unwrap_or_default();
+                if source_text.starts_with("__") {
+                    *self.builtins.entry(source_text.to_owned()).or_default() += gas;
+                    stack.push(source_text.to_owned());
+                }
+
+                let line_number = contents[..entry.source_start].matches('\n').count() + 1;
+                let line_gas = self
+                    .lines
+                    .entry((source.path.clone(), line_number))
+                    .or_default();
+                line_gas.gas += gas;
+                line_gas.text = contents
+                    .lines()
+                    .nth(line_number - 1)
+                    .unwrap_or_default()
+                    .trim()
+                    .to_owned();
+
+                let runs_of_line = line_runs
+                    .entry((source.path.clone(), line_number))
+                    .or_default();
+                *runs_of_line = (*runs_of_line).max(runs);
+
+                stack.push(format!("{}:{}", source.path, line_number));
+            }
+
+            //`;` separates the frames of a folded stack and the count follows the last space
+            let stack: Vec<String> = stack
+                .iter()
+                .map(|frame| frame.replace(';', ",").replace(' ', ""))
+                .collect();
+            *self.stacks.entry(stack.join(";")).or_default() += gas;
+        }
+
+        for (line, runs) in line_runs {
+            if let Some(line_gas) = self.lines.get_mut(&line) {
+                line_gas.runs += runs;
+            }
+        }
+    }
+
+    ///Formats the stacks in the folded format read by flamegraph.pl, inferno and speedscope, one `stack gas` line each
+    pub fn folded_stacks(&self) -> String {
+        self.stacks
+            .iter()
+            .filter(|(_, gas)| **gas > 0)
+            .map(|(stack, gas)| format!("{} {}\n", stack, gas))
+            .collect()
+    }
+
+    ///Formats the gas used by each label and built-in, and the lines that used the most gas, each sorted from the
+    ///most gas down
+    pub fn report(&self, hottest_lines: usize) -> String {
+        let total: u64 = self.labels.values().sum();
+        let percentage = |gas: u64| {
+            let percentage = (gas as f64 * 100.0) / (total.max(1) as f64);
+            format!("{:.1}%", percentage)
+        };
+
+        let mut report = format!("Instruction gas: {}\n", total);
+
+        let mut labels: Vec<(String, u64)> = self
+            .labels
+            .iter()
+            .map(|((contract, label), gas)| (format!("{}:{}", contract, label), *gas))
+            .collect();
+        labels.sort_by_key(|(_, gas)| Reverse(*gas));
+        report.push_str("\nGas by label\n");
+        report.push_str(&table(
+            labels
+                .into_iter()
+                .map(|(label, gas)| vec![gas.to_string(), percentage(gas), label])
+                .collect(),
+            2,
+        ));
+
+        if !self.builtins.is_empty() {
+            let mut builtins: Vec<(&String, &u64)> = self.builtins.iter().collect();
+            builtins.sort_by_key(|(_, gas)| Reverse(**gas));
+            report.push_str("\nGas by built-in\n");
+            report.push_str(&table(
+                builtins
+                    .into_iter()
+                    .map(|(builtin, gas)| vec![gas.to_string(), percentage(*gas), builtin.clone()])
+                    .collect(),
+                2,
+            ));
+        }
+
+        let mut lines: Vec<(&(String, usize), &LineGas)> = self.lines.iter().collect();
+        lines.sort_by_key(|(_, line_gas)| Reverse(line_gas.gas));
+        report.push_str("\nHottest lines\n");
+        report.push_str(&table(
+            lines
+                .into_iter()
+                .take(hottest_lines)
+                .map(|((path, line), line_gas)| {
+                    vec![
+                        line_gas.gas.to_string(),
+                        percentage(line_gas.gas),
+                        format!("{}x", line_gas.runs),
+                        format!("{}:{}", path, line),
+                        line_gas.text.clone(),
+                    ]
+                })
+                .collect(),
+            3,
+        ));
+
+        report
+    }
+}
+
+//formats rows indented under a heading, with the first columns of numbers aligned right and the rest aligned left
+fn table(rows: Vec<Vec<String>>, number_columns: usize) -> String {
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.len())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut table = String::new();
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .enumerate()
+            .map(|(column, (cell, width))| {
+                if column < number_columns {
+                    format!("{:>width$}", cell, width = width)
+                } else {
+                    format!("{:<width$}", cell, width = width)
+                }
+            })
+            .collect();
+        table.push_str(&format!("  {}\n", cells.join("  ").trim_end()));
+    }
+
+    table
+}
+
+///Runs the tests whose name contains the filter while profiling the gas they use, then prints a report of the gas
+///used by each label, built-in and the hottest lines, and writes the folded stacks to the output file.
+///The profile is written even when tests fail.
+pub fn run_gas_profile(
+    manifest: &Manifest,
+    filter: &str,
+    output_path: &Path,
+    hottest_lines: usize,
+) -> Result<(), EVMMError> {
+    let mut profile = GasProfile::new();
+
+    let test_result = run_tests_with_tracer(
+        manifest,
+        filter,
+        GasTracer::new,
+        |compiled_contract, tracer| profile.add(compiled_contract, &tracer),
+    );
+    if let Err(error) = &test_result {
+        if !matches!(error, EVMMError::TestsFailed(_)) {
+            return test_result;
+        }
+    }
+
+    if profile.labels.is_empty() {
+        return test_result;
+    }
+
+    println!();
+    print!("{}", profile.report(hottest_lines));
+
+    fs::write(output_path, profile.folded_stacks()).map_err(|error| {
+        EVMMError::ProfileFailed(output_path.display().to_string(), error.to_string())
+    })?;
+    println!();
+    println!("Wrote folded stacks to {}", output_path.display());
+
+    test_result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::contract::{compile_str, CompileOptions};
+    use crate::core::test_runner::{parse_test_file, run_test_case};
+    use std::path::PathBuf;
+
+    const COUNTER: &str = r#"#define function increment()
+
+__DISPATCHER(fallback)
+
+increment:
+    PUSH1 0x00
+    SLOAD
+    PUSH1 0x01
+    ADD
+    PUSH1 0x00
+    SSTORE
+    STOP
+
+fallback:
+    PUSH1 0x00
+    DUP1
+    REVERT
+"#;
+
+    const COUNTER_TESTS: &str = r#"
+contract = "Counter"
+
+[[test]]
+name = "increments"
+
+[[test.call]]
+function = "increment()"
+
+[[test.call]]
+function = "increment()"
+"#;
+
+    fn counter_profile() -> GasProfile {
+        let compiled_contract =
+            compile_str("Counter", COUNTER, &CompileOptions::default()).unwrap();
+        let test_file = parse_test_file(COUNTER_TESTS, PathBuf::from("Counter.t.toml")).unwrap();
+
+        let mut tracer = GasTracer::new(&compiled_contract);
+        assert!(run_test_case(&compiled_contract, &test_file.tests[0], &mut tracer).passed());
+
+        let mut profile = GasProfile::new();
+        profile.add(&compiled_contract, &tracer);
+        profile
+    }
+
+    #[test]
+    fn test_gas_by_line_and_label() {
+        let profile = counter_profile();
+
+        //each call loads the slot cold, then stores zero to one and one to two
+        let sload = &profile.lines[&(String::from("Counter.evmm"), 7)];
+        assert_eq!(
+            (sload.gas, sload.runs, sload.text.as_str()),
+            (4200, 2, "SLOAD")
+        );
+        let sstore = &profile.lines[&(String::from("Counter.evmm"), 11)];
+        assert_eq!((sstore.gas, sstore.runs), (20000 + 2900, 2));
+
+        //the JUMPDEST, pushes and ADD of both calls, then the storage gas
+        let increment_gas = profile.labels[&(String::from("Counter"), String::from("increment"))];
+        assert_eq!(increment_gas, 2 * (1 + 3 + 3 + 3 + 3) + 4200 + 22900);
+        assert!(!profile
+            .labels
+            .contains_key(&(String::from("Counter"), String::from("fallback"))));
+
+        let dispatcher_gas = profile.builtins["__DISPATCHER(fallback)"];
+        assert!(dispatcher_gas > 0);
+        assert_eq!(
+            profile.stacks["Counter;<entry>;__DISPATCHER(fallback);Counter.evmm:3"],
+            dispatcher_gas
+        );
+    }
+
+    #[test]
+    fn test_folded_stacks_and_report() {
+        let profile = counter_profile();
+
+        let folded_stacks = profile.folded_stacks();
+        assert!(folded_stacks.contains("Counter;increment;Counter.evmm:11 22900\n"));
+        assert!(folded_stacks.lines().all(|line| line
+            .rsplit_once(' ')
+            .unwrap()
+            .1
+            .parse::<u64>()
+            .unwrap()
+            > 0));
+
+        let report = profile.report(2);
+        let hottest_lines: Vec<&str> = report
+            .split("Hottest lines\n")
+            .nth(1)
+            .unwrap()
+            .lines()
+            .collect();
+        assert_eq!(hottest_lines.len(), 2);
+        assert!(hottest_lines[0].contains("2x  Counter.evmm:11  SSTORE"));
+        assert!(hottest_lines[1].contains("2x  Counter.evmm:7   SLOAD"));
+    }
+}
@@ -0,0 +1,751 @@
+use crate::core::contract::{compile_file, CompiledContract};
+use crate::core::evmm::get_contract_paths;
+use crate::core::fuzz::{parse_invariant, Invariant};
+use crate::core::manifest::{boolean, string, string_array, table_value, Manifest};
+use crate::evm::abi::{encode_call, parse_hex, parse_u256};
+use crate::evm::interpreter::{
+    Evm, ExecutionResult, ExitStatus, Log, NoTracer, Tracer, Transaction,
+};
+use crate::evm::state::{Address, WorldState};
+use crate::evmm_error::evmm_error::EVMMError;
+use crate::utils::keccak::keccak256;
+use primitive_types::U256;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::value::Table;
+use toml::Value;
+
+pub const TEST_FILE_SUFFIX: &str = ".t.toml";
+
+///The account that deploys the contract and sends every call that does not set a caller
+pub const DEFAULT_CALLER: Address = Address::repeat_byte(0xCA);
+
+///A file of tests for one contract
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestFile {
+    pub path: PathBuf,
+    //the name of the contract, which is the file name of a contract in the source directories without its extension
+    pub contract: String,
+    pub tests: Vec<TestCase>,
+    //properties checked by `evmm fuzz`
+    pub invariants: Vec<Invariant>,
+}
+
+///A test that deploys a fresh copy of the contract, makes each call in order and then checks the storage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    pub calls: Vec<TestCall>,
+    //storage slots of the contract that are checked after the calls, slots that are not listed are not checked
+    pub storage: BTreeMap<U256, U256>,
+}
+
+///A call to the contract and what it is expected to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCall {
+    //the function signature, or None if the calldata is given directly
+    pub function: Option<String>,
+    pub caller: Address,
+    pub calldata: Vec<u8>,
+    pub value: U256,
+    //the return data, or the revert data if the call reverts. Not checked if None.
+    pub returns: Option<Vec<u8>>,
+    pub reverts: bool,
+    //every log the call emits, in order. Not checked if None.
+    pub logs: Option<Vec<ExpectedLog>>,
+}
+
+///A log a call is expected to emit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedLog {
+    pub topics: Vec<U256>,
+    //the data of the log, which is not checked if None
+    pub data: Option<Vec<u8>>,
+}
+
+///The outcome of a test, with a description of each difference from what was expected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResult {
+    pub name: String,
+    pub failures: Vec<String>,
+    //the gas used by the calls, not counting the deployment
+    pub gas_used: u64,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+///Runs every test in the test directory of the manifest whose name contains the filter, printing the result of each
+///test along with the differences from what was expected. Returns an error if any test fails.
+pub fn run_tests(manifest: &Manifest, filter: &str) -> Result<(), EVMMError> {
+    run_tests_with_tracer(manifest, filter, |_| NoTracer, |_, _| {})
+}
+
+///Runs the tests like `run_tests`, passing every instruction run by the tests of a contract to the tracer that
+///`new_tracer` creates for the contract. The tracer is given back to `tests_run` once the tests of the contract have run.
+pub fn run_tests_with_tracer<T: Tracer>(
+    manifest: &Manifest,
+    filter: &str,
+    mut new_tracer: impl FnMut(&CompiledContract) -> T,
+    mut tests_run: impl FnMut(&CompiledContract, T),
+) -> Result<(), EVMMError> {
+    let test_files = load_test_files(&manifest.test_directory)?;
+
+    let mut passed = 0;
+    let mut failed = 0;
+    for test_file in test_files.iter() {
+        let tests: Vec<&TestCase> = test_file
+            .tests
+            .iter()
+            .filter(|test_case| test_case.name.contains(filter))
+            .collect();
+        if tests.is_empty() {
+            continue;
+        }
+
+        let compiled_contract = compile_test_contract(manifest, test_file)?;
+        println!(
+            "Running {} tests for {} in {}",
+            tests.len(),
+            test_file.contract,
+            test_file.path.display()
+        );
+
+        let mut tracer = new_tracer(&compiled_contract);
+        for test_case in tests {
+            let test_result = run_test_case(&compiled_contract, test_case, &mut tracer);
+
+            if test_result.passed() {
+                passed += 1;
+                println!(
+                    "[PASS] {} (gas: {})",
+                    test_result.name, test_result.gas_used
+                );
+            } else {
+                failed += 1;
+                println!("[FAIL] {}", test_result.name);
+                for failure in test_result.failures.iter() {
+                    println!("    {}", failure.replace('\n', "\n    "));
+                }
+            }
+        }
+
+        tests_run(&compiled_contract, tracer);
+    }
+
+    if passed + failed == 0 {
+        println!("No tests found in {}", manifest.test_directory.display());
+        return Ok(());
+    }
+
+    println!("Test result: {} passed; {} failed", passed, failed);
+
+    if failed > 0 {
+        Err(EVMMError::TestsFailed(failed))
+    } else {
+        Ok(())
+    }
+}
+
+///Finds and parses every `.t.toml` file in the directory and its subdirectories, in path order
+pub fn load_test_files(directory: &Path) -> Result<Vec<TestFile>, EVMMError> {
+    let mut test_file_paths = vec![];
+    find_test_files(directory, &mut test_file_paths);
+    test_file_paths.sort();
+
+    test_file_paths
+        .into_iter()
+        .map(|path| {
+            let contents = fs::read_to_string(&path).map_err(|error| {
+                EVMMError::InvalidTestFile(path.display().to_string(), error.to_string())
+            })?;
+
+            parse_test_file(&contents, path.clone())
+                .map_err(|error| EVMMError::InvalidTestFile(path.display().to_string(), error))
+        })
+        .collect()
+}
+
+//directories that do not exist have no tests
+fn find_test_files(directory: &Path, test_file_paths: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            find_test_files(&path, test_file_paths);
+        } else if path.to_string_lossy().ends_with(TEST_FILE_SUFFIX) {
+            test_file_paths.push(path);
+        }
+    }
+}
+
+///Parses the contents of a test file
+pub fn parse_test_file(contents: &str, path: PathBuf) -> Result<TestFile, String> {
+    let table: Table = toml::from_str(contents).map_err(|error| error.to_string())?;
+
+    let mut contract = None;
+    let mut tests = vec![];
+    let mut invariants = vec![];
+    for (key, value) in table.iter() {
+        match key.as_str() {
+            "contract" => contract = Some(string(key, value)?.to_owned()),
+            "test" => {
+                for test_value in table_array(key, value)? {
+                    tests.push(parse_test_case(test_value)?);
+                }
+            }
+            "invariant" => {
+                for invariant_value in table_array(key, value)? {
+                    invariants.push(parse_invariant(invariant_value)?);
+                }
+            }
+            _ => return Err(format!("unknown key `{}`", key)),
+        }
+    }
+
+    Ok(TestFile {
+        path,
+        contract: contract.ok_or("missing `contract`")?,
+        tests,
+        invariants,
+    })
+}
+
+fn parse_test_case(table: &Table) -> Result<TestCase, String> {
+    let name = string("name", table.get("name").ok_or("a test is missing `name`")?)?.to_owned();
+    let in_test = |error: String| format!("{} in test `{}`", error, name);
+
+    //the caller of the test is the default for each of its calls
+    let caller = match table.get("caller") {
+        Some(value) => address("caller", value).map_err(in_test)?,
+        None => DEFAULT_CALLER,
+    };
+
+    let mut test_case = TestCase {
+        name: name.clone(),
+        calls: vec![],
+        storage: BTreeMap::new(),
+    };
+
+    for (key, value) in table.iter() {
+        match key.as_str() {
+            "name" | "caller" => {}
+            "call" => {
+                for call_value in table_array(key, value).map_err(in_test)? {
+                    test_case
+                        .calls
+                        .push(parse_test_call(call_value, caller).map_err(in_test)?);
+                }
+            }
+            "storage" => {
+                for (slot, slot_value) in table_value(key, value).map_err(in_test)? {
+                    let slot = parse_u256(slot)
+                        .ok_or_else(|| in_test(format!("invalid storage slot `{}`", slot)))?;
+                    test_case
+                        .storage
+                        .insert(slot, number(key, slot_value).map_err(in_test)?);
+                }
+            }
+            _ => return Err(in_test(format!("unknown key `{}`", key))),
+        }
+    }
+
+    Ok(test_case)
+}
+
+fn parse_test_call(table: &Table, default_caller: Address) -> Result<TestCall, String> {
+    let mut test_call = TestCall {
+        function: None,
+        caller: default_caller,
+        calldata: vec![],
+        value: U256::zero(),
+        returns: None,
+        reverts: false,
+        logs: None,
+    };
+
+    let mut arguments = vec![];
+    let mut has_calldata = false;
+    for (key, value) in table.iter() {
+        match key.as_str() {
+            "function" => test_call.function = Some(string(key, value)?.to_owned()),
+            "args" => {
+                arguments = string_array(key, value)?
+                    .iter()
+                    .map(|argument| argument.to_string())
+                    .collect()
+            }
+            "calldata" => {
+                test_call.calldata = bytes(key, value)?;
+                has_calldata = true;
+            }
+            "caller" => test_call.caller = address(key, value)?,
+            "value" => test_call.value = number(key, value)?,
+            "returns" => test_call.returns = Some(bytes(key, value)?),
+            "reverts" => test_call.reverts = boolean(key, value)?,
+            "logs" => {
+                test_call.logs = Some(
+                    table_array(key, value)?
+                        .iter()
+                        .map(|log_table| parse_expected_log(log_table))
+                        .collect::<Result<Vec<ExpectedLog>, String>>()?,
+                )
+            }
+            _ => return Err(format!("unknown key `{}` in a call", key)),
+        }
+    }
+
+    match (&test_call.function, has_calldata) {
+        (Some(function), false) => test_call.calldata = encode_call(function, &arguments)?,
+        (Some(_), true) => return Err(String::from("a call has both `function` and `calldata`")),
+        (None, _) if !arguments.is_empty() => {
+            return Err(String::from("a call has `args` without `function`"))
+        }
+        (None, _) => {}
+    }
+
+    Ok(test_call)
+}
+
+fn parse_expected_log(table: &Table) -> Result<ExpectedLog, String> {
+    let mut expected_log = ExpectedLog {
+        topics: vec![],
+        data: None,
+    };
+
+    //the event signature is the first topic, followed by the other topics
+    if let Some(event) = table.get("event") {
+        let signature: String = string("event", event)?
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        expected_log
+            .topics
+            .push(U256::from_big_endian(&keccak256(signature.as_bytes())));
+    }
+
+    for (key, value) in table.iter() {
+        match key.as_str() {
+            "event" => {}
+            "topics" => {
+                for topic in string_array(key, value)? {
+                    expected_log.topics.push(
+                        parse_u256(topic).ok_or_else(|| format!("invalid topic `{}`", topic))?,
+                    );
+                }
+            }
+            "data" => expected_log.data = Some(bytes(key, value)?),
+            _ => return Err(format!("unknown key `{}` in a log", key)),
+        }
+    }
+
+    if expected_log.topics.len() > 4 {
+        return Err(String::from("a log has more than 4 topics"));
+    }
+
+    Ok(expected_log)
+}
+
+pub(crate) fn table_array<'a>(key: &str, value: &'a Value) -> Result<Vec<&'a Table>, String> {
+    value
+        .as_array()
+        .and_then(|values| values.iter().map(|value| value.as_table()).collect())
+        .ok_or_else(|| format!("`{}` must be an array of tables", key))
+}
+
+//numbers are integers, or strings for values that do not fit in an integer
+fn number(key: &str, value: &Value) -> Result<U256, String> {
+    match value {
+        Value::Integer(integer) if *integer >= 0 => Ok(U256::from(*integer)),
+        Value::String(text) => {
+            parse_u256(text).ok_or_else(|| format!("`{}` has an invalid value `{}`", key, text))
+        }
+        _ => Err(format!("`{}` must be a positive integer or a string", key)),
+    }
+}
+
+fn bytes(key: &str, value: &Value) -> Result<Vec<u8>, String> {
+    let text = string(key, value)?;
+    parse_hex(text).ok_or_else(|| format!("`{}` must be `0x` prefixed hex, got `{}`", key, text))
+}
+
+fn address(key: &str, value: &Value) -> Result<Address, String> {
+    let address_bytes = bytes(key, value)?;
+    if address_bytes.len() != 20 {
+        return Err(format!("`{}` must be a 20 byte address", key));
+    }
+    Ok(Address::from_slice(&address_bytes))
+}
+
+///Compiles the contract a test file tests, which is found by name in the source directories of the manifest
+pub fn compile_test_contract(
+    manifest: &Manifest,
+    test_file: &TestFile,
+) -> Result<CompiledContract, EVMMError> {
+    let contract_path = get_contract_paths(manifest, "", "")?
+        .into_iter()
+        .find(|contract_path| {
+            contract_path
+                .path
+                .file_stem()
+                .is_some_and(|file_stem| file_stem.to_string_lossy() == test_file.contract)
+        })
+        .ok_or_else(|| {
+            EVMMError::InvalidTestFile(
+                test_file.path.display().to_string(),
+                format!("contract `{}` was not found", test_file.contract),
+            )
+        })?;
+
+    compile_file(
+        &contract_path.path,
+        &manifest.compile_options(&test_file.contract),
+    )
+}
+
+///Creates an interpreter with the default caller funded, then deploys the contract from it
+pub fn deploy_contract(compiled_contract: &CompiledContract) -> Result<(Evm, Address), String> {
+    //the compiler only produces valid hex
+    let deployment_bytecode =
+        parse_hex(&format!("0x{}", compiled_contract.deployment_bytecode)).unwrap_or_default();
+
+    deploy_bytecode(deployment_bytecode)
+}
+
+///Creates an interpreter with the default caller funded, then runs the deployment bytecode from it. The contract is
+///always created at the same address, since the default caller starts with a nonce of zero.
+pub fn deploy_bytecode(deployment_bytecode: Vec<u8>) -> Result<(Evm, Address), String> {
+    let mut state = WorldState::new();
+    state.account_mut(&DEFAULT_CALLER).balance = U256::from(u128::MAX);
+    let mut evm = Evm::new(state, Default::default());
+
+    let result = evm.transact(&Transaction::new(
+        DEFAULT_CALLER,
+        None,
+        U256::zero(),
+        deployment_bytecode,
+    ));
+
+    match result.created_address {
+        Some(address) => Ok((evm, address)),
+        None => Err(format!("deployment failed: {}", result.status)),
+    }
+}
+
+///Runs a test against a freshly deployed copy of the contract, passing every instruction the calls run to the tracer
+pub fn run_test_case(
+    compiled_contract: &CompiledContract,
+    test_case: &TestCase,
+    tracer: &mut dyn Tracer,
+) -> TestResult {
+    let mut test_result = TestResult {
+        name: test_case.name.clone(),
+        failures: vec![],
+        gas_used: 0,
+    };
+
+    let (mut evm, address) = match deploy_contract(compiled_contract) {
+        Ok(deployment) => deployment,
+        Err(error) => {
+            test_result.failures.push(error);
+            return test_result;
+        }
+    };
+
+    for (index, test_call) in test_case.calls.iter().enumerate() {
+        //callers other than the default caller are funded as they are used
+        if evm.state.balance(&test_call.caller) < test_call.value {
+            evm.state.account_mut(&test_call.caller).balance = test_call.value;
+        }
+
+        let result = evm.transact_with_tracer(
+            &Transaction::new(
+                test_call.caller,
+                Some(address),
+                test_call.value,
+                test_call.calldata.clone(),
+            ),
+            tracer,
+        );
+        test_result.gas_used += result.gas_used;
+
+        let call_name = match &test_call.function {
+            Some(function) => format!("call {} {}", index + 1, function),
+            None => format!("call {}", index + 1),
+        };
+        for difference in call_differences(test_call, &result) {
+            test_result
+                .failures
+                .push(format!("{}: {}", call_name, difference));
+        }
+    }
+
+    for (slot, expected_value) in test_case.storage.iter() {
+        let value = evm.state.storage(&address, slot);
+        if value != *expected_value {
+            test_result.failures.push(format!(
+                "storage slot {:#x}: expected {:#x}, got {:#x}",
+                slot, expected_value, value
+            ));
+        }
+    }
+
+    test_result
+}
+
+//describes each way the result of a call differs from what was expected
+fn call_differences(test_call: &TestCall, result: &ExecutionResult) -> Vec<String> {
+    let mut differences = vec![];
+
+    match (test_call.reverts, result.status) {
+        (true, ExitStatus::Revert) | (false, ExitStatus::Success) => {}
+        (true, status) => differences.push(format!("expected a revert, got {}", status)),
+        (false, status) => {
+            differences.push(format!("expected success, got {}", status));
+            //the return data is not compared if the call did not end the way it was expected to
+            return differences;
+        }
+    }
+
+    if let Some(returns) = &test_call.returns {
+        if *returns != result.output {
+            differences.push(format!(
+                "return data differs\n{}",
+                bytes_difference(returns, &result.output)
+            ));
+        }
+    }
+
+    if let Some(expected_logs) = &test_call.logs {
+        differences.extend(log_differences(expected_logs, &result.logs));
+    }
+
+    differences
+}
+
+//lists the 32 byte words that differ, followed by the difference in length
+pub(crate) fn bytes_difference(expected: &[u8], actual: &[u8]) -> String {
+    let word_count = expected.len().max(actual.len()).div_ceil(32);
+    let word = |bytes: &[u8], index: usize| -> String {
+        let start = (index * 32).min(bytes.len());
+        let end = ((index + 1) * 32).min(bytes.len());
+        bytes[start..end]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    };
+
+    let mut lines = vec![];
+    for index in 0..word_count {
+        let (expected_word, actual_word) = (word(expected, index), word(actual, index));
+        if expected_word != actual_word {
+            lines.push(format!(
+                "  word {}: expected 0x{}, got 0x{}",
+                index, expected_word, actual_word
+            ));
+        }
+    }
+
+    if expected.len() != actual.len() {
+        lines.push(format!(
+            "  expected {} bytes, got {} bytes",
+            expected.len(),
+            actual.len()
+        ));
+    }
+
+    lines.join("\n")
+}
+
+pub(crate) fn log_differences(expected_logs: &[ExpectedLog], logs: &[Log]) -> Vec<String> {
+    let mut differences = vec![];
+
+    if expected_logs.len() != logs.len() {
+        differences.push(format!(
+            "expected {} logs, got {}",
+            expected_logs.len(),
+            logs.len()
+        ));
+    }
+
+    for (index, (expected_log, log)) in expected_logs.iter().zip(logs.iter()).enumerate() {
+        if expected_log.topics != log.topics {
+            let format_topics = |topics: &[U256]| {
+                topics
+                    .iter()
+                    .map(|topic| format!("{:#x}", topic))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            };
+            differences.push(format!(
+                "log {} topics differ\n  expected [{}]\n  got [{}]",
+                index,
+                format_topics(&expected_log.topics),
+                format_topics(&log.topics)
+            ));
+        }
+
+        if let Some(data) = &expected_log.data {
+            if *data != log.data {
+                differences.push(format!(
+                    "log {} data differs\n{}",
+                    index,
+                    bytes_difference(data, &log.data)
+                ));
+            }
+        }
+    }
+
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::contract::{compile_str, CompileOptions};
+
+    const COUNTER: &str = r#"
+#define function increment()
+#define function count() view returns (uint256)
+#define event Incremented(uint256)
+
+__DISPATCHER(fallback)
+
+increment:
+    PUSH1 0x00
+    SLOAD
+    PUSH1 0x01
+    ADD
+    DUP1
+    PUSH1 0x00
+    SSTORE
+    PUSH1 0x00
+    MSTORE
+    __EVENT_HASH(Incremented)
+    PUSH1 0x20
+    PUSH1 0x00
+    LOG1
+    STOP
+
+count:
+    PUSH1 0x00
+    SLOAD
+    PUSH1 0x00
+    MSTORE
+    PUSH1 0x20
+    PUSH1 0x00
+    RETURN
+
+fallback:
+    PUSH1 0x00
+    DUP1
+    REVERT
+"#;
+
+    const COUNTER_TESTS: &str = r#"
+contract = "Counter"
+
+[[test]]
+name = "increment"
+
+[[test.call]]
+function = "increment()"
+
+[[test.call.logs]]
+event = "Incremented(uint256)"
+data = "0x0000000000000000000000000000000000000000000000000000000000000001"
+
+[[test.call]]
+function = "count()"
+returns = "0x0000000000000000000000000000000000000000000000000000000000000001"
+
+[test.storage]
+"0x00" = 1
+
+[[test]]
+name = "wrong expectations"
+caller = "0x0000000000000000000000000000000000000001"
+
+[[test.call]]
+function = "count()"
+returns = "0x0000000000000000000000000000000000000000000000000000000000000002"
+
+[[test.call]]
+calldata = "0xdeadbeef"
+
+[test.storage]
+"0x00" = "0x05"
+"#;
+
+    #[test]
+    fn test_parse_test_file() {
+        let test_file = parse_test_file(COUNTER_TESTS, PathBuf::from("Counter.t.toml")).unwrap();
+
+        assert_eq!(test_file.contract, "Counter");
+        assert_eq!(test_file.tests.len(), 2);
+        assert_eq!(
+            test_file.tests[0].calls[0].calldata,
+            vec![0xd0, 0x9d, 0xe0, 0x8a]
+        );
+        assert_eq!(
+            test_file.tests[0].calls[0].logs.as_ref().unwrap()[0]
+                .topics
+                .len(),
+            1
+        );
+        assert_eq!(
+            test_file.tests[1].calls[1].caller,
+            Address::from_low_u64_be(1)
+        );
+
+        for (contents, error) in [
+            ("[[test]]\nname = \"a\"", "missing `contract`"),
+            (
+                "contract = \"A\"\n[[test]]\nname = \"a\"\n[[test.call]]\nfunction = \"f(uint8)\"\nargs = [\"256\"]",
+                "invalid uint8 value `256` in test `a`",
+            ),
+            (
+                "contract = \"A\"\n[[test]]\nname = \"a\"\nexpect = 1",
+                "unknown key `expect` in test `a`",
+            ),
+        ] {
+            assert_eq!(
+                parse_test_file(contents, PathBuf::from("A.t.toml")).unwrap_err(),
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_test_case() {
+        let compiled_contract =
+            compile_str("Counter", COUNTER, &CompileOptions::default()).unwrap();
+        let test_file = parse_test_file(COUNTER_TESTS, PathBuf::from("Counter.t.toml")).unwrap();
+
+        let test_result = run_test_case(&compiled_contract, &test_file.tests[0], &mut NoTracer);
+        assert_eq!(test_result.failures, Vec::<String>::new());
+        assert!(test_result.gas_used > 0);
+
+        let test_result = run_test_case(&compiled_contract, &test_file.tests[1], &mut NoTracer);
+        assert_eq!(
+            test_result.failures,
+            vec![
+                format!(
+                    "call 1 count(): return data differs\n  word 0: expected 0x{:0>64}, got 0x{:0>64}",
+                    "2", "0"
+                ),
+                String::from("call 2: expected success, got revert"),
+                String::from("storage slot 0x0: expected 0x5, got 0x0"),
+            ]
+        );
+    }
+}
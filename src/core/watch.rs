@@ -0,0 +1,309 @@
+use crate::core::cache::BuildCache;
+use crate::core::discover::ContractPath;
+use crate::core::discover::CONTRACT_EXTENSION;
+use crate::core::evmm::{
+    compile_contract, contract_options_key, get_contract_paths, print_warnings, write_contract,
+    EVMASMFile,
+};
+use crate::core::manifest::Manifest;
+use crate::evmm_error::evmm_error::EVMMError;
+use notify::{Event, RecursiveMode, Watcher};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+//changes that arrive within this time of each other are compiled together, since editors often write a file several times when saving
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(100);
+
+///The size and deployment gas of a contract the last time it compiled, which changes are compared against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ContractStats {
+    runtime_size: usize,
+    deployment_gas: u64,
+}
+
+impl ContractStats {
+    fn new(runtime_size: usize, deployment_gas: u64) -> ContractStats {
+        ContractStats {
+            runtime_size,
+            deployment_gas,
+        }
+    }
+}
+
+///What is known about the contracts between changes
+struct WatchState {
+    build_cache: BuildCache,
+    //the stats of each contract the last time it compiled
+    contract_stats: HashMap<PathBuf, ContractStats>,
+    //contracts that failed to compile the last time they were compiled, which are compiled again after any change
+    failed_contracts: HashSet<PathBuf>,
+}
+
+impl WatchState {
+    fn new(build_cache: BuildCache) -> WatchState {
+        WatchState {
+            build_cache,
+            contract_stats: HashMap::new(),
+            failed_contracts: HashSet::new(),
+        }
+    }
+}
+
+///Compiles the contracts, then watches the directories they are in and the include paths, recompiling the contracts
+///affected by each change. Errors are printed without stopping, and each contract that compiles prints its size and
+///deployment gas along with how much they changed.
+pub fn watch_contracts(
+    manifest: &Manifest,
+    contract_path: &str,
+    directory_to_compile: &str,
+) -> Result<(), EVMMError> {
+    let (sender, receiver) = channel();
+    let mut watcher = notify::recommended_watcher(sender)
+        .map_err(|error| EVMMError::WatchFailed(String::from("."), error.to_string()))?;
+
+    for directory in watched_directories(manifest, contract_path, directory_to_compile)? {
+        watcher
+            .watch(&directory, RecursiveMode::Recursive)
+            .map_err(|error| {
+                EVMMError::WatchFailed(directory.display().to_string(), error.to_string())
+            })?;
+    }
+
+    fs::create_dir_all(&manifest.output_directory).map_err(|error| {
+        EVMMError::WriteFailed(
+            manifest.output_directory.display().to_string(),
+            error.to_string(),
+        )
+    })?;
+    let mut watch_state = WatchState::new(BuildCache::load(&manifest.output_directory));
+
+    compile_changed_contracts(
+        manifest,
+        contract_path,
+        directory_to_compile,
+        &mut watch_state,
+    );
+    println!("Watching for changes");
+
+    //the channel only closes if the watcher stops
+    while let Ok(event) = receiver.recv() {
+        let mut source_changed = is_source_change(&event);
+        while let Ok(event) = receiver.recv_timeout(DEBOUNCE_DURATION) {
+            source_changed |= is_source_change(&event);
+        }
+
+        if source_changed {
+            compile_changed_contracts(
+                manifest,
+                contract_path,
+                directory_to_compile,
+                &mut watch_state,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+//the directories containing the contracts, along with the include paths that are not inside them
+fn watched_directories(
+    manifest: &Manifest,
+    contract_path: &str,
+    directory_to_compile: &str,
+) -> Result<Vec<PathBuf>, EVMMError> {
+    let mut directories: Vec<PathBuf> = if !directory_to_compile.is_empty() {
+        vec![PathBuf::from(directory_to_compile)]
+    } else if !contract_path.is_empty() {
+        get_contract_paths(manifest, contract_path, directory_to_compile)?
+            .iter()
+            .map(|contract_path| match contract_path.path.parent() {
+                Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            })
+            .collect()
+    } else {
+        manifest.source_directories.clone()
+    };
+
+    for include_path in manifest.include_paths.iter() {
+        let is_watched = fs::canonicalize(include_path).is_ok_and(|include_path| {
+            directories.iter().any(|directory| {
+                fs::canonicalize(directory)
+                    .is_ok_and(|directory| include_path.starts_with(directory))
+            })
+        });
+
+        //include paths that do not exist can not be watched, but do not stop the contracts from compiling
+        if !is_watched && include_path.is_dir() {
+            directories.push(include_path.clone());
+        }
+    }
+
+    Ok(directories)
+}
+
+//only changes to `.evmm` files can change the compiled contracts
+fn is_source_change(event: &notify::Result<Event>) -> bool {
+    match event {
+        Ok(event) => {
+            (event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove())
+                && event.paths.iter().any(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension == CONTRACT_EXTENSION)
+                })
+        }
+        Err(_) => false,
+    }
+}
+
+//compiles every contract that has not been compiled yet or that changed since it last compiled
+fn compile_changed_contracts(
+    manifest: &Manifest,
+    contract_path: &str,
+    directory_to_compile: &str,
+    watch_state: &mut WatchState,
+) {
+    //contracts are found again each time so that new contracts are compiled
+    let contract_paths = match get_contract_paths(manifest, contract_path, directory_to_compile) {
+        Ok(contract_paths) => contract_paths,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            return;
+        }
+    };
+
+    //the build cache checks the contract and every file it includes
+    let contract_paths: Vec<ContractPath> = contract_paths
+        .into_iter()
+        .filter(|contract_path| {
+            !watch_state.contract_stats.contains_key(&contract_path.path)
+                || watch_state.failed_contracts.contains(&contract_path.path)
+                || !watch_state.build_cache.is_up_to_date(
+                    &contract_path.path,
+                    &contract_options_key(manifest, &contract_path.path),
+                    &manifest.output_directory,
+                )
+        })
+        .collect();
+
+    //contracts are compiled in parallel, then reported in order
+    let compiled_contracts: Vec<(PathBuf, Result<EVMASMFile, EVMMError>)> = contract_paths
+        .into_par_iter()
+        .map(|contract_path| {
+            (
+                contract_path.path.clone(),
+                compile_contract(manifest, contract_path),
+            )
+        })
+        .collect();
+
+    for (path, compiled_contract) in compiled_contracts {
+        let options_key = contract_options_key(manifest, &path);
+
+        let evmasm_file = match compiled_contract {
+            Ok(evmasm_file) => evmasm_file,
+            Err(error) => {
+                eprintln!("Error in {}: {}", path.display(), error);
+                watch_state.failed_contracts.insert(path);
+                continue;
+            }
+        };
+
+        match write_contract(&evmasm_file, &manifest.output_directory) {
+            Ok(output_files) => watch_state.build_cache.insert(
+                &path,
+                &options_key,
+                &evmasm_file.sources,
+                &output_files,
+            ),
+            Err(error) => {
+                eprintln!("Error writing the output of {}: {}", path.display(), error);
+                watch_state.failed_contracts.insert(path);
+                continue;
+            }
+        }
+
+        let stats = ContractStats::new(evmasm_file.runtime_size, evmasm_file.deployment_gas);
+        println!(
+            "{}",
+            compile_summary(&path, stats, watch_state.contract_stats.get(&path))
+        );
+        print_warnings(&evmasm_file);
+        watch_state.failed_contracts.remove(&path);
+        watch_state.contract_stats.insert(path, stats);
+    }
+
+    if let Err(error) = watch_state.build_cache.save() {
+        eprintln!("Error saving the build cache: {}", error);
+    }
+}
+
+//a one line summary of a compiled contract, with the changes since it last compiled
+fn compile_summary(
+    contract_path: &Path,
+    stats: ContractStats,
+    previous_stats: Option<&ContractStats>,
+) -> String {
+    match previous_stats {
+        Some(previous_stats) => format!(
+            "Compiled {}: {} bytes ({:+}), {} deployment gas ({:+})",
+            contract_path.display(),
+            stats.runtime_size,
+            stats.runtime_size as i64 - previous_stats.runtime_size as i64,
+            stats.deployment_gas,
+            stats.deployment_gas as i64 - previous_stats.deployment_gas as i64
+        ),
+        None => format!(
+            "Compiled {}: {} bytes, {} deployment gas",
+            contract_path.display(),
+            stats.runtime_size,
+            stats.deployment_gas
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, EventKind};
+
+    #[test]
+    fn test_compile_summary() {
+        let contract_path = Path::new("evmm_contracts/Counter.evmm");
+
+        assert_eq!(
+            compile_summary(contract_path, ContractStats::new(45, 62000), None),
+            "Compiled evmm_contracts/Counter.evmm: 45 bytes, 62000 deployment gas"
+        );
+        assert_eq!(
+            compile_summary(
+                contract_path,
+                ContractStats::new(42, 61300),
+                Some(&ContractStats::new(45, 62000))
+            ),
+            "Compiled evmm_contracts/Counter.evmm: 42 bytes (-3), 61300 deployment gas (-700)"
+        );
+    }
+
+    #[test]
+    fn test_is_source_change() {
+        let event = |kind, path| Ok(Event::new(kind).add_path(PathBuf::from(path)));
+
+        assert!(is_source_change(&event(
+            EventKind::Create(CreateKind::File),
+            "evmm_contracts/Counter.evmm"
+        )));
+        assert!(!is_source_change(&event(
+            EventKind::Create(CreateKind::File),
+            "evmm_contracts/.Counter.evmm.swp"
+        )));
+        assert!(!is_source_change(&event(
+            EventKind::Access(AccessKind::Any),
+            "evmm_contracts/Counter.evmm"
+        )));
+    }
+}
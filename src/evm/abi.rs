@@ -0,0 +1,529 @@
+use crate::evm::state::u256_to_bytes;
+use crate::utils::keccak::keccak256;
+use primitive_types::U256;
+
+///A solidity abi type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbiType {
+    //the size in bits
+    Uint(usize),
+    Int(usize),
+    Address,
+    Bool,
+    //the size in bytes
+    FixedBytes(usize),
+    Bytes,
+    String,
+    Array(Box<AbiType>),
+    FixedArray(Box<AbiType>, usize),
+    Tuple(Vec<AbiType>),
+}
+
+impl AbiType {
+    ///Parses a canonical type name, ex. `uint256`, `bytes32[]` or `(address,bool)`
+    pub fn parse(text: &str) -> Option<AbiType> {
+        let text = text.trim();
+
+        //arrays are parsed from the last dimension, so `uint8[2][]` is a dynamic array of `uint8[2]`
+        if let Some(text) = text.strip_suffix(']') {
+            let open_bracket = text.rfind('[')?;
+            let element_type = Box::new(AbiType::parse(&text[..open_bracket])?);
+            let length = &text[open_bracket + 1..];
+
+            return if length.is_empty() {
+                Some(AbiType::Array(element_type))
+            } else {
+                Some(AbiType::FixedArray(element_type, length.parse().ok()?))
+            };
+        }
+
+        if let Some(text) = text.strip_prefix('(') {
+            let element_types = split_top_level(text.strip_suffix(')')?)
+                .iter()
+                .map(|element_type| AbiType::parse(element_type))
+                .collect::<Option<Vec<AbiType>>>()?;
+            return Some(AbiType::Tuple(element_types));
+        }
+
+        match text {
+            "address" => Some(AbiType::Address),
+            "bool" => Some(AbiType::Bool),
+            "bytes" => Some(AbiType::Bytes),
+            "string" => Some(AbiType::String),
+            "uint" => Some(AbiType::Uint(256)),
+            "int" => Some(AbiType::Int(256)),
+            _ => {
+                let (constructor, size): (fn(usize) -> AbiType, &str) =
+                    if let Some(size) = text.strip_prefix("uint") {
+                        (AbiType::Uint, size)
+                    } else if let Some(size) = text.strip_prefix("int") {
+                        (AbiType::Int, size)
+                    } else {
+                        (AbiType::FixedBytes, text.strip_prefix("bytes")?)
+                    };
+
+                let size: usize = size.parse().ok()?;
+                let is_valid = match text.starts_with("bytes") {
+                    true => (1..=32).contains(&size),
+                    false => size.is_multiple_of(8) && (8..=256).contains(&size),
+                };
+                is_valid.then(|| constructor(size))
+            }
+        }
+    }
+
+    ///Checks if the encoding of the type has a length that depends on its value
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            AbiType::Bytes | AbiType::String | AbiType::Array(_) => true,
+            AbiType::FixedArray(element_type, _) => element_type.is_dynamic(),
+            AbiType::Tuple(element_types) => element_types.iter().any(AbiType::is_dynamic),
+            _ => false,
+        }
+    }
+}
+
+///A value to encode, with the meaning given by its abi type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbiValue {
+    //a single word, for every type that is encoded in one word
+    Word(U256),
+    //the contents of `bytes` or `string`
+    Bytes(Vec<u8>),
+    //the elements of an array or tuple
+    List(Vec<AbiValue>),
+}
+
+///Splits a function signature, ex. `transfer(address,uint256)`, into its name and parameter types
+pub fn parse_signature(signature: &str) -> Option<(String, Vec<AbiType>)> {
+    let open_parenthesis = signature.find('(')?;
+    let name = signature[..open_parenthesis].trim();
+
+    match AbiType::parse(&signature[open_parenthesis..])? {
+        AbiType::Tuple(parameter_types) if !name.is_empty() => {
+            Some((name.to_owned(), parameter_types))
+        }
+        _ => None,
+    }
+}
+
+///Gets the function selector of a canonical signature
+pub fn selector(signature: &str) -> [u8; 4] {
+    let mut selector = [0u8; 4];
+    selector.copy_from_slice(&keccak256(signature.as_bytes())[0..4]);
+    selector
+}
+
+///Encodes a call to a function, with each argument written as text, ex. `encode_call("transfer(address,uint256)",
+///&["0x01", "100"])`
+pub fn encode_call(signature: &str, arguments: &[String]) -> Result<Vec<u8>, String> {
+    let (name, parameter_types) = parse_signature(signature)
+        .ok_or_else(|| format!("invalid function signature `{}`", signature))?;
+
+    if arguments.len() != parameter_types.len() {
+        return Err(format!(
+            "{} takes {} arguments but {} were given",
+            name,
+            parameter_types.len(),
+            arguments.len()
+        ));
+    }
+
+    let values = parameter_types
+        .iter()
+        .zip(arguments.iter())
+        .map(|(parameter_type, argument)| parse_value(parameter_type, argument))
+        .collect::<Result<Vec<AbiValue>, String>>()?;
+
+    //the signature is normalized so that spaces do not change the selector
+    let canonical_signature: String = signature.chars().filter(|c| !c.is_whitespace()).collect();
+
+    let mut calldata = selector(&canonical_signature).to_vec();
+    calldata.extend(encode(&parameter_types, &values));
+    Ok(calldata)
+}
+
+///Encodes values as a tuple of the types, the way function arguments and return data are encoded
+pub fn encode(types: &[AbiType], values: &[AbiValue]) -> Vec<u8> {
+    let head_size: usize = types.iter().map(head_size).sum();
+    let mut head = vec![];
+    let mut tail = vec![];
+
+    for (value_type, value) in types.iter().zip(values.iter()) {
+        let encoded_value = encode_value(value_type, value);
+
+        if value_type.is_dynamic() {
+            //dynamic values are stored after the head, which holds their offset
+            head.extend_from_slice(&u256_to_bytes(U256::from(head_size + tail.len())));
+            tail.extend(encoded_value);
+        } else {
+            head.extend(encoded_value);
+        }
+    }
+
+    head.extend(tail);
+    head
+}
+
+fn encode_value(value_type: &AbiType, value: &AbiValue) -> Vec<u8> {
+    match (value_type, value) {
+        (AbiType::Bytes | AbiType::String, AbiValue::Bytes(bytes)) => {
+            let mut encoded = u256_to_bytes(U256::from(bytes.len())).to_vec();
+            encoded.extend_from_slice(bytes);
+            encoded.resize(32 + bytes.len().div_ceil(32) * 32, 0);
+            encoded
+        }
+        (AbiType::Array(element_type), AbiValue::List(elements)) => {
+            let mut encoded = u256_to_bytes(U256::from(elements.len())).to_vec();
+            encoded.extend(encode(
+                &vec![element_type.as_ref().clone(); elements.len()],
+                elements,
+            ));
+            encoded
+        }
+        (AbiType::FixedArray(element_type, _), AbiValue::List(elements)) => encode(
+            &vec![element_type.as_ref().clone(); elements.len()],
+            elements,
+        ),
+        (AbiType::Tuple(element_types), AbiValue::List(elements)) => {
+            encode(element_types, elements)
+        }
+        (_, AbiValue::Word(word)) => u256_to_bytes(*word).to_vec(),
+        //values are parsed for their type, so the other combinations do not happen
+        _ => vec![0u8; head_size(value_type)],
+    }
+}
+
+//the size of a value in the head of a tuple, which is the size of its encoding for static types
+fn head_size(value_type: &AbiType) -> usize {
+    match value_type {
+        _ if value_type.is_dynamic() => 32,
+        AbiType::FixedArray(element_type, length) => head_size(element_type) * length,
+        AbiType::Tuple(element_types) => element_types.iter().map(head_size).sum(),
+        _ => 32,
+    }
+}
+
+///Parses a value of a type written as text. Numbers are decimal or `0x` prefixed hex, bytes are hex, arrays are
+///written as `[1, 2]` and tuples as `(1, true)`.
+pub fn parse_value(value_type: &AbiType, text: &str) -> Result<AbiValue, String> {
+    let text = text.trim();
+    let invalid_value = || format!("invalid {} value `{}`", type_name(value_type), text);
+
+    match value_type {
+        AbiType::Uint(size) => {
+            let value = parse_u256(text).ok_or_else(invalid_value)?;
+            if *size < 256 && value.bits() > *size {
+                return Err(invalid_value());
+            }
+            Ok(AbiValue::Word(value))
+        }
+        AbiType::Int(size) => {
+            let (is_negative, magnitude) = match text.strip_prefix('-') {
+                Some(magnitude) => (true, magnitude),
+                None => (false, text),
+            };
+            let magnitude = parse_u256(magnitude).ok_or_else(invalid_value)?;
+
+            //the magnitude of a negative value can be one larger than the largest positive value
+            let limit = U256::one() << (size - 1);
+            if magnitude > limit || (!is_negative && magnitude == limit) {
+                return Err(invalid_value());
+            }
+
+            if is_negative {
+                Ok(AbiValue::Word((!magnitude).overflowing_add(U256::one()).0))
+            } else {
+                Ok(AbiValue::Word(magnitude))
+            }
+        }
+        AbiType::Address => {
+            let bytes = parse_hex(text).ok_or_else(invalid_value)?;
+            if bytes.len() != 20 {
+                return Err(invalid_value());
+            }
+            Ok(AbiValue::Word(U256::from_big_endian(&bytes)))
+        }
+        AbiType::Bool => match text {
+            "true" => Ok(AbiValue::Word(U256::one())),
+            "false" => Ok(AbiValue::Word(U256::zero())),
+            _ => Err(invalid_value()),
+        },
+        AbiType::FixedBytes(size) => {
+            let mut bytes = parse_hex(text).ok_or_else(invalid_value)?;
+            if bytes.len() > *size {
+                return Err(invalid_value());
+            }
+            //fixed size bytes are left aligned
+            bytes.resize(32, 0);
+            Ok(AbiValue::Word(U256::from_big_endian(&bytes)))
+        }
+        AbiType::Bytes => Ok(AbiValue::Bytes(parse_hex(text).ok_or_else(invalid_value)?)),
+        AbiType::String => {
+            let text = text
+                .strip_prefix('"')
+                .and_then(|text| text.strip_suffix('"'))
+                .unwrap_or(text);
+            Ok(AbiValue::Bytes(text.as_bytes().to_vec()))
+        }
+        AbiType::Array(element_type) | AbiType::FixedArray(element_type, _) => {
+            let elements = text
+                .strip_prefix('[')
+                .and_then(|text| text.strip_suffix(']'))
+                .ok_or_else(invalid_value)?;
+            let elements = split_top_level(elements)
+                .iter()
+                .map(|element| parse_value(element_type, element))
+                .collect::<Result<Vec<AbiValue>, String>>()?;
+
+            if let AbiType::FixedArray(_, length) = value_type {
+                if elements.len() != *length {
+                    return Err(invalid_value());
+                }
+            }
+            Ok(AbiValue::List(elements))
+        }
+        AbiType::Tuple(element_types) => {
+            let elements = text
+                .strip_prefix('(')
+                .and_then(|text| text.strip_suffix(')'))
+                .ok_or_else(invalid_value)?;
+            let elements = split_top_level(elements);
+
+            if elements.len() != element_types.len() {
+                return Err(invalid_value());
+            }
+            let elements = element_types
+                .iter()
+                .zip(elements.iter())
+                .map(|(element_type, element)| parse_value(element_type, element))
+                .collect::<Result<Vec<AbiValue>, String>>()?;
+            Ok(AbiValue::List(elements))
+        }
+    }
+}
+
+///Parses a decimal or `0x` prefixed hex number
+pub fn parse_u256(text: &str) -> Option<U256> {
+    let text = text.trim();
+
+    match text.strip_prefix("0x") {
+        Some(hex) if !hex.is_empty() && hex.len() <= 64 => U256::from_str_radix(hex, 16).ok(),
+        Some(_) => None,
+        None if !text.is_empty() && text.chars().all(|c| c.is_ascii_digit()) => {
+            U256::from_dec_str(text).ok()
+        }
+        None => None,
+    }
+}
+
+///Parses `0x` prefixed hex bytes
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let hex = text.trim().strip_prefix("0x")?;
+
+    if hex.len() % 2 != 0 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+///Writes a value as text that `parse_value` parses back to the same value
+pub fn format_value(value_type: &AbiType, value: &AbiValue) -> String {
+    match (value_type, value) {
+        (AbiType::Uint(_), AbiValue::Word(word)) => word.to_string(),
+        (AbiType::Int(_), AbiValue::Word(word)) if word.bit(255) => {
+            format!("-{}", (!*word).overflowing_add(U256::one()).0)
+        }
+        (AbiType::Int(_), AbiValue::Word(word)) => word.to_string(),
+        (AbiType::Address, AbiValue::Word(word)) => format_hex(&u256_to_bytes(*word)[12..]),
+        (AbiType::Bool, AbiValue::Word(word)) => (!word.is_zero()).to_string(),
+        (AbiType::FixedBytes(size), AbiValue::Word(word)) => {
+            format_hex(&u256_to_bytes(*word)[..*size])
+        }
+        (AbiType::String, AbiValue::Bytes(bytes)) => {
+            format!("\"{}\"", String::from_utf8_lossy(bytes))
+        }
+        (_, AbiValue::Bytes(bytes)) => format_hex(bytes),
+        (AbiType::Array(element_type), AbiValue::List(elements))
+        | (AbiType::FixedArray(element_type, _), AbiValue::List(elements)) => format!(
+            "[{}]",
+            elements
+                .iter()
+                .map(|element| format_value(element_type, element))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        (AbiType::Tuple(element_types), AbiValue::List(elements)) => format!(
+            "({})",
+            element_types
+                .iter()
+                .zip(elements.iter())
+                .map(|(element_type, element)| format_value(element_type, element))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        (_, AbiValue::Word(word)) => format!("{:#x}", word),
+        (_, AbiValue::List(_)) => String::from("[]"),
+    }
+}
+
+///Writes bytes as `0x` prefixed lowercase hex
+pub fn format_hex(bytes: &[u8]) -> String {
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("0x{}", hex)
+}
+
+///Gets the canonical name of a type, ex. `uint256[]`
+pub fn type_name(value_type: &AbiType) -> String {
+    match value_type {
+        AbiType::Uint(size) => format!("uint{}", size),
+        AbiType::Int(size) => format!("int{}", size),
+        AbiType::Address => String::from("address"),
+        AbiType::Bool => String::from("bool"),
+        AbiType::FixedBytes(size) => format!("bytes{}", size),
+        AbiType::Bytes => String::from("bytes"),
+        AbiType::String => String::from("string"),
+        AbiType::Array(element_type) => format!("{}[]", type_name(element_type)),
+        AbiType::FixedArray(element_type, length) => {
+            format!("{}[{}]", type_name(element_type), length)
+        }
+        AbiType::Tuple(element_types) => format!(
+            "({})",
+            element_types
+                .iter()
+                .map(type_name)
+                .collect::<Vec<String>>()
+                .join(",")
+        ),
+    }
+}
+
+//splits a comma separated list, ignoring commas inside brackets, parentheses and quotes
+fn split_top_level(text: &str) -> Vec<String> {
+    if text.trim().is_empty() {
+        return vec![];
+    }
+
+    let mut elements = vec![];
+    let mut element = String::new();
+    let mut depth = 0;
+    let mut in_quotes = false;
+
+    for c in text.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '[' | '(' if !in_quotes => depth += 1,
+            ']' | ')' if !in_quotes => depth -= 1,
+            ',' if !in_quotes && depth == 0 => {
+                elements.push(element.trim().to_owned());
+                element.clear();
+                continue;
+            }
+            _ => {}
+        }
+        element.push(c);
+    }
+    elements.push(element.trim().to_owned());
+
+    elements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn test_parse_type() {
+        assert_eq!(AbiType::parse("uint256"), Some(AbiType::Uint(256)));
+        assert_eq!(
+            AbiType::parse("(address,bytes32[2])[]"),
+            Some(AbiType::Array(Box::new(AbiType::Tuple(vec![
+                AbiType::Address,
+                AbiType::FixedArray(Box::new(AbiType::FixedBytes(32)), 2)
+            ]))))
+        );
+        assert_eq!(AbiType::parse("uint7"), None);
+        assert_eq!(AbiType::parse("bytes33"), None);
+    }
+
+    #[test]
+    fn test_encode_call() {
+        let calldata = encode_call(
+            "transfer(address,uint256)",
+            &[
+                String::from("0x00000000000000000000000000000000000000ff"),
+                String::from("100"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            to_hex(&calldata),
+            format!("a9059cbb{:0>64}{:0>64}", "ff", "64")
+        );
+    }
+
+    #[test]
+    fn test_encode_dynamic_values() {
+        //the example from the solidity abi specification
+        let calldata = encode_call(
+            "f(uint256,uint32[],bytes10,bytes)",
+            &[
+                String::from("0x123"),
+                String::from("[0x456, 0x789]"),
+                String::from("0x31323334353637383930"),
+                String::from("0x48656c6c6f2c20776f726c6421"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            to_hex(&calldata),
+            [
+                "8be65246",
+                "0000000000000000000000000000000000000000000000000000000000000123",
+                "0000000000000000000000000000000000000000000000000000000000000080",
+                "3132333435363738393000000000000000000000000000000000000000000000",
+                "00000000000000000000000000000000000000000000000000000000000000e0",
+                "0000000000000000000000000000000000000000000000000000000000000002",
+                "0000000000000000000000000000000000000000000000000000000000000456",
+                "0000000000000000000000000000000000000000000000000000000000000789",
+                "000000000000000000000000000000000000000000000000000000000000000d",
+                "48656c6c6f2c20776f726c642100000000000000000000000000000000000000",
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(
+            parse_value(&AbiType::Int(8), "-1"),
+            Ok(AbiValue::Word(U256::MAX))
+        );
+        assert!(parse_value(&AbiType::Int(8), "128").is_err());
+        assert!(parse_value(&AbiType::Uint(8), "256").is_err());
+        assert!(parse_value(&AbiType::Address, "0x01").is_err());
+        assert_eq!(
+            parse_value(&AbiType::String, "\"a, b\""),
+            Ok(AbiValue::Bytes(b"a, b".to_vec()))
+        );
+    }
+
+    #[test]
+    fn test_format_value() {
+        let value_type = AbiType::parse("(int8,address,bytes2,string)[]").unwrap();
+        let text = "[(-128, 0x00000000000000000000000000000000000000ff, 0xabcd, \"a, b\")]";
+
+        let value = parse_value(&value_type, text).unwrap();
+        assert_eq!(format_value(&value_type, &value), text);
+    }
+}
@@ -0,0 +1,136 @@
+//gas costs from the yellow paper as of the Cancun hard fork, for the parts of the cost that depend on the inputs of an
+//instruction. The static cost of each instruction is in the opcode table.
+
+use primitive_types::U256;
+
+pub const TRANSACTION_GAS: u64 = 21000;
+pub const CREATE_GAS: u64 = 32000;
+pub const CALLDATA_ZERO_BYTE_GAS: u64 = 4;
+pub const CALLDATA_NON_ZERO_BYTE_GAS: u64 = 16;
+pub const INIT_CODE_WORD_GAS: u64 = 2;
+pub const CODE_DEPOSIT_BYTE_GAS: u64 = 200;
+
+pub const WARM_ACCESS_GAS: u64 = 100;
+pub const COLD_ACCOUNT_ACCESS_GAS: u64 = 2600;
+pub const COLD_SLOAD_GAS: u64 = 2100;
+
+pub const SSTORE_SET_GAS: u64 = 20000;
+pub const SSTORE_RESET_GAS: u64 = 2900;
+pub const SSTORE_CLEAR_REFUND: i64 = 4800;
+//SSTORE fails if less than this much gas is left, so that it can not run with the stipend of a value transfer
+pub const SSTORE_MINIMUM_GAS: u64 = 2300;
+
+pub const CALL_VALUE_GAS: u64 = 9000;
+pub const CALL_STIPEND: u64 = 2300;
+pub const NEW_ACCOUNT_GAS: u64 = 25000;
+
+pub const COPY_WORD_GAS: u64 = 3;
+pub const KECCAK256_WORD_GAS: u64 = 6;
+pub const EXP_BYTE_GAS: u64 = 50;
+pub const LOG_BYTE_GAS: u64 = 8;
+
+pub const MAX_CODE_SIZE: usize = 24576;
+pub const MAX_INIT_CODE_SIZE: usize = 2 * MAX_CODE_SIZE;
+
+///Gets the number of 32 byte words needed to hold a number of bytes
+pub fn words(length: u64) -> u64 {
+    length.div_ceil(32)
+}
+
+///Gets the total cost of memory of a given size in words
+pub fn memory_gas(words: u64) -> u64 {
+    3 * words + words * words / 512
+}
+
+///Gets the gas charged for the calldata of a transaction
+pub fn calldata_gas(data: &[u8]) -> u64 {
+    data.iter()
+        .map(|byte| {
+            if *byte == 0 {
+                CALLDATA_ZERO_BYTE_GAS
+            } else {
+                CALLDATA_NON_ZERO_BYTE_GAS
+            }
+        })
+        .sum()
+}
+
+///Gets the gas charged for a transaction before any code runs
+pub fn intrinsic_gas(data: &[u8], is_create: bool) -> u64 {
+    if is_create {
+        TRANSACTION_GAS
+            + CREATE_GAS
+            + calldata_gas(data)
+            + INIT_CODE_WORD_GAS * words(data.len() as u64)
+    } else {
+        TRANSACTION_GAS + calldata_gas(data)
+    }
+}
+
+///Gets the gas and the change in the refund for an SSTORE to a warm slot, given the value of the slot at the start of
+///the transaction, its current value and the new value
+pub fn sstore_gas(original: U256, current: U256, new: U256) -> (u64, i64) {
+    if current == new {
+        return (WARM_ACCESS_GAS, 0);
+    }
+
+    //the first write to the slot in the transaction
+    if original == current {
+        if original.is_zero() {
+            return (SSTORE_SET_GAS, 0);
+        }
+
+        let refund = if new.is_zero() {
+            SSTORE_CLEAR_REFUND
+        } else {
+            0
+        };
+        return (SSTORE_RESET_GAS, refund);
+    }
+
+    //the slot was already written to, so only the refund changes
+    let mut refund = 0;
+    if !original.is_zero() {
+        if current.is_zero() {
+            refund -= SSTORE_CLEAR_REFUND;
+        } else if new.is_zero() {
+            refund += SSTORE_CLEAR_REFUND;
+        }
+    }
+
+    if original == new {
+        refund += if original.is_zero() {
+            (SSTORE_SET_GAS - WARM_ACCESS_GAS) as i64
+        } else {
+            (SSTORE_RESET_GAS - WARM_ACCESS_GAS) as i64
+        };
+    }
+
+    (WARM_ACCESS_GAS, refund)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_gas() {
+        assert_eq!(memory_gas(0), 0);
+        assert_eq!(memory_gas(1), 3);
+        assert_eq!(memory_gas(1024), 3 * 1024 + 2048);
+    }
+
+    #[test]
+    fn test_sstore_gas() {
+        let (zero, one, two) = (U256::zero(), U256::one(), U256::from(2));
+
+        assert_eq!(sstore_gas(zero, zero, zero), (100, 0));
+        assert_eq!(sstore_gas(zero, zero, one), (20000, 0));
+        assert_eq!(sstore_gas(one, one, zero), (2900, 4800));
+        assert_eq!(sstore_gas(one, one, two), (2900, 0));
+        //set and then cleared in the same transaction
+        assert_eq!(sstore_gas(zero, one, zero), (100, 19900));
+        //cleared and then restored
+        assert_eq!(sstore_gas(one, zero, one), (100, -4800 + 2800));
+    }
+}
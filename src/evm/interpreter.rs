@@ -0,0 +1,1490 @@
+use crate::compiler::opcodes::{opcode_info, push_size};
+use crate::evm::gas;
+use crate::evm::state::{
+    address_to_u256, create2_address, create_address, u256_to_address, u256_to_bytes, Address,
+    WorldState,
+};
+use crate::utils::keccak::keccak256;
+use primitive_types::{U256, U512};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+pub const MAX_STACK_SIZE: usize = 1024;
+pub const MAX_CALL_DEPTH: usize = 1024;
+pub const DEFAULT_GAS_LIMIT: u64 = 30_000_000;
+
+//precompiles live at the lowest addresses. Only the identity precompile is implemented, the others behave like
+//accounts without code.
+const PRECOMPILE_COUNT: u64 = 10;
+const IDENTITY_PRECOMPILE: u64 = 4;
+
+///The block that transactions run in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockEnv {
+    pub number: U256,
+    pub timestamp: U256,
+    pub coinbase: Address,
+    pub gas_limit: U256,
+    pub base_fee: U256,
+    pub chain_id: U256,
+    //the value of PREVRANDAO, which replaced DIFFICULTY
+    pub prevrandao: U256,
+    pub blob_base_fee: U256,
+}
+
+impl Default for BlockEnv {
+    fn default() -> BlockEnv {
+        BlockEnv {
+            number: U256::one(),
+            timestamp: U256::one(),
+            coinbase: Address::zero(),
+            gas_limit: U256::from(DEFAULT_GAS_LIMIT),
+            base_fee: U256::zero(),
+            chain_id: U256::one(),
+            prevrandao: U256::zero(),
+            blob_base_fee: U256::one(),
+        }
+    }
+}
+
+///A transaction that calls an account or creates a contract
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    pub caller: Address,
+    //the account to call, or None to create a contract with the data as its init code
+    pub to: Option<Address>,
+    pub value: U256,
+    pub data: Vec<u8>,
+    pub gas_limit: u64,
+    //only read by GASPRICE, the gas is not paid for from the caller balance
+    pub gas_price: U256,
+}
+
+impl Transaction {
+    pub fn new(caller: Address, to: Option<Address>, value: U256, data: Vec<u8>) -> Transaction {
+        Transaction {
+            caller,
+            to,
+            value,
+            data,
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+        }
+    }
+}
+
+///A log emitted by LOG0 to LOG4
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Log {
+    pub address: Address,
+    pub topics: Vec<U256>,
+    pub data: Vec<u8>,
+}
+
+///Why execution stopped with an error, which reverts the state changes and uses all the gas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltReason {
+    OutOfGas,
+    StackUnderflow,
+    StackOverflow,
+    InvalidOpcode(u8),
+    //a jump to an offset that is not a JUMPDEST
+    InvalidJump(usize),
+    //a state change inside a STATICCALL
+    WriteInStaticCall,
+    //RETURNDATACOPY reading past the end of the return data
+    ReturnDataOutOfBounds,
+    //the caller can not afford the value of the transaction
+    InsufficientBalance,
+    //a contract is created at an address that already has code or a nonce
+    CreateCollision,
+    //the deployed code is larger than 24576 bytes
+    CodeSizeLimit,
+    //the deployed code starts with 0xEF, which is reserved
+    InvalidCode,
+}
+
+impl fmt::Display for HaltReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HaltReason::OutOfGas => write!(f, "out of gas"),
+            HaltReason::StackUnderflow => write!(f, "stack underflow"),
+            HaltReason::StackOverflow => write!(f, "stack overflow"),
+            HaltReason::InvalidOpcode(opcode) => write!(f, "invalid opcode 0x{:02x}", opcode),
+            HaltReason::InvalidJump(destination) => {
+                write!(f, "invalid jump destination 0x{:x}", destination)
+            }
+            HaltReason::WriteInStaticCall => write!(f, "state change in a static call"),
+            HaltReason::ReturnDataOutOfBounds => write!(f, "return data out of bounds"),
+            HaltReason::InsufficientBalance => write!(f, "insufficient balance"),
+            HaltReason::CreateCollision => write!(f, "contract address collision"),
+            HaltReason::CodeSizeLimit => write!(f, "contract code size limit exceeded"),
+            HaltReason::InvalidCode => write!(f, "contract code starts with 0xef"),
+        }
+    }
+}
+
+///How a call or transaction ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    //STOP, RETURN or SELFDESTRUCT, or running past the end of the code
+    Success,
+    Revert,
+    Halt(HaltReason),
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExitStatus::Success => write!(f, "success"),
+            ExitStatus::Revert => write!(f, "revert"),
+            ExitStatus::Halt(halt_reason) => write!(f, "{}", halt_reason),
+        }
+    }
+}
+
+///The outcome of a transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionResult {
+    pub status: ExitStatus,
+    //the return or revert data, or the deployed code when a contract is created
+    pub output: Vec<u8>,
+    //the gas used after refunds, including the intrinsic gas of the transaction
+    pub gas_used: u64,
+    //the logs emitted, which are only kept if the transaction succeeds
+    pub logs: Vec<Log>,
+    //the address of the created contract, if the transaction created one
+    pub created_address: Option<Address>,
+}
+
+impl ExecutionResult {
+    pub fn is_success(&self) -> bool {
+        self.status == ExitStatus::Success
+    }
+
+    fn halted(halt_reason: HaltReason, gas_used: u64) -> ExecutionResult {
+        ExecutionResult {
+            status: ExitStatus::Halt(halt_reason),
+            output: vec![],
+            gas_used,
+            logs: vec![],
+            created_address: None,
+        }
+    }
+}
+
+///An instruction that ran, passed to the tracer after it runs
+#[derive(Debug, Clone, Copy)]
+pub struct Step<'a> {
+    //the call depth, which is 0 for the code called by the transaction
+    pub depth: usize,
+    //the account the code runs as
+    pub address: Address,
+    //the code being run, which is the init code while a contract is created
+    pub code: &'a [u8],
+    pub is_create: bool,
+    pub pc: usize,
+    pub opcode: u8,
+    //the values the instruction took from the stack, from the top down
+    pub inputs: &'a [U256],
+    //the gas used by the instruction, not counting the gas used by the calls or contract creations it made
+    pub gas_cost: u64,
+    pub gas_left: u64,
+}
+
+///Observes every instruction that runs, to collect coverage, gas profiles or traces
+pub trait Tracer {
+    fn step(&mut self, step: &Step);
+}
+
+///A tracer that ignores every instruction
+pub struct NoTracer;
+
+impl Tracer for NoTracer {
+    fn step(&mut self, _step: &Step) {}
+}
+
+///Runs transactions against a world state, with the rules of the Cancun hard fork
+pub struct Evm {
+    pub state: WorldState,
+    pub block: BlockEnv,
+    //the state of the transaction being run
+    origin: Address,
+    gas_price: U256,
+    substate: Substate,
+    //the value of each written storage slot at the start of the transaction, which the cost of SSTORE depends on
+    original_storage: HashMap<(Address, U256), U256>,
+}
+
+//the parts of the transaction state that are reverted along with the world state when a call fails
+#[derive(Debug, Clone, Default)]
+struct Substate {
+    accessed_addresses: HashSet<Address>,
+    accessed_storage: HashSet<(Address, U256)>,
+    transient_storage: HashMap<(Address, U256), U256>,
+    //SELFDESTRUCT only deletes contracts created in the same transaction
+    created_contracts: HashSet<Address>,
+    destroyed_contracts: HashSet<Address>,
+    logs: Vec<Log>,
+    refund: i64,
+}
+
+//a call to the code of an account
+struct Message {
+    caller: Address,
+    //the account whose storage and balance the code uses
+    address: Address,
+    //the account the code is loaded from, which is different from the address for DELEGATECALL and CALLCODE
+    code_address: Address,
+    value: U256,
+    //false for DELEGATECALL, which keeps the value of its caller without transferring it
+    transfers_value: bool,
+    data: Vec<u8>,
+    gas: u64,
+    is_static: bool,
+    depth: usize,
+}
+
+//the code being run and the context it runs in
+struct Frame {
+    caller: Address,
+    address: Address,
+    value: U256,
+    data: Vec<u8>,
+    code: Vec<u8>,
+    gas: u64,
+    is_static: bool,
+    is_create: bool,
+    depth: usize,
+}
+
+struct FrameResult {
+    status: ExitStatus,
+    output: Vec<u8>,
+    gas_left: u64,
+}
+
+impl FrameResult {
+    fn new(status: ExitStatus, output: Vec<u8>, gas_left: u64) -> FrameResult {
+        FrameResult {
+            status,
+            output,
+            gas_left,
+        }
+    }
+
+    fn halted(halt_reason: HaltReason) -> FrameResult {
+        FrameResult::new(ExitStatus::Halt(halt_reason), vec![], 0)
+    }
+}
+
+//the stack, memory and gas of a running frame
+struct Machine {
+    pc: usize,
+    stack: Vec<U256>,
+    memory: Vec<u8>,
+    gas: u64,
+    return_data: Vec<u8>,
+    //the gas used by a call or contract creation made by the current instruction
+    child_gas_used: u64,
+}
+
+impl Machine {
+    fn pop(&mut self) -> U256 {
+        //the stack size is checked before each instruction runs
+        self.stack.pop().unwrap_or_default()
+    }
+
+    fn push(&mut self, value: U256) {
+        self.stack.push(value);
+    }
+
+    fn charge(&mut self, gas: u64) -> Result<(), HaltReason> {
+        if gas > self.gas {
+            self.gas = 0;
+            return Err(HaltReason::OutOfGas);
+        }
+
+        self.gas -= gas;
+        Ok(())
+    }
+
+    ///Grows the memory to hold the range and charges for the expansion, returning the range as offsets
+    fn expand_memory(&mut self, offset: U256, length: U256) -> Result<(usize, usize), HaltReason> {
+        if length.is_zero() {
+            return Ok((0, 0));
+        }
+
+        //ranges this large could never be paid for
+        if offset > U256::from(u32::MAX) || length > U256::from(u32::MAX) {
+            return Err(HaltReason::OutOfGas);
+        }
+
+        let (offset, length) = (offset.as_usize(), length.as_usize());
+        let current_words = gas::words(self.memory.len() as u64);
+        let new_words = gas::words((offset + length) as u64);
+
+        if new_words > current_words {
+            self.charge(gas::memory_gas(new_words) - gas::memory_gas(current_words))?;
+            self.memory.resize(new_words as usize * 32, 0);
+        }
+
+        Ok((offset, length))
+    }
+
+    fn read_memory(&self, offset: usize, length: usize) -> Vec<u8> {
+        self.memory[offset..offset + length].to_vec()
+    }
+
+    fn write_memory(&mut self, offset: usize, data: &[u8]) {
+        self.memory[offset..offset + data.len()].copy_from_slice(data);
+    }
+}
+
+impl Evm {
+    pub fn new(state: WorldState, block: BlockEnv) -> Evm {
+        Evm {
+            state,
+            block,
+            origin: Address::zero(),
+            gas_price: U256::zero(),
+            substate: Substate::default(),
+            original_storage: HashMap::new(),
+        }
+    }
+
+    ///Runs a transaction, keeping its state changes if it succeeds
+    pub fn transact(&mut self, transaction: &Transaction) -> ExecutionResult {
+        self.transact_with_tracer(transaction, &mut NoTracer)
+    }
+
+    ///Runs a transaction, passing every instruction that runs to the tracer
+    pub fn transact_with_tracer(
+        &mut self,
+        transaction: &Transaction,
+        tracer: &mut dyn Tracer,
+    ) -> ExecutionResult {
+        let is_create = transaction.to.is_none();
+        let intrinsic_gas = gas::intrinsic_gas(&transaction.data, is_create);
+
+        if intrinsic_gas > transaction.gas_limit
+            || (is_create && transaction.data.len() > gas::MAX_INIT_CODE_SIZE)
+        {
+            return ExecutionResult::halted(HaltReason::OutOfGas, transaction.gas_limit);
+        }
+
+        if self.state.balance(&transaction.caller) < transaction.value {
+            return ExecutionResult::halted(HaltReason::InsufficientBalance, 0);
+        }
+
+        self.origin = transaction.caller;
+        self.gas_price = transaction.gas_price;
+        self.substate = Substate::default();
+        self.original_storage.clear();
+
+        //the caller, the coinbase and the precompiles start out warm
+        self.substate.accessed_addresses.insert(transaction.caller);
+        self.substate.accessed_addresses.insert(self.block.coinbase);
+        for precompile in 1..=PRECOMPILE_COUNT {
+            self.substate
+                .accessed_addresses
+                .insert(Address::from_low_u64_be(precompile));
+        }
+
+        let nonce = self.state.nonce(&transaction.caller);
+        self.state.account_mut(&transaction.caller).nonce += 1;
+        let gas = transaction.gas_limit - intrinsic_gas;
+
+        let (result, created_address) = match transaction.to {
+            Some(to) => {
+                self.substate.accessed_addresses.insert(to);
+
+                let message = Message {
+                    caller: transaction.caller,
+                    address: to,
+                    code_address: to,
+                    value: transaction.value,
+                    transfers_value: true,
+                    data: transaction.data.clone(),
+                    gas,
+                    is_static: false,
+                    depth: 0,
+                };
+                (self.call(message, tracer), None)
+            }
+            None => {
+                let address = create_address(&transaction.caller, nonce);
+                let result = self.create(
+                    transaction.caller,
+                    address,
+                    transaction.value,
+                    transaction.data.clone(),
+                    gas,
+                    0,
+                    tracer,
+                );
+                (result, Some(address))
+            }
+        };
+
+        let is_success = result.status == ExitStatus::Success;
+
+        //refunds are capped at a fifth of the gas used
+        let mut gas_used = transaction.gas_limit - result.gas_left;
+        gas_used -= (self.substate.refund.max(0) as u64).min(gas_used / 5);
+
+        if is_success {
+            for destroyed_contract in self.substate.destroyed_contracts.iter() {
+                self.state.accounts.remove(destroyed_contract);
+            }
+        }
+
+        ExecutionResult {
+            status: result.status,
+            output: result.output,
+            gas_used,
+            logs: std::mem::take(&mut self.substate.logs),
+            created_address: created_address.filter(|_| is_success),
+        }
+    }
+
+    fn call(&mut self, message: Message, tracer: &mut dyn Tracer) -> FrameResult {
+        let checkpoint = (self.state.clone(), self.substate.clone());
+
+        //the balance was checked before the call
+        if message.transfers_value {
+            self.state
+                .transfer(&message.caller, &message.address, message.value);
+        }
+
+        let result = if message.code_address == Address::from_low_u64_be(IDENTITY_PRECOMPILE) {
+            let cost = 15 + 3 * gas::words(message.data.len() as u64);
+            if cost > message.gas {
+                FrameResult::halted(HaltReason::OutOfGas)
+            } else {
+                FrameResult::new(ExitStatus::Success, message.data, message.gas - cost)
+            }
+        } else {
+            let code = self.state.code(&message.code_address).to_vec();
+
+            if code.is_empty() {
+                FrameResult::new(ExitStatus::Success, vec![], message.gas)
+            } else {
+                let frame = Frame {
+                    caller: message.caller,
+                    address: message.address,
+                    value: message.value,
+                    data: message.data,
+                    code,
+                    gas: message.gas,
+                    is_static: message.is_static,
+                    is_create: false,
+                    depth: message.depth,
+                };
+                self.execute(&frame, tracer)
+            }
+        };
+
+        if result.status != ExitStatus::Success {
+            (self.state, self.substate) = checkpoint;
+        }
+
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create(
+        &mut self,
+        caller: Address,
+        address: Address,
+        value: U256,
+        init_code: Vec<u8>,
+        gas: u64,
+        depth: usize,
+        tracer: &mut dyn Tracer,
+    ) -> FrameResult {
+        self.substate.accessed_addresses.insert(address);
+
+        if self
+            .state
+            .account(&address)
+            .is_some_and(|account| account.nonce != 0 || !account.code.is_empty())
+        {
+            return FrameResult::halted(HaltReason::CreateCollision);
+        }
+
+        let checkpoint = (self.state.clone(), self.substate.clone());
+
+        //the balance was checked before the contract was created
+        self.state.transfer(&caller, &address, value);
+        self.state.account_mut(&address).nonce = 1;
+        self.substate.created_contracts.insert(address);
+
+        let frame = Frame {
+            caller,
+            address,
+            value,
+            data: vec![],
+            code: init_code,
+            gas,
+            is_static: false,
+            is_create: true,
+            depth,
+        };
+        let mut result = self.execute(&frame, tracer);
+
+        if result.status == ExitStatus::Success {
+            let code_deposit_gas = gas::CODE_DEPOSIT_BYTE_GAS * result.output.len() as u64;
+
+            if result.output.len() > gas::MAX_CODE_SIZE {
+                result = FrameResult::halted(HaltReason::CodeSizeLimit);
+            } else if result.output.first() == Some(&0xEF) {
+                result = FrameResult::halted(HaltReason::InvalidCode);
+            } else if code_deposit_gas > result.gas_left {
+                result = FrameResult::halted(HaltReason::OutOfGas);
+            } else {
+                result.gas_left -= code_deposit_gas;
+                self.state.account_mut(&address).code = result.output.clone();
+            }
+        }
+
+        if result.status != ExitStatus::Success {
+            (self.state, self.substate) = checkpoint;
+        }
+
+        result
+    }
+
+    fn execute(&mut self, frame: &Frame, tracer: &mut dyn Tracer) -> FrameResult {
+        let jump_destinations = jump_destinations(&frame.code);
+        let mut machine = Machine {
+            pc: 0,
+            stack: Vec::with_capacity(MAX_STACK_SIZE),
+            memory: vec![],
+            gas: frame.gas,
+            return_data: vec![],
+            child_gas_used: 0,
+        };
+
+        loop {
+            let pc = machine.pc;
+            //running past the end of the code stops
+            let opcode = frame.code.get(pc).copied().unwrap_or(0x00);
+            let gas_before = machine.gas;
+            machine.child_gas_used = 0;
+
+            let input_count = opcode_info(opcode).map_or(0, |opcode_info| opcode_info.stack_inputs);
+            let inputs: Vec<U256> = machine
+                .stack
+                .iter()
+                .rev()
+                .take(input_count)
+                .copied()
+                .collect();
+
+            let outcome = self.step(frame, &mut machine, opcode, &jump_destinations, tracer);
+
+            let outcome = match outcome {
+                Ok(outcome) => outcome,
+                Err(halt_reason) => {
+                    machine.gas = 0;
+                    Some(FrameResult::halted(halt_reason))
+                }
+            };
+
+            tracer.step(&Step {
+                depth: frame.depth,
+                address: frame.address,
+                code: &frame.code,
+                is_create: frame.is_create,
+                pc,
+                opcode,
+                inputs: &inputs,
+                gas_cost: (gas_before - machine.gas).saturating_sub(machine.child_gas_used),
+                gas_left: machine.gas,
+            });
+
+            if let Some(mut result) = outcome {
+                if !matches!(result.status, ExitStatus::Halt(_)) {
+                    result.gas_left = machine.gas;
+                }
+                return result;
+            }
+        }
+    }
+
+    //runs a single instruction, returning the result of the frame if the instruction ends it
+    fn step(
+        &mut self,
+        frame: &Frame,
+        machine: &mut Machine,
+        opcode: u8,
+        jump_destinations: &[bool],
+        tracer: &mut dyn Tracer,
+    ) -> Result<Option<FrameResult>, HaltReason> {
+        let opcode_info = opcode_info(opcode).ok_or(HaltReason::InvalidOpcode(opcode))?;
+
+        if machine.stack.len() < opcode_info.stack_inputs {
+            return Err(HaltReason::StackUnderflow);
+        }
+        if machine.stack.len() - opcode_info.stack_inputs + opcode_info.stack_outputs
+            > MAX_STACK_SIZE
+        {
+            return Err(HaltReason::StackOverflow);
+        }
+
+        machine.charge(opcode_info.static_gas)?;
+        let mut next_pc = machine.pc + 1 + push_size(opcode);
+
+        match opcode {
+            //STOP
+            0x00 => return Ok(Some(FrameResult::new(ExitStatus::Success, vec![], 0))),
+            //EXP also charges for the size of the exponent
+            0x0A => {
+                let (base, exponent) = (machine.pop(), machine.pop());
+                machine.charge(gas::EXP_BYTE_GAS * exponent.bits().div_ceil(8) as u64)?;
+                machine.push(arithmetic(opcode, &[base, exponent]).unwrap_or_default());
+            }
+            //arithmetic, comparison and bitwise instructions only depend on their inputs
+            0x01..=0x1D => {
+                let inputs: Vec<U256> = (0..opcode_info.stack_inputs)
+                    .map(|_| machine.pop())
+                    .collect();
+                machine.push(arithmetic(opcode, &inputs).unwrap_or_default());
+            }
+            //KECCAK256
+            0x20 => {
+                let (offset, length) = (machine.pop(), machine.pop());
+                let (offset, length) = machine.expand_memory(offset, length)?;
+                machine.charge(gas::KECCAK256_WORD_GAS * gas::words(length as u64))?;
+
+                let hash = keccak256(&machine.memory[offset..offset + length]);
+                machine.push(U256::from_big_endian(&hash));
+            }
+            //ADDRESS
+            0x30 => machine.push(address_to_u256(&frame.address)),
+            //BALANCE
+            0x31 => {
+                let address = u256_to_address(machine.pop());
+                machine.charge(self.access_address(address))?;
+                machine.push(self.state.balance(&address));
+            }
+            //ORIGIN
+            0x32 => machine.push(address_to_u256(&self.origin)),
+            //CALLER
+            0x33 => machine.push(address_to_u256(&frame.caller)),
+            //CALLVALUE
+            0x34 => machine.push(frame.value),
+            //CALLDATALOAD
+            0x35 => {
+                let offset = machine.pop();
+                machine.push(U256::from_big_endian(&copy_padded(&frame.data, offset, 32)));
+            }
+            //CALLDATASIZE
+            0x36 => machine.push(U256::from(frame.data.len())),
+            //CALLDATACOPY and CODECOPY
+            0x37 | 0x39 => {
+                let (memory_offset, offset, length) = (machine.pop(), machine.pop(), machine.pop());
+                let (memory_offset, length) = machine.expand_memory(memory_offset, length)?;
+                machine.charge(gas::COPY_WORD_GAS * gas::words(length as u64))?;
+
+                let source = if opcode == 0x37 {
+                    &frame.data
+                } else {
+                    &frame.code
+                };
+                machine.write_memory(memory_offset, &copy_padded(source, offset, length));
+            }
+            //CODESIZE
+            0x38 => machine.push(U256::from(frame.code.len())),
+            //GASPRICE
+            0x3A => machine.push(self.gas_price),
+            //EXTCODESIZE
+            0x3B => {
+                let address = u256_to_address(machine.pop());
+                machine.charge(self.access_address(address))?;
+                machine.push(U256::from(self.state.code(&address).len()));
+            }
+            //EXTCODECOPY
+            0x3C => {
+                let address = u256_to_address(machine.pop());
+                let (memory_offset, offset, length) = (machine.pop(), machine.pop(), machine.pop());
+                let (memory_offset, length) = machine.expand_memory(memory_offset, length)?;
+                machine.charge(
+                    gas::COPY_WORD_GAS * gas::words(length as u64) + self.access_address(address),
+                )?;
+
+                let code = copy_padded(self.state.code(&address), offset, length);
+                machine.write_memory(memory_offset, &code);
+            }
+            //RETURNDATASIZE
+            0x3D => machine.push(U256::from(machine.return_data.len())),
+            //RETURNDATACOPY
+            0x3E => {
+                let (memory_offset, offset, length) = (machine.pop(), machine.pop(), machine.pop());
+
+                if offset.overflowing_add(length).0 > U256::from(machine.return_data.len())
+                    || offset.overflowing_add(length).1
+                {
+                    return Err(HaltReason::ReturnDataOutOfBounds);
+                }
+
+                let (memory_offset, length) = machine.expand_memory(memory_offset, length)?;
+                machine.charge(gas::COPY_WORD_GAS * gas::words(length as u64))?;
+
+                let data = copy_padded(&machine.return_data, offset, length);
+                machine.write_memory(memory_offset, &data);
+            }
+            //EXTCODEHASH, which is zero for accounts that do not exist
+            0x3F => {
+                let address = u256_to_address(machine.pop());
+                machine.charge(self.access_address(address))?;
+
+                let hash = if self.state.is_empty(&address) {
+                    U256::zero()
+                } else {
+                    U256::from_big_endian(&keccak256(self.state.code(&address)))
+                };
+                machine.push(hash);
+            }
+            //BLOCKHASH, which is a hash of the block number for the last 256 blocks
+            0x40 => {
+                let number = machine.pop();
+
+                let hash = if number < self.block.number
+                    && self.block.number - number <= U256::from(256)
+                {
+                    U256::from_big_endian(&keccak256(&u256_to_bytes(number)))
+                } else {
+                    U256::zero()
+                };
+                machine.push(hash);
+            }
+            //COINBASE
+            0x41 => machine.push(address_to_u256(&self.block.coinbase)),
+            //TIMESTAMP
+            0x42 => machine.push(self.block.timestamp),
+            //NUMBER
+            0x43 => machine.push(self.block.number),
+            //PREVRANDAO
+            0x44 => machine.push(self.block.prevrandao),
+            //GASLIMIT
+            0x45 => machine.push(self.block.gas_limit),
+            //CHAINID
+            0x46 => machine.push(self.block.chain_id),
+            //SELFBALANCE
+            0x47 => machine.push(self.state.balance(&frame.address)),
+            //BASEFEE
+            0x48 => machine.push(self.block.base_fee),
+            //BLOBHASH, transactions do not carry blobs
+            0x49 => {
+                machine.pop();
+                machine.push(U256::zero());
+            }
+            //BLOBBASEFEE
+            0x4A => machine.push(self.block.blob_base_fee),
+            //POP
+            0x50 => {
+                machine.pop();
+            }
+            //MLOAD
+            0x51 => {
+                let offset = machine.pop();
+                let (offset, _) = machine.expand_memory(offset, U256::from(32))?;
+                let value = U256::from_big_endian(&machine.read_memory(offset, 32));
+                machine.push(value);
+            }
+            //MSTORE
+            0x52 => {
+                let (offset, value) = (machine.pop(), machine.pop());
+                let (offset, _) = machine.expand_memory(offset, U256::from(32))?;
+                machine.write_memory(offset, &u256_to_bytes(value));
+            }
+            //MSTORE8
+            0x53 => {
+                let (offset, value) = (machine.pop(), machine.pop());
+                let (offset, _) = machine.expand_memory(offset, U256::one())?;
+                machine.write_memory(offset, &[value.byte(0)]);
+            }
+            //SLOAD
+            0x54 => {
+                let key = machine.pop();
+                machine.charge(self.access_storage(frame.address, key))?;
+                machine.push(self.state.storage(&frame.address, &key));
+            }
+            //SSTORE
+            0x55 => {
+                if frame.is_static {
+                    return Err(HaltReason::WriteInStaticCall);
+                }
+                if machine.gas + opcode_info.static_gas <= gas::SSTORE_MINIMUM_GAS {
+                    return Err(HaltReason::OutOfGas);
+                }
+
+                let (key, value) = (machine.pop(), machine.pop());
+                //a cold SSTORE is charged the whole cost of a cold access, unlike SLOAD
+                let cold_gas = if self.access_storage(frame.address, key) > 0 {
+                    gas::COLD_SLOAD_GAS
+                } else {
+                    0
+                };
+
+                let current = self.state.storage(&frame.address, &key);
+                let original = *self
+                    .original_storage
+                    .entry((frame.address, key))
+                    .or_insert(current);
+                let (sstore_gas, refund) = gas::sstore_gas(original, current, value);
+
+                machine.charge(sstore_gas - opcode_info.static_gas + cold_gas)?;
+                self.substate.refund += refund;
+                self.state.set_storage(&frame.address, key, value);
+            }
+            //JUMP
+            0x56 => {
+                let destination = machine.pop();
+                next_pc = jump_destination(destination, jump_destinations)?;
+            }
+            //JUMPI
+            0x57 => {
+                let (destination, condition) = (machine.pop(), machine.pop());
+                if !condition.is_zero() {
+                    next_pc = jump_destination(destination, jump_destinations)?;
+                }
+            }
+            //PC
+            0x58 => machine.push(U256::from(machine.pc)),
+            //MSIZE
+            0x59 => machine.push(U256::from(machine.memory.len())),
+            //GAS
+            0x5A => machine.push(U256::from(machine.gas)),
+            //JUMPDEST
+            0x5B => {}
+            //TLOAD
+            0x5C => {
+                let key = machine.pop();
+                let value = self
+                    .substate
+                    .transient_storage
+                    .get(&(frame.address, key))
+                    .copied()
+                    .unwrap_or_default();
+                machine.push(value);
+            }
+            //TSTORE
+            0x5D => {
+                if frame.is_static {
+                    return Err(HaltReason::WriteInStaticCall);
+                }
+
+                let (key, value) = (machine.pop(), machine.pop());
+                self.substate
+                    .transient_storage
+                    .insert((frame.address, key), value);
+            }
+            //MCOPY
+            0x5E => {
+                let (destination, source, length) = (machine.pop(), machine.pop(), machine.pop());
+                let (source, length) = machine.expand_memory(source, length)?;
+                let (destination, length) =
+                    machine.expand_memory(destination, U256::from(length))?;
+                machine.charge(gas::COPY_WORD_GAS * gas::words(length as u64))?;
+
+                machine
+                    .memory
+                    .copy_within(source..source + length, destination);
+            }
+            //PUSH0 to PUSH32, where push data past the end of the code is zero
+            0x5F..=0x7F => {
+                let size = push_size(opcode);
+                let value = copy_padded(&frame.code, U256::from(machine.pc + 1), size);
+                machine.push(U256::from_big_endian(&value));
+            }
+            //DUP1 to DUP16
+            0x80..=0x8F => {
+                let position = (opcode - 0x7F) as usize;
+                let value = machine.stack[machine.stack.len() - position];
+                machine.push(value);
+            }
+            //SWAP1 to SWAP16
+            0x90..=0x9F => {
+                let position = (opcode - 0x8F) as usize;
+                let top = machine.stack.len() - 1;
+                machine.stack.swap(top, top - position);
+            }
+            //LOG0 to LOG4
+            0xA0..=0xA4 => {
+                if frame.is_static {
+                    return Err(HaltReason::WriteInStaticCall);
+                }
+
+                let (offset, length) = (machine.pop(), machine.pop());
+                let topics: Vec<U256> = (0..opcode - 0xA0).map(|_| machine.pop()).collect();
+                let (offset, length) = machine.expand_memory(offset, length)?;
+                machine.charge(gas::LOG_BYTE_GAS * length as u64)?;
+
+                self.substate.logs.push(Log {
+                    address: frame.address,
+                    topics,
+                    data: machine.read_memory(offset, length),
+                });
+            }
+            //CREATE and CREATE2
+            0xF0 | 0xF5 => {
+                if frame.is_static {
+                    return Err(HaltReason::WriteInStaticCall);
+                }
+
+                let (value, offset, length) = (machine.pop(), machine.pop(), machine.pop());
+                let salt = if opcode == 0xF5 {
+                    machine.pop()
+                } else {
+                    U256::zero()
+                };
+
+                let (offset, length) = machine.expand_memory(offset, length)?;
+                if length > gas::MAX_INIT_CODE_SIZE {
+                    return Err(HaltReason::OutOfGas);
+                }
+
+                let mut create_gas = gas::INIT_CODE_WORD_GAS * gas::words(length as u64);
+                if opcode == 0xF5 {
+                    create_gas += gas::KECCAK256_WORD_GAS * gas::words(length as u64);
+                }
+                machine.charge(create_gas)?;
+                machine.return_data.clear();
+
+                if frame.depth + 1 > MAX_CALL_DEPTH || self.state.balance(&frame.address) < value {
+                    machine.push(U256::zero());
+                } else {
+                    let init_code = machine.read_memory(offset, length);
+                    let address = if opcode == 0xF0 {
+                        create_address(&frame.address, self.state.nonce(&frame.address))
+                    } else {
+                        create2_address(&frame.address, salt, &init_code)
+                    };
+                    self.state.account_mut(&frame.address).nonce += 1;
+
+                    //all but one 64th of the gas is given to the init code
+                    let child_gas = machine.gas - machine.gas / 64;
+                    machine.charge(child_gas)?;
+
+                    let result = self.create(
+                        frame.address,
+                        address,
+                        value,
+                        init_code,
+                        child_gas,
+                        frame.depth + 1,
+                        tracer,
+                    );
+                    machine.gas += result.gas_left;
+                    machine.child_gas_used = child_gas - result.gas_left;
+
+                    match result.status {
+                        ExitStatus::Success => machine.push(address_to_u256(&address)),
+                        ExitStatus::Revert => {
+                            machine.return_data = result.output;
+                            machine.push(U256::zero());
+                        }
+                        ExitStatus::Halt(_) => machine.push(U256::zero()),
+                    }
+                }
+            }
+            //CALL, CALLCODE, DELEGATECALL and STATICCALL
+            0xF1 | 0xF2 | 0xF4 | 0xFA => {
+                let gas_limit = machine.pop();
+                let address = u256_to_address(machine.pop());
+                let value = if opcode == 0xF1 || opcode == 0xF2 {
+                    machine.pop()
+                } else {
+                    U256::zero()
+                };
+                let (input_offset, input_length) = (machine.pop(), machine.pop());
+                let (output_offset, output_length) = (machine.pop(), machine.pop());
+
+                if opcode == 0xF1 && frame.is_static && !value.is_zero() {
+                    return Err(HaltReason::WriteInStaticCall);
+                }
+
+                let (input_offset, input_length) =
+                    machine.expand_memory(input_offset, input_length)?;
+                let (output_offset, output_length) =
+                    machine.expand_memory(output_offset, output_length)?;
+
+                let mut call_gas = self.access_address(address);
+                if !value.is_zero() {
+                    call_gas += gas::CALL_VALUE_GAS;
+                    if opcode == 0xF1 && self.state.is_empty(&address) {
+                        call_gas += gas::NEW_ACCOUNT_GAS;
+                    }
+                }
+                machine.charge(call_gas)?;
+
+                //at most all but one 64th of the gas is given to the call
+                let available_gas = machine.gas - machine.gas / 64;
+                let mut child_gas = if gas_limit > U256::from(available_gas) {
+                    available_gas
+                } else {
+                    gas_limit.as_u64()
+                };
+                machine.charge(child_gas)?;
+                if !value.is_zero() {
+                    child_gas += gas::CALL_STIPEND;
+                }
+
+                machine.return_data.clear();
+
+                if frame.depth + 1 > MAX_CALL_DEPTH || self.state.balance(&frame.address) < value {
+                    machine.gas += child_gas;
+                    machine.push(U256::zero());
+                } else {
+                    let (caller, storage_address, call_value, transfers_value, is_static) =
+                        match opcode {
+                            0xF1 => (frame.address, address, value, true, frame.is_static),
+                            0xF2 => (frame.address, frame.address, value, true, frame.is_static),
+                            0xF4 => (
+                                frame.caller,
+                                frame.address,
+                                frame.value,
+                                false,
+                                frame.is_static,
+                            ),
+                            _ => (frame.address, address, U256::zero(), false, true),
+                        };
+
+                    let message = Message {
+                        caller,
+                        address: storage_address,
+                        code_address: address,
+                        value: call_value,
+                        transfers_value,
+                        data: machine.read_memory(input_offset, input_length),
+                        gas: child_gas,
+                        is_static,
+                        depth: frame.depth + 1,
+                    };
+                    let result = self.call(message, tracer);
+
+                    machine.gas += result.gas_left;
+                    machine.child_gas_used = child_gas.saturating_sub(result.gas_left);
+
+                    let copied_length = output_length.min(result.output.len());
+                    machine.write_memory(output_offset, &result.output[..copied_length]);
+                    machine.return_data = result.output;
+                    machine.push(bool_to_u256(result.status == ExitStatus::Success));
+                }
+            }
+            //RETURN and REVERT
+            0xF3 | 0xFD => {
+                let (offset, length) = (machine.pop(), machine.pop());
+                let (offset, length) = machine.expand_memory(offset, length)?;
+
+                let status = if opcode == 0xF3 {
+                    ExitStatus::Success
+                } else {
+                    ExitStatus::Revert
+                };
+                return Ok(Some(FrameResult::new(
+                    status,
+                    machine.read_memory(offset, length),
+                    0,
+                )));
+            }
+            //SELFDESTRUCT, which only deletes the contract if it was created in the same transaction
+            0xFF => {
+                if frame.is_static {
+                    return Err(HaltReason::WriteInStaticCall);
+                }
+
+                let beneficiary = u256_to_address(machine.pop());
+                let balance = self.state.balance(&frame.address);
+
+                let mut selfdestruct_gas = 0;
+                if self.substate.accessed_addresses.insert(beneficiary) {
+                    selfdestruct_gas += gas::COLD_ACCOUNT_ACCESS_GAS;
+                }
+                if !balance.is_zero() && self.state.is_empty(&beneficiary) {
+                    selfdestruct_gas += gas::NEW_ACCOUNT_GAS;
+                }
+                machine.charge(selfdestruct_gas)?;
+
+                let is_created = self.substate.created_contracts.contains(&frame.address);
+                if beneficiary != frame.address {
+                    self.state.transfer(&frame.address, &beneficiary, balance);
+                } else if is_created {
+                    //the balance is burned
+                    self.state.account_mut(&frame.address).balance = U256::zero();
+                }
+
+                if is_created {
+                    self.substate.destroyed_contracts.insert(frame.address);
+                }
+
+                return Ok(Some(FrameResult::new(ExitStatus::Success, vec![], 0)));
+            }
+            //INVALID, along with every opcode that is not in the table
+            _ => return Err(HaltReason::InvalidOpcode(opcode)),
+        }
+
+        machine.pc = next_pc;
+        Ok(None)
+    }
+
+    //marks an account as accessed, returning the extra gas charged if this is the first access in the transaction
+    fn access_address(&mut self, address: Address) -> u64 {
+        if self.substate.accessed_addresses.insert(address) {
+            gas::COLD_ACCOUNT_ACCESS_GAS - gas::WARM_ACCESS_GAS
+        } else {
+            0
+        }
+    }
+
+    //marks a storage slot as accessed, returning the extra gas charged if this is the first access in the transaction
+    fn access_storage(&mut self, address: Address, key: U256) -> u64 {
+        if self.substate.accessed_storage.insert((address, key)) {
+            gas::COLD_SLOAD_GAS - gas::WARM_ACCESS_GAS
+        } else {
+            0
+        }
+    }
+}
+
+///Finds the offsets of the JUMPDEST instructions in the code, skipping push data
+pub fn jump_destinations(code: &[u8]) -> Vec<bool> {
+    let mut jump_destinations = vec![false; code.len()];
+
+    let mut offset = 0;
+    while offset < code.len() {
+        if code[offset] == 0x5B {
+            jump_destinations[offset] = true;
+        }
+        offset += 1 + push_size(code[offset]);
+    }
+
+    jump_destinations
+}
+
+///Computes the result of an arithmetic, comparison or bitwise instruction, ADD through SAR, from its inputs with the
+///top of the stack first. Returns None for other instructions or if there are too few inputs.
+pub fn arithmetic(opcode: u8, inputs: &[U256]) -> Option<U256> {
+    let input_count = opcode_info(opcode)?.stack_inputs;
+    if !(0x01..=0x1D).contains(&opcode) || inputs.len() < input_count {
+        return None;
+    }
+    let (a, b) = (inputs[0], inputs.get(1).copied().unwrap_or_default());
+
+    let result = match opcode {
+        //ADD, MUL and SUB wrap around
+        0x01 => a.overflowing_add(b).0,
+        0x02 => a.overflowing_mul(b).0,
+        0x03 => a.overflowing_sub(b).0,
+        //DIV and MOD by zero are zero
+        0x04 => {
+            if b.is_zero() {
+                b
+            } else {
+                a / b
+            }
+        }
+        0x05 => signed_div(a, b),
+        0x06 => {
+            if b.is_zero() {
+                b
+            } else {
+                a % b
+            }
+        }
+        0x07 => signed_mod(a, b),
+        //ADDMOD and MULMOD do not wrap around before taking the modulus
+        0x08 | 0x09 => {
+            let n = inputs[2];
+            if n.is_zero() {
+                n
+            } else {
+                let full_result = if opcode == 0x08 {
+                    U512::from(a) + U512::from(b)
+                } else {
+                    a.full_mul(b)
+                };
+                U256::try_from(full_result % U512::from(n)).unwrap_or_default()
+            }
+        }
+        0x0A => a.overflowing_pow(b).0,
+        0x0B => sign_extend(a, b),
+        //LT, GT, SLT, SGT and EQ
+        0x10 => bool_to_u256(a < b),
+        0x11 => bool_to_u256(a > b),
+        0x12 => bool_to_u256(signed_less_than(a, b)),
+        0x13 => bool_to_u256(signed_less_than(b, a)),
+        0x14 => bool_to_u256(a == b),
+        0x15 => bool_to_u256(a.is_zero()),
+        0x16 => a & b,
+        0x17 => a | b,
+        0x18 => a ^ b,
+        0x19 => !a,
+        //BYTE, counting from the most significant byte
+        0x1A => {
+            if a < U256::from(32) {
+                U256::from(b.byte(31 - a.as_usize()))
+            } else {
+                U256::zero()
+            }
+        }
+        //SHL, SHR and SAR
+        _ => shift_value(opcode, a, b),
+    };
+
+    Some(result)
+}
+
+fn jump_destination(destination: U256, jump_destinations: &[bool]) -> Result<usize, HaltReason> {
+    if destination < U256::from(jump_destinations.len())
+        && jump_destinations[destination.as_usize()]
+    {
+        Ok(destination.as_usize())
+    } else if destination > U256::from(usize::MAX) {
+        Err(HaltReason::InvalidJump(usize::MAX))
+    } else {
+        Err(HaltReason::InvalidJump(destination.as_usize()))
+    }
+}
+
+//reads bytes from the source, with zeros past its end
+fn copy_padded(source: &[u8], offset: U256, length: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; length];
+
+    if offset < U256::from(source.len()) {
+        let offset = offset.as_usize();
+        let available = (source.len() - offset).min(length);
+        bytes[..available].copy_from_slice(&source[offset..offset + available]);
+    }
+
+    bytes
+}
+
+fn bool_to_u256(value: bool) -> U256 {
+    if value {
+        U256::one()
+    } else {
+        U256::zero()
+    }
+}
+
+//values are signed by interpreting them as two's complement
+fn is_negative(value: U256) -> bool {
+    value.bit(255)
+}
+
+fn negate(value: U256) -> U256 {
+    (!value).overflowing_add(U256::one()).0
+}
+
+fn absolute(value: U256) -> U256 {
+    if is_negative(value) {
+        negate(value)
+    } else {
+        value
+    }
+}
+
+fn signed_div(a: U256, b: U256) -> U256 {
+    if b.is_zero() {
+        return b;
+    }
+
+    let quotient = absolute(a) / absolute(b);
+    if is_negative(a) != is_negative(b) {
+        negate(quotient)
+    } else {
+        quotient
+    }
+}
+
+fn signed_mod(a: U256, b: U256) -> U256 {
+    if b.is_zero() {
+        return b;
+    }
+
+    //the result has the sign of the dividend
+    let remainder = absolute(a) % absolute(b);
+    if is_negative(a) {
+        negate(remainder)
+    } else {
+        remainder
+    }
+}
+
+fn signed_less_than(a: U256, b: U256) -> bool {
+    match (is_negative(a), is_negative(b)) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => a < b,
+    }
+}
+
+fn sign_extend(byte_index: U256, value: U256) -> U256 {
+    if byte_index >= U256::from(31) {
+        return value;
+    }
+
+    let sign_bit = byte_index.as_usize() * 8 + 7;
+    let mask = (U256::one() << sign_bit) - 1;
+
+    if value.bit(sign_bit) {
+        value | !mask
+    } else {
+        value & mask
+    }
+}
+
+fn shift_value(opcode: u8, shift: U256, value: U256) -> U256 {
+    let negative = is_negative(value);
+
+    if shift >= U256::from(256) {
+        return if opcode == 0x1D && negative {
+            U256::MAX
+        } else {
+            U256::zero()
+        };
+    }
+
+    let shift = shift.as_usize();
+    match opcode {
+        //SHL
+        0x1B => value << shift,
+        //SAR fills with the sign bit
+        0x1D if negative => !((!value) >> shift),
+        //SHR
+        _ => value >> shift,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile::decode_hex;
+    use crate::evm::state::Account;
+
+    const CALLER: u64 = 0xCA11E7;
+    const CONTRACT: u64 = 0xC0DE;
+
+    fn evm_with_contract(code: &str) -> Evm {
+        let mut state = WorldState::new();
+        state.accounts.insert(
+            Address::from_low_u64_be(CALLER),
+            Account::new(U256::from(1_000_000), vec![]),
+        );
+        state.accounts.insert(
+            Address::from_low_u64_be(CONTRACT),
+            Account::new(U256::zero(), decode_hex(code).unwrap()),
+        );
+        Evm::new(state, BlockEnv::default())
+    }
+
+    fn call_contract(evm: &mut Evm, data: Vec<u8>) -> ExecutionResult {
+        evm.transact(&Transaction::new(
+            Address::from_low_u64_be(CALLER),
+            Some(Address::from_low_u64_be(CONTRACT)),
+            U256::zero(),
+            data,
+        ))
+    }
+
+    #[test]
+    fn test_return_value() {
+        //returns 2 + 3
+        let mut evm = evm_with_contract("600360020160005260206000f3");
+        let result = call_contract(&mut evm, vec![]);
+
+        assert!(result.is_success());
+        assert_eq!(result.output, u256_to_bytes(U256::from(5)).to_vec());
+        //PUSH1, PUSH1, ADD, PUSH1, MSTORE with one word of memory, PUSH1, PUSH1 and RETURN
+        assert_eq!(result.gas_used, 21000 + 3 + 3 + 3 + 3 + 6 + 3 + 3);
+    }
+
+    #[test]
+    fn test_sstore_and_revert() {
+        //stores 1 in slot 0, then reverts if the calldata is not empty
+        let mut evm = evm_with_contract("60016000553615600f5760006000fd5b00");
+
+        let result = call_contract(&mut evm, vec![0x01]);
+        assert_eq!(result.status, ExitStatus::Revert);
+        assert!(evm
+            .state
+            .storage(&Address::from_low_u64_be(CONTRACT), &U256::zero())
+            .is_zero());
+
+        let result = call_contract(&mut evm, vec![]);
+        assert!(result.is_success());
+        assert_eq!(
+            evm.state
+                .storage(&Address::from_low_u64_be(CONTRACT), &U256::zero()),
+            U256::one()
+        );
+        //a cold SSTORE to an empty slot
+        assert_eq!(
+            result.gas_used,
+            21000 + 3 + 3 + 2100 + 20000 + 2 + 3 + 3 + 10 + 1
+        );
+    }
+
+    #[test]
+    fn test_invalid_jump() {
+        //jumps to the push data of PUSH1 0x5b
+        let mut evm = evm_with_contract("600356605b");
+        let result = call_contract(&mut evm, vec![]);
+
+        assert_eq!(result.status, ExitStatus::Halt(HaltReason::InvalidJump(3)));
+        assert_eq!(result.gas_used, DEFAULT_GAS_LIMIT);
+    }
+
+    #[test]
+    fn test_create_and_call() {
+        let mut evm = evm_with_contract("");
+        let caller = Address::from_low_u64_be(CALLER);
+
+        //deploys a contract that returns its caller
+        let init_code = decode_hex("6009600c60003960096000f33360005260206000f3").unwrap();
+        let result = evm.transact(&Transaction::new(caller, None, U256::zero(), init_code));
+        assert!(result.is_success());
+
+        let address = result.created_address.unwrap();
+        assert_eq!(address, create_address(&caller, 0));
+        assert_eq!(
+            evm.state.code(&address),
+            decode_hex("3360005260206000f3").unwrap()
+        );
+
+        let result = evm.transact(&Transaction::new(
+            caller,
+            Some(address),
+            U256::zero(),
+            vec![],
+        ));
+        assert_eq!(
+            result.output,
+            u256_to_bytes(address_to_u256(&caller)).to_vec()
+        );
+        assert_eq!(evm.state.nonce(&caller), 2);
+    }
+
+    #[test]
+    fn test_signed_arithmetic() {
+        let minus = |value: u64| negate(U256::from(value));
+
+        assert_eq!(signed_div(minus(7), U256::from(2)), minus(3));
+        assert_eq!(signed_mod(minus(7), U256::from(2)), minus(1));
+        assert!(signed_less_than(minus(1), U256::zero()));
+        assert_eq!(sign_extend(U256::zero(), U256::from(0xFF)), U256::MAX);
+        assert_eq!(shift_value(0x1D, U256::from(4), minus(32)), minus(2));
+    }
+
+    #[test]
+    fn test_jump_destinations() {
+        assert_eq!(
+            jump_destinations(&decode_hex("5b605b5b").unwrap()),
+            vec![true, false, false, true]
+        );
+    }
+}
@@ -0,0 +1,5 @@
+pub mod abi;
+pub mod gas;
+pub mod interpreter;
+pub mod state;
+pub mod symbolic;
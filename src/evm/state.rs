@@ -0,0 +1,201 @@
+use crate::utils::keccak::keccak256;
+use primitive_types::{H160, U256};
+use std::collections::BTreeMap;
+
+pub type Address = H160;
+
+///An account in the world state
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Account {
+    pub balance: U256,
+    pub nonce: u64,
+    pub code: Vec<u8>,
+    //storage slots that are not in the map are zero
+    pub storage: BTreeMap<U256, U256>,
+}
+
+impl Account {
+    pub fn new(balance: U256, code: Vec<u8>) -> Account {
+        Account {
+            balance,
+            nonce: 0,
+            code,
+            storage: BTreeMap::new(),
+        }
+    }
+
+    ///An account with no code, nonce or balance, which is treated as if it does not exist
+    pub fn is_empty(&self) -> bool {
+        self.code.is_empty() && self.nonce == 0 && self.balance.is_zero()
+    }
+}
+
+///Every account, by address. Accounts that are not in the map do not exist.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorldState {
+    pub accounts: BTreeMap<Address, Account>,
+}
+
+impl WorldState {
+    pub fn new() -> WorldState {
+        WorldState::default()
+    }
+
+    pub fn account(&self, address: &Address) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+
+    ///Gets an account to modify, creating it if it does not exist
+    pub fn account_mut(&mut self, address: &Address) -> &mut Account {
+        self.accounts.entry(*address).or_default()
+    }
+
+    pub fn balance(&self, address: &Address) -> U256 {
+        self.account(address)
+            .map(|account| account.balance)
+            .unwrap_or_default()
+    }
+
+    pub fn nonce(&self, address: &Address) -> u64 {
+        self.account(address)
+            .map(|account| account.nonce)
+            .unwrap_or_default()
+    }
+
+    pub fn code(&self, address: &Address) -> &[u8] {
+        self.account(address)
+            .map(|account| account.code.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn storage(&self, address: &Address, key: &U256) -> U256 {
+        self.account(address)
+            .and_then(|account| account.storage.get(key).copied())
+            .unwrap_or_default()
+    }
+
+    pub fn set_storage(&mut self, address: &Address, key: U256, value: U256) {
+        let storage = &mut self.account_mut(address).storage;
+
+        //zero slots are removed so that states with the same values compare as equal
+        if value.is_zero() {
+            storage.remove(&key);
+        } else {
+            storage.insert(key, value);
+        }
+    }
+
+    ///Checks if the account does not exist or is empty
+    pub fn is_empty(&self, address: &Address) -> bool {
+        self.account(address).is_none_or(Account::is_empty)
+    }
+
+    ///Moves value between accounts, returning false without changing either account if the sender can not afford it
+    pub fn transfer(&mut self, from: &Address, to: &Address, value: U256) -> bool {
+        if self.balance(from) < value {
+            return false;
+        }
+
+        if !value.is_zero() {
+            self.account_mut(from).balance -= value;
+            self.account_mut(to).balance += value;
+        }
+
+        true
+    }
+}
+
+///Gets the address of a contract created with CREATE, which is the hash of the sender and its nonce
+pub fn create_address(sender: &Address, nonce: u64) -> Address {
+    //the rlp encoding of the list [sender, nonce]
+    let nonce_bytes: Vec<u8> = nonce
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|byte| *byte == 0)
+        .collect();
+
+    let mut encoded_nonce = vec![];
+    match nonce_bytes.as_slice() {
+        [] => encoded_nonce.push(0x80),
+        [byte] if *byte < 0x80 => encoded_nonce.push(*byte),
+        bytes => {
+            encoded_nonce.push(0x80 + bytes.len() as u8);
+            encoded_nonce.extend_from_slice(bytes);
+        }
+    }
+
+    let mut encoded = vec![0xC0 + 21 + encoded_nonce.len() as u8, 0x94];
+    encoded.extend_from_slice(sender.as_bytes());
+    encoded.extend_from_slice(&encoded_nonce);
+
+    Address::from_slice(&keccak256(&encoded)[12..])
+}
+
+///Gets the address of a contract created with CREATE2, which is the hash of the sender, salt and init code
+pub fn create2_address(sender: &Address, salt: U256, init_code: &[u8]) -> Address {
+    let mut encoded = vec![0xFF];
+    encoded.extend_from_slice(sender.as_bytes());
+    encoded.extend_from_slice(&u256_to_bytes(salt));
+    encoded.extend_from_slice(&keccak256(init_code));
+
+    Address::from_slice(&keccak256(&encoded)[12..])
+}
+
+///Converts an address to a stack value
+pub fn address_to_u256(address: &Address) -> U256 {
+    U256::from_big_endian(address.as_bytes())
+}
+
+///Converts a stack value to an address, keeping the lowest 20 bytes
+pub fn u256_to_address(value: U256) -> Address {
+    Address::from_slice(&u256_to_bytes(value)[12..])
+}
+
+///Converts a value to 32 big endian bytes
+pub fn u256_to_bytes(value: U256) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_create_address() {
+        let sender = Address::from_str("6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0").unwrap();
+
+        assert_eq!(
+            create_address(&sender, 0),
+            Address::from_str("cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d").unwrap()
+        );
+        assert_eq!(
+            create_address(&sender, 1),
+            Address::from_str("343c43a37d37dff08ae8c4a11544c718abb4fcf8").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_create2_address() {
+        //the first example from EIP-1014
+        assert_eq!(
+            create2_address(&Address::zero(), U256::zero(), &[0x00]),
+            Address::from_str("4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_transfer() {
+        let from = Address::from_low_u64_be(1);
+        let to = Address::from_low_u64_be(2);
+        let mut state = WorldState::new();
+        state.account_mut(&from).balance = U256::from(10);
+
+        assert!(state.transfer(&from, &to, U256::from(4)));
+        assert!(!state.transfer(&from, &to, U256::from(7)));
+        assert_eq!(state.balance(&from), U256::from(6));
+        assert_eq!(state.balance(&to), U256::from(4));
+    }
+}
@@ -0,0 +1,1371 @@
+//Symbolic execution of a contract over unconstrained calldata, caller and value. Every path keeps the conditions of
+//the branches it took along with a witness, a concrete transaction that takes the same branches. When a branch can go
+//both ways, the witness is changed to take the other branch by inverting the condition through the instructions that
+//computed it, which is a small bounded search rather than a complete solver.
+
+use crate::compiler::opcodes::{opcode_info, push_size};
+use crate::evm::interpreter::{arithmetic, jump_destinations, BlockEnv, MAX_STACK_SIZE};
+use crate::evm::state::{address_to_u256, u256_to_address, Address, WorldState};
+use crate::utils::keccak::keccak256;
+use primitive_types::U256;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+///The number of paths explored before exploration stops
+pub const MAX_PATHS: usize = 4096;
+///The number of instructions a path runs before it is abandoned, which bounds loops over the inputs
+pub const MAX_PATH_STEPS: usize = 10_000;
+
+//calldata is explored up to this size and memory up to MAX_MEMORY bytes
+const MAX_CALLDATA_SIZE: usize = 1 << 16;
+const MAX_MEMORY: usize = 1 << 20;
+//the number of witnesses tried when solving for a branch, and how many broken conditions are repaired in a row
+const MAX_CANDIDATES: usize = 512;
+const MAX_REPAIRS: usize = 4;
+//the depth of instructions a condition is inverted through
+const MAX_INVERT_DEPTH: usize = 12;
+
+///A part of the transaction that symbolic execution leaves unconstrained
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Input {
+    //the 32 bytes of calldata starting at an offset
+    CalldataWord(usize),
+    CalldataSize,
+    CallValue,
+    Caller,
+}
+
+///A byte of memory, either known or a byte of a value counting from the most significant byte
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryByte {
+    Constant(u8),
+    Byte(usize, Rc<Expr>),
+}
+
+///A value computed from the inputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Constant(U256),
+    Input(Input),
+    //CALLDATALOAD of an offset that depends on the inputs
+    CalldataLoad(Rc<Expr>),
+    //an arithmetic, comparison or bitwise instruction and its inputs, top of the stack first
+    Operation(u8, Vec<Rc<Expr>>),
+    //32 bytes of memory that are not all from one value
+    Bytes(Vec<MemoryByte>),
+    Keccak(Vec<MemoryByte>),
+    //SLOAD or TLOAD of a slot after the stores before it, which reads the state if none of them wrote the slot
+    Storage {
+        slot: Rc<Expr>,
+        writes: Rc<Vec<(Rc<Expr>, Rc<Expr>)>>,
+        transient: bool,
+    },
+}
+
+///A transaction that takes a path
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Witness {
+    pub calldata: Vec<u8>,
+    pub value: U256,
+    pub caller: Address,
+}
+
+impl Witness {
+    fn get(&self, input: Input) -> U256 {
+        match input {
+            Input::CalldataWord(offset) => {
+                let mut word = [0; 32];
+                for (index, byte) in word.iter_mut().enumerate() {
+                    *byte = self.calldata.get(offset + index).copied().unwrap_or(0);
+                }
+                U256::from_big_endian(&word)
+            }
+            Input::CalldataSize => U256::from(self.calldata.len()),
+            Input::CallValue => self.value,
+            Input::Caller => address_to_u256(&self.caller),
+        }
+    }
+
+    //gets a copy of the witness with the input set, or None if the value is out of range for the input
+    fn with(&self, input: Input, value: U256) -> Option<Witness> {
+        let mut witness = self.clone();
+
+        match input {
+            Input::CalldataWord(offset) => {
+                if witness.calldata.len() < offset + 32 {
+                    witness.calldata.resize(offset + 32, 0);
+                }
+                let mut word = [0; 32];
+                value.to_big_endian(&mut word);
+                witness.calldata[offset..offset + 32].copy_from_slice(&word);
+            }
+            Input::CalldataSize => {
+                if value >= U256::from(MAX_CALLDATA_SIZE) {
+                    return None;
+                }
+                witness.calldata.resize(value.as_usize(), 0);
+            }
+            Input::CallValue => witness.value = value,
+            Input::Caller => {
+                if value.bits() > 160 {
+                    return None;
+                }
+                witness.caller = u256_to_address(value);
+            }
+        }
+
+        Some(witness)
+    }
+}
+
+///What a path can do that is reported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    //INVALID or an opcode that is not defined
+    InvalidOpcode(u8),
+    InvalidJump(U256),
+    StackUnderflow,
+    Selfdestruct,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::InvalidOpcode(0xFE) => write!(f, "reaches INVALID"),
+            Issue::InvalidOpcode(opcode) => write!(f, "runs the undefined opcode 0x{:02x}", opcode),
+            Issue::InvalidJump(destination) => {
+                write!(f, "jumps to {:#x}, which is not a JUMPDEST", destination)
+            }
+            Issue::StackUnderflow => write!(f, "underflows the stack"),
+            Issue::Selfdestruct => write!(f, "reaches SELFDESTRUCT"),
+        }
+    }
+}
+
+///An issue a path reaches, with a transaction that reaches it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub pc: usize,
+    pub issue: Issue,
+    pub witness: Witness,
+}
+
+///The findings of an exploration, the first one for each instruction
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Exploration {
+    pub findings: Vec<Finding>,
+    pub paths: usize,
+    //the instructions that paths stopped at without reaching the end, with the reason
+    pub unexplored: BTreeSet<(usize, String)>,
+    //false if the path limit was reached
+    pub complete: bool,
+}
+
+///The state a contract is explored in, which is the state the transactions of the witnesses run in
+pub struct SymbolicContext<'a> {
+    pub state: &'a WorldState,
+    pub block: &'a BlockEnv,
+    pub address: Address,
+    //the caller of the first path, before any condition on the caller is solved for
+    pub caller: Address,
+}
+
+//how a path ended
+enum PathEnd {
+    Halted,
+    Found(Issue),
+    Unexplored(String),
+}
+
+#[derive(Clone)]
+struct Path {
+    pc: usize,
+    stack: Vec<Rc<Expr>>,
+    memory: Vec<MemoryByte>,
+    storage_writes: Rc<Vec<(Rc<Expr>, Rc<Expr>)>>,
+    transient_writes: Rc<Vec<(Rc<Expr>, Rc<Expr>)>>,
+    //the conditions of the branches taken, and whether each was true
+    constraints: Vec<(Rc<Expr>, bool)>,
+    witness: Witness,
+    steps: usize,
+}
+
+///Explores the paths of the code called by a transaction, stopping paths at the first issue they reach
+pub fn explore(code: &[u8], context: &SymbolicContext) -> Exploration {
+    let jump_destinations = jump_destinations(code);
+    let mut exploration = Exploration {
+        complete: true,
+        ..Default::default()
+    };
+    let mut found: HashSet<usize> = HashSet::new();
+
+    let mut paths = vec![Path {
+        pc: 0,
+        stack: vec![],
+        memory: vec![],
+        storage_writes: Rc::new(vec![]),
+        transient_writes: Rc::new(vec![]),
+        constraints: vec![],
+        witness: Witness {
+            caller: context.caller,
+            ..Default::default()
+        },
+        steps: 0,
+    }];
+
+    while let Some(mut path) = paths.pop() {
+        if exploration.paths == MAX_PATHS {
+            exploration.complete = false;
+            break;
+        }
+        exploration.paths += 1;
+
+        match run_path(&mut path, code, &jump_destinations, context, &mut paths) {
+            PathEnd::Halted => {}
+            PathEnd::Found(issue) => {
+                if found.insert(path.pc) {
+                    exploration.findings.push(Finding {
+                        pc: path.pc,
+                        issue,
+                        witness: path.witness,
+                    });
+                }
+            }
+            PathEnd::Unexplored(reason) => {
+                exploration.unexplored.insert((path.pc, reason));
+            }
+        }
+    }
+
+    exploration.findings.sort_by_key(|finding| finding.pc);
+    exploration
+}
+
+//runs a path until it ends, adding the paths of the branches it does not take
+fn run_path(
+    path: &mut Path,
+    code: &[u8],
+    jump_destinations: &[bool],
+    context: &SymbolicContext,
+    paths: &mut Vec<Path>,
+) -> PathEnd {
+    loop {
+        if path.steps == MAX_PATH_STEPS {
+            return PathEnd::Unexplored(String::from("the step limit was reached"));
+        }
+        path.steps += 1;
+
+        //running past the end of the code stops
+        let opcode = code.get(path.pc).copied().unwrap_or(0x00);
+        let opcode_info = match opcode_info(opcode) {
+            Some(opcode_info) if opcode != 0xFE => opcode_info,
+            _ => return PathEnd::Found(Issue::InvalidOpcode(opcode)),
+        };
+
+        if path.stack.len() < opcode_info.stack_inputs {
+            return PathEnd::Found(Issue::StackUnderflow);
+        }
+        if path.stack.len() - opcode_info.stack_inputs + opcode_info.stack_outputs > MAX_STACK_SIZE
+        {
+            return PathEnd::Halted;
+        }
+
+        let inputs: Vec<Rc<Expr>> = (0..opcode_info.stack_inputs)
+            .map(|_| path.stack.pop().unwrap())
+            .collect();
+        let mut next_pc = path.pc + 1 + push_size(opcode);
+
+        match opcode {
+            //STOP, RETURN and REVERT
+            0x00 | 0xF3 | 0xFD => return PathEnd::Halted,
+            0x01..=0x1D => path.stack.push(operation(opcode, inputs)),
+            //KECCAK256
+            0x20 => {
+                let bytes = match read_memory(path, &inputs[0], &inputs[1]) {
+                    Ok(bytes) => bytes,
+                    Err(reason) => return PathEnd::Unexplored(reason),
+                };
+                path.stack.push(match constant_bytes(&bytes) {
+                    Some(bytes) => constant(U256::from_big_endian(&keccak256(&bytes))),
+                    None => Rc::new(Expr::Keccak(bytes)),
+                });
+            }
+            //ADDRESS
+            0x30 => path.stack.push(constant(address_to_u256(&context.address))),
+            //BALANCE, the balance of the contract includes the value sent to it
+            0x31 => match as_constant(&inputs[0]) {
+                Some(address) if u256_to_address(address) == context.address => {
+                    path.stack.push(self_balance(context))
+                }
+                Some(address) => path
+                    .stack
+                    .push(constant(context.state.balance(&u256_to_address(address)))),
+                None => return unexplored("BALANCE of an address that depends on the inputs"),
+            },
+            //ORIGIN and CALLER
+            0x32 | 0x33 => path.stack.push(input(Input::Caller)),
+            0x34 => path.stack.push(input(Input::CallValue)),
+            //CALLDATALOAD
+            0x35 => path.stack.push(match as_constant(&inputs[0]) {
+                Some(offset) if offset < U256::from(MAX_CALLDATA_SIZE) => {
+                    input(Input::CalldataWord(offset.as_usize()))
+                }
+                Some(_) => constant(U256::zero()),
+                None => Rc::new(Expr::CalldataLoad(inputs[0].clone())),
+            }),
+            0x36 => path.stack.push(input(Input::CalldataSize)),
+            //CALLDATACOPY, where each byte is a byte of the calldata word it starts
+            0x37 => {
+                let offset = match as_constant(&inputs[1]) {
+                    Some(offset) => offset,
+                    None => {
+                        return unexplored("CALLDATACOPY from an offset that depends on the inputs")
+                    }
+                };
+                let length = as_constant(&inputs[2]).unwrap_or_default();
+                let bytes = (0..length.min(U256::from(MAX_MEMORY)).as_usize())
+                    .map(|index| {
+                        //offsets past the largest offset read zeros, like offsets past the end of the calldata
+                        match offset.checked_add(U256::from(index / 32 * 32)) {
+                            Some(word_offset) if word_offset < U256::from(MAX_CALLDATA_SIZE) => {
+                                MemoryByte::Byte(
+                                    index % 32,
+                                    input(Input::CalldataWord(word_offset.as_usize())),
+                                )
+                            }
+                            _ => MemoryByte::Constant(0),
+                        }
+                    })
+                    .collect();
+                if let Err(reason) = write_memory(path, &inputs[0], &inputs[2], bytes) {
+                    return PathEnd::Unexplored(reason);
+                }
+            }
+            //CODESIZE
+            0x38 => path.stack.push(constant(U256::from(code.len()))),
+            //CODECOPY and EXTCODECOPY
+            0x39 | 0x3C => {
+                let (code, inputs) = if opcode == 0x39 {
+                    (code, &inputs[..])
+                } else {
+                    match as_constant(&inputs[0]) {
+                        Some(address) => {
+                            (context.state.code(&u256_to_address(address)), &inputs[1..])
+                        }
+                        None => {
+                            return unexplored(
+                                "EXTCODECOPY of an address that depends on the inputs",
+                            )
+                        }
+                    }
+                };
+                let bytes = match (as_constant(&inputs[1]), as_constant(&inputs[2])) {
+                    (Some(offset), Some(length)) if length <= U256::from(MAX_MEMORY) => (0..length
+                        .as_usize())
+                        .map(|index| {
+                            let byte = offset
+                                .checked_add(U256::from(index))
+                                .filter(|offset| *offset < U256::from(code.len()))
+                                .map_or(0, |offset| code[offset.as_usize()]);
+                            MemoryByte::Constant(byte)
+                        })
+                        .collect(),
+                    _ => return unexplored("a code copy that depends on the inputs"),
+                };
+                if let Err(reason) = write_memory(path, &inputs[0], &inputs[2], bytes) {
+                    return PathEnd::Unexplored(reason);
+                }
+            }
+            //GASPRICE, transactions are explored with a gas price of zero
+            0x3A => path.stack.push(constant(U256::zero())),
+            //EXTCODESIZE and EXTCODEHASH
+            0x3B | 0x3F => {
+                let address = match as_constant(&inputs[0]) {
+                    Some(address) => u256_to_address(address),
+                    None => return unexplored("an account that depends on the inputs"),
+                };
+                let code = context.state.code(&address);
+                path.stack.push(constant(if opcode == 0x3B {
+                    U256::from(code.len())
+                } else if context.state.is_empty(&address) {
+                    U256::zero()
+                } else {
+                    U256::from_big_endian(&keccak256(code))
+                }));
+            }
+            //RETURNDATASIZE, there is no return data since calls are not explored
+            0x3D => path.stack.push(constant(U256::zero())),
+            //RETURNDATACOPY of anything but nothing halts
+            0x3E => {
+                if as_constant(&inputs[1]) != Some(U256::zero())
+                    || as_constant(&inputs[2]) != Some(U256::zero())
+                {
+                    return PathEnd::Halted;
+                }
+            }
+            //BLOCKHASH, which is a hash of the block number for the last 256 blocks
+            0x40 => {
+                let number = match as_constant(&inputs[0]) {
+                    Some(number) => number,
+                    None => return unexplored("BLOCKHASH of a block that depends on the inputs"),
+                };
+                let block_number = context.block.number;
+                let mut number_bytes = [0; 32];
+                number.to_big_endian(&mut number_bytes);
+
+                path.stack.push(constant(
+                    if number < block_number && block_number - number <= U256::from(256) {
+                        U256::from_big_endian(&keccak256(&number_bytes))
+                    } else {
+                        U256::zero()
+                    },
+                ));
+            }
+            0x41 => path
+                .stack
+                .push(constant(address_to_u256(&context.block.coinbase))),
+            0x42 => path.stack.push(constant(context.block.timestamp)),
+            0x43 => path.stack.push(constant(context.block.number)),
+            0x44 => path.stack.push(constant(context.block.prevrandao)),
+            0x45 => path.stack.push(constant(context.block.gas_limit)),
+            0x46 => path.stack.push(constant(context.block.chain_id)),
+            0x47 => path.stack.push(self_balance(context)),
+            0x48 => path.stack.push(constant(context.block.base_fee)),
+            //BLOBHASH, transactions do not carry blobs
+            0x49 => path.stack.push(constant(U256::zero())),
+            0x4A => path.stack.push(constant(context.block.blob_base_fee)),
+            //POP
+            0x50 => {}
+            //MLOAD
+            0x51 => match read_memory(path, &inputs[0], &constant(U256::from(32))) {
+                Ok(bytes) => path.stack.push(word(bytes)),
+                Err(reason) => return PathEnd::Unexplored(reason),
+            },
+            //MSTORE and MSTORE8
+            0x52 | 0x53 => {
+                let value = &inputs[1];
+                let bytes: Vec<MemoryByte> = if opcode == 0x52 {
+                    (0..32).map(|index| memory_byte(value, index)).collect()
+                } else {
+                    vec![memory_byte(value, 31)]
+                };
+                let length = constant(U256::from(bytes.len()));
+                if let Err(reason) = write_memory(path, &inputs[0], &length, bytes) {
+                    return PathEnd::Unexplored(reason);
+                }
+            }
+            //SLOAD and TLOAD
+            0x54 | 0x5C => {
+                let transient = opcode == 0x5C;
+                let writes = if transient {
+                    &path.transient_writes
+                } else {
+                    &path.storage_writes
+                };
+                path.stack
+                    .push(load(&inputs[0], writes, transient, context));
+            }
+            //SSTORE and TSTORE
+            0x55 | 0x5D => {
+                let writes = if opcode == 0x5D {
+                    &mut path.transient_writes
+                } else {
+                    &mut path.storage_writes
+                };
+                Rc::make_mut(writes).push((inputs[0].clone(), inputs[1].clone()));
+            }
+            //JUMP
+            0x56 => match jump(path, &inputs[0], jump_destinations, context) {
+                Ok(destination) => next_pc = destination,
+                Err(end) => return end,
+            },
+            //JUMPI takes the branch the witness takes, and the other branch becomes a new path if a witness is found for it
+            0x57 => {
+                let condition = &inputs[1];
+                let jumps = match as_constant(condition) {
+                    Some(condition) => !condition.is_zero(),
+                    None => {
+                        let jumps = !Evaluator::new(&path.witness, context)
+                            .evaluate(condition)
+                            .is_zero();
+
+                        let mut constraints = path.constraints.clone();
+                        constraints.push((condition.clone(), !jumps));
+                        if let Some(witness) = solve(&constraints, &path.witness, context) {
+                            let mut other_path = path.clone();
+                            other_path.constraints = constraints;
+                            other_path.witness = witness;
+                            other_path.stack.extend(inputs.iter().rev().cloned());
+                            paths.push(other_path);
+                        }
+
+                        path.constraints.push((condition.clone(), jumps));
+                        jumps
+                    }
+                };
+
+                if jumps {
+                    match jump(path, &inputs[0], jump_destinations, context) {
+                        Ok(destination) => next_pc = destination,
+                        Err(end) => return end,
+                    }
+                }
+            }
+            //PC
+            0x58 => path.stack.push(constant(U256::from(path.pc))),
+            //MSIZE
+            0x59 => path.stack.push(constant(U256::from(path.memory.len()))),
+            0x5A => return unexplored("GAS depends on the gas limit"),
+            //JUMPDEST
+            0x5B => {}
+            //MCOPY
+            0x5E => {
+                let bytes = match read_memory(path, &inputs[1], &inputs[2]) {
+                    Ok(bytes) => bytes,
+                    Err(reason) => return PathEnd::Unexplored(reason),
+                };
+                if let Err(reason) = write_memory(path, &inputs[0], &inputs[2], bytes) {
+                    return PathEnd::Unexplored(reason);
+                }
+            }
+            //PUSH0 - PUSH32, where push data cut short by the end of the code is padded with zeros
+            0x5F..=0x7F => {
+                let mut value = [0; 32];
+                for index in 0..push_size(opcode) {
+                    value[32 - push_size(opcode) + index] =
+                        code.get(path.pc + 1 + index).copied().unwrap_or(0);
+                }
+                path.stack.push(constant(U256::from_big_endian(&value)));
+            }
+            //DUP1 - DUP16
+            0x80..=0x8F => {
+                path.stack.extend(inputs.iter().rev().cloned());
+                path.stack.push(inputs[inputs.len() - 1].clone());
+            }
+            //SWAP1 - SWAP16
+            0x90..=0x9F => {
+                let mut inputs = inputs;
+                let last = inputs.len() - 1;
+                inputs.swap(0, last);
+                path.stack.extend(inputs.into_iter().rev());
+            }
+            //LOG0 - LOG4 do not change the state a path depends on
+            0xA0..=0xA4 => {}
+            //CREATE, CALL, CALLCODE, DELEGATECALL, CREATE2 and STATICCALL
+            0xF0..=0xF2 | 0xF4 | 0xF5 | 0xFA => {
+                return PathEnd::Unexplored(format!(
+                    "{} runs other code, which is not explored",
+                    opcode_info.mnemonic
+                ))
+            }
+            0xFF => return PathEnd::Found(Issue::Selfdestruct),
+            _ => return unexplored("the instruction is not supported"),
+        }
+
+        path.pc = next_pc;
+    }
+}
+
+fn unexplored(reason: &str) -> PathEnd {
+    PathEnd::Unexplored(reason.to_owned())
+}
+
+fn constant(value: U256) -> Rc<Expr> {
+    Rc::new(Expr::Constant(value))
+}
+
+fn input(input: Input) -> Rc<Expr> {
+    Rc::new(Expr::Input(input))
+}
+
+fn as_constant(expr: &Expr) -> Option<U256> {
+    match expr {
+        Expr::Constant(value) => Some(*value),
+        _ => None,
+    }
+}
+
+//the balance of the contract, which includes the value of the transaction
+fn self_balance(context: &SymbolicContext) -> Rc<Expr> {
+    operation(
+        0x01,
+        vec![
+            constant(context.state.balance(&context.address)),
+            input(Input::CallValue),
+        ],
+    )
+}
+
+//builds an arithmetic, comparison or bitwise instruction, computing it if the inputs are constant
+fn operation(opcode: u8, inputs: Vec<Rc<Expr>>) -> Rc<Expr> {
+    let values: Option<Vec<U256>> = inputs.iter().map(|input| as_constant(input)).collect();
+
+    match values.and_then(|values| arithmetic(opcode, &values)) {
+        Some(value) => constant(value),
+        None => Rc::new(Expr::Operation(opcode, inputs)),
+    }
+}
+
+fn memory_byte(value: &Rc<Expr>, index: usize) -> MemoryByte {
+    match as_constant(value) {
+        Some(value) => MemoryByte::Constant(value.byte(31 - index)),
+        None => MemoryByte::Byte(index, value.clone()),
+    }
+}
+
+fn constant_bytes(bytes: &[MemoryByte]) -> Option<Vec<u8>> {
+    bytes
+        .iter()
+        .map(|byte| match byte {
+            MemoryByte::Constant(byte) => Some(*byte),
+            MemoryByte::Byte(_, _) => None,
+        })
+        .collect()
+}
+
+//the value of 32 bytes of memory, which is the value they were stored from if they are all of one value
+fn word(bytes: Vec<MemoryByte>) -> Rc<Expr> {
+    if let Some(bytes) = constant_bytes(&bytes) {
+        return constant(U256::from_big_endian(&bytes));
+    }
+
+    if let MemoryByte::Byte(0, value) = &bytes[0] {
+        let same_value = bytes.iter().enumerate().all(|(index, byte)| {
+            matches!(byte, MemoryByte::Byte(byte_index, byte_value) if *byte_index == index && Rc::ptr_eq(byte_value, value))
+        });
+        if same_value {
+            return value.clone();
+        }
+    }
+
+    Rc::new(Expr::Bytes(bytes))
+}
+
+//the range of memory an instruction uses, expanding memory to include it
+fn memory_range(path: &mut Path, offset: &Expr, length: &Expr) -> Result<(usize, usize), String> {
+    let length = as_constant(length)
+        .ok_or_else(|| String::from("a memory length that depends on the inputs"))?;
+    if length.is_zero() {
+        return Ok((0, 0));
+    }
+    let offset = as_constant(offset)
+        .ok_or_else(|| String::from("a memory offset that depends on the inputs"))?;
+
+    let end = offset
+        .checked_add(length)
+        .filter(|end| *end <= U256::from(MAX_MEMORY));
+    let end = end
+        .ok_or_else(|| String::from("more memory than is explored"))?
+        .as_usize();
+
+    if path.memory.len() < end {
+        path.memory
+            .resize(end.div_ceil(32) * 32, MemoryByte::Constant(0));
+    }
+    Ok((offset.as_usize(), length.as_usize()))
+}
+
+fn read_memory(path: &mut Path, offset: &Expr, length: &Expr) -> Result<Vec<MemoryByte>, String> {
+    let (offset, length) = memory_range(path, offset, length)?;
+    Ok(path.memory[offset..offset + length].to_vec())
+}
+
+fn write_memory(
+    path: &mut Path,
+    offset: &Expr,
+    length: &Expr,
+    bytes: Vec<MemoryByte>,
+) -> Result<(), String> {
+    let (offset, length) = memory_range(path, offset, length)?;
+    for (index, byte) in bytes.into_iter().take(length).enumerate() {
+        path.memory[offset + index] = byte;
+    }
+    Ok(())
+}
+
+//loads a slot, reading the value directly when the slot and the slots written are constant
+fn load(
+    slot: &Rc<Expr>,
+    writes: &Rc<Vec<(Rc<Expr>, Rc<Expr>)>>,
+    transient: bool,
+    context: &SymbolicContext,
+) -> Rc<Expr> {
+    if let Some(slot_value) = as_constant(slot) {
+        let mut value = None;
+        for (write_slot, write_value) in writes.iter().rev() {
+            match as_constant(write_slot) {
+                Some(write_slot) if write_slot == slot_value => {
+                    value = Some(write_value.clone());
+                    break;
+                }
+                Some(_) => {}
+                None => {
+                    return Rc::new(Expr::Storage {
+                        slot: slot.clone(),
+                        writes: writes.clone(),
+                        transient,
+                    })
+                }
+            }
+        }
+
+        return value.unwrap_or_else(|| {
+            constant(if transient {
+                U256::zero()
+            } else {
+                context.state.storage(&context.address, &slot_value)
+            })
+        });
+    }
+
+    Rc::new(Expr::Storage {
+        slot: slot.clone(),
+        writes: writes.clone(),
+        transient,
+    })
+}
+
+//the destination of a jump the witness takes. A destination that depends on the inputs is fixed to the one the witness
+//jumps to.
+fn jump(
+    path: &mut Path,
+    destination: &Rc<Expr>,
+    jump_destinations: &[bool],
+    context: &SymbolicContext,
+) -> Result<usize, PathEnd> {
+    let value = match as_constant(destination) {
+        Some(value) => value,
+        None => {
+            let value = Evaluator::new(&path.witness, context).evaluate(destination);
+            path.constraints.push((
+                operation(0x14, vec![destination.clone(), constant(value)]),
+                true,
+            ));
+            value
+        }
+    };
+
+    if value < U256::from(jump_destinations.len()) && jump_destinations[value.as_usize()] {
+        Ok(value.as_usize())
+    } else {
+        Err(PathEnd::Found(Issue::InvalidJump(value)))
+    }
+}
+
+//evaluates expressions for a witness, caching the value of each shared expression
+struct Evaluator<'a> {
+    witness: &'a Witness,
+    context: &'a SymbolicContext<'a>,
+    values: HashMap<*const Expr, U256>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(witness: &'a Witness, context: &'a SymbolicContext<'a>) -> Evaluator<'a> {
+        Evaluator {
+            witness,
+            context,
+            values: HashMap::new(),
+        }
+    }
+
+    fn evaluate(&mut self, expr: &Rc<Expr>) -> U256 {
+        if let Some(value) = self.values.get(&Rc::as_ptr(expr)) {
+            return *value;
+        }
+
+        let value = match expr.as_ref() {
+            Expr::Constant(value) => *value,
+            Expr::Input(input) => self.witness.get(*input),
+            Expr::CalldataLoad(offset) => {
+                let offset = self.evaluate(offset);
+                if offset < U256::from(MAX_CALLDATA_SIZE) {
+                    self.witness.get(Input::CalldataWord(offset.as_usize()))
+                } else {
+                    U256::zero()
+                }
+            }
+            Expr::Operation(opcode, inputs) => {
+                let values: Vec<U256> = inputs.iter().map(|input| self.evaluate(input)).collect();
+                arithmetic(*opcode, &values).unwrap_or_default()
+            }
+            Expr::Bytes(bytes) => U256::from_big_endian(&self.evaluate_bytes(bytes)),
+            Expr::Keccak(bytes) => U256::from_big_endian(&keccak256(&self.evaluate_bytes(bytes))),
+            Expr::Storage {
+                slot,
+                writes,
+                transient,
+            } => {
+                let slot = self.evaluate(slot);
+                let mut value = None;
+                for (write_slot, write_value) in writes.iter().rev() {
+                    if self.evaluate(write_slot) == slot {
+                        value = Some(self.evaluate(write_value));
+                        break;
+                    }
+                }
+
+                value.unwrap_or_else(|| {
+                    if *transient {
+                        U256::zero()
+                    } else {
+                        self.context.state.storage(&self.context.address, &slot)
+                    }
+                })
+            }
+        };
+
+        self.values.insert(Rc::as_ptr(expr), value);
+        value
+    }
+
+    fn evaluate_bytes(&mut self, bytes: &[MemoryByte]) -> Vec<u8> {
+        bytes
+            .iter()
+            .map(|byte| match byte {
+                MemoryByte::Constant(byte) => *byte,
+                MemoryByte::Byte(index, value) => self.evaluate(value).byte(31 - index),
+            })
+            .collect()
+    }
+
+    fn holds(&mut self, (condition, taken): &(Rc<Expr>, bool)) -> bool {
+        self.evaluate(condition).is_zero() != *taken
+    }
+}
+
+///Finds a witness for which every condition has the given truth value, starting from a witness that satisfies all of
+///them except the last. Returns None if none is found within the search limits.
+pub fn solve(
+    constraints: &[(Rc<Expr>, bool)],
+    witness: &Witness,
+    context: &SymbolicContext,
+) -> Option<Witness> {
+    let mut budget = MAX_CANDIDATES;
+    repair(constraints, witness, context, MAX_REPAIRS, &mut budget)
+}
+
+//changes the witness to satisfy the first condition it breaks, then repairs the conditions the change breaks
+fn repair(
+    constraints: &[(Rc<Expr>, bool)],
+    witness: &Witness,
+    context: &SymbolicContext,
+    repairs: usize,
+    budget: &mut usize,
+) -> Option<Witness> {
+    let mut evaluator = Evaluator::new(witness, context);
+    let broken = match constraints
+        .iter()
+        .position(|constraint| !evaluator.holds(constraint))
+    {
+        Some(broken) => broken,
+        None => return Some(witness.clone()),
+    };
+    if repairs == 0 {
+        return None;
+    }
+
+    let (condition, taken) = &constraints[broken];
+    for candidate in candidates(condition, *taken, witness, context) {
+        if *budget == 0 {
+            return None;
+        }
+        *budget -= 1;
+
+        //candidates that do not satisfy the broken condition are not worth repairing further
+        if !Evaluator::new(&candidate, context).holds(&constraints[broken]) {
+            continue;
+        }
+        if let Some(solution) = repair(constraints, &candidate, context, repairs - 1, budget) {
+            return Some(solution);
+        }
+    }
+
+    None
+}
+
+//witnesses that may give the condition the truth value, first from inverting the condition and then from trying the
+//constants it uses for each input it depends on
+fn candidates(
+    condition: &Rc<Expr>,
+    taken: bool,
+    witness: &Witness,
+    context: &SymbolicContext,
+) -> Vec<Witness> {
+    let mut candidates = vec![];
+    if taken {
+        invert(condition, U256::one(), witness, context, &mut candidates, 0);
+        invert(condition, U256::MAX, witness, context, &mut candidates, 0);
+    } else {
+        invert(
+            condition,
+            U256::zero(),
+            witness,
+            context,
+            &mut candidates,
+            0,
+        );
+    }
+
+    let mut inputs = BTreeSet::new();
+    let mut constants = BTreeSet::from([U256::zero(), U256::one()]);
+    collect_leaves(condition, witness, context, &mut inputs, &mut constants);
+
+    for input in inputs {
+        for constant in constants.iter().take(16) {
+            for value in [
+                *constant,
+                constant.overflowing_add(U256::one()).0,
+                constant.overflowing_sub(U256::one()).0,
+            ] {
+                candidates.extend(witness.with(input, value));
+            }
+        }
+    }
+
+    candidates
+}
+
+//collects the inputs and constants an expression depends on
+fn collect_leaves(
+    expr: &Rc<Expr>,
+    witness: &Witness,
+    context: &SymbolicContext,
+    inputs: &mut BTreeSet<Input>,
+    constants: &mut BTreeSet<U256>,
+) {
+    match expr.as_ref() {
+        Expr::Constant(value) => {
+            constants.insert(*value);
+        }
+        Expr::Input(input) => {
+            inputs.insert(*input);
+        }
+        Expr::CalldataLoad(offset) => {
+            let offset_value = Evaluator::new(witness, context).evaluate(offset);
+            if offset_value < U256::from(MAX_CALLDATA_SIZE) {
+                inputs.insert(Input::CalldataWord(offset_value.as_usize()));
+            }
+            collect_leaves(offset, witness, context, inputs, constants);
+        }
+        Expr::Operation(_, operation_inputs) => {
+            for operation_input in operation_inputs {
+                collect_leaves(operation_input, witness, context, inputs, constants);
+            }
+        }
+        Expr::Bytes(bytes) | Expr::Keccak(bytes) => {
+            for byte in bytes {
+                if let MemoryByte::Byte(_, value) = byte {
+                    collect_leaves(value, witness, context, inputs, constants);
+                }
+            }
+        }
+        Expr::Storage { slot, .. } => collect_leaves(slot, witness, context, inputs, constants),
+    }
+}
+
+//adds witnesses for which the expression is the target, by working back through the instructions that computed it
+fn invert(
+    expr: &Rc<Expr>,
+    target: U256,
+    witness: &Witness,
+    context: &SymbolicContext,
+    candidates: &mut Vec<Witness>,
+    depth: usize,
+) {
+    if depth == MAX_INVERT_DEPTH {
+        return;
+    }
+    let invert_input = |expr: &Rc<Expr>, target: U256, candidates: &mut Vec<Witness>| {
+        invert(expr, target, witness, context, candidates, depth + 1)
+    };
+
+    match expr.as_ref() {
+        Expr::Constant(_) | Expr::Keccak(_) | Expr::Storage { .. } => {}
+        Expr::Input(input) => candidates.extend(witness.with(*input, target)),
+        Expr::CalldataLoad(offset) => {
+            let offset = Evaluator::new(witness, context).evaluate(offset);
+            if offset < U256::from(MAX_CALLDATA_SIZE) {
+                candidates.extend(witness.with(Input::CalldataWord(offset.as_usize()), target));
+            }
+        }
+        //bytes of a single value are inverted by setting those bytes of the value
+        Expr::Bytes(bytes) => {
+            let mut sources = bytes.iter().filter_map(|byte| match byte {
+                MemoryByte::Byte(_, value) => Some(value),
+                MemoryByte::Constant(_) => None,
+            });
+            let source = match sources.next() {
+                Some(source) if sources.all(|value| Rc::ptr_eq(value, source)) => source,
+                _ => return,
+            };
+
+            let mut source_bytes = [0; 32];
+            Evaluator::new(witness, context)
+                .evaluate(source)
+                .to_big_endian(&mut source_bytes);
+            for (index, byte) in bytes.iter().enumerate() {
+                match byte {
+                    MemoryByte::Byte(source_index, _) => {
+                        source_bytes[*source_index] = target.byte(31 - index)
+                    }
+                    MemoryByte::Constant(byte) if *byte != target.byte(31 - index) => return,
+                    MemoryByte::Constant(_) => {}
+                }
+            }
+            invert_input(source, U256::from_big_endian(&source_bytes), candidates);
+        }
+        Expr::Operation(opcode, inputs) => {
+            let mut evaluator = Evaluator::new(witness, context);
+            let values: Vec<U256> = inputs
+                .iter()
+                .map(|input| evaluator.evaluate(input))
+                .collect();
+            let (a, b) = (&inputs[0], inputs.get(1));
+            let (value_a, value_b) = (values[0], values.get(1).copied().unwrap_or_default());
+            let one = U256::one();
+
+            match (opcode, b) {
+                //ADD
+                (0x01, Some(b)) => {
+                    invert_input(a, target.overflowing_sub(value_b).0, candidates);
+                    invert_input(b, target.overflowing_sub(value_a).0, candidates);
+                }
+                //MUL
+                (0x02, Some(b)) => {
+                    if !value_b.is_zero() && (target % value_b).is_zero() {
+                        invert_input(a, target / value_b, candidates);
+                    }
+                    if !value_a.is_zero() && (target % value_a).is_zero() {
+                        invert_input(b, target / value_a, candidates);
+                    }
+                }
+                //SUB
+                (0x03, Some(b)) => {
+                    invert_input(a, target.overflowing_add(value_b).0, candidates);
+                    invert_input(b, value_a.overflowing_sub(target).0, candidates);
+                }
+                //DIV
+                (0x04, Some(_)) => {
+                    if let Some(value) = target.checked_mul(value_b) {
+                        invert_input(a, value, candidates);
+                    }
+                }
+                //MOD
+                (0x06, Some(_)) if target < value_b => {
+                    //the value closest to the current one with the remainder, unless it is past the maximum
+                    if let Some(value) = (value_a - value_a % value_b).checked_add(target) {
+                        invert_input(a, value, candidates);
+                    }
+                    invert_input(a, target, candidates);
+                }
+                //SIGNEXTEND
+                (0x0B, Some(b)) => invert_input(b, target, candidates),
+                //LT, GT, SLT and SGT, where GT and SGT are LT and SLT with the inputs swapped
+                (0x10..=0x13, Some(b)) => {
+                    let (a, b, value_a, value_b) = if *opcode == 0x10 || *opcode == 0x12 {
+                        (a, b, value_a, value_b)
+                    } else {
+                        (b, a, value_b, value_a)
+                    };
+                    let (min, max) = if *opcode >= 0x12 {
+                        (one << 255, (one << 255) - one)
+                    } else {
+                        (U256::zero(), U256::MAX)
+                    };
+
+                    if !target.is_zero() {
+                        if value_b != min {
+                            invert_input(a, value_b.overflowing_sub(one).0, candidates);
+                        }
+                        invert_input(a, min, candidates);
+                        if value_a != max {
+                            invert_input(b, value_a.overflowing_add(one).0, candidates);
+                        }
+                        invert_input(b, max, candidates);
+                    } else {
+                        invert_input(a, value_b, candidates);
+                        invert_input(a, max, candidates);
+                        invert_input(b, value_a, candidates);
+                        invert_input(b, min, candidates);
+                    }
+                }
+                //EQ
+                (0x14, Some(b)) => {
+                    if !target.is_zero() {
+                        invert_input(a, value_b, candidates);
+                        invert_input(b, value_a, candidates);
+                    } else {
+                        invert_input(a, value_b.overflowing_add(one).0, candidates);
+                        invert_input(b, value_a.overflowing_add(one).0, candidates);
+                    }
+                }
+                //ISZERO
+                (0x15, None) => {
+                    if target.is_zero() {
+                        invert_input(a, one, candidates);
+                        invert_input(a, U256::MAX, candidates);
+                    } else {
+                        invert_input(a, U256::zero(), candidates);
+                    }
+                }
+                //AND
+                (0x16, Some(b)) => {
+                    if (target & !value_b).is_zero() {
+                        invert_input(a, (value_a & !value_b) | target, candidates);
+                    }
+                    if (target & !value_a).is_zero() {
+                        invert_input(b, (value_b & !value_a) | target, candidates);
+                    }
+                    //otherwise both inputs need the bits of the target
+                    invert_both(
+                        a,
+                        b,
+                        value_a | target,
+                        |value_b| value_b | target,
+                        witness,
+                        context,
+                        candidates,
+                        depth,
+                    );
+                }
+                //OR
+                (0x17, Some(b)) => {
+                    if (value_b & !target).is_zero() {
+                        invert_input(a, target & !value_b, candidates);
+                    }
+                    if (value_a & !target).is_zero() {
+                        invert_input(b, target & !value_a, candidates);
+                    }
+                    //otherwise both inputs need to lose the bits that are not in the target
+                    invert_both(
+                        a,
+                        b,
+                        value_a & target,
+                        |value_b| value_b & target,
+                        witness,
+                        context,
+                        candidates,
+                        depth,
+                    );
+                }
+                //XOR
+                (0x18, Some(b)) => {
+                    invert_input(a, target ^ value_b, candidates);
+                    invert_input(b, target ^ value_a, candidates);
+                }
+                //NOT
+                (0x19, None) => invert_input(a, !target, candidates),
+                //BYTE
+                (0x1A, Some(b)) if value_a < U256::from(32) && target < U256::from(256) => {
+                    let mut bytes = [0; 32];
+                    value_b.to_big_endian(&mut bytes);
+                    bytes[value_a.as_usize()] = target.byte(0);
+                    invert_input(b, U256::from_big_endian(&bytes), candidates);
+                }
+                //SHL, where the bits shifted out of the value are kept
+                (0x1B, Some(b)) if value_a < U256::from(256) => {
+                    let shift = value_a.as_usize();
+                    if (target >> shift) << shift == target {
+                        let shifted_out = value_b & !(U256::MAX >> shift);
+                        invert_input(b, (target >> shift) | shifted_out, candidates);
+                    }
+                }
+                //SHR and SAR of values that are not negative, where the bits shifted out of the value are kept
+                (0x1C | 0x1D, Some(b))
+                    if value_a < U256::from(256) && (*opcode == 0x1C || !target.bit(255)) =>
+                {
+                    let shift = value_a.as_usize();
+                    if (target << shift) >> shift == target {
+                        let shifted_out = value_b & !(U256::MAX << shift);
+                        invert_input(b, (target << shift) | shifted_out, candidates);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+//adds witnesses that invert the first input to a target and then the second input to a target computed from its value
+//after the first is inverted, for instructions such as AND where both inputs need to change
+#[allow(clippy::too_many_arguments)]
+fn invert_both(
+    a: &Rc<Expr>,
+    b: &Rc<Expr>,
+    target_a: U256,
+    target_b: impl Fn(U256) -> U256,
+    witness: &Witness,
+    context: &SymbolicContext,
+    candidates: &mut Vec<Witness>,
+    depth: usize,
+) {
+    let mut first_candidates = vec![];
+    invert(
+        a,
+        target_a,
+        witness,
+        context,
+        &mut first_candidates,
+        depth + 1,
+    );
+
+    for first_candidate in first_candidates.iter().take(8) {
+        let value_b = Evaluator::new(first_candidate, context).evaluate(b);
+        invert(
+            b,
+            target_b(value_b),
+            first_candidate,
+            context,
+            candidates,
+            depth + 1,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::abi::parse_hex;
+    use crate::evm::interpreter::{Evm, ExitStatus, HaltReason, Transaction};
+
+    fn explore_code(code: &str) -> Exploration {
+        let code = parse_hex(&format!("0x{}", code)).unwrap();
+        let address = Address::repeat_byte(0x0C);
+        let mut state = WorldState::new();
+        state.account_mut(&address).code = code.clone();
+
+        explore(
+            &code,
+            &SymbolicContext {
+                state: &state,
+                block: &BlockEnv::default(),
+                address,
+                caller: Address::repeat_byte(0xCA),
+            },
+        )
+    }
+
+    //runs the witness of a finding on the interpreter
+    fn run_witness(code: &str, witness: &Witness) -> ExitStatus {
+        let address = Address::repeat_byte(0x0C);
+        let mut state = WorldState::new();
+        state.account_mut(&address).code = parse_hex(&format!("0x{}", code)).unwrap();
+        state.account_mut(&witness.caller).balance = witness.value;
+
+        Evm::new(state, BlockEnv::default())
+            .transact(&Transaction::new(
+                witness.caller,
+                Some(address),
+                witness.value,
+                witness.calldata.clone(),
+            ))
+            .status
+    }
+
+    #[test]
+    fn test_invalid_behind_a_selector() {
+        //jumps to INVALID if the selector is 0x12345678
+        let code = "60003560E01C63123456781460115700005BFE";
+        let exploration = explore_code(code);
+        assert!(exploration.complete);
+        assert_eq!(exploration.paths, 2);
+
+        let finding = &exploration.findings[0];
+        assert_eq!(
+            (finding.pc, &finding.issue),
+            (0x12, &Issue::InvalidOpcode(0xFE))
+        );
+        assert_eq!(finding.witness.calldata[..4], [0x12, 0x34, 0x56, 0x78]);
+        assert_eq!(
+            run_witness(code, &finding.witness),
+            ExitStatus::Halt(HaltReason::InvalidOpcode(0xFE))
+        );
+    }
+
+    #[test]
+    fn test_invalid_jump_and_stack_underflow() {
+        //jumps to 0x0b, which is not a JUMPDEST, if the calldata word at 4 is over 100,
+        //and pops an empty stack if it is 7
+        let code = "606460043511600B57600435600714601357005B50";
+        let exploration = explore_code(code);
+        let issues: Vec<(usize, &Issue)> = exploration
+            .findings
+            .iter()
+            .map(|finding| (finding.pc, &finding.issue))
+            .collect();
+
+        assert_eq!(
+            issues,
+            vec![
+                (0x08, &Issue::InvalidJump(U256::from(0x0B))),
+                (0x14, &Issue::StackUnderflow)
+            ]
+        );
+        assert_eq!(
+            run_witness(code, &exploration.findings[0].witness),
+            ExitStatus::Halt(HaltReason::InvalidJump(0x0B))
+        );
+        assert_eq!(
+            run_witness(code, &exploration.findings[1].witness),
+            ExitStatus::Halt(HaltReason::StackUnderflow)
+        );
+    }
+
+    #[test]
+    fn test_selfdestruct_needs_value_and_caller() {
+        //SELFDESTRUCT if the value is 5 and the caller is 0xaa..aa
+        let code = format!("3460051433{}{}1416602057005B33FF", "73", "AA".repeat(20));
+        let exploration = explore_code(&code);
+
+        let finding = &exploration.findings[0];
+        assert_eq!((finding.pc, &finding.issue), (0x22, &Issue::Selfdestruct));
+        assert_eq!(finding.witness.value, U256::from(5));
+        assert_eq!(finding.witness.caller, Address::repeat_byte(0xAA));
+    }
+
+    #[test]
+    fn test_calldatacopy_from_the_end_of_the_offsets() {
+        //CALLDATACOPY of 0x40 bytes from offset 0xff..ff, which reads zeros past the largest offset
+        let exploration = explore_code(&format!("60407F{}600037600051FE", "FF".repeat(32)));
+        assert!(exploration.complete);
+        assert_eq!(exploration.findings.len(), 1);
+        assert_eq!(exploration.findings[0].issue, Issue::InvalidOpcode(0xFE));
+    }
+
+    #[test]
+    fn test_mod_of_a_value_near_the_maximum() {
+        //jumps to INVALID if NOT of the first calldata word modulo 7 is 5, starting from the zero calldata, where NOT
+        //is the maximum value and 5 is more than its remainder of 1
+        let code = "60076000351906600514600E57005BFE";
+        let exploration = explore_code(code);
+        assert!(exploration.complete);
+
+        let finding = &exploration.findings[0];
+        assert_eq!(
+            (finding.pc, &finding.issue),
+            (0x0F, &Issue::InvalidOpcode(0xFE))
+        );
+        assert_eq!(
+            run_witness(code, &finding.witness),
+            ExitStatus::Halt(HaltReason::InvalidOpcode(0xFE))
+        );
+    }
+
+    #[test]
+    fn test_calls_are_not_explored() {
+        //CALL with a constant gas and zero for the other arguments
+        let exploration = explore_code("60006000600060006000600061FFFFF1");
+        assert!(exploration.findings.is_empty());
+        assert_eq!(
+            exploration
+                .unexplored
+                .into_iter()
+                .collect::<Vec<(usize, String)>>(),
+            vec![(
+                0x0F,
+                String::from("CALL runs other code, which is not explored")
+            )]
+        );
+    }
+}
@@ -1,4 +1,3 @@
-use std::error::Error;
 use std::fmt;
 #[derive(Debug)]
 pub enum EVMMError {
@@ -7,6 +6,36 @@ pub enum EVMMError {
     UnexpectedInstruction(String),
     ExpectedInstruction(),
     ContractNotFound(String),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    DuplicateFunctionSelector(String, String),
+    UndeclaredSignature(String),
+    MissingFunctionImplementation(String),
+    ParseError(String),
+    IncludeNotFound(String, String),
+    CircularInclude(String),
+    InIncludedFile(String, Box<EVMMError>),
+    UnsupportedOpcode(String, String),
+    InvalidJumpDestination(String, String, String),
+    RuntimeTooLarge(usize),
+    InvalidManifest(String, String),
+    ProjectAlreadyExists(String),
+    InitFailed(String, String),
+    WriteFailed(String, String),
+    WatchFailed(String, String),
+    InvalidTestFile(String, String),
+    TestsFailed(usize),
+    InvariantsBroken(usize),
+    InvalidReference(String, String),
+    ImplementationsDiverge(String),
+    CoverageFailed(String, String),
+    ProfileFailed(String, String),
+    AnalysisFailed(String, String),
+    IssuesFound(usize),
+    LintFailed(usize),
+    FormatFailed(String, String),
+    NotFormatted(usize),
+    LspFailed(String),
 }
 
 impl std::error::Error for EVMMError {}
@@ -41,6 +70,162 @@ impl fmt::Display for EVMMError {
             EVMMError::ContractNotFound(contract_path) => {
                 write!(f, "Contract at {:?} could not be found", contract_path)
             }
+
+            EVMMError::UndefinedLabel(label) => {
+                write!(f, "Label {:?} is referenced but never defined", label)
+            }
+
+            EVMMError::DuplicateLabel(label) => {
+                write!(f, "Label {:?} is defined more than once", label)
+            }
+
+            EVMMError::DuplicateFunctionSelector(signature, other_signature) => {
+                write!(
+                    f,
+                    "Functions {:?} and {:?} have the same selector",
+                    signature, other_signature
+                )
+            }
+
+            EVMMError::UndeclaredSignature(name) => {
+                write!(
+                    f,
+                    "{:?} is referenced but no function, event or error with that name is declared",
+                    name
+                )
+            }
+
+            EVMMError::MissingFunctionImplementation(signature) => {
+                write!(
+                    f,
+                    "Function {:?} is declared but there is no label with the same name implementing it",
+                    signature
+                )
+            }
+
+            EVMMError::ParseError(error) => {
+                write!(f, "Error when parsing file\n{}", error)
+            }
+
+            EVMMError::IncludeNotFound(include_path, including_file) => {
+                write!(
+                    f,
+                    "Included file {:?} could not be found. Included from {:?}",
+                    include_path, including_file
+                )
+            }
+
+            EVMMError::CircularInclude(include_chain) => {
+                write!(f, "Files include each other in a cycle: {}", include_chain)
+            }
+
+            EVMMError::InIncludedFile(file_path, error) => {
+                write!(f, "In included file {:?}: {}", file_path, error)
+            }
+
+            EVMMError::UnsupportedOpcode(instruction, evm_version) => {
+                write!(
+                    f,
+                    "Instruction {:?} is not available in the {:?} EVM version",
+                    instruction, evm_version
+                )
+            }
+
+            EVMMError::InvalidJumpDestination(instruction, destination, reason) => {
+                write!(f, "{} jumps to {}, {}", instruction, destination, reason)
+            }
+
+            EVMMError::RuntimeTooLarge(runtime_size) => {
+                write!(
+                    f,
+                    "The runtime bytecode is {} bytes, but the constructor can only deploy up to 65535 bytes",
+                    runtime_size
+                )
+            }
+
+            EVMMError::InvalidManifest(manifest_path, error) => {
+                write!(f, "Invalid manifest {:?}: {}", manifest_path, error)
+            }
+
+            EVMMError::ProjectAlreadyExists(directory) => {
+                write!(f, "There is already an evmm project in {:?}", directory)
+            }
+
+            EVMMError::InitFailed(file_path, error) => {
+                write!(f, "Could not create {:?}: {}", file_path, error)
+            }
+
+            EVMMError::WriteFailed(path, error) => {
+                write!(f, "Could not write {:?}: {}", path, error)
+            }
+
+            EVMMError::WatchFailed(path, error) => {
+                write!(f, "Could not watch {:?} for changes: {}", path, error)
+            }
+
+            EVMMError::InvalidTestFile(test_file_path, error) => {
+                write!(f, "Invalid test file {:?}: {}", test_file_path, error)
+            }
+
+            EVMMError::TestsFailed(failed_tests) => {
+                write!(f, "{} tests failed", failed_tests)
+            }
+
+            EVMMError::InvariantsBroken(broken_invariants) => {
+                write!(f, "{} invariants were broken", broken_invariants)
+            }
+
+            EVMMError::InvalidReference(reference_path, error) => {
+                write!(
+                    f,
+                    "Invalid reference bytecode {:?}: {}",
+                    reference_path, error
+                )
+            }
+
+            EVMMError::ImplementationsDiverge(contract_path) => {
+                write!(f, "{} diverges from the reference", contract_path)
+            }
+
+            EVMMError::CoverageFailed(output_path, error) => {
+                write!(
+                    f,
+                    "Failed to write coverage to {:?}: {}",
+                    output_path, error
+                )
+            }
+
+            EVMMError::ProfileFailed(output_path, error) => {
+                write!(
+                    f,
+                    "Failed to write the gas profile to {:?}: {}",
+                    output_path, error
+                )
+            }
+
+            EVMMError::AnalysisFailed(contract_path, error) => {
+                write!(f, "Could not analyze {:?}: {}", contract_path, error)
+            }
+
+            EVMMError::IssuesFound(issues_found) => {
+                write!(f, "{} issues were found", issues_found)
+            }
+
+            EVMMError::LintFailed(errors) => {
+                write!(f, "{} lint errors were found", errors)
+            }
+
+            EVMMError::FormatFailed(file_path, error) => {
+                write!(f, "Could not format {:?}: {}", file_path, error)
+            }
+
+            EVMMError::NotFormatted(unformatted_files) => {
+                write!(f, "{} files are not formatted", unformatted_files)
+            }
+
+            EVMMError::LspFailed(error) => {
+                write!(f, "The language server stopped: {}", error)
+            }
         }
     }
 }
@@ -0,0 +1,28 @@
+//! EVMM compiles `.evmm` contracts written in pure opcodes to EVM bytecode.
+//!
+//! ```no_run
+//! use evmm::{compile_file, compile_str, CompileOptions};
+//!
+//! let compiled_contract =
+//!     compile_str("MstoreOne", "PUSH1 0x01 PUSH1 0x00 MSTORE", &CompileOptions::default())?;
+//! assert_eq!(compiled_contract.runtime.bytecode, "6001600052");
+//!
+//! let token = compile_file("./evmm_contracts/Token.evmm", &CompileOptions::default())?;
+//! println!("{}", token.deployment_bytecode);
+//! # Ok::<(), evmm::EVMMError>(())
+//! ```
+pub mod compiler;
+pub mod core;
+pub mod evm;
+pub mod evmm_error;
+pub mod lsp;
+pub mod parser;
+pub mod utils;
+
+extern crate pest;
+#[macro_use]
+extern crate pest_derive;
+
+pub use crate::core::contract::{compile_file, compile_str, CompileOptions, CompiledContract};
+pub use crate::core::format::OutputFormat;
+pub use crate::evmm_error::evmm_error::EVMMError;
@@ -0,0 +1,880 @@
+use crate::compiler::opcodes::{opcode_description, opcode_info, OpcodeInfo};
+use crate::core::annotate::{execute, SymbolicStack};
+use crate::core::contract::{compile_file_contents, CompileOptions};
+use crate::evmm_error::evmm_error::EVMMError;
+use crate::parser::ast::{Builtin, InstructionKind, Program, PushValue};
+use crate::parser::include::parse_program_with_includes;
+use crate::parser::parse::{parse_file, EVMMParser, Rule};
+use pest::error::InputLocation;
+use pest::Parser;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+//https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#diagnosticSeverity
+const SEVERITY_ERROR: u64 = 1;
+const SEVERITY_WARNING: u64 = 2;
+
+//https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#completionItemKind
+const COMPLETION_FUNCTION: u64 = 3;
+const COMPLETION_KEYWORD: u64 = 14;
+const COMPLETION_REFERENCE: u64 = 18;
+
+//https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#symbolKind
+const SYMBOL_INTERFACE: u64 = 11;
+const SYMBOL_FUNCTION: u64 = 12;
+const SYMBOL_OBJECT: u64 = 19;
+const SYMBOL_EVENT: u64 = 24;
+
+///The built-ins and what they compile to
+const BUILTINS: [(&str, &str); 4] = [
+    (
+        "__DISPATCHER",
+        "Jumps to the label with the name of the function whose selector matches the calldata, or to the fallback label",
+    ),
+    (
+        "__FUNC_SIG",
+        "Pushes the 4 byte selector of a function, by name or by signature",
+    ),
+    (
+        "__EVENT_HASH",
+        "Pushes the 32 byte topic of an event, by name or by signature",
+    ),
+    (
+        "__ERROR",
+        "Pushes the 4 byte selector of an error, left aligned in 32 bytes so it can be stored in memory as the start of the revert data",
+    ),
+];
+
+///A position in a document as a line and a character offset in UTF-16 code units, both counting from zero
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, character: usize) -> Position {
+        Position { line, character }
+    }
+
+    ///Reads a position from its json, ex. `{"line": 2, "character": 4}`
+    pub fn from_json(position: &Value) -> Option<Position> {
+        Some(Position::new(
+            position["line"].as_u64()? as usize,
+            position["character"].as_u64()? as usize,
+        ))
+    }
+
+    pub fn to_json(self) -> Value {
+        json!({"line": self.line, "character": self.character})
+    }
+}
+
+///Converts a byte offset in the text to a position
+pub fn offset_to_position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+    Position::new(
+        before.matches('\n').count(),
+        before[line_start..].encode_utf16().count(),
+    )
+}
+
+///Converts a position to a byte offset in the text, clamping it to the end of its line and the end of the text
+pub fn position_to_offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(index) => line_start += index + 1,
+            None => return text.len(),
+        }
+    }
+    let line_end = text[line_start..]
+        .find('\n')
+        .map_or(text.len(), |index| line_start + index);
+
+    let mut units = 0;
+    for (index, character) in text[line_start..line_end].char_indices() {
+        if units >= position.character {
+            return line_start + index;
+        }
+        units += character.len_utf16();
+    }
+
+    line_end
+}
+
+fn range_json(text: &str, start: usize, end: usize) -> Value {
+    json!({
+        "start": offset_to_position(text, start).to_json(),
+        "end": offset_to_position(text, end).to_json(),
+    })
+}
+
+///Converts a `file://` uri to a path, decoding escaped characters
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+
+    let mut bytes = vec![];
+    let mut index = 0;
+    while index < path.len() {
+        let escaped = path
+            .get(index + 1..index + 3)
+            .filter(|_| path.as_bytes()[index] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                bytes.push(byte);
+                index += 3;
+            }
+            None => {
+                bytes.push(path.as_bytes()[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+///Converts a path to a `file://` uri, escaping the characters that can not appear in a uri
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.display().to_string().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+///Replaces every comment in the text with spaces, keeping the offsets and line breaks of everything else
+fn strip_comments(text: &str) -> String {
+    let mut code = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut characters = text.char_indices().peekable();
+
+    while let Some((index, character)) = characters.next() {
+        let rest = &text[index..];
+
+        if !in_string && (rest.starts_with("//") || rest.starts_with("/*")) {
+            let end = if rest.starts_with("//") {
+                rest.find('\n').unwrap_or(rest.len())
+            } else {
+                rest[2..].find("*/").map_or(rest.len(), |end| end + 4)
+            };
+
+            for comment_character in rest[..end].chars() {
+                code.push(if comment_character == '\n' { '\n' } else { ' ' });
+                for _ in 1..comment_character.len_utf8() {
+                    code.push(' ');
+                }
+            }
+            while characters
+                .peek()
+                .is_some_and(|(next_index, _)| *next_index < index + end)
+            {
+                characters.next();
+            }
+            continue;
+        }
+
+        match character {
+            '"' => in_string = !in_string,
+            '\n' => in_string = false,
+            _ => {}
+        }
+        code.push(character);
+    }
+
+    code
+}
+
+fn is_word_character(character: char) -> bool {
+    character.is_ascii_alphanumeric() || character == '_' || character == '#'
+}
+
+//the words of the code and their offsets, where `#define` and `#include` are words
+fn words(code: &str) -> Vec<(usize, &str)> {
+    let mut words = vec![];
+    let mut start = None;
+
+    for (index, character) in code.char_indices() {
+        match (start, is_word_character(character)) {
+            (None, true) => start = Some(index),
+            (Some(word_start), false) => {
+                words.push((word_start, &code[word_start..index]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(word_start) = start {
+        words.push((word_start, &code[word_start..]));
+    }
+
+    words
+}
+
+//the word at an offset, or None if the offset is in a comment or between words
+fn word_at(code: &str, offset: usize) -> Option<(usize, &str)> {
+    words(code)
+        .into_iter()
+        .find(|(start, word)| (*start..=start + word.len()).contains(&offset))
+}
+
+///A label or interface declaration in a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration {
+    pub name: String,
+    pub kind: DeclarationKind,
+    //the byte offsets of the name
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclarationKind {
+    Label,
+    Function,
+    Event,
+    Error,
+}
+
+///Finds the labels and interface declarations in a source file. The source is read word by word rather than parsed,
+///so declarations are found while the file is being edited even if it does not parse.
+pub fn declarations(text: &str) -> Vec<Declaration> {
+    let code = strip_comments(text);
+    let words = words(&code);
+    let mut declarations = vec![];
+
+    for (index, (start, word)) in words.iter().enumerate() {
+        let end = start + word.len();
+
+        if code[end..].starts_with(':') && !word.starts_with('#') {
+            declarations.push(Declaration {
+                name: word.to_string(),
+                kind: DeclarationKind::Label,
+                start: *start,
+                end,
+            });
+        } else if *word == "#define" {
+            let kind = match words.get(index + 1).map(|(_, word)| *word) {
+                Some("function") => DeclarationKind::Function,
+                Some("event") => DeclarationKind::Event,
+                Some("error") => DeclarationKind::Error,
+                _ => continue,
+            };
+            if let Some((name_start, name)) = words.get(index + 2) {
+                declarations.push(Declaration {
+                    name: name.to_string(),
+                    kind,
+                    start: *name_start,
+                    end: name_start + name.len(),
+                });
+            }
+        }
+    }
+
+    declarations
+}
+
+//the opcode of a mnemonic written the way the grammar accepts it, in lowercase or uppercase
+fn opcode_of(word: &str) -> Option<(u8, OpcodeInfo)> {
+    if word != word.to_uppercase() && word != word.to_lowercase() {
+        return None;
+    }
+
+    (0..=u8::MAX)
+        .filter_map(|opcode| opcode_info(opcode).map(|opcode_info| (opcode, opcode_info)))
+        .find(|(_, opcode_info)| opcode_info.mnemonic.eq_ignore_ascii_case(word))
+        .filter(|(_, opcode_info)| is_instruction(opcode_info.mnemonic))
+}
+
+//opcodes that the grammar does not have an instruction for are parsed as label references
+fn is_instruction(mnemonic: &str) -> bool {
+    parse_file(mnemonic)
+        .ok()
+        .and_then(|file| file.into_inner().next())
+        .is_some_and(|pair| pair.as_rule() != Rule::label_reference)
+}
+
+///Compiles the document and describes each error and warning, with the range of the source it is about
+pub fn diagnostics(file_path: &Path, text: &str, options: &CompileOptions) -> Vec<Value> {
+    let (severity, problems) = match compile_file_contents(file_path, text, options) {
+        Ok(compiled_contract) => (SEVERITY_WARNING, compiled_contract.warnings),
+        Err(error) => {
+            let (start, end) = error_range(&error, file_path, text);
+            return vec![json!({
+                "range": range_json(text, start, end),
+                "severity": SEVERITY_ERROR,
+                "source": "evmm",
+                "message": error.to_string(),
+            })];
+        }
+    };
+
+    problems
+        .into_iter()
+        .map(|problem| {
+            let (start, end) =
+                location_range(&problem, file_path, text).unwrap_or((0, line_end(text, 0)));
+            json!({
+                "range": range_json(text, start, end),
+                "severity": severity,
+                "source": "evmm",
+                "message": problem,
+            })
+        })
+        .collect()
+}
+
+//the part of the source an error is about. Errors that are not about a part of the source are shown on the first line.
+fn error_range(error: &EVMMError, file_path: &Path, text: &str) -> (usize, usize) {
+    let code = strip_comments(text);
+    let find_word = |target: &str| {
+        words(&code)
+            .into_iter()
+            .find(|(_, word)| *word == target)
+            .map(|(start, word)| (start, start + word.len()))
+    };
+
+    let range = match error {
+        //the errors from the compiler only have the line and column in their message
+        EVMMError::ParseError(_) => match EVMMParser::parse(Rule::file, text) {
+            Err(error) => match error.location {
+                InputLocation::Pos(position) => Some((position, position)),
+                InputLocation::Span(span) => Some(span),
+            },
+            Ok(_) => None,
+        },
+        EVMMError::DuplicateLabel(label) => declarations(text)
+            .into_iter()
+            .filter(|declaration| {
+                declaration.kind == DeclarationKind::Label && declaration.name == *label
+            })
+            .nth(1)
+            .map(|declaration| (declaration.start, declaration.end)),
+        EVMMError::MissingFunctionImplementation(signature) => {
+            find_word(signature.split('(').next().unwrap_or_default())
+        }
+        EVMMError::UndefinedLabel(name) | EVMMError::UndeclaredSignature(name) => find_word(name),
+        EVMMError::ValueTooBigForPushInstruction(_, value, _) => find_word(value),
+        EVMMError::UnexpectedInstruction(instruction)
+        | EVMMError::UnsupportedOpcode(instruction, _) => find_word(instruction),
+        //compile errors name the line in the included file as well, ex. `lib/math.evmm:3`
+        EVMMError::InIncludedFile(included_path, _) => include_range(
+            text,
+            included_path
+                .rsplit_once(':')
+                .filter(|(_, line)| line.parse::<usize>().is_ok())
+                .map_or(included_path, |(path, _)| path),
+        ),
+        EVMMError::IncludeNotFound(include_path, _) => text
+            .find(&format!("\"{}\"", include_path))
+            .map(|start| (start, start + include_path.len() + 2)),
+        EVMMError::InvalidJumpDestination(location, _, _) => {
+            location_range(location, file_path, text)
+        }
+        _ => None,
+    };
+
+    range.unwrap_or((0, line_end(text, 0)))
+}
+
+//the line an error or warning is about, from the location in its message, ex. `JUMP at 0x2 (Counter.evmm:2)`. Locations
+//in included files are shown on the include.
+fn location_range(message: &str, file_path: &Path, text: &str) -> Option<(usize, usize)> {
+    let (path, line) = message.split('(').skip(1).find_map(|location| {
+        let (path, line) = location.split(')').next()?.rsplit_once(':')?;
+        Some((path, line.parse::<usize>().ok()?))
+    })?;
+
+    if Path::new(path) != file_path {
+        return include_range(text, path);
+    }
+
+    let start = position_to_offset(text, Position::new(line.checked_sub(1)?, 0));
+    Some((start, line_end(text, start)))
+}
+
+//the `#include` of a file, found by its file name since the path in the include is relative
+fn include_range(text: &str, included_path: &str) -> Option<(usize, usize)> {
+    let file_name = Path::new(included_path).file_name()?;
+    let code = strip_comments(text);
+
+    let mut line_start = 0;
+    for line in code.split_inclusive('\n') {
+        if let Some(include_path) = line.trim().strip_prefix("#include") {
+            if Path::new(include_path.trim().trim_matches('"')).file_name() == Some(file_name) {
+                return Some((line_start, line_start + line.trim_end().len()));
+            }
+        }
+        line_start += line.len();
+    }
+
+    None
+}
+
+fn line_end(text: &str, offset: usize) -> usize {
+    text[offset..]
+        .find('\n')
+        .map_or(text.len(), |end| offset + end)
+}
+
+///Describes the opcode, built-in or label at a position. Opcodes are described with their gas and stack effect, along
+///with the values on the stack after the instructions on their line.
+pub fn hover(text: &str, position: Position) -> Option<Value> {
+    let code = strip_comments(text);
+    let offset = position_to_offset(text, position);
+    let (start, word) = word_at(&code, offset)?;
+
+    let contents = if let Some((opcode, opcode_info)) = opcode_of(word) {
+        let mut gas = format!("{} gas", opcode_info.static_gas);
+        if opcode_info.dynamic_gas {
+            gas.push_str(
+                " plus a cost that depends on its inputs, memory expansion or account access",
+            );
+        }
+
+        let mut contents = format!(
+            "**{}** `0x{:02X}`\n\n{}\n\nTakes {} values from the stack and leaves {}, costs {}",
+            opcode_info.mnemonic,
+            opcode,
+            opcode_description(opcode).unwrap_or_default(),
+            opcode_info.stack_inputs,
+            opcode_info.stack_outputs,
+            gas
+        );
+        if let Some(stack) = stack_after_line(text, position.line) {
+            contents.push_str(&format!("\n\nStack after this line: `{}`", stack));
+            if stack.contains("$0") {
+                contents.push_str(
+                    "\n\n`$0`, `$1`, ... are the values on the stack when the block started, from the top",
+                );
+            }
+        }
+        contents
+    } else if let Some((name, description)) = BUILTINS.iter().find(|(name, _)| *name == word) {
+        format!("**{}**\n\n{}", name, description)
+    } else {
+        let declaration = declarations(text)
+            .into_iter()
+            .find(|declaration| declaration.name == word)?;
+        let kind = match declaration.kind {
+            DeclarationKind::Label => "Label",
+            DeclarationKind::Function => "Function",
+            DeclarationKind::Event => "Event",
+            DeclarationKind::Error => "Error",
+        };
+        format!(
+            "{} `{}`, declared on line {}",
+            kind,
+            declaration.name,
+            offset_to_position(text, declaration.start).line + 1
+        )
+    };
+
+    Some(json!({
+        "contents": {"kind": "markdown", "value": contents},
+        "range": range_json(text, start, start + word.len()),
+    }))
+}
+
+///Follows the values on the stack through the instructions from the start of the block up to and including the
+///instructions on a line, counting from zero, with the symbolic stack of the annotated listing. Values that are too
+///long are named by the mnemonic and line of the instruction, ex. `add@L9`. Returns the stack from the top, ex.
+///`[add(0x01, sload(0x00)), $0]`, or None if the file does not parse.
+pub fn stack_after_line(text: &str, line: usize) -> Option<String> {
+    let program = Program::from_pair(parse_file(text).ok()?, 0).ok()?;
+
+    let mut stack = SymbolicStack::default();
+    for instruction in program.instructions.iter() {
+        let instruction_line = offset_to_position(text, instruction.span.start).line;
+        if instruction_line > line {
+            break;
+        }
+
+        match &instruction.kind {
+            //a block starts at every label, which can be reached with any stack
+            InstructionKind::Label(_) => stack.reset(),
+
+            //the dispatcher jumps away, and included instructions are not known
+            InstructionKind::Builtin(Builtin::Dispatcher { .. }) | InstructionKind::Include(_) => {
+                stack.reset()
+            }
+
+            InstructionKind::Builtin(_) => stack.push(
+                text[instruction.span.start..instruction.span.end]
+                    .split_whitespace()
+                    .collect::<String>(),
+            ),
+
+            //pushes are shown like the push data in the listing, and label offsets by name
+            InstructionKind::Push { size, value } => {
+                let opcode = 0x5F + *size as u8;
+                let pushed_value = match value {
+                    PushValue::Label(label) => label.clone(),
+                    PushValue::Literal(bytes) => format!(
+                        "0x{}{}",
+                        "00".repeat(size - bytes.len()),
+                        bytes
+                            .iter()
+                            .map(|byte| format!("{:02x}", byte))
+                            .collect::<String>()
+                    ),
+                };
+                if let Some(opcode_info) = opcode_info(opcode) {
+                    execute(
+                        opcode,
+                        &opcode_info,
+                        pushed_value,
+                        &format!("L{}", instruction_line + 1),
+                        &mut stack,
+                    );
+                }
+            }
+
+            InstructionKind::Opcode(opcode) => match opcode_info(*opcode) {
+                Some(opcode_info) => execute(
+                    *opcode,
+                    &opcode_info,
+                    String::new(),
+                    &format!("L{}", instruction_line + 1),
+                    &mut stack,
+                ),
+                None => stack.reset(),
+            },
+
+            InstructionKind::Definition(_) => {}
+        }
+    }
+
+    Some(stack.to_string())
+}
+
+///Finds where the label, function, event or error at a position is declared, in the document or in the files it
+///includes. Returns the path of the file, its contents and the byte offsets of the declaration.
+pub fn definition(
+    file_path: &Path,
+    text: &str,
+    position: Position,
+    include_paths: &[PathBuf],
+) -> Option<(PathBuf, String, usize, usize)> {
+    let code = strip_comments(text);
+    let (_, word) = word_at(&code, position_to_offset(text, position))?;
+
+    //the included files can only be found if every include resolves
+    let sources = match parse_program_with_includes(text, file_path, include_paths) {
+        Ok(program) => program
+            .sources
+            .into_iter()
+            .map(|source| (PathBuf::from(source.path), source.contents))
+            .collect(),
+        Err(_) => vec![(file_path.to_path_buf(), text.to_owned())],
+    };
+
+    //labels are looked for first, since functions are implemented by labels with the same name
+    for kinds in [
+        &[DeclarationKind::Label][..],
+        &[
+            DeclarationKind::Function,
+            DeclarationKind::Event,
+            DeclarationKind::Error,
+        ][..],
+    ] {
+        for (path, contents) in sources.iter() {
+            if let Some(declaration) = declarations(contents)
+                .into_iter()
+                .find(|declaration| declaration.name == word && kinds.contains(&declaration.kind))
+            {
+                return Some((
+                    path.clone(),
+                    contents.clone(),
+                    declaration.start,
+                    declaration.end,
+                ));
+            }
+        }
+    }
+
+    None
+}
+
+///Describes a declaration as a location, ex. for the response to a definition request
+pub fn location_json(path: &Path, contents: &str, start: usize, end: usize) -> Value {
+    json!({
+        "uri": path_to_uri(path),
+        "range": range_json(contents, start, end),
+    })
+}
+
+///Lists the mnemonics, built-ins and the labels of the document as completion items
+pub fn completions(text: &str) -> Vec<Value> {
+    let mut items = vec![];
+
+    for opcode in 0..=u8::MAX {
+        let opcode_info = match opcode_info(opcode) {
+            Some(opcode_info) if is_instruction(opcode_info.mnemonic) => opcode_info,
+            _ => continue,
+        };
+        items.push(json!({
+            "label": opcode_info.mnemonic,
+            "kind": COMPLETION_KEYWORD,
+            "detail": format!(
+                "0x{:02X}, takes {} and leaves {}, {} gas",
+                opcode,
+                opcode_info.stack_inputs,
+                opcode_info.stack_outputs,
+                opcode_info.static_gas
+            ),
+            "documentation": opcode_description(opcode).unwrap_or_default(),
+        }));
+    }
+
+    for (name, description) in BUILTINS {
+        items.push(json!({
+            "label": name,
+            "kind": COMPLETION_FUNCTION,
+            "documentation": description,
+        }));
+    }
+
+    for declaration in declarations(text) {
+        if declaration.kind == DeclarationKind::Label {
+            items.push(json!({
+                "label": declaration.name,
+                "kind": COMPLETION_REFERENCE,
+                "detail": "label",
+            }));
+        }
+    }
+
+    items
+}
+
+///Lists the labels and interface declarations of the document as symbols. The range of a label covers the
+///instructions up to the next label.
+pub fn document_symbols(text: &str) -> Vec<Value> {
+    let declarations = declarations(text);
+    let label_starts: Vec<usize> = declarations
+        .iter()
+        .filter(|declaration| declaration.kind == DeclarationKind::Label)
+        .map(|declaration| declaration.start)
+        .collect();
+
+    declarations
+        .iter()
+        .map(|declaration| {
+            let (kind, end) = match declaration.kind {
+                DeclarationKind::Label => {
+                    let next_label = label_starts
+                        .iter()
+                        .find(|start| **start > declaration.start)
+                        .copied()
+                        .unwrap_or(text.len());
+                    (SYMBOL_FUNCTION, text[..next_label].trim_end().len())
+                }
+                DeclarationKind::Function => (SYMBOL_INTERFACE, declaration.end),
+                DeclarationKind::Event => (SYMBOL_EVENT, declaration.end),
+                DeclarationKind::Error => (SYMBOL_OBJECT, declaration.end),
+            };
+
+            json!({
+                "name": declaration.name,
+                "kind": kind,
+                "range": range_json(text, declaration.start, end.max(declaration.end)),
+                "selectionRange": range_json(text, declaration.start, declaration.end),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COUNTER: &str = r#"#define function increment()
+
+__DISPATCHER(fallback)
+
+//adds one to the count
+increment:
+    PUSH1 0x00
+    SLOAD //count
+    PUSH1 0x01 ADD
+    CALLER SWAP1
+    PUSH1 0x00
+    SSTORE
+    STOP
+
+fallback:
+    PUSH1 0x00
+    DUP1
+    REVERT
+"#;
+
+    #[test]
+    fn test_positions() {
+        let text = "PUSH1 0x01\n//ü\nADD";
+
+        assert_eq!(offset_to_position(text, 13), Position::new(1, 2));
+        assert_eq!(position_to_offset(text, Position::new(1, 2)), 13);
+        assert_eq!(offset_to_position(text, 17), Position::new(2, 1));
+        assert_eq!(position_to_offset(text, Position::new(2, 1)), 17);
+        //positions past the end of a line are at the end of the line
+        assert_eq!(position_to_offset(text, Position::new(0, 40)), 10);
+    }
+
+    #[test]
+    fn test_uris() {
+        let path = Path::new("/home/user/my contracts/Counter.evmm");
+        let uri = path_to_uri(path);
+
+        assert_eq!(uri, "file:///home/user/my%20contracts/Counter.evmm");
+        assert_eq!(uri_to_path(&uri).unwrap(), path);
+    }
+
+    #[test]
+    fn test_declarations() {
+        let declarations = declarations(COUNTER);
+
+        assert_eq!(
+            declarations
+                .iter()
+                .map(|declaration| (declaration.name.as_str(), declaration.kind))
+                .collect::<Vec<_>>(),
+            vec![
+                ("increment", DeclarationKind::Function),
+                ("increment", DeclarationKind::Label),
+                ("fallback", DeclarationKind::Label),
+            ]
+        );
+        assert_eq!(
+            &COUNTER[declarations[1].start..declarations[1].end],
+            "increment"
+        );
+    }
+
+    #[test]
+    fn test_stack_after_line() {
+        assert_eq!(stack_after_line(COUNTER, 7).unwrap(), "[sload(0x00)]");
+        assert_eq!(
+            stack_after_line(COUNTER, 9).unwrap(),
+            "[add(0x01, sload(0x00)), caller]"
+        );
+        assert_eq!(stack_after_line(COUNTER, 12).unwrap(), "[]");
+        assert_eq!(stack_after_line(COUNTER, 16).unwrap(), "[0x00, 0x00]");
+
+        //values from before the block are numbered from the top
+        assert_eq!(
+            stack_after_line("start:\nSWAP1 DUP3 ADD", 1).unwrap(),
+            "[add($2, $1), $0, $2]"
+        );
+
+        //values that are too long are named by their line
+        assert_eq!(
+            stack_after_line("PUSH32 0x01\nPUSH32 0x02\nADD", 2).unwrap(),
+            "[add@L3]"
+        );
+    }
+
+    #[test]
+    fn test_hover() {
+        let hover = hover(COUNTER, Position::new(8, 17)).unwrap();
+        let contents = hover["contents"]["value"].as_str().unwrap();
+
+        assert!(contents.starts_with("**ADD** `0x01`\n\nAdds the top two values"));
+        assert!(contents.contains("Takes 2 values from the stack and leaves 1, costs 3 gas"));
+        assert!(contents.contains("Stack after this line: `[add(0x01, sload(0x00))]`"));
+        assert_eq!(hover["range"]["start"], Position::new(8, 15).to_json());
+
+        //comments are not described
+        assert!(super::hover(COUNTER, Position::new(7, 12)).is_none());
+    }
+
+    #[test]
+    fn test_definition() {
+        let (path, _, start, end) = definition(
+            Path::new("Counter.evmm"),
+            COUNTER,
+            Position::new(2, 16),
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(path, Path::new("Counter.evmm"));
+        assert_eq!(offset_to_position(COUNTER, start), Position::new(14, 0));
+        assert_eq!(&COUNTER[start..end], "fallback");
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let diagnostics = diagnostics(
+            Path::new("Counter.evmm"),
+            "start:\nPUSH2 missing\nJUMP",
+            &CompileOptions::default(),
+        );
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["severity"], SEVERITY_ERROR);
+        assert_eq!(
+            diagnostics[0]["range"],
+            json!({"start": {"line": 1, "character": 6}, "end": {"line": 1, "character": 13}})
+        );
+
+        //jumps that can not be checked are warnings on their line
+        let diagnostics = super::diagnostics(
+            Path::new("Counter.evmm"),
+            "CALLDATASIZE\nJUMP",
+            &CompileOptions::default(),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["severity"], SEVERITY_WARNING);
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            Position::new(1, 0).to_json()
+        );
+
+        //errors in an included file are shown on the include
+        let directory = std::env::temp_dir().join("evmm_lsp_included_diagnostics");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(directory.join("lib")).unwrap();
+        std::fs::write(directory.join("lib/bad.evmm"), "PUSH1 0x00\nADD").unwrap();
+        let diagnostics = super::diagnostics(
+            &directory.join("Main.evmm"),
+            "#include \"./lib/bad.evmm\"\nSTOP",
+            &CompileOptions::default(),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0]["range"],
+            json!({"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 25}})
+        );
+    }
+
+    #[test]
+    fn test_completions_and_symbols() {
+        let completions = completions(COUNTER);
+        let labels: Vec<&str> = completions
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert!(labels.contains(&"MSTORE8"));
+        assert!(labels.contains(&"__FUNC_SIG"));
+        assert!(labels.contains(&"fallback"));
+        //INVALID has no instruction in the grammar
+        assert!(!labels.contains(&"INVALID"));
+
+        let symbols = document_symbols(COUNTER);
+        assert_eq!(symbols.len(), 3);
+        assert_eq!(symbols[1]["name"], "increment");
+        assert_eq!(
+            symbols[1]["range"],
+            json!({"start": {"line": 5, "character": 0}, "end": {"line": 12, "character": 8}})
+        );
+    }
+}
@@ -0,0 +1,3 @@
+pub mod document;
+pub mod server;
+pub mod transport;
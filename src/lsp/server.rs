@@ -0,0 +1,265 @@
+use crate::core::manifest::Manifest;
+use crate::evmm_error::evmm_error::EVMMError;
+use crate::lsp::document::{
+    completions, definition, diagnostics, document_symbols, hover, location_json, uri_to_path,
+    Position,
+};
+use crate::lsp::transport::{read_message, write_message};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+//https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#errorCodes
+const METHOD_NOT_FOUND: i64 = -32601;
+
+//https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#textDocumentSyncKind
+const FULL_SYNC: u64 = 1;
+
+///Runs a language server, reading requests from the reader and writing responses and diagnostics to the writer
+///until the client sends `exit` or closes the input
+pub fn run_server<R: BufRead, W: Write>(reader: &mut R, writer: &mut W) -> Result<(), EVMMError> {
+    let mut server = Server {
+        documents: HashMap::new(),
+        writer,
+    };
+
+    while let Some(message) =
+        read_message(reader).map_err(|error| EVMMError::LspFailed(error.to_string()))?
+    {
+        if message["method"] == "exit" {
+            break;
+        }
+        server
+            .handle(&message)
+            .map_err(|error| EVMMError::LspFailed(error.to_string()))?;
+    }
+
+    Ok(())
+}
+
+struct Server<'a, W: Write> {
+    //the text of each open document, by uri
+    documents: HashMap<String, String>,
+    writer: &'a mut W,
+}
+
+impl<W: Write> Server<'_, W> {
+    fn handle(&mut self, message: &Value) -> std::io::Result<()> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned();
+        let position = Position::from_json(&params["position"]);
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": FULL_SYNC,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {},
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": {"name": "evmm", "version": env!("CARGO_PKG_VERSION")},
+            }),
+
+            "shutdown" => Value::Null,
+
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_owned());
+                return self.publish_diagnostics(&uri);
+            }
+
+            //the whole text is sent with every change
+            "textDocument/didChange" => {
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.clone(), text.to_owned());
+                }
+                return self.publish_diagnostics(&uri);
+            }
+
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return self.notify(
+                    "textDocument/publishDiagnostics",
+                    json!({"uri": uri, "diagnostics": []}),
+                );
+            }
+
+            "textDocument/hover" => match (self.documents.get(&uri), position) {
+                (Some(text), Some(position)) => hover(text, position).unwrap_or(Value::Null),
+                _ => Value::Null,
+            },
+
+            "textDocument/definition" => match (self.documents.get(&uri), position) {
+                (Some(text), Some(position)) => {
+                    let file_path = document_path(&uri);
+                    let include_paths = manifest(&file_path).include_paths;
+                    definition(&file_path, text, position, &include_paths)
+                        .map(|(path, contents, start, end)| {
+                            location_json(&path, &contents, start, end)
+                        })
+                        .unwrap_or(Value::Null)
+                }
+                _ => Value::Null,
+            },
+
+            "textDocument/completion" => match self.documents.get(&uri) {
+                Some(text) => json!(completions(text)),
+                None => Value::Null,
+            },
+
+            "textDocument/documentSymbol" => match self.documents.get(&uri) {
+                Some(text) => json!(document_symbols(text)),
+                None => Value::Null,
+            },
+
+            //requests that are not supported are answered with an error, and other notifications are ignored
+            _ => {
+                if message.get("id").is_some() && !method.is_empty() {
+                    return write_message(
+                        self.writer,
+                        &json!({
+                            "jsonrpc": "2.0",
+                            "id": message["id"],
+                            "error": {
+                                "code": METHOD_NOT_FOUND,
+                                "message": format!("{} is not supported", method),
+                            },
+                        }),
+                    );
+                }
+                return Ok(());
+            }
+        };
+
+        write_message(
+            self.writer,
+            &json!({"jsonrpc": "2.0", "id": message["id"], "result": result}),
+        )
+    }
+
+    //compiles the document with the settings of the manifest of its project
+    fn publish_diagnostics(&mut self, uri: &str) -> std::io::Result<()> {
+        let text = match self.documents.get(uri) {
+            Some(text) => text,
+            None => return Ok(()),
+        };
+
+        let file_path = document_path(uri);
+        let contract_name = file_path
+            .file_stem()
+            .map(|file_stem| file_stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let options = manifest(&file_path).compile_options(&contract_name);
+
+        let diagnostics = diagnostics(&file_path, text, &options);
+        self.notify(
+            "textDocument/publishDiagnostics",
+            json!({"uri": uri, "diagnostics": diagnostics}),
+        )
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> std::io::Result<()> {
+        write_message(
+            self.writer,
+            &json!({"jsonrpc": "2.0", "method": method, "params": params}),
+        )
+    }
+}
+
+//documents that are not files, such as unsaved buffers, are compiled as if they were in the current directory
+fn document_path(uri: &str) -> PathBuf {
+    uri_to_path(uri).unwrap_or_else(|| PathBuf::from(uri.rsplit('/').next().unwrap_or_default()))
+}
+
+//the manifest of the project the document is in, or the default settings if there is none
+fn manifest(file_path: &Path) -> Manifest {
+    let directory = file_path.parent().unwrap_or(Path::new("."));
+
+    Manifest::find(directory).ok().flatten().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    }
+
+    fn notification(method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "method": method, "params": params})
+    }
+
+    #[test]
+    fn test_session() {
+        let uri = "file:///nonexistent/Counter.evmm";
+        let mut input = vec![];
+        for message in [
+            request(1, "initialize", json!({"capabilities": {}})),
+            notification("initialized", json!({})),
+            notification(
+                "textDocument/didOpen",
+                json!({"textDocument": {"uri": uri, "languageId": "evmm", "version": 1, "text": "PUSH2 missing\nJUMP"}}),
+            ),
+            notification(
+                "textDocument/didChange",
+                json!({"textDocument": {"uri": uri, "version": 2}, "contentChanges": [{"text": "start:\nPUSH2 start\nJUMP"}]}),
+            ),
+            request(
+                2,
+                "textDocument/definition",
+                json!({"textDocument": {"uri": uri}, "position": {"line": 1, "character": 8}}),
+            ),
+            request(
+                3,
+                "textDocument/formatting",
+                json!({"textDocument": {"uri": uri}}),
+            ),
+            request(4, "shutdown", Value::Null),
+            notification("exit", Value::Null),
+        ] {
+            write_message(&mut input, &message).unwrap();
+        }
+
+        let mut output = vec![];
+        run_server(&mut &input[..], &mut output).unwrap();
+
+        let mut reader = &output[..];
+        let mut messages = vec![];
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            messages.push(message);
+        }
+
+        assert_eq!(messages.len(), 6);
+        assert_eq!(messages[0]["result"]["capabilities"]["hoverProvider"], true);
+
+        //the undefined label is reported when the document opens, and cleared once it is defined
+        let diagnostics = &messages[1]["params"]["diagnostics"];
+        assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+        assert_eq!(
+            diagnostics[0]["message"],
+            "Label \"missing\" is referenced but never defined"
+        );
+        assert_eq!(messages[2]["params"]["diagnostics"], json!([]));
+
+        assert_eq!(
+            messages[3]["result"],
+            json!({"uri": uri, "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 5}}})
+        );
+        assert_eq!(messages[4]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(
+            messages[5],
+            json!({"jsonrpc": "2.0", "id": 4, "result": null})
+        );
+    }
+}
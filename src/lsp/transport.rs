@@ -0,0 +1,75 @@
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+
+///Reads a json-rpc message framed by a `Content-Length` header, or None once the input is closed
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+
+    //the headers end with an empty line
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let content_length = content_length.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message without a Content-Length header",
+        )
+    })?;
+
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content)?;
+
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+///Writes a json-rpc message with its `Content-Length` header
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(
+        writer,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_read_written_messages() {
+        let mut input = vec![];
+        write_message(&mut input, &json!({"jsonrpc": "2.0", "method": "exit"})).unwrap();
+        write_message(&mut input, &json!({"text": "PUSH1 0x01 //ü"})).unwrap();
+
+        let mut reader = &input[..];
+        assert_eq!(
+            read_message(&mut reader).unwrap(),
+            Some(json!({"jsonrpc": "2.0", "method": "exit"}))
+        );
+        assert_eq!(
+            read_message(&mut reader).unwrap(),
+            Some(json!({"text": "PUSH1 0x01 //ü"}))
+        );
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+}
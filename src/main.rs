@@ -1,16 +1,22 @@
-use std::env;
-use std::fs;
-mod compiler;
-mod core;
-mod evmm_error;
-mod parser;
-use crate::core::evmm::{evmm_parse_and_compile, DEFAULT_COMPILATION_DIR};
-
-use crate::evmm_error::evmm_error::EVMMError;
-use clap::{Arg, ArgAction, Command};
-extern crate pest;
-#[macro_use]
-extern crate pest_derive;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use evmm::compiler::evm_version::EvmVersion;
+use evmm::core::analyze::analyze_contracts;
+use evmm::core::coverage::run_coverage;
+use evmm::core::differential::compare_with_reference;
+use evmm::core::evmm::evmm_parse_and_compile;
+use evmm::core::fmt::format_contracts;
+use evmm::core::fuzz::{fuzz_contracts, FuzzSettings};
+use evmm::core::init::init_project;
+use evmm::core::lint::lint_contracts;
+use evmm::core::manifest::Manifest;
+use evmm::core::profile::run_gas_profile;
+use evmm::core::test_runner::run_tests;
+use evmm::core::watch::watch_contracts;
+use evmm::lsp::server::run_server;
+use evmm::{EVMMError, OutputFormat};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 fn main() -> Result<(), EVMMError> {
     let matches = Command::new("evmm")
@@ -22,14 +28,14 @@ fn main() -> Result<(), EVMMError> {
         // compile subcommand
         .subcommand(
             Command::new("compile")
-            .about("Compile .evmm contracts to bytecode. If a specific contract is provided, the compile command will only compile that contract. If no contract is provided, the compile command will compile everything in `./src/contracts/` by default. You can set the target directory to compile with the `-d` option. The contract bytecode is compiled by default, however you can specify the --deployment-bytecode or -d flag to compile the deployement bytecode See `compile --help` for full usage.")
+            .about("Compile .evmm contracts to bytecode. If a specific contract is provided, the compile command will only compile that contract. If no contract is provided, the compile command will compile everything in the source directories of the `evmm.toml` manifest, or `./evmm_contracts/` if there is no manifest. You can set the target directory to compile with the `-t` option. The contract bytecode is compiled by default, however you can specify the --deployment-bytecode or -d flag to compile the deployement bytecode See `compile --help` for full usage.")
                 //
                 //--contract option
                 .arg(
                     Arg::new("contract")
                         .long("contract")
                         .short('c')
-                        .help("Target a specific contract to compile. This flag takes a path as the argument. If the filename of the contract is only provided, the program will look in the ./evmm_contracts directory by default.")
+                        .help("Target a specific contract to compile. This flag takes a path as the argument. If the filename of the contract is only provided, the program will look in the source directories of the manifest, or ./evmm_contracts by default.")
                         .conflicts_with("target-directory")
                         .action(ArgAction::Set)
                         .number_of_values(1),
@@ -71,13 +77,313 @@ fn main() -> Result<(), EVMMError> {
                         .conflicts_with("print")
                         .help("Outputs the bytecode for each compiled contract as a `.evmasm` file into the specified output directory. This command can be chained with `--bytecode` or `--deploymentBytecode`, but if neither is specified, the `--output` option will default to writing the contract's bytecode to the output file. For example, to write a specific contract's bytecode to a file, you can use `compile <contract_name.evmm> --bytecode --output. To compile all contracts and write the deployment bytecode, you can use `compile --deploymentBytecode --output`")
                         .action(ArgAction::Set).number_of_values(1),
+                )
+                //
+                //--format option
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .help("The format to output the bytecode in. `hex` writes uppercase hex without a prefix, `0x` writes lowercase hex with a `0x` prefix, `binary` writes the raw bytes, `rust` and `solidity` write a byte array constant `listing` writes `<contract>.lst` with the offset, bytes, mnemonic and source line of each instruction and `annotated` writes `<contract>.asm` with the offset, bytes, mnemonic, static gas and symbolic stack of each instruction, with labels on their own lines. Can be given more than once to write several formats. Defaults to the formats in the manifest, or `hex`.")
+                        .value_parser(OutputFormat::NAMES)
+                        .action(ArgAction::Append)
+                        .number_of_values(1),
+                )
+                //
+                //--force option
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("Recompiles every contract, even the contracts that have not changed since they were last compiled.")
+                )
+                //
+                //--watch option
+                .arg(
+                    Arg::new("watch")
+                        .long("watch")
+                        .short('w')
+                        .conflicts_with("print")
+                        .help("Keeps running after compiling and recompiles the contracts affected by each change to the contracts or the files they include, printing errors or the size and deployment gas of each recompiled contract.")
+                )
+                //
+                //--evm-version option
+                .arg(
+                    Arg::new("evm-version")
+                        .long("evm-version")
+                        .help("The EVM version to target. Compilation fails if the contract uses an opcode that is not available in this version. Defaults to the version in the manifest, or `cancun`.")
+                        .value_parser(EvmVersion::NAMES)
+                        .action(ArgAction::Set)
+                        .number_of_values(1),
                 ),
         )
         //
         // init subcommand
         .subcommand(
             Command::new("init")
-                .about("Creates a new Foundry project with all the moving parts to test EVMM contracts")
+                .about("Creates a new project with a manifest, an example contract and tests that can be run with `evmm test`. Existing files are left as they are.")
+                //
+                //directory argument
+                .arg(
+                    Arg::new("directory")
+                        .help("The directory to create the project in. Defaults to the current directory.")
+                        .default_value(".")
+                        .action(ArgAction::Set),
+                )
+                //
+                //--foundry option
+                .arg(
+                    Arg::new("foundry")
+                        .long("foundry")
+                        .help("Also creates a Foundry config, a deployer contract that compiles and deploys EVMM contracts from Foundry tests and an example Foundry test. Install forge-std with `forge install foundry-rs/forge-std` to run them.")
+                )
+        )
+        //
+        // test subcommand
+        .subcommand(
+            Command::new("test")
+                .about("Runs the `.t.toml` test files in the tests directory of the manifest, or `./evmm_tests/` if there is no manifest. Each test deploys a freshly compiled copy of its contract to the built-in EVM interpreter, makes its calls and checks the return data, reverts, logs and storage, printing the differences for each failing test.")
+                //
+                //--match option
+                .arg(
+                    Arg::new("match")
+                        .long("match")
+                        .short('m')
+                        .help("Only runs the tests whose name contains this text.")
+                        .action(ArgAction::Set)
+                        .number_of_values(1),
+                )
+        )
+        //
+        // coverage subcommand
+        .subcommand(
+            Command::new("coverage")
+                .about("Runs the tests in the `.t.toml` files while recording which instructions run, then prints the lines, labels, basic blocks and JUMPI branches covered in each source file and writes the coverage as lcov.")
+                //
+                //--output option
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("The file the lcov coverage is written to.")
+                        .default_value("lcov.info")
+                        .action(ArgAction::Set),
+                )
+                //
+                //--match option
+                .arg(
+                    Arg::new("match")
+                        .long("match")
+                        .short('m')
+                        .help("Only runs the tests whose name contains this text.")
+                        .action(ArgAction::Set)
+                        .number_of_values(1),
+                )
+        )
+        //
+        // profile subcommand
+        .subcommand(
+            Command::new("profile")
+                .about("Runs the tests in the `.t.toml` files while recording the gas used by every instruction, then prints the gas used by each label and built-in and the lines that used the most gas. The gas is also written as folded stacks of contract, label, built-in and line, which flame graph tools such as flamegraph.pl, inferno and speedscope can show.")
+                //
+                //--output option
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("The file the folded stacks are written to.")
+                        .default_value("gas.folded")
+                        .action(ArgAction::Set),
+                )
+                //
+                //--top option
+                .arg(
+                    Arg::new("top")
+                        .long("top")
+                        .help("The number of hottest lines to print.")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10")
+                        .action(ArgAction::Set),
+                )
+                //
+                //--match option
+                .arg(
+                    Arg::new("match")
+                        .long("match")
+                        .short('m')
+                        .help("Only runs the tests whose name contains this text.")
+                        .action(ArgAction::Set)
+                        .number_of_values(1),
+                )
+        )
+        //
+        // fuzz subcommand
+        .subcommand(
+            Command::new("fuzz")
+                .about("Checks the `[[invariant]]` properties declared in the `.t.toml` test files by running random call sequences against fresh deployments of each contract on the built-in EVM interpreter. Calls are generated from the declared interface, with random callers and values. Each broken invariant is reported with the shortest call sequence found that breaks it, written as a test that `evmm test` can run.")
+                //
+                //--runs option
+                .arg(
+                    Arg::new("runs")
+                        .long("runs")
+                        .help("The number of call sequences to run for each contract.")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("256")
+                        .action(ArgAction::Set),
+                )
+                //
+                //--depth option
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .help("The number of calls in each sequence.")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10")
+                        .action(ArgAction::Set),
+                )
+                //
+                //--seed option
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .help("The seed of the random calls, which reproduces an earlier run. Defaults to a seed based on the current time, which is printed.")
+                        .value_parser(clap::value_parser!(u64))
+                        .action(ArgAction::Set),
+                )
+                //
+                //--match option
+                .arg(
+                    Arg::new("match")
+                        .long("match")
+                        .short('m')
+                        .help("Only checks the invariants whose name contains this text.")
+                        .action(ArgAction::Set)
+                        .number_of_values(1),
+                )
+        )
+        //
+        // diff subcommand
+        .subcommand(
+            Command::new("diff")
+                .about("Compares a contract with a reference implementation by making the same random calls to both on the built-in EVM interpreter. Calls are generated from the declared interface of the contract. The first divergence in return data, reverts, logs or storage is reported with the shortest call sequence found that causes it, written as a test that `evmm test` can run.")
+                //
+                //--contract option
+                .arg(
+                    Arg::new("contract")
+                        .long("contract")
+                        .short('c')
+                        .help("The contract to compare. This flag takes a path as the argument. If the filename of the contract is only provided, the program will look in the source directories of the manifest, or ./evmm_contracts by default.")
+                        .required(true)
+                        .action(ArgAction::Set)
+                        .number_of_values(1),
+                )
+                //
+                //--reference option
+                .arg(
+                    Arg::new("reference")
+                        .long("reference")
+                        .short('r')
+                        .help("A file with the deployment bytecode of the reference as hex, or a json artifact with a `bytecode` field.")
+                        .required(true)
+                        .action(ArgAction::Set)
+                        .number_of_values(1),
+                )
+                //
+                //--reference-runtime flag
+                .arg(
+                    Arg::new("reference-runtime")
+                        .long("reference-runtime")
+                        .help("The reference is runtime bytecode, which is installed at the contract address without running a constructor. Json artifacts use their `deployedBytecode` field."),
+                )
+                //
+                //--runs option
+                .arg(
+                    Arg::new("runs")
+                        .long("runs")
+                        .help("The number of call sequences to run.")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("256")
+                        .action(ArgAction::Set),
+                )
+                //
+                //--depth option
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .help("The number of calls in each sequence.")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10")
+                        .action(ArgAction::Set),
+                )
+                //
+                //--seed option
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .help("The seed of the random calls, which reproduces an earlier run. Defaults to a seed based on the current time, which is printed.")
+                        .value_parser(clap::value_parser!(u64))
+                        .action(ArgAction::Set),
+                )
+        )
+        //
+        // lint subcommand
+        .subcommand(
+            Command::new("lint")
+                .about("Checks the compiled bytecode of each contract for unchecked call success values, non-payable functions that do not check CALLVALUE, DELEGATECALL, CALLCODE and SELFDESTRUCT, TIMESTAMP or DIFFICULTY (PREVRANDAO since the merge) used as randomness, authorization with ORIGIN, RETURNDATACOPY without checking RETURNDATASIZE and SSTORE after a call. Rules are set to `off`, `warn` or `error` in the `[lint]` table of the manifest, and a `//lint-ignore <rule>` comment ignores a rule on its line, or on the next line if the comment is on a line of its own. Fails if any rule at the `error` level is broken.")
+                //
+                //--contract option
+                .arg(
+                    Arg::new("contract")
+                        .long("contract")
+                        .short('c')
+                        .help("Only lints this contract. This flag takes a path as the argument. If the filename of the contract is only provided, the program will look in the source directories of the manifest, or ./evmm_contracts by default.")
+                        .action(ArgAction::Set)
+                        .number_of_values(1),
+                )
+        )
+        //
+        // fmt subcommand
+        .subcommand(
+            Command::new("fmt")
+                .about("Formats each contract and the files in the include paths, keeping their comments and the lines their instructions are on. Mnemonics are written in uppercase, hex push values as `0x` followed by uppercase digits padded to whole bytes, instructions after a label are indented and the comments at the end of consecutive lines are aligned.")
+                //
+                //--contract option
+                .arg(
+                    Arg::new("contract")
+                        .long("contract")
+                        .short('c')
+                        .help("Only formats this contract. This flag takes a path as the argument. If the filename of the contract is only provided, the program will look in the source directories of the manifest, or ./evmm_contracts by default.")
+                        .action(ArgAction::Set)
+                        .number_of_values(1),
+                )
+                //
+                //--check flag
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .help("Lists the files that are not formatted without changing them, and fails if there are any."),
+                )
+        )
+        //
+        // lsp subcommand
+        .subcommand(
+            Command::new("lsp")
+                .about("Runs a language server over stdin and stdout for editors. It reports compiler errors and warnings as diagnostics, describes opcodes on hover with their gas, stack effect and the values on the stack after the line, goes to the definition of labels, functions, events and errors, completes mnemonics, built-ins and labels, and lists the labels and declarations of a file as symbols.")
+        )
+        //
+        // analyze subcommand
+        .subcommand(
+            Command::new("analyze")
+                .about("Symbolically executes every path of a call to each contract, solving for the calldata, caller and value that take each branch. Paths that reach INVALID or an undefined opcode, jump to an offset that is not a JUMPDEST, underflow the stack or reach SELFDESTRUCT are reported with their source line and a transaction that reaches them, which is replayed on the built-in EVM interpreter to confirm it. Calls to other contracts are not followed.")
+                //
+                //--contract option
+                .arg(
+                    Arg::new("contract")
+                        .long("contract")
+                        .short('c')
+                        .help("Only analyzes this contract. This flag takes a path as the argument. If the filename of the contract is only provided, the program will look in the source directories of the manifest, or ./evmm_contracts by default.")
+                        .action(ArgAction::Set)
+                        .number_of_values(1),
+                )
         )
         .get_matches();
 
@@ -85,51 +391,169 @@ fn main() -> Result<(), EVMMError> {
     match matches.subcommand() {
         Some(("compile", arg_matches)) => {
             //handle the command line args
-            let mut deployment_bytecode = false;
             let mut print_in_terminal = false;
             let mut contract = "";
             let mut directory_to_compile = "";
-            let mut output_directory = "";
+            let mut output_directory = None;
 
-            if arg_matches.contains_id("deployment-bytecode") {
-                deployment_bytecode = true;
-            }
+            let deployment_bytecode = arg_matches.contains_id("deployment-bytecode");
 
             if arg_matches.contains_id("contract") {
-                //Clap should not let the program get this far if the --directory flag is used
+                //Clap should not let the program get this far if the --contract flag is used
                 //unless there was a value set so we can use unwrap
                 contract = arg_matches.get_one::<String>("contract").unwrap();
             } else if arg_matches.contains_id("target-directory") {
-                //Clap should not let the program get this far if the --directory flag is used
+                //Clap should not let the program get this far if the --target-directory flag is used
                 //unless there was a value set so we can use unwrap
-                directory_to_compile = arg_matches.get_one::<String>("directory").unwrap();
+                directory_to_compile = arg_matches.get_one::<String>("target-directory").unwrap();
             }
 
             //if the output directory is specified
             if arg_matches.contains_id("output-directory") {
-                output_directory = arg_matches.get_one::<String>("output").unwrap();
+                output_directory = Some(PathBuf::from(
+                    arg_matches.get_one::<String>("output-directory").unwrap(),
+                ));
             } else if arg_matches.contains_id("print") {
                 //if the print arg is specified
                 print_in_terminal = true;
-            } else if !arg_matches.contains_id("output-directory")
-                && !arg_matches.contains_id("print")
-            {
-                //if the output directory is not specified and the print flag is not true, output to the default directory
-                output_directory = DEFAULT_COMPILATION_DIR;
             }
 
-            //compile evmm contracts with command line args
-            evmm_parse_and_compile(
+            //Clap only accepts the format and version names, so the names are always valid
+            let output_formats: Vec<OutputFormat> = arg_matches
+                .get_many::<String>("format")
+                .unwrap_or_default()
+                .map(|name| OutputFormat::from_name(name).unwrap())
+                .collect();
+            let evm_version = arg_matches
+                .get_one::<String>("evm-version")
+                .map(|name| EvmVersion::from_name(name).unwrap());
+
+            //settings from the command line take precedence over the manifest, which is found by walking up from the current directory
+            let mut manifest = Manifest::find(Path::new("."))?.unwrap_or_default();
+            manifest.merge_cli_flags(
                 deployment_bytecode,
-                contract,
-                directory_to_compile,
+                output_formats,
+                evm_version,
                 output_directory,
-                print_in_terminal,
+            );
+
+            //compile evmm contracts with command line args
+            if arg_matches.contains_id("watch") {
+                watch_contracts(&manifest, contract, directory_to_compile)?;
+            } else {
+                evmm_parse_and_compile(
+                    &manifest,
+                    contract,
+                    directory_to_compile,
+                    print_in_terminal,
+                    arg_matches.contains_id("force"),
+                )?;
+            }
+        }
+
+        Some(("init", arg_matches)) => {
+            //Clap sets a default directory, so there is always a value
+            let directory = arg_matches.get_one::<String>("directory").unwrap();
+
+            for created_file in
+                init_project(Path::new(directory), arg_matches.contains_id("foundry"))?
+            {
+                println!("Created {}", created_file.display());
+            }
+        }
+
+        Some(("test", arg_matches)) => {
+            let manifest = Manifest::find(Path::new("."))?.unwrap_or_default();
+            let filter = arg_matches
+                .get_one::<String>("match")
+                .map(|filter| filter.as_str())
+                .unwrap_or_default();
+
+            run_tests(&manifest, filter)?;
+        }
+
+        Some(("coverage", arg_matches)) => {
+            let manifest = Manifest::find(Path::new("."))?.unwrap_or_default();
+            let filter = arg_matches
+                .get_one::<String>("match")
+                .map(|filter| filter.as_str())
+                .unwrap_or_default();
+
+            //Clap sets a default output, so there is always a value
+            let output_path = Path::new(arg_matches.get_one::<String>("output").unwrap());
+
+            run_coverage(&manifest, filter, output_path)?;
+        }
+
+        Some(("profile", arg_matches)) => {
+            let manifest = Manifest::find(Path::new("."))?.unwrap_or_default();
+            let filter = arg_matches
+                .get_one::<String>("match")
+                .map(|filter| filter.as_str())
+                .unwrap_or_default();
+
+            //Clap sets a default output and number of lines, so there is always a value
+            let output_path = Path::new(arg_matches.get_one::<String>("output").unwrap());
+            let hottest_lines = *arg_matches.get_one::<usize>("top").unwrap();
+
+            run_gas_profile(&manifest, filter, output_path, hottest_lines)?;
+        }
+
+        Some(("fuzz", arg_matches)) => {
+            let manifest = Manifest::find(Path::new("."))?.unwrap_or_default();
+            let filter = arg_matches
+                .get_one::<String>("match")
+                .map(|filter| filter.as_str())
+                .unwrap_or_default();
+
+            fuzz_contracts(&manifest, filter, fuzz_settings(arg_matches))?;
+        }
+
+        Some(("diff", arg_matches)) => {
+            let manifest = Manifest::find(Path::new("."))?.unwrap_or_default();
+
+            //Clap requires the contract and reference, so there is always a value
+            compare_with_reference(
+                &manifest,
+                arg_matches.get_one::<String>("contract").unwrap(),
+                Path::new(arg_matches.get_one::<String>("reference").unwrap()),
+                arg_matches.contains_id("reference-runtime"),
+                fuzz_settings(arg_matches),
             )?;
         }
 
-        Some(("init", _)) => {
-            //TODO: initialize new foundry project with all evmm dependencies
+        Some(("lint", arg_matches)) => {
+            let manifest = Manifest::find(Path::new("."))?.unwrap_or_default();
+            let contract = arg_matches
+                .get_one::<String>("contract")
+                .map(|contract| contract.as_str())
+                .unwrap_or_default();
+
+            lint_contracts(&manifest, contract)?;
+        }
+
+        Some(("fmt", arg_matches)) => {
+            let manifest = Manifest::find(Path::new("."))?.unwrap_or_default();
+            let contract = arg_matches
+                .get_one::<String>("contract")
+                .map(|contract| contract.as_str())
+                .unwrap_or_default();
+
+            format_contracts(&manifest, contract, arg_matches.contains_id("check"))?;
+        }
+
+        Some(("lsp", _)) => {
+            run_server(&mut io::stdin().lock(), &mut io::stdout().lock())?;
+        }
+
+        Some(("analyze", arg_matches)) => {
+            let manifest = Manifest::find(Path::new("."))?.unwrap_or_default();
+            let contract = arg_matches
+                .get_one::<String>("contract")
+                .map(|contract| contract.as_str())
+                .unwrap_or_default();
+
+            analyze_contracts(&manifest, contract)?;
         }
 
         _ => unreachable!("clap should ensure we don't get here"),
@@ -137,3 +561,23 @@ fn main() -> Result<(), EVMMError> {
 
     Ok(())
 }
+
+//gets the runs, depth and seed of the random calls made by the fuzz and diff subcommands
+fn fuzz_settings(arg_matches: &ArgMatches) -> FuzzSettings {
+    //Clap sets a default for the runs and depth, so there is always a value
+    let seed = arg_matches
+        .get_one::<u64>("seed")
+        .copied()
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_nanos() as u64)
+                .unwrap_or_default()
+        });
+
+    FuzzSettings::new(
+        *arg_matches.get_one::<u64>("runs").unwrap(),
+        *arg_matches.get_one::<usize>("depth").unwrap(),
+        seed,
+    )
+}
@@ -0,0 +1,406 @@
+use crate::compiler::compile::{compile_instruction, decode_hex};
+use crate::compiler::interface::{EventOrErrorDefinition, FunctionDefinition, Interface};
+use crate::compiler::opcodes::push_size;
+use crate::evmm_error::evmm_error::EVMMError;
+use crate::parser::include::parse_program_with_includes;
+use crate::parser::parse::Rule;
+use num256::uint256::Uint256;
+use pest::iterators::Pair;
+use std::path::Path;
+use std::str::FromStr;
+
+///A range of byte offsets in one of the source files of a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    //the index of the file in the program sources
+    pub source: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, source: usize) -> Span {
+        Span { start, end, source }
+    }
+
+    fn from_pair(pair: &Pair<Rule>, source: usize) -> Span {
+        Span::new(pair.as_span().start(), pair.as_span().end(), source)
+    }
+}
+
+///A file that instructions were parsed from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    pub path: String,
+    pub contents: String,
+}
+
+impl SourceFile {
+    pub fn new(path: String, contents: String) -> SourceFile {
+        SourceFile { path, contents }
+    }
+}
+
+///The value of a push instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushValue {
+    //the big endian bytes of a number, keeping leading zeros of hex numbers as written
+    Literal(Vec<u8>),
+    //the offset of a label, resolved once every label in the contract is known
+    Label(String),
+}
+
+///The argument of a signature built-in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureArgument {
+    //a quoted signature in its canonical form, ex. `transfer(address,uint256)`
+    Signature(String),
+    //the name of a declared function, event or error
+    Name(String),
+}
+
+///How the dispatcher searches for the function matching the selector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchMode {
+    Linear,
+    Binary,
+}
+
+///A built-in that expands to one or more instructions at compile time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Builtin {
+    //`__DISPATCHER(fallback)`
+    Dispatcher {
+        fallback: String,
+        mode: DispatchMode,
+    },
+    //`__FUNC_SIG(..)`
+    FuncSig(SignatureArgument),
+    //`__EVENT_HASH(..)`
+    EventHash(SignatureArgument),
+    //`__ERROR(..)`
+    Error(SignatureArgument),
+}
+
+///An interface declaration, which does not compile to any bytecode
+#[derive(Debug, Clone, PartialEq)]
+pub enum Definition {
+    Function(FunctionDefinition),
+    Event(EventOrErrorDefinition),
+    Error(EventOrErrorDefinition),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstructionKind {
+    //an opcode without an immediate value, ex. `ADD`
+    Opcode(u8),
+    //PUSH1 through PUSH32 and the value to push, where size is the number of bytes pushed
+    Push { size: usize, value: PushValue },
+    //a jump destination, ex. `transfer:`
+    Label(String),
+    Builtin(Builtin),
+    Definition(Definition),
+    //`#include "./lib/ownable.evmm"`, which is replaced by the instructions of the included file once includes are resolved
+    Include(String),
+}
+
+///An instruction and the span of the source it was parsed from. The span of a push includes its value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub kind: InstructionKind,
+    pub span: Span,
+}
+
+impl Instruction {
+    pub fn new(kind: InstructionKind, span: Span) -> Instruction {
+        Instruction { kind, span }
+    }
+}
+
+///A parsed contract, in the order the instructions appear in the source
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    //the contract followed by every file it includes, indexed by the instruction spans
+    pub sources: Vec<SourceFile>,
+}
+
+impl Program {
+    ///Converts a parsed file into instructions, where `source` is the index of the file used in the instruction spans.
+    ///The sources are left empty and includes are left unresolved.
+    pub fn from_pair(file: Pair<Rule>, source: usize) -> Result<Program, EVMMError> {
+        let mut instructions = vec![];
+        let mut pairs = file.into_inner();
+
+        while let Some(pair) = pairs.next() {
+            let mut span = Span::from_pair(&pair, source);
+
+            let kind = match pair.as_rule() {
+                Rule::EOI => continue,
+
+                Rule::include => {
+                    //the path is inside the quotes of the include path
+                    let include_path = pair
+                        .into_inner()
+                        .next()
+                        .unwrap()
+                        .into_inner()
+                        .next()
+                        .unwrap();
+                    InstructionKind::Include(include_path.as_str().to_owned())
+                }
+
+                Rule::label => {
+                    InstructionKind::Label(pair.into_inner().next().unwrap().as_str().to_owned())
+                }
+
+                Rule::function_definition => InstructionKind::Definition(Definition::Function(
+                    FunctionDefinition::from_pair(pair),
+                )),
+                Rule::event_definition => InstructionKind::Definition(Definition::Event(
+                    EventOrErrorDefinition::from_pair(pair),
+                )),
+                Rule::error_definition => InstructionKind::Definition(Definition::Error(
+                    EventOrErrorDefinition::from_pair(pair),
+                )),
+
+                Rule::dispatcher => {
+                    let mut fallback = String::new();
+                    let mut mode = DispatchMode::Linear;
+
+                    for inner_pair in pair.into_inner() {
+                        match inner_pair.as_rule() {
+                            Rule::identifier => fallback = inner_pair.as_str().to_owned(),
+                            Rule::dispatch_mode if inner_pair.as_str() == "binary" => {
+                                mode = DispatchMode::Binary
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    InstructionKind::Builtin(Builtin::Dispatcher { fallback, mode })
+                }
+
+                Rule::func_sig => {
+                    InstructionKind::Builtin(Builtin::FuncSig(signature_argument(pair)))
+                }
+                Rule::event_hash => {
+                    InstructionKind::Builtin(Builtin::EventHash(signature_argument(pair)))
+                }
+                Rule::error_selector => {
+                    InstructionKind::Builtin(Builtin::Error(signature_argument(pair)))
+                }
+
+                //values are consumed with the push instruction before them
+                Rule::number | Rule::hex_number | Rule::label_reference => {
+                    return Err(EVMMError::UnexpectedInstruction(pair.as_str().to_owned()))
+                }
+
+                rule => {
+                    let opcode = u8::from_str_radix(&compile_instruction(rule), 16).unwrap();
+                    let size = push_size(opcode);
+
+                    if size == 0 {
+                        InstructionKind::Opcode(opcode)
+                    } else {
+                        let value = match pairs.next() {
+                            Some(value) if value.as_rule() != Rule::EOI => value,
+                            _ => return Err(EVMMError::ExpectedInstruction()),
+                        };
+                        span.end = value.as_span().end();
+
+                        InstructionKind::Push {
+                            size,
+                            value: push_value(&pair, &value, size)?,
+                        }
+                    }
+                }
+            };
+
+            instructions.push(Instruction::new(kind, span));
+        }
+
+        Ok(Program {
+            instructions,
+            sources: vec![],
+        })
+    }
+
+    ///Collects the functions, events and errors declared anywhere in the program
+    pub fn interface(&self) -> Interface {
+        let mut interface = Interface::default();
+
+        for instruction in self.instructions.iter() {
+            if let InstructionKind::Definition(definition) = &instruction.kind {
+                match definition {
+                    Definition::Function(function) => interface.functions.push(function.clone()),
+                    Definition::Event(event) => interface.events.push(event.clone()),
+                    Definition::Error(error) => interface.errors.push(error.clone()),
+                }
+            }
+        }
+
+        interface
+    }
+}
+
+///Parses a contract into a program, resolving includes relative to the current directory
+pub fn parse_program(source: &str) -> Result<Program, EVMMError> {
+    parse_program_with_includes(source, Path::new(""), &[])
+}
+
+fn signature_argument(builtin: Pair<Rule>) -> SignatureArgument {
+    let argument = builtin.into_inner().next().unwrap();
+
+    match argument.as_rule() {
+        //the quoted signature is parsed like a function definition, so the signature is always canonical
+        Rule::quoted_signature => {
+            SignatureArgument::Signature(FunctionDefinition::from_pair(argument).signature())
+        }
+        _ => SignatureArgument::Name(argument.as_str().to_owned()),
+    }
+}
+
+///Converts the value following a push instruction, checking that it fits in the number of bytes pushed
+fn push_value(
+    push_instruction: &Pair<Rule>,
+    value: &Pair<Rule>,
+    size: usize,
+) -> Result<PushValue, EVMMError> {
+    let bytes = match value.as_rule() {
+        Rule::label_reference => return Ok(PushValue::Label(value.as_str().to_owned())),
+
+        Rule::number => match Uint256::from_str(value.as_str()) {
+            Ok(number) => number
+                .to_bytes_be()
+                .into_iter()
+                .skip_while(|byte| *byte == 0)
+                .collect(),
+            //numbers larger than 32 bytes can not be pushed by any push instruction
+            Err(_) => {
+                return Err(EVMMError::ValueTooBigForPushInstruction(
+                    push_instruction.as_str().to_owned(),
+                    value.as_str().to_owned(),
+                    33,
+                ))
+            }
+        },
+
+        Rule::hex_number => {
+            let mut digits = value.as_str()[2..].to_owned();
+            if digits.len() % 2 == 1 {
+                digits.insert(0, '0');
+            }
+
+            //the grammar only allows hex digits
+            decode_hex(&digits).unwrap()
+        }
+
+        _ => return Err(EVMMError::UnexpectedInstruction(value.as_str().to_owned())),
+    };
+
+    if bytes.len() > size {
+        return Err(EVMMError::ValueTooBigForPushInstruction(
+            push_instruction.as_str().to_owned(),
+            value.as_str().to_owned(),
+            bytes.len(),
+        ));
+    }
+
+    Ok(PushValue::Literal(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_program() {
+        let source =
+            "#define function stop()\n__DISPATCHER(stop)\nstop:\n  PUSH2 256\n  PUSH1 end\n  ADD";
+        let program = parse_program(source).unwrap();
+
+        let kinds: Vec<&InstructionKind> = program
+            .instructions
+            .iter()
+            .map(|instruction| &instruction.kind)
+            .collect();
+
+        assert!(matches!(
+            kinds[0],
+            InstructionKind::Definition(Definition::Function(_))
+        ));
+        assert_eq!(
+            kinds[1],
+            &InstructionKind::Builtin(Builtin::Dispatcher {
+                fallback: String::from("stop"),
+                mode: DispatchMode::Linear
+            })
+        );
+        assert_eq!(kinds[2], &InstructionKind::Label(String::from("stop")));
+        assert_eq!(
+            kinds[3],
+            &InstructionKind::Push {
+                size: 2,
+                value: PushValue::Literal(vec![0x01, 0x00])
+            }
+        );
+        assert_eq!(
+            kinds[4],
+            &InstructionKind::Push {
+                size: 1,
+                value: PushValue::Label(String::from("end"))
+            }
+        );
+        assert_eq!(kinds[5], &InstructionKind::Opcode(0x01));
+
+        let push_span = program.instructions[3].span;
+        assert_eq!(&source[push_span.start..push_span.end], "PUSH2 256");
+
+        assert_eq!(program.interface().functions[0].signature(), "stop()");
+    }
+
+    #[test]
+    fn test_parse_push_values() {
+        let program = parse_program("PUSH2 0x1 PUSH2 0x0001 PUSH1 0").unwrap();
+        let values: Vec<&InstructionKind> = program
+            .instructions
+            .iter()
+            .map(|instruction| &instruction.kind)
+            .collect();
+
+        assert_eq!(
+            values,
+            vec![
+                &InstructionKind::Push {
+                    size: 2,
+                    value: PushValue::Literal(vec![0x01])
+                },
+                &InstructionKind::Push {
+                    size: 2,
+                    value: PushValue::Literal(vec![0x00, 0x01])
+                },
+                &InstructionKind::Push {
+                    size: 1,
+                    value: PushValue::Literal(vec![])
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_push_errors() {
+        assert!(matches!(
+            parse_program("PUSH1 0x0102"),
+            Err(EVMMError::ValueTooBigForPushInstruction(_, _, 2))
+        ));
+        assert!(matches!(
+            parse_program("PUSH1"),
+            Err(EVMMError::ExpectedInstruction())
+        ));
+        assert!(matches!(
+            parse_program("ADD 0x01"),
+            Err(EVMMError::UnexpectedInstruction(_))
+        ));
+    }
+}
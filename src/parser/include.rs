@@ -0,0 +1,308 @@
+use crate::evmm_error::evmm_error::EVMMError;
+use crate::parser::ast::{Instruction, InstructionKind, Program, SourceFile};
+use crate::parser::parse::{parse_file, parse_named_file};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+///Parses a contract and replaces every `#include` with the instructions of the included file.
+///Include paths are resolved relative to the directory of the file that includes them, then relative to each of the
+///include directories. Each file is only included once, so shared snippets can be included by several files, and files
+///that include each other in a cycle are an error.
+pub fn parse_program_with_includes(
+    source: &str,
+    source_path: &Path,
+    include_directories: &[PathBuf],
+) -> Result<Program, EVMMError> {
+    let mut resolver = IncludeResolver {
+        include_directories: include_directories.to_vec(),
+        ..IncludeResolver::default()
+    };
+
+    //contracts compiled from a string have no file that could be included again
+    if let Ok(canonical_path) = fs::canonicalize(source_path) {
+        resolver.included.insert(canonical_path.clone());
+        resolver
+            .include_stack
+            .push((canonical_path, source_path.display().to_string()));
+    }
+
+    let instructions = resolver.parse_source(source, source_path)?;
+
+    Ok(Program {
+        instructions,
+        sources: resolver.sources,
+    })
+}
+
+#[derive(Default)]
+struct IncludeResolver {
+    include_directories: Vec<PathBuf>,
+    sources: Vec<SourceFile>,
+    //the canonical path of every file that has been included
+    included: HashSet<PathBuf>,
+    //the canonical and displayed path of each file that is being parsed, from the contract to the current file
+    include_stack: Vec<(PathBuf, String)>,
+}
+
+impl IncludeResolver {
+    fn parse_source(
+        &mut self,
+        source: &str,
+        source_path: &Path,
+    ) -> Result<Vec<Instruction>, EVMMError> {
+        let source_index = self.sources.len();
+        let display_path = source_path.display().to_string();
+        self.sources
+            .push(SourceFile::new(display_path.clone(), source.to_owned()));
+
+        let parsed_file = if display_path.is_empty() {
+            parse_file(source)?
+        } else {
+            parse_named_file(source, &display_path)?
+        };
+
+        //errors from included files name the file they come from, parse errors already include the path
+        let program = Program::from_pair(parsed_file, source_index).map_err(|error| {
+            if source_index == 0 {
+                error
+            } else {
+                EVMMError::InIncludedFile(display_path.clone(), Box::new(error))
+            }
+        })?;
+
+        let mut instructions = vec![];
+        for instruction in program.instructions {
+            match instruction.kind {
+                InstructionKind::Include(include_path) => {
+                    instructions.extend(self.include(&include_path, source_path)?);
+                }
+                _ => instructions.push(instruction),
+            }
+        }
+
+        Ok(instructions)
+    }
+
+    fn include(
+        &mut self,
+        include_path: &str,
+        including_path: &Path,
+    ) -> Result<Vec<Instruction>, EVMMError> {
+        let mut path = join_include_path(including_path, include_path);
+        if !path.exists() {
+            if let Some(include_directory_path) = self
+                .include_directories
+                .iter()
+                .map(|include_directory| include_directory.join(include_path))
+                .find(|include_directory_path| include_directory_path.exists())
+            {
+                path = include_directory_path;
+            }
+        }
+        let display_path = path.display().to_string();
+
+        let include_not_found = || {
+            EVMMError::IncludeNotFound(
+                include_path.to_owned(),
+                including_path.display().to_string(),
+            )
+        };
+
+        let canonical_path = fs::canonicalize(&path).map_err(|_| include_not_found())?;
+
+        if let Some(cycle_start) = self
+            .include_stack
+            .iter()
+            .position(|(included_path, _)| *included_path == canonical_path)
+        {
+            let mut include_chain: Vec<&str> = self.include_stack[cycle_start..]
+                .iter()
+                .map(|(_, display_path)| display_path.as_str())
+                .collect();
+            include_chain.push(&display_path);
+
+            return Err(EVMMError::CircularInclude(include_chain.join(" -> ")));
+        }
+
+        //the file was already included elsewhere
+        if !self.included.insert(canonical_path.clone()) {
+            return Ok(vec![]);
+        }
+
+        let source = fs::read_to_string(&path).map_err(|_| include_not_found())?;
+
+        self.include_stack.push((canonical_path, display_path));
+        let instructions = self.parse_source(&source, &path)?;
+        self.include_stack.pop();
+
+        Ok(instructions)
+    }
+}
+
+///Joins the include path to the directory of the including file, dropping the `.` components after the first
+///so that `./contracts` and `./lib/ownable.evmm` are displayed as `./contracts/lib/ownable.evmm`
+fn join_include_path(including_path: &Path, include_path: &str) -> PathBuf {
+    let path = including_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(include_path);
+
+    let mut joined_path = PathBuf::new();
+    for (index, component) in path.components().enumerate() {
+        if index == 0 || component != Component::CurDir {
+            joined_path.push(component);
+        }
+    }
+
+    joined_path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    //writes the files to a new directory in the system temp directory and returns the directory
+    fn write_files(test_name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = env::temp_dir().join(format!("evmm_include_{}", test_name));
+        let _ = fs::remove_dir_all(&directory);
+
+        for (file_path, contents) in files {
+            let path = directory.join(file_path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        directory
+    }
+
+    fn parse(directory: &Path, file_path: &str) -> Result<Program, EVMMError> {
+        let path = directory.join(file_path);
+        parse_program_with_includes(&fs::read_to_string(&path).unwrap(), &path, &[])
+    }
+
+    #[test]
+    fn test_include_once() {
+        let directory = write_files(
+            "include_once",
+            &[
+                (
+                    "Main.evmm",
+                    "#include \"./lib/ownable.evmm\"\n#include \"./lib/math.evmm\"\nSTOP",
+                ),
+                ("lib/ownable.evmm", "#include \"./math.evmm\"\nCALLER"),
+                ("lib/math.evmm", "ADD"),
+            ],
+        );
+
+        let program = parse(&directory, "Main.evmm").unwrap();
+        let instructions: Vec<(&InstructionKind, usize)> = program
+            .instructions
+            .iter()
+            .map(|instruction| (&instruction.kind, instruction.span.source))
+            .collect();
+
+        assert_eq!(
+            instructions,
+            vec![
+                (&InstructionKind::Opcode(0x01), 2),
+                (&InstructionKind::Opcode(0x33), 1),
+                (&InstructionKind::Opcode(0x00), 0),
+            ]
+        );
+        assert_eq!(program.sources.len(), 3);
+        assert!(program.sources[2].path.ends_with("math.evmm"));
+    }
+
+    #[test]
+    fn test_include_directories() {
+        let directory = write_files(
+            "include_directories",
+            &[
+                ("contracts/Main.evmm", "#include \"ownable.evmm\"\nSTOP"),
+                ("lib/ownable.evmm", "CALLER"),
+            ],
+        );
+        let path = directory.join("contracts/Main.evmm");
+        let source = fs::read_to_string(&path).unwrap();
+
+        assert!(matches!(
+            parse_program_with_includes(&source, &path, &[]),
+            Err(EVMMError::IncludeNotFound(_, _))
+        ));
+
+        let program =
+            parse_program_with_includes(&source, &path, &[directory.join("lib")]).unwrap();
+        assert_eq!(program.instructions[0].kind, InstructionKind::Opcode(0x33));
+    }
+
+    #[test]
+    fn test_join_include_path() {
+        assert_eq!(
+            join_include_path(Path::new("./contracts/Main.evmm"), "./lib/ownable.evmm"),
+            PathBuf::from("./contracts/lib/ownable.evmm")
+        );
+        assert_eq!(
+            join_include_path(Path::new("Main.evmm"), "../lib/ownable.evmm"),
+            PathBuf::from("../lib/ownable.evmm")
+        );
+    }
+
+    #[test]
+    fn test_circular_include() {
+        let directory = write_files(
+            "circular_include",
+            &[
+                ("Main.evmm", "#include \"./a.evmm\""),
+                ("a.evmm", "#include \"./b.evmm\""),
+                ("b.evmm", "#include \"./a.evmm\""),
+            ],
+        );
+
+        match parse(&directory, "Main.evmm") {
+            Err(EVMMError::CircularInclude(include_chain)) => {
+                assert_eq!(include_chain.matches("a.evmm").count(), 2);
+                assert_eq!(include_chain.matches("b.evmm").count(), 1);
+            }
+            _ => panic!("expected a circular include error"),
+        }
+    }
+
+    #[test]
+    fn test_include_errors_name_the_included_file() {
+        let directory = write_files(
+            "include_errors",
+            &[
+                (
+                    "Main.evmm",
+                    "#include \"./lib/bad_push.evmm\"\n#include \"./lib/missing.evmm\"",
+                ),
+                ("lib/bad_push.evmm", "PUSH1 0x0102"),
+            ],
+        );
+
+        match parse(&directory, "Main.evmm") {
+            Err(EVMMError::InIncludedFile(file_path, error)) => {
+                assert!(file_path.ends_with("bad_push.evmm"));
+                assert!(matches!(
+                    *error,
+                    EVMMError::ValueTooBigForPushInstruction(_, _, 2)
+                ));
+            }
+            _ => panic!("expected an error in the included file"),
+        }
+
+        fs::write(directory.join("lib/bad_push.evmm"), "PUSH1 )").unwrap();
+        match parse(&directory, "Main.evmm") {
+            Err(EVMMError::ParseError(error)) => assert!(error.contains("bad_push.evmm:1:7")),
+            _ => panic!("expected a parse error"),
+        }
+
+        fs::write(directory.join("lib/bad_push.evmm"), "PUSH1 0x01").unwrap();
+        assert!(matches!(
+            parse(&directory, "Main.evmm"),
+            Err(EVMMError::IncludeNotFound(include_path, _)) if include_path == "./lib/missing.evmm"
+        ));
+    }
+}
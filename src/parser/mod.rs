@@ -1 +1,3 @@
+pub mod ast;
+pub mod include;
 pub mod parse;
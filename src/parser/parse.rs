@@ -1,3 +1,4 @@
+use crate::evmm_error::evmm_error::EVMMError;
 use pest::iterators::Pair;
 use pest::Parser;
 
@@ -5,11 +6,23 @@ use pest::Parser;
 #[grammar = "evmm.pest"]
 pub struct EVMMParser;
 
-pub fn parse_file(unparsed_file: &str) -> Pair<Rule> {
-    EVMMParser::parse(Rule::file, unparsed_file)
-        .unwrap_or_else(|_| panic!("Error when parsing file"))
-        .next()
-        .unwrap()
+pub fn parse_file(unparsed_file: &str) -> Result<Pair<'_, Rule>, EVMMError> {
+    let mut parsed_file = EVMMParser::parse(Rule::file, unparsed_file)
+        .map_err(|error| EVMMError::ParseError(error.to_string()))?;
+
+    //a successful parse always produces the file rule
+    Ok(parsed_file.next().unwrap())
+}
+
+///Parses a file, including the file path in the error message if the file can not be parsed
+pub fn parse_named_file<'a>(
+    unparsed_file: &'a str,
+    file_path: &str,
+) -> Result<Pair<'a, Rule>, EVMMError> {
+    let mut parsed_file = EVMMParser::parse(Rule::file, unparsed_file)
+        .map_err(|error| EVMMError::ParseError(error.with_path(file_path).to_string()))?;
+
+    Ok(parsed_file.next().unwrap())
 }
 
 #[cfg(test)]
@@ -24,7 +37,7 @@ mod tests {
     CALLER //[CALLER 0x0102030405 0x01]
     "#;
 
-        let parsed_file = parse_file(file);
+        let parsed_file = parse_file(file).unwrap();
 
         println!("{:?}", parsed_file);
     }
@@ -35,8 +48,42 @@ mod tests {
         PUSH1 0x01
     "#;
 
-        let parsed_file = parse_file(file);
+        let parsed_file = parse_file(file).unwrap();
 
         println!("{:?}", parsed_file);
     }
+
+    #[test]
+    fn test_parse_instructions_sharing_a_prefix() {
+        let file = r#"
+        JUMPI
+        ADDMOD
+        CREATE2
+        MSTORE8
+        DUP10
+        add_liquidity:
+        PUSH2 add_liquidity
+    "#;
+
+        let rules: Vec<Rule> = parse_file(file)
+            .unwrap()
+            .into_inner()
+            .map(|instruction| instruction.as_rule())
+            .collect();
+
+        assert_eq!(
+            rules,
+            vec![
+                Rule::jumpi,
+                Rule::addmod,
+                Rule::create2,
+                Rule::mstore8,
+                Rule::dup10,
+                Rule::label,
+                Rule::push2,
+                Rule::label_reference,
+                Rule::EOI
+            ]
+        );
+    }
 }
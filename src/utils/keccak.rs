@@ -0,0 +1,142 @@
+//Keccak-256 as used by the EVM (the original Keccak padding, not the finalized SHA3-256 padding)
+
+//Number of bytes absorbed per permutation for a 256 bit output
+const RATE: usize = 136;
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808A,
+    0x8000000080008000,
+    0x000000000000808B,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008A,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000A,
+    0x000000008000808B,
+    0x800000000000008B,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800A,
+    0x800000008000000A,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+const ROTATION_OFFSETS: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+
+const LANE_PERMUTATION: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+///Hashes the input with Keccak-256, returning the 32 byte digest
+pub fn keccak256(input: &[u8]) -> [u8; 32] {
+    let mut state = [0u64; 25];
+
+    //pad the input to a multiple of the rate with the Keccak padding rule (0x01 ... 0x80)
+    let mut padded_input = input.to_vec();
+    padded_input.push(0x01);
+    while !padded_input.len().is_multiple_of(RATE) {
+        padded_input.push(0x00);
+    }
+    let last_index = padded_input.len() - 1;
+    padded_input[last_index] |= 0x80;
+
+    //absorb each block into the state as little endian lanes
+    for block in padded_input.chunks(RATE) {
+        for (lane, lane_bytes) in state.iter_mut().zip(block.chunks(8)) {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(lane_bytes);
+            *lane ^= u64::from_le_bytes(bytes);
+        }
+
+        keccak_f(&mut state);
+    }
+
+    //squeeze the first 32 bytes of the state
+    let mut digest = [0u8; 32];
+    for (digest_bytes, lane) in digest.chunks_mut(8).zip(state.iter()) {
+        digest_bytes.copy_from_slice(&lane.to_le_bytes());
+    }
+
+    digest
+}
+
+fn keccak_f(state: &mut [u64; 25]) {
+    for round_constant in ROUND_CONSTANTS {
+        //theta
+        let mut column_parity = [0u64; 5];
+        for (x, parity) in column_parity.iter_mut().enumerate() {
+            *parity = state[x] ^ state[x + 5] ^ state[x + 10] ^ state[x + 15] ^ state[x + 20];
+        }
+        for x in 0..5 {
+            let d = column_parity[(x + 4) % 5] ^ column_parity[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                state[x + 5 * y] ^= d;
+            }
+        }
+
+        //rho and pi
+        let mut last_lane = state[1];
+        for (rotation, lane_index) in ROTATION_OFFSETS.iter().zip(LANE_PERMUTATION) {
+            let current_lane = state[lane_index];
+            state[lane_index] = last_lane.rotate_left(*rotation);
+            last_lane = current_lane;
+        }
+
+        //chi
+        for y in 0..5 {
+            let mut row = [0u64; 5];
+            row.copy_from_slice(&state[5 * y..5 * y + 5]);
+            for x in 0..5 {
+                state[x + 5 * y] = row[x] ^ (!row[(x + 1) % 5] & row[(x + 2) % 5]);
+            }
+        }
+
+        //iota
+        state[0] ^= round_constant;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn test_keccak256_empty_input() {
+        assert_eq!(
+            to_hex(&keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+    }
+
+    #[test]
+    fn test_keccak256_function_signature() {
+        assert_eq!(
+            to_hex(&keccak256(b"transfer(address,uint256)")[0..4]),
+            "a9059cbb"
+        );
+    }
+
+    #[test]
+    fn test_keccak256_multiple_blocks() {
+        //200 bytes spans two blocks of the 136 byte rate
+        assert_eq!(
+            to_hex(&keccak256(&[0x61; 200])),
+            "96ea54061def936c4be90b518992fdc6f12f535068a256229aca54267b4d084d"
+        );
+    }
+}
@@ -0,0 +1,2 @@
+pub mod keccak;
+pub mod random;
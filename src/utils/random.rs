@@ -0,0 +1,73 @@
+//A small seeded random number generator (SplitMix64), so that fuzzing runs can be reproduced from their seed
+
+use primitive_types::U256;
+
+const GOLDEN_GAMMA: u64 = 0x9E3779B97F4A7C15;
+
+///Generates the same sequence of numbers for the same seed
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    ///Gets a number below the bound, which must not be zero
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    ///Returns true one time in `one_in`
+    pub fn one_in(&mut self, one_in: u64) -> bool {
+        self.below(one_in) == 0
+    }
+
+    pub fn bytes(&mut self, length: usize) -> Vec<u8> {
+        (0..length).map(|_| self.next_u64() as u8).collect()
+    }
+
+    pub fn u256(&mut self) -> U256 {
+        U256([
+            self.next_u64(),
+            self.next_u64(),
+            self.next_u64(),
+            self.next_u64(),
+        ])
+    }
+
+    ///Picks an element of a slice, which must not be empty
+    pub fn choose<'a, T>(&mut self, elements: &'a [T]) -> &'a T {
+        &elements[self.below(elements.len() as u64) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_is_reproducible() {
+        let mut random = Random::new(1234);
+        let mut same_seed = Random::new(1234);
+
+        let numbers: Vec<u64> = (0..8).map(|_| random.next_u64()).collect();
+        assert_eq!(
+            numbers,
+            (0..8).map(|_| same_seed.next_u64()).collect::<Vec<u64>>()
+        );
+        assert_ne!(numbers[0], Random::new(1235).next_u64());
+        assert!((0..100).all(|_| random.below(7) < 7));
+    }
+}